@@ -0,0 +1,25 @@
+//! Compares parsing the bundled fingerprint responses with `serde_json` and
+//! `simd-json`. Run with `cargo bench --features simd`.
+
+use ajour_api_test::bench;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_json");
+
+    for (name, api, body) in bench::samples() {
+        group.throughput(Throughput::Bytes(body.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("serde_json", name), &body, |b, body| {
+            b.iter(|| bench::serde_json(api, body).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("simd-json", name), &body, |b, body| {
+            b.iter(|| bench::simd_json(api, body).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,61 @@
+//! AIMD concurrency control for `--adaptive`, which finds how many requests
+//! an API handles at once without its latency or error rate degrading.
+
+use std::time::Duration;
+
+/// Upper bound on in-flight requests per API, which is also the client's
+/// connection limit in adaptive mode.
+pub const MAX_CONCURRENCY: usize = 16;
+
+/// A request slower than this multiple of the fastest one seen counts as
+/// congestion.
+const LATENCY_TOLERANCE: u32 = 2;
+
+/// Grows the limit by one after a full window of healthy requests and halves
+/// it on a failure, a retry or a latency spike.
+pub struct Aimd {
+    limit: usize,
+    healthy: usize,
+    fastest: Option<Duration>,
+}
+
+impl Aimd {
+    /// Starts at `limit` requests in flight, capped to `MAX_CONCURRENCY`.
+    pub fn new(limit: usize) -> Aimd {
+        Aimd {
+            limit: limit.clamp(1, MAX_CONCURRENCY),
+            healthy: 0,
+            fastest: None,
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Feeds back the outcome of one completed request. A request that
+    /// needed retries, such as after a timeout or a 429, isn't a success
+    /// even if its final attempt was.
+    pub fn record(&mut self, latency: Duration, success: bool) {
+        if success {
+            self.fastest = Some(self.fastest.map_or(latency, |f| f.min(latency)));
+        }
+
+        let congested = match self.fastest {
+            Some(fastest) if success => latency > fastest * LATENCY_TOLERANCE,
+            _ => !success,
+        };
+
+        if congested {
+            self.limit = (self.limit / 2).max(1);
+            self.healthy = 0;
+        } else {
+            self.healthy += 1;
+
+            if self.healthy >= self.limit {
+                self.limit = (self.limit + 1).min(MAX_CONCURRENCY);
+                self.healthy = 0;
+            }
+        }
+    }
+}
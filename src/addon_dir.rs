@@ -0,0 +1,308 @@
+//! Audits the addons installed in a local AddOns directory for
+//! `--addon-dir`, instead of packages found through the search.
+
+use crate::fnv::Fnv1a;
+use crate::ApiResults;
+
+use ajour_core::parse::fingerprint_addon_dir;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use std::collections::BTreeMap;
+use std::hash::Hasher;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// File in `--cache-dir` holding the fingerprints of earlier scans.
+const CACHE_FILE: &str = "addon-fingerprints.json";
+
+/// An addon folder and the fingerprint ajour-core computed for it.
+pub struct AddonFolder {
+    pub name: String,
+    pub fingerprint: u32,
+}
+
+/// Fingerprints computed by earlier scans, keyed by folder path.
+#[derive(Default, Serialize, Deserialize)]
+struct FingerprintCache {
+    folders: BTreeMap<String, CachedFingerprint>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedFingerprint {
+    /// `folder_stamp` of the folder when it was fingerprinted.
+    stamp: u64,
+    fingerprint: u32,
+}
+
+/// Fingerprints every addon folder in `dir`, sorted by name. Folders that
+/// can't be fingerprinted are skipped with a warning.
+///
+/// With a `cache_dir`, folders whose files are unchanged since an earlier
+/// scan reuse its fingerprint instead of hashing them again.
+pub fn scan(dir: &Path, cache_dir: Option<&Path>) -> io::Result<Vec<AddonFolder>> {
+    let mut cache = match cache_dir {
+        Some(cache_dir) => load_cache(&cache_dir.join(CACHE_FILE)),
+        None => FingerprintCache::default(),
+    };
+    let (mut hits, mut misses) = (0, 0);
+    let mut folders = vec![];
+
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let path = entry.path();
+        let key = path.to_string_lossy().into_owned();
+
+        // A folder that can't be stamped is fingerprinted without caching
+        let stamp = cache_dir.and_then(|_| folder_stamp(&path, &path).ok());
+
+        let cached = cache
+            .folders
+            .get(&key)
+            .filter(|cached| Some(cached.stamp) == stamp);
+
+        if let Some(cached) = cached {
+            hits += 1;
+            folders.push(AddonFolder {
+                name,
+                fingerprint: cached.fingerprint,
+            });
+            continue;
+        }
+
+        misses += 1;
+
+        match fingerprint_addon_dir(&path) {
+            Ok(fingerprint) => {
+                if let Some(stamp) = stamp {
+                    let cached = CachedFingerprint { stamp, fingerprint };
+                    cache.folders.insert(key, cached);
+                }
+
+                folders.push(AddonFolder { name, fingerprint });
+            }
+            Err(e) => warn!("failed to fingerprint {} - {}", name, e),
+        }
+    }
+
+    if let Some(cache_dir) = cache_dir {
+        info!("Fingerprint cache: {} hits, {} misses", hits, misses);
+
+        std::fs::create_dir_all(cache_dir)?;
+        std::fs::write(cache_dir.join(CACHE_FILE), serde_json::to_vec(&cache)?)?;
+    }
+
+    folders.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(folders)
+}
+
+/// Reads the cache, starting from an empty one if there is none yet or it
+/// can't be read.
+fn load_cache(path: &Path) -> FingerprintCache {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return FingerprintCache::default(),
+        Err(e) => {
+            warn!("failed to read {} - {}", path.display(), e);
+            return FingerprintCache::default();
+        }
+    };
+
+    serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+        warn!(
+            "ignoring invalid fingerprint cache {} - {}",
+            path.display(),
+            e
+        );
+        FingerprintCache::default()
+    })
+}
+
+/// FNV-1a hash of the relative path, size and modification time of every
+/// file below `dir`, which changes whenever any file is added, removed or
+/// modified.
+fn folder_stamp(root: &Path, dir: &Path) -> io::Result<u64> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    let mut hasher = Fnv1a::default();
+
+    for entry in entries {
+        let path = entry.path();
+        let metadata = entry.metadata()?;
+
+        if metadata.is_dir() {
+            hasher.write(&folder_stamp(root, &path)?.to_le_bytes());
+            continue;
+        }
+
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+
+        let relative = path.strip_prefix(root).unwrap_or(&path);
+        hasher.write(relative.to_string_lossy().as_bytes());
+        hasher.write(&metadata.len().to_le_bytes());
+        hasher.write(&modified.to_le_bytes());
+    }
+
+    Ok(hasher.finish())
+}
+
+#[derive(Serialize)]
+pub struct FolderStatus<'a> {
+    pub folder: &'a str,
+    pub fingerprint: u32,
+    /// The package and file each API matched the folder to, keyed by API.
+    pub matches: BTreeMap<&'static str, FolderMatch>,
+    /// APIs whose batch with this folder failed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failed_on: Vec<&'static str>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct FolderMatch {
+    pub package_id: i32,
+    pub file: String,
+}
+
+/// Match status of every folder, in the order of `folders`.
+///
+/// `failed` holds the folder indices of each API's failed batches.
+pub fn statuses<'a>(
+    folders: &'a [AddonFolder],
+    results: &[ApiResults],
+    failed: &[Vec<usize>],
+) -> Vec<FolderStatus<'a>> {
+    let matched = results
+        .iter()
+        .map(|result| {
+            result
+                .exact_matches()
+                .into_iter()
+                .flat_map(|m| {
+                    m.file.modules.iter().map(move |module| {
+                        let folder_match = FolderMatch {
+                            package_id: m.id,
+                            file: m.file.display_name.clone(),
+                        };
+
+                        (module.fingerprint, folder_match)
+                    })
+                })
+                .collect::<BTreeMap<_, _>>()
+        })
+        .collect::<Vec<_>>();
+
+    folders
+        .iter()
+        .enumerate()
+        .map(|(index, folder)| FolderStatus {
+            folder: &folder.name,
+            fingerprint: folder.fingerprint,
+            matches: results
+                .iter()
+                .zip(&matched)
+                .filter_map(|(result, matched)| {
+                    matched
+                        .get(&folder.fingerprint)
+                        .map(|m| (result.api_choice.key(), m.clone()))
+                })
+                .collect(),
+            failed_on: results
+                .iter()
+                .zip(failed)
+                .filter(|(_, failed)| failed.contains(&index))
+                .map(|(result, _)| result.api_choice.key())
+                .collect(),
+        })
+        .collect()
+}
+
+/// Writes a line per folder with the APIs that matched it.
+pub fn write_text(
+    out: &mut impl Write,
+    statuses: &[FolderStatus<'_>],
+    results: &[ApiResults],
+) -> io::Result<()> {
+    for status in statuses {
+        let apis = results
+            .iter()
+            .map(|result| {
+                let key = result.api_choice.key();
+
+                match status.matches.get(key) {
+                    Some(m) => format!("{} {} ({})", key, m.file, m.package_id),
+                    None if status.failed_on.contains(&key) => format!("{} failed", key),
+                    None => format!("{} unmatched", key),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        writeln!(out, "{} - {}", status.folder, apis.join(", "))?;
+    }
+
+    for result in results {
+        let key = result.api_choice.key();
+        let matched = statuses
+            .iter()
+            .filter(|s| s.matches.contains_key(key))
+            .count();
+
+        writeln!(
+            out,
+            "{} of {} folders matched by {}",
+            matched,
+            statuses.len(),
+            result.api_choice.name()
+        )?;
+    }
+
+    if results.len() > 1 {
+        let compared = statuses
+            .iter()
+            .filter(|s| s.failed_on.is_empty() && !s.matches.is_empty())
+            .collect::<Vec<_>>();
+        let differing = compared
+            .iter()
+            .filter(|s| !resolves_identically(s, results.len()))
+            .map(|s| s.folder)
+            .collect::<Vec<_>>();
+
+        writeln!(
+            out,
+            "{} of {} matched folders resolved identically on every API",
+            compared.len() - differing.len(),
+            compared.len()
+        )?;
+
+        if !differing.is_empty() {
+            writeln!(out, "Resolved differently: {}", differing.join(", "))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether every one of `apis` matched the folder to the same package and
+/// file.
+fn resolves_identically(status: &FolderStatus<'_>, apis: usize) -> bool {
+    let mut matches = status.matches.values();
+
+    match matches.next() {
+        Some(first) => {
+            status.matches.len() == apis
+                && matches.all(|m| m.package_id == first.package_id && m.file == first.file)
+        }
+        None => false,
+    }
+}
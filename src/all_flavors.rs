@@ -0,0 +1,237 @@
+//! Audits Retail, Classic Era and Wrath at once for `--all-flavors`.
+//!
+//! The search doesn't depend on the flavor, so it's run once and every
+//! flavor's audit filters the same packages to its own latest files, as
+//! `--flavor` would. The audits then fingerprint concurrently, and their
+//! reports are written together with a matrix of each API's match rate per
+//! flavor.
+
+use crate::cli::{Format, Opts};
+use crate::rate_limit::RateLimits;
+use crate::report::AuditReport;
+use crate::retry::RetryBudget;
+use crate::spill::Spill;
+use crate::{
+    audit_and_report, exit_on_search_failure, print_status, sample, search_all_packages,
+    write_json, ApiChoice, GameAudit, SearchPlan, Searched,
+};
+
+use futures::future;
+use isahc::HttpClient;
+use serde::Serialize;
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::time::Instant;
+
+/// Curse's game version flavor of each audited flavor, with its name.
+pub const FLAVORS: [(&str, &str); 3] = [
+    ("wow_retail", "Retail"),
+    ("wow_classic", "Classic Era"),
+    ("wow_wrath_classic", "Wrath"),
+];
+
+/// Report of an `--all-flavors` run.
+#[derive(Serialize)]
+struct MultiFlavorReport<'a> {
+    flavors: Vec<FlavorReport<'a>>,
+    matrix: Vec<MatrixRow>,
+}
+
+#[derive(Serialize)]
+struct FlavorReport<'a> {
+    flavor: &'static str,
+    #[serde(flatten)]
+    report: &'a AuditReport,
+}
+
+#[derive(Serialize)]
+struct MatrixRow {
+    flavor: &'static str,
+    #[serde(skip)]
+    name: &'static str,
+    packages: usize,
+    /// Share of the flavor's packages each API matched, keyed by API.
+    match_pct: BTreeMap<&'static str, f64>,
+}
+
+/// Searches once and audits every flavor of `FLAVORS` from the results,
+/// returning the audits in the same order.
+pub async fn audit(
+    client: &HttpClient,
+    opts: &Opts,
+    apis: &[ApiChoice],
+    retry_budget: &RetryBudget,
+    rate_limits: &RateLimits,
+) -> Result<Vec<GameAudit>, anyhow::Error> {
+    let game_id = opts.game_ids[0];
+    let search_start = Instant::now();
+    let plan = SearchPlan::new(opts, opts.seed.unwrap_or_else(sample::random_seed));
+
+    let packages = search_all_packages(client, opts, game_id, &plan).await;
+    let searched = Searched {
+        packages: exit_on_search_failure(opts, packages)?,
+        seed: plan.seed,
+        duration: search_start.elapsed(),
+    };
+
+    if opts.format == Format::Text && !opts.only_discrepancies {
+        let names = FLAVORS.iter().map(|(_, name)| *name).collect::<Vec<_>>();
+
+        print_status(
+            opts,
+            format_args!(
+                "Auditing {} from {} searched packages",
+                names.join(", "),
+                searched.packages.len()
+            ),
+        );
+    }
+
+    let audits = FLAVORS.iter().map(|&(flavor, _)| {
+        let mut opts = opts.clone();
+        opts.flavors = vec![flavor.to_owned()];
+        let searched = &searched;
+
+        async move {
+            // --max-memory-mb is rejected, so nothing is ever spilled
+            let spill = Spill::new(None);
+            let (game, _) = audit_and_report(
+                client,
+                &opts,
+                apis,
+                retry_budget,
+                rate_limits,
+                &spill,
+                game_id,
+                Some(searched),
+            )
+            .await?;
+
+            Ok::<_, anyhow::Error>(game)
+        }
+    });
+
+    future::try_join_all(audits).await
+}
+
+/// Writes the audits `audit` returned in `format`, with the match rate
+/// matrix after the flavors' reports. CSV only has the matrix.
+pub fn write(
+    out: &mut impl Write,
+    format: Format,
+    opts: &Opts,
+    games: &[GameAudit],
+) -> io::Result<()> {
+    let matrix = matrix(games);
+
+    match format {
+        Format::Text => {
+            for (&(_, name), game) in FLAVORS.iter().zip(games) {
+                writeln!(out, "\n{}:", name)?;
+                game.report
+                    .write_text(out, opts.top, opts.template.as_deref())?;
+            }
+
+            write_matrix(out, games, &matrix)
+        }
+        Format::Json => {
+            let report = MultiFlavorReport {
+                flavors: FLAVORS
+                    .iter()
+                    .zip(games)
+                    .map(|(&(flavor, _), game)| FlavorReport {
+                        flavor,
+                        report: &game.report,
+                    })
+                    .collect(),
+                matrix,
+            };
+
+            write_json(out, &report, opts.compact)
+        }
+        Format::Csv => {
+            let apis = api_keys(games);
+            writeln!(out, "flavor,packages,{}", apis.join(","))?;
+
+            for row in &matrix {
+                let rates = apis
+                    .iter()
+                    .map(|api| format!("{:.1}", row.match_pct.get(api).copied().unwrap_or(0.0)))
+                    .collect::<Vec<_>>();
+
+                writeln!(out, "{},{},{}", row.flavor, row.packages, rates.join(","))?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+fn matrix(games: &[GameAudit]) -> Vec<MatrixRow> {
+    FLAVORS
+        .iter()
+        .zip(games)
+        .map(|(&(flavor, name), game)| MatrixRow {
+            flavor,
+            name,
+            packages: game.report.packages_audited,
+            match_pct: game
+                .report
+                .apis
+                .iter()
+                .map(|api| (api.api, game.report.match_rate(api)))
+                .collect(),
+        })
+        .collect()
+}
+
+/// Keys of the APIs, which every flavor audits alike.
+fn api_keys(games: &[GameAudit]) -> Vec<&'static str> {
+    games.first().map_or_else(Vec::new, |game| {
+        game.report.apis.iter().map(|api| api.api).collect()
+    })
+}
+
+fn write_matrix(out: &mut impl Write, games: &[GameAudit], matrix: &[MatrixRow]) -> io::Result<()> {
+    let apis = games.first().map_or_else(Vec::new, |game| {
+        game.report
+            .apis
+            .iter()
+            .map(|api| (api.api, api.name, api.name.len().max(6)))
+            .collect()
+    });
+    let width = FLAVORS
+        .iter()
+        .map(|(_, name)| name.len())
+        .max()
+        .unwrap_or(0);
+
+    writeln!(out, "\nMatch rate by flavor:")?;
+    write!(out, "  {:<width$}  packages", "", width = width)?;
+
+    for &(_, name, column) in &apis {
+        write!(out, "  {:>column$}", name, column = column)?;
+    }
+
+    writeln!(out)?;
+
+    for row in matrix {
+        write!(
+            out,
+            "  {:<width$}  {:>8}",
+            row.name,
+            row.packages,
+            width = width
+        )?;
+
+        for &(api, _, column) in &apis {
+            let pct = format!("{:.1}%", row.match_pct.get(api).copied().unwrap_or(0.0));
+            write!(out, "  {:>column$}", pct, column = column)?;
+        }
+
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
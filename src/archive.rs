@@ -0,0 +1,93 @@
+//! Keeps the raw body of every response for `--archive-dir`, so a response
+//! that failed to deserialize can be looked at byte for byte afterwards.
+//!
+//! Each body is gzipped into its own file named after when it arrived, the
+//! order it arrived in and the endpoint it came from, like
+//! `20240101T030000Z-000042-hub.wowup.io-curseforge-addons-fingerprint.gz`.
+//! `index.ndjson` lists every file with the full url it came from.
+
+use crate::date::Timestamp;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Serialize;
+
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Longest endpoint part of a file name, so long urls still make valid
+/// names.
+const MAX_ENDPOINT_CHARS: usize = 80;
+
+/// Responses archived by this process, which numbers the files so ones that
+/// arrive in the same second stay apart.
+static ARCHIVED: AtomicUsize = AtomicUsize::new(0);
+
+/// One line of `index.ndjson`.
+#[derive(Serialize)]
+struct Entry<'a> {
+    file: &'a str,
+    url: &'a str,
+    received_at: String,
+    bytes: usize,
+}
+
+/// Gzips `body`, the response to `url`, into `dir` and adds it to the index.
+pub fn save(dir: &Path, url: &str, body: &[u8]) -> io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+
+    let received_at = Timestamp::now();
+    let sequence = ARCHIVED.fetch_add(1, Ordering::SeqCst);
+    let file = format!(
+        "{}-{:06}-{}.gz",
+        received_at.compact(),
+        sequence,
+        endpoint(url)
+    );
+
+    let mut encoder = GzEncoder::new(
+        std::fs::File::create(dir.join(&file))?,
+        Compression::default(),
+    );
+    encoder.write_all(body)?;
+    encoder.finish()?;
+
+    let mut line = serde_json::to_vec(&Entry {
+        file: &file,
+        url,
+        received_at: received_at.to_string(),
+        bytes: body.len(),
+    })?;
+    line.push(b'\n');
+
+    // Appended with a single write, so concurrent responses don't
+    // interleave their lines
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join("index.ndjson"))?
+        .write_all(&line)
+}
+
+/// The host and path of `url` with everything but letters, digits and dots
+/// replaced by dashes.
+fn endpoint(url: &str) -> String {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let without_query = without_scheme.split('?').next().unwrap_or_default();
+
+    let endpoint = without_query
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .take(MAX_ENDPOINT_CHARS)
+        .collect::<String>();
+
+    endpoint.trim_matches('-').to_owned()
+}
@@ -0,0 +1,230 @@
+//! Compares a live run against a previously blessed json report, or a
+//! snapshot saved with `--save-baseline`.
+
+use crate::report::{AuditReport, PackageStatus};
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Display};
+use std::path::Path;
+
+/// The subset of a saved `AuditReport` needed for the comparison, which is
+/// also what `--save-baseline` writes.
+#[derive(Serialize, Deserialize)]
+pub struct Baseline {
+    /// Absent from snapshots of reports written before it was added.
+    #[serde(default)]
+    unique_packages: Option<usize>,
+    #[serde(default)]
+    apis: Vec<ApiCounts>,
+    packages: Vec<PackageStatus>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ApiCounts {
+    api: String,
+    matched_packages: usize,
+    fingerprint_matches: usize,
+}
+
+/// A count that differs between the baseline and the current report.
+pub struct CountChange {
+    /// Key of the API the count is for, absent for unique packages.
+    pub api: Option<String>,
+    pub what: &'static str,
+    pub before: usize,
+    pub after: usize,
+}
+
+pub enum Regression {
+    /// The package matched on this API in the baseline but no longer does.
+    Lost {
+        package: String,
+        id: i32,
+        api: String,
+    },
+    /// The package matches on this API but didn't in the baseline. Only
+    /// reported with `--baseline-strict`.
+    Gained {
+        package: String,
+        id: i32,
+        api: String,
+    },
+}
+
+impl Regression {
+    /// Id of the package whose match status changed.
+    pub fn id(&self) -> i32 {
+        match self {
+            Regression::Lost { id, .. } | Regression::Gained { id, .. } => *id,
+        }
+    }
+}
+
+impl Display for Regression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Regression::Lost { package, id, api } => {
+                write!(f, "{} ({}) no longer matches on {}", package, id, api)
+            }
+            Regression::Gained { package, id, api } => {
+                write!(f, "{} ({}) now matches on {}", package, id, api)
+            }
+        }
+    }
+}
+
+impl Baseline {
+    /// Snapshots the unique packages, match counts and per-package match
+    /// status of `report`.
+    pub fn new(report: &AuditReport) -> Baseline {
+        Baseline {
+            unique_packages: Some(report.unique_packages),
+            apis: report
+                .apis
+                .iter()
+                .map(|api| ApiCounts {
+                    api: api.api.to_owned(),
+                    matched_packages: api.matched_packages,
+                    fingerprint_matches: api.fingerprint_matches,
+                })
+                .collect(),
+            packages: report
+                .packages
+                .iter()
+                .map(|p| PackageStatus {
+                    id: p.id,
+                    name: p.name.clone(),
+                    download_count: p.download_count,
+                    matched_by: p.matched_by.clone(),
+                    partial_on: p.partial_on.clone(),
+                    failed_on: p.failed_on.clone(),
+                    download_urls: None,
+                    latest_files: vec![],
+                })
+                .collect(),
+        }
+    }
+
+    /// Reads a snapshot or a full json report.
+    pub fn load(path: &Path) -> Result<Baseline, anyhow::Error> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), anyhow::Error> {
+        let mut json = serde_json::to_vec_pretty(self)?;
+        json.push(b'\n');
+        std::fs::write(path, json)?;
+
+        Ok(())
+    }
+
+    /// Unique packages and per-API match counts that differ from `report`,
+    /// leaving out APIs the baseline didn't audit.
+    pub fn count_changes(&self, report: &AuditReport) -> Vec<CountChange> {
+        let mut changes = vec![];
+
+        if let Some(before) = self.unique_packages {
+            if before != report.unique_packages {
+                changes.push(CountChange {
+                    api: None,
+                    what: "unique packages",
+                    before,
+                    after: report.unique_packages,
+                });
+            }
+        }
+
+        for api in &report.apis {
+            let previous = match self.apis.iter().find(|a| a.api == api.api) {
+                Some(previous) => previous,
+                None => continue,
+            };
+
+            let counts = [
+                (
+                    "matched packages",
+                    previous.matched_packages,
+                    api.matched_packages,
+                ),
+                (
+                    "fingerprint matches",
+                    previous.fingerprint_matches,
+                    api.fingerprint_matches,
+                ),
+            ];
+
+            for &(what, before, after) in &counts {
+                if before != after {
+                    changes.push(CountChange {
+                        api: Some(api.api.to_owned()),
+                        what,
+                        before,
+                        after,
+                    });
+                }
+            }
+        }
+
+        changes
+    }
+
+    /// Returns every change in match status for packages present in both
+    /// the baseline and the current report, sorted by package id.
+    pub fn compare(&self, report: &AuditReport, strict: bool) -> Vec<Regression> {
+        let previous = self
+            .packages
+            .iter()
+            .map(|p| (p.id, p))
+            .collect::<HashMap<_, _>>();
+
+        let mut regressions = vec![];
+
+        for current in &report.packages {
+            let previous = match previous.get(&current.id) {
+                Some(previous) => previous,
+                None => continue,
+            };
+
+            for api in &previous.matched_by {
+                // A failed batch says nothing about whether the package matches
+                if !current.matched_by.contains(api) && !current.failed_on.contains(api) {
+                    regressions.push(Regression::Lost {
+                        package: current.name.clone(),
+                        id: current.id,
+                        api: api.clone(),
+                    });
+                }
+            }
+
+            if strict {
+                for api in &current.matched_by {
+                    if !previous.matched_by.contains(api) {
+                        regressions.push(Regression::Gained {
+                            package: current.name.clone(),
+                            id: current.id,
+                            api: api.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        regressions
+    }
+}
+
+/// Packages that no longer match, counted by the key of the API they were
+/// lost on.
+pub fn lost_by_api(regressions: &[Regression]) -> BTreeMap<&str, usize> {
+    let mut lost = BTreeMap::new();
+
+    for regression in regressions {
+        if let Regression::Lost { api, .. } = regression {
+            *lost.entry(api.as_str()).or_default() += 1;
+        }
+    }
+
+    lost
+}
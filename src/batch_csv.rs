@@ -0,0 +1,36 @@
+//! Writes a CSV row per fingerprint batch of every API for `--batches-file`.
+
+use crate::ApiResults;
+
+use std::io::{self, Write};
+
+pub fn write_header(out: &mut impl Write) -> io::Result<()> {
+    writeln!(
+        out,
+        "game_id,api,batch,fingerprints,attempts,succeeded,error,latency_ms,total_latency_ms"
+    )
+}
+
+/// Writes the batches of one game in dispatch order. `error` is the kind of
+/// the final attempt's error, empty if it succeeded.
+pub fn write(out: &mut impl Write, game_id: u32, results: &[ApiResults]) -> io::Result<()> {
+    for result in results {
+        for (index, response) in result.responses.iter().enumerate() {
+            writeln!(
+                out,
+                "{},{},{},{},{},{},{},{},{}",
+                game_id,
+                result.api_choice.key(),
+                index,
+                response.fingerprints,
+                response.attempts,
+                response.result.is_ok(),
+                response.result.as_ref().err().map_or("", |e| e.kind()),
+                response.latency.as_millis(),
+                response.total_latency.as_millis()
+            )?;
+        }
+    }
+
+    Ok(())
+}
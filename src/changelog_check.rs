@@ -0,0 +1,232 @@
+//! Requests the changelog of every exactly matched file for
+//! `--check-changelogs`, since Ajour shows it for each update, and reports
+//! the ones each API returns empty, missing or garbled.
+//!
+//! The requests go through `send_request`, so they're recorded and replayed
+//! like the fingerprint requests.
+
+use crate::cli::{ApiVersion, Opts};
+use crate::error::AuditError;
+use crate::{curse_v1, send_request, ApiChoice, ApiResults, CURSE_ADDON_URL};
+
+use async_std::future::timeout;
+use futures::{stream, StreamExt};
+use isahc::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+const WOWUP_ADDON_URL: &str = "https://hub.wowup.io/curseforge/addons";
+
+/// What a changelog in mangled encoding or escaped twice contains, which
+/// Ajour would show as is.
+const GARBLED_MARKERS: [&str; 5] = ["\u{fffd}", "â€", "Ã©", "&lt;/", "&amp;lt;"];
+
+#[derive(Default, Serialize)]
+pub struct ChangelogCheck {
+    /// Matched files whose changelog was requested.
+    pub checked: usize,
+    /// Changelogs with readable content.
+    pub ok: usize,
+    /// Changelogs that came back blank.
+    pub empty: usize,
+    /// Changelogs the API answered 404 for.
+    pub not_found: usize,
+    /// Changelogs that aren't valid UTF-8, or hold mangled or double
+    /// escaped HTML.
+    pub garbled: usize,
+    /// Requests that failed otherwise, such as after a timeout.
+    pub failed: usize,
+    /// Every changelog that wasn't ok, sorted by package id.
+    pub broken: Vec<BrokenChangelog>,
+}
+
+#[derive(Serialize)]
+pub struct BrokenChangelog {
+    pub package_id: i32,
+    pub file_id: i64,
+    pub problem: ChangelogProblem,
+    /// Whether another API returned a readable changelog for the package,
+    /// which points at this API rather than the author.
+    pub ok_elsewhere: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangelogProblem {
+    Empty,
+    NotFound,
+    Garbled,
+    Failed,
+}
+
+/// The v1 changelog response, whose `data` is the changelog HTML.
+#[derive(Deserialize)]
+struct ChangelogEnvelope {
+    data: String,
+}
+
+/// Requests the changelog of every API's matched files, returning the
+/// outcome per API in `results` order.
+pub async fn check(
+    client: &HttpClient,
+    opts: &Opts,
+    results: &[ApiResults],
+) -> Vec<ChangelogCheck> {
+    let request_timeout = Duration::from_secs(opts.request_timeout);
+
+    let mut outcomes = vec![];
+
+    for result in results {
+        let files = result
+            .matched_files()
+            .into_iter()
+            .map(|(package_id, file)| (package_id, file.id))
+            .collect::<Vec<_>>();
+
+        let problems = stream::iter(files)
+            .map(|(package_id, file_id)| async move {
+                let request = changelog(client, opts, result.api_choice, package_id, file_id);
+
+                let problem = match timeout(request_timeout, request).await {
+                    Ok(Ok(text)) => classify(&text),
+                    Ok(Err(AuditError::BadStatus { code: 404, .. })) => {
+                        Some(ChangelogProblem::NotFound)
+                    }
+                    Ok(Err(AuditError::Deserialize(_))) => Some(ChangelogProblem::Garbled),
+                    Ok(Err(e)) => {
+                        debug!(package_id, file_id, "changelog request failed - {}", e);
+                        Some(ChangelogProblem::Failed)
+                    }
+                    Err(_) => {
+                        debug!(package_id, file_id, "changelog request timed out");
+                        Some(ChangelogProblem::Failed)
+                    }
+                };
+
+                (package_id, (file_id, problem))
+            })
+            .buffer_unordered(opts.max_connections)
+            .collect::<BTreeMap<_, _>>()
+            .await;
+
+        outcomes.push(problems);
+    }
+
+    // Packages with a readable changelog on at least one API
+    let readable = outcomes
+        .iter()
+        .flat_map(|problems| {
+            problems
+                .iter()
+                .filter(|(_, (_, problem))| problem.is_none())
+                .map(|(&package_id, _)| package_id)
+        })
+        .collect::<BTreeSet<_>>();
+
+    outcomes
+        .into_iter()
+        .map(|problems| {
+            let mut check = ChangelogCheck::default();
+
+            for (package_id, (file_id, problem)) in problems {
+                check.checked += 1;
+
+                match problem {
+                    None => check.ok += 1,
+                    Some(ChangelogProblem::Empty) => check.empty += 1,
+                    Some(ChangelogProblem::NotFound) => check.not_found += 1,
+                    Some(ChangelogProblem::Garbled) => check.garbled += 1,
+                    Some(ChangelogProblem::Failed) => check.failed += 1,
+                }
+
+                if let Some(problem) = problem {
+                    check.broken.push(BrokenChangelog {
+                        package_id,
+                        file_id,
+                        problem,
+                        ok_elsewhere: readable.contains(&package_id),
+                    });
+                }
+            }
+
+            check
+        })
+        .collect()
+}
+
+/// Fetches a file's changelog from `api_choice`. Bodies that aren't UTF-8
+/// are decoded lossily, which `classify` flags as garbled.
+async fn changelog(
+    client: &HttpClient,
+    opts: &Opts,
+    api_choice: ApiChoice,
+    package_id: i32,
+    file_id: i64,
+) -> Result<String, AuditError> {
+    let url = match (api_choice, opts.api_version) {
+        (ApiChoice::Curse, ApiVersion::V1) => format!(
+            "{}/{}/files/{}/changelog",
+            curse_v1::MOD_URL,
+            package_id,
+            file_id
+        ),
+        (ApiChoice::Curse, ApiVersion::V2) => format!(
+            "{}/{}/file/{}/changelog",
+            CURSE_ADDON_URL, package_id, file_id
+        ),
+        (ApiChoice::WowUp, _) => format!(
+            "{}/{}/file/{}/changelog",
+            WOWUP_ADDON_URL, package_id, file_id
+        ),
+        // `check_options` rejects --check-changelogs with these
+        (ApiChoice::Tukui, _) | (ApiChoice::WowInterface, _) | (ApiChoice::TownlongYak, _) => {
+            return Err(AuditError::InvalidResponse(format!(
+                "{} has no changelog endpoint",
+                api_choice.name()
+            )))
+        }
+    };
+
+    let request = Request::builder().method("GET").uri(&url);
+    let request = api_choice.api().authorize(opts, request);
+
+    let (body, _, _) = send_request(client, opts, request.body(vec![])?).await?;
+
+    if matches!(
+        (api_choice, opts.api_version),
+        (ApiChoice::Curse, ApiVersion::V1)
+    ) {
+        let envelope: ChangelogEnvelope =
+            serde_json::from_slice(&body).map_err(|e| AuditError::Deserialize(e.into()))?;
+
+        return Ok(envelope.data);
+    }
+
+    let text = String::from_utf8_lossy(&body).into_owned();
+
+    // Some backends send the HTML as a json string
+    match serde_json::from_str::<String>(&text) {
+        Ok(unquoted) => Ok(unquoted),
+        Err(_) => Ok(text),
+    }
+}
+
+/// The problem with a changelog's text, if any.
+fn classify(text: &str) -> Option<ChangelogProblem> {
+    let readable = text
+        .replace("<p>", "")
+        .replace("</p>", "")
+        .replace("<br>", "")
+        .replace("&nbsp;", "");
+
+    if readable.trim().is_empty() {
+        Some(ChangelogProblem::Empty)
+    } else if GARBLED_MARKERS.iter().any(|marker| text.contains(marker)) {
+        Some(ChangelogProblem::Garbled)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,37 @@
+//! Checks a finished run failed, gathered so every one of them is reported
+//! before the process exits.
+
+use serde::Serialize;
+
+use std::io::{self, Write};
+
+#[derive(Serialize)]
+pub struct CheckFailure {
+    /// Flag of the check, such as `--min-success-rate`.
+    pub check: &'static str,
+    /// Keys of the APIs that failed it, empty for checks across every API.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub apis: Vec<&'static str>,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+struct Summary<'a> {
+    status: &'static str,
+    exit_code: i32,
+    failures: &'a [CheckFailure],
+}
+
+/// Writes a single json line to stderr describing every failed check, for
+/// automation that wants more than the exit code.
+pub fn write_summary(failures: &[CheckFailure], exit_code: i32) -> io::Result<()> {
+    let summary = Summary {
+        status: "failed",
+        exit_code,
+        failures,
+    };
+
+    let mut stderr = io::stderr();
+    serde_json::to_writer(&mut stderr, &summary)?;
+    writeln!(stderr)
+}
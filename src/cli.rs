@@ -0,0 +1,1633 @@
+use crate::date::Timestamp;
+
+use isahc::http::Uri;
+use structopt::StructOpt;
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(
+    name = "ajour-api-test",
+    about = "Compares fingerprint matches between the Curse and WowUp APIs"
+)]
+pub struct Opts {
+    /// Log additional diagnostics, such as every request with its status
+    /// and latency, or with `-vv` the HTTP client's own trace events
+    #[structopt(short, long, parse(from_occurrences))]
+    pub verbose: u8,
+
+    /// Format of the log events written to stderr: text or json
+    ///
+    /// json writes an object per line with the event's time, level, message
+    /// and fields.
+    #[structopt(long, default_value = "text")]
+    pub log_format: LogFormat,
+
+    /// Check every API's request encoding and response decoding against
+    /// bundled samples without any network access, then exit
+    ///
+    /// Exits with code 2 if any API fails.
+    #[structopt(long)]
+    pub self_test: bool,
+
+    /// Print the settings the run would use and where each came from, then
+    /// exit without sending any request
+    ///
+    /// Sources are a flag, an environment variable, the flag's default or
+    /// a value built into the tool. The API key itself is never printed.
+    #[structopt(long)]
+    pub explain: bool,
+
+    /// Take every option not given as a flag from this profile of the
+    /// config file
+    ///
+    /// Profiles are `[profile.<name>]` tables keyed by flag names with
+    /// underscores, like `page_size = 100` or `flavor = ["wow_classic"]`.
+    /// Game ids, total, page and batch size, connections, flavors, APIs,
+    /// format, output file and directory, retries, the failure thresholds,
+    /// the latency SLA and the baseline can be set.
+    #[structopt(long)]
+    pub config_profile: Option<String>,
+
+    /// Config file to read `--config-profile` from
+    #[structopt(long, parse(from_os_str), default_value = "api-test.toml")]
+    pub config: PathBuf,
+
+    /// Audit the addons installed in this AddOns directory instead of
+    /// searching, reporting which APIs match each addon folder
+    ///
+    /// Each folder is fingerprinted the way ajour-core does it.
+    #[structopt(
+        long,
+        parse(from_os_str),
+        conflicts_with_all = &["package-id", "packages-stdin", "stream-search"]
+    )]
+    pub addon_dir: Option<PathBuf>,
+
+    /// Keep the fingerprints computed by `--addon-dir` in this directory, so
+    /// later scans only fingerprint the folders whose files changed
+    ///
+    /// A folder is fingerprinted again whenever any of its files is added,
+    /// removed or has a different size or modification time.
+    #[structopt(long, parse(from_os_str), requires = "addon-dir")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Run every audit job of this ndjson file instead of a single audit,
+    /// writing one json report per job as ndjson
+    ///
+    /// Each line is an object that overrides some flags for its job, with
+    /// any of `id`, `game_id`, `apis`, `total`, `name_contains`, `category`,
+    /// `exclude_category`, `flavor`, `since`, `sample`, `seed` and
+    /// `package_limit`, for example
+    /// `{"id": "classic", "game_id": 1, "apis": ["curse"], "total": 500}`.
+    /// Output lines have a `status` of `ok` with the `report`, or `error`
+    /// with the `error`, and the job's `line` and `id`. A failed job doesn't
+    /// stop the others, but exits with code 2 once every job ran.
+    #[structopt(
+        long,
+        parse(from_os_str),
+        conflicts_with_all = &[
+            "addon-dir",
+            "package-id",
+            "packages-stdin",
+            "baseline",
+            "save-baseline",
+            "summary-file",
+            "metrics-file",
+            "raw-matches-file",
+            "compare-against-ajour-core",
+        ]
+    )]
+    pub jobs_file: Option<PathBuf>,
+
+    /// Number of `--jobs-file` jobs audited at once
+    ///
+    /// Output lines stay in file order. Concurrent jobs share the retry
+    /// budget, so each report's `retries_used` may include the others'.
+    #[structopt(long, default_value = "1", requires = "jobs-file")]
+    pub job_concurrency: usize,
+
+    /// Comma separated CurseForge game ids to audit, each searched and
+    /// fingerprinted independently
+    ///
+    /// With several games the report has a section per game followed by
+    /// totals across them.
+    #[structopt(long = "game-id", use_delimiter = true, default_value = "1")]
+    pub game_ids: Vec<u32>,
+
+    /// Number of packages to fetch from the search, paging as needed
+    ///
+    /// Pages are requested `--max-connections` at a time. Auditing every
+    /// package of a game needs a `--total` past its package count and
+    /// enough `--max-pages` to cover it.
+    #[structopt(long, default_value = "500")]
+    pub total: usize,
+
+    /// Number of packages requested per search page
+    #[structopt(long, default_value = "500")]
+    pub page_size: usize,
+
+    /// Seconds a cached search page is reused for, or 0 to always search
+    ///
+    /// Pages are cached by their query parameters, so changing any search
+    /// flag searches again. Not used with `--record` or `--replay`.
+    #[structopt(long, default_value = "3600")]
+    pub search_cache_ttl: u64,
+
+    /// Directory of the search cache, defaulting to `ajour-api-test` under
+    /// `XDG_CACHE_HOME` or `~/.cache`
+    #[structopt(long, parse(from_os_str))]
+    pub search_cache_dir: Option<PathBuf>,
+
+    /// Search again instead of using cached search pages, replacing them
+    #[structopt(long)]
+    pub refresh: bool,
+
+    /// Order of the search: popularity, total-downloads, last-updated, name
+    /// or date-created
+    ///
+    /// `--offset`, `--total` and `--max-pages` count packages in this order.
+    #[structopt(long, default_value = "popularity")]
+    pub sort: CurseSort,
+
+    /// Which search pages to audit: top, random-pages or stratified
+    ///
+    /// `top` takes `--total` packages from `--offset` on, which only covers
+    /// the most maintained addons. `random-pages` requests pages at random
+    /// indices below `--catalog-size` instead, and `stratified` sorts by
+    /// total downloads and takes the same number of random pages from each
+    /// of `--strata` ranges of the catalog, so the long tail of addons is
+    /// audited too. Pages are picked with `--seed`.
+    #[structopt(long, default_value = "top")]
+    pub search_sampling: SearchSampling,
+
+    /// Search indices `--search-sampling` picks pages from
+    #[structopt(long, default_value = "10000")]
+    pub catalog_size: usize,
+
+    /// Ranges of total downloads `--search-sampling stratified` splits the
+    /// catalog into, from most to least downloaded
+    #[structopt(long, default_value = "4")]
+    pub strata: usize,
+
+    /// Number of packages whose fingerprints are sent in each request
+    #[structopt(long, default_value = "25")]
+    pub batch_size: usize,
+
+    /// Also end a batch before its fingerprints pass this many
+    ///
+    /// Packages with many modules make some batches of `--batch-size` far
+    /// larger than others. A package with more fingerprints than this is
+    /// still sent whole, in a batch of its own.
+    #[structopt(long)]
+    pub max_batch_fingerprints: Option<usize>,
+
+    /// Number of connections kept open to each API host, which caps how many
+    /// search and fingerprint requests are in flight at once
+    ///
+    /// With `--adaptive` it's where each API's fingerprint requests start.
+    #[structopt(long, default_value = "3")]
+    pub max_connections: usize,
+
+    /// Search index to start from, e.g. `--offset 500` skips the 500 most
+    /// popular packages
+    #[structopt(long, default_value = "0")]
+    pub offset: usize,
+
+    /// Read a json array of packages from stdin instead of searching
+    ///
+    /// Filters, `--sample` and `--package-limit` still apply to the packages
+    /// read.
+    #[structopt(long, conflicts_with_all = &["package-id", "stream-search"])]
+    pub packages_stdin: bool,
+
+    /// Audit the CurseForge ids or fingerprints listed in this file, one per
+    /// line, instead of searching
+    ///
+    /// Blank lines and anything after a `#` are ignored. Ids are fetched
+    /// and audited like searched packages, so filters, `--sample` and
+    /// `--package-limit` still apply, and ids without a package are skipped
+    /// with a warning. Fingerprints are looked up like the `fingerprint`
+    /// subcommand does.
+    #[structopt(
+        long,
+        parse(from_os_str),
+        conflicts_with_all = &["package-id", "packages-stdin", "stream-search", "jobs-file", "addon-dir"]
+    )]
+    pub input_file: Option<PathBuf>,
+
+    /// What `--input-file` lists: id or fingerprint
+    #[structopt(long, default_value = "id", requires = "input-file")]
+    pub input_kind: InputKind,
+
+    /// Skip the search and audit only the package with this CurseForge id,
+    /// printing which API matched each of its fingerprints
+    #[structopt(long, conflicts_with_all = &["stream-search", "sample", "package-limit"])]
+    pub package_id: Option<i32>,
+
+    /// Start fingerprinting each search page as soon as it arrives instead of
+    /// waiting for the whole search
+    ///
+    /// Batches never span search pages in this mode, so fingerprints shared
+    /// between pages aren't deduplicated. Up to `--max-connections` pages are
+    /// fingerprinted at once.
+    #[structopt(long, conflicts_with_all = &["sample", "package-limit"])]
+    pub stream_search: bool,
+
+    /// Only audit packages updated on or after this date, given as YYYY-MM-DD
+    /// or an RFC 3339 timestamp
+    #[structopt(long, conflicts_with_all = &["stream-search", "package-id"])]
+    pub since: Option<Timestamp>,
+
+    /// Only audit packages whose name contains this text, ignoring case
+    #[structopt(long, conflicts_with_all = &["stream-search", "package-id"])]
+    pub name_contains: Option<String>,
+
+    /// Only audit packages in one of these comma separated Curse categories,
+    /// e.g. `Unit Frames`, ignoring case
+    #[structopt(
+        long = "category",
+        use_delimiter = true,
+        conflicts_with_all = &["stream-search", "package-id"]
+    )]
+    pub categories: Vec<String>,
+
+    /// Skip packages in any of these comma separated Curse categories, e.g.
+    /// `Plugins`, ignoring case
+    #[structopt(
+        long = "exclude-category",
+        use_delimiter = true,
+        conflicts_with_all = &["stream-search", "package-id"]
+    )]
+    pub exclude_categories: Vec<String>,
+
+    /// Only audit the latest files of these comma separated game version
+    /// flavors, e.g. `wow_classic`, skipping packages without one
+    ///
+    /// Fingerprints of other flavors' files aren't sent, so every count
+    /// covers just these flavors.
+    #[structopt(
+        long = "flavor",
+        use_delimiter = true,
+        conflicts_with_all = &["stream-search", "package-id"]
+    )]
+    pub flavors: Vec<String>,
+
+    /// Audit Retail, Classic Era and Wrath concurrently from a single search
+    /// and add a matrix of every API's match rate per flavor
+    ///
+    /// Each flavor is audited as with `--flavor`, so its report only covers
+    /// its own latest files. The flavors' batches are sent at the same time,
+    /// each with up to `--max-connections` requests.
+    #[structopt(
+        long,
+        conflicts_with_all = &[
+            "flavor",
+            "stream-search",
+            "package-id",
+            "packages-stdin",
+            "input-file",
+            "max-memory-mb",
+            "compare-against-ajour-core",
+            "watch",
+            "jobs-file",
+        ]
+    )]
+    pub all_flavors: bool,
+
+    /// Only audit packages whose latest files have at least this many
+    /// distinct fingerprints
+    ///
+    /// Matches of packages with only one or two folders say little, so this
+    /// keeps the audit to substantial addons.
+    #[structopt(long, conflicts_with_all = &["stream-search", "package-id"])]
+    pub min_fingerprints: Option<usize>,
+
+    /// Count partial fingerprint matches as matches too
+    ///
+    /// A package then counts as matched by an API if any of its files matched
+    /// exactly or partially, which affects matched package counts,
+    /// discrepancies and baseline comparisons. Fingerprint match counts,
+    /// version disagreements and latencies still only consider exact matches.
+    #[structopt(long)]
+    pub count_partial: bool,
+
+    /// Fail the run if the search and filters leave fewer packages than
+    /// this
+    ///
+    /// Guards monitoring against a search that silently came back short,
+    /// which would otherwise produce a small but clean looking audit. Exits
+    /// with code 5, before sampling and `--package-limit` are applied.
+    #[structopt(long, conflicts_with = "package-id")]
+    pub assert_total: Option<usize>,
+
+    /// Exit code used when the search and filters leave no packages to audit
+    #[structopt(long, default_value = "0")]
+    pub empty_exit_code: i32,
+
+    /// Audit a random sample of N packages from the search results
+    ///
+    /// Sampling happens after filters and before `--package-limit`.
+    #[structopt(long)]
+    pub sample: Option<usize>,
+
+    /// Seed for `--sample` and `--search-sampling`, so the same sample can
+    /// be reproduced
+    ///
+    /// Defaults to a random seed, which is printed and recorded in the report.
+    #[structopt(long)]
+    pub seed: Option<u64>,
+
+    /// Audit only the first N packages returned by the search
+    #[structopt(long)]
+    pub package_limit: Option<usize>,
+
+    /// Maximum number of search requests to issue, regardless of `--total`
+    #[structopt(long, default_value = "50")]
+    pub max_pages: usize,
+
+    /// Comma-separated APIs to audit: curse, wowup, tukui, wowinterface,
+    /// townlong-yak
+    ///
+    /// A single API is audited on its own, its match rate against the
+    /// searched packages telling its health without the other API having to
+    /// be up. Tukui, WowInterface and Townlong-Yak have no fingerprint
+    /// endpoint, so they match the searched packages by name against the
+    /// addon list they serve.
+    #[structopt(
+        long,
+        alias = "backends",
+        use_delimiter = true,
+        default_value = "curse,wowup"
+    )]
+    pub apis: Vec<String>,
+
+    /// Version of the Curse API to query: v2, the legacy forgesvc.net API, or
+    /// v1, the current api.curseforge.com API
+    ///
+    /// Also accepted as `--curse-endpoint legacy` or `--curse-endpoint core`.
+    /// WowUp is unaffected. v1 requires `--api-key`.
+    #[structopt(long, alias = "curse-endpoint", default_value = "v2")]
+    pub api_version: ApiVersion,
+
+    /// Output format of the audit results: text, json or csv
+    ///
+    /// csv writes a row per package with its status on each API, and leaves
+    /// out the report's totals and timings.
+    #[structopt(long, default_value = "text")]
+    pub format: Format,
+
+    /// How to show batch progress: auto, bar, lines or none
+    ///
+    /// `bar` redraws a single stderr line with each API's completed and
+    /// failed batches and the time left. `lines` logs one plain line per
+    /// completed batch to stderr instead, which suits CI logs. `auto` picks
+    /// `bar` when stderr is a terminal and logs are text, and `lines`
+    /// otherwise.
+    #[structopt(long, default_value = "auto")]
+    pub progress: Progress,
+
+    /// Don't show batch progress, the same as `--progress none`
+    #[structopt(long)]
+    pub no_progress: bool,
+
+    /// Template of the summary at the top of the text output, replacing
+    /// `{placeholder}`s with the run's numbers and `\n` with line breaks
+    ///
+    /// For example `{total} audited, {curse_matches} on curse, {discrepancy_pct}% apart`.
+    /// Available are `total`, `packages_fetched`, `unique_packages`,
+    /// `discrepancies` and `discrepancy_pct`, plus `<api>_matches`,
+    /// `<api>_fingerprint_matches`, `<api>_match_pct`, `<api>_failed_batches`
+    /// and `<api>_success_rate` for every audited API. Defaults to the usual
+    /// summary.
+    #[structopt(long)]
+    pub template: Option<String>,
+
+    /// With `--format json`, write the report on a single line instead of
+    /// pretty printing it
+    #[structopt(long)]
+    pub compact: bool,
+
+    /// Write the report in the chosen `--format` to this file instead of
+    /// stdout, creating parent directories as needed
+    ///
+    /// Progress and logs still go to stdout and stderr. `-` means stdout.
+    /// `{run_id}` and `{timestamp}` in the path are replaced with the run's
+    /// id and compact start time, like `report-{timestamp}.json`.
+    #[structopt(long, parse(from_os_str))]
+    pub output_file: Option<PathBuf>,
+
+    /// Write each package's outcome to stdout as soon as every API answered
+    /// its batch, as one json object per line with `ndjson`
+    ///
+    /// Each line holds the package's id, name and the APIs that matched it,
+    /// partially matched it or failed its batch. Since stdout is taken, the
+    /// report needs `--output-file` or `--output-dir`.
+    #[structopt(
+        long,
+        conflicts_with_all = &["jobs-file", "addon-dir", "watch", "max-memory-mb"]
+    )]
+    pub stream: Option<Stream>,
+
+    /// Write every artifact of the run into a new `<timestamp>-<run_id>`
+    /// directory under this one
+    ///
+    /// The bundle holds `report.json` or `report.txt` by `--format`,
+    /// `matches.ndjson`, `per-batch.csv`, `timings.json`, `profile.csv` with
+    /// `--profile`, and for a single game `summary.txt` and `metrics.prom`.
+    /// `--output-file`, `--raw-matches-file`, `--batches-file`,
+    /// `--timings-file`, `--profile-file`, `--summary-file` and
+    /// `--metrics-file` still place their artifact elsewhere. With
+    /// `--jobs-file` only the reports go in.
+    #[structopt(long, parse(from_os_str))]
+    pub output_dir: Option<PathBuf>,
+
+    /// Only print the N most downloaded entries of each discrepancy list in
+    /// text output
+    ///
+    /// The json report always includes every entry.
+    #[structopt(long)]
+    pub top: Option<usize>,
+
+    /// Include every matched fingerprint per API in the json report
+    ///
+    /// This can significantly inflate the report size.
+    #[structopt(long)]
+    pub include_fingerprints_in_report: bool,
+
+    /// Include the download URL of each API's matched file in the package
+    /// and version disagreement entries of the json report
+    ///
+    /// Off by default since it inflates the report size.
+    #[structopt(long)]
+    pub include_urls: bool,
+
+    /// Include the packages that no API exactly matched in the json report,
+    /// as `fully_unmatched`
+    ///
+    /// Packages without any fingerprints to submit are left out.
+    #[structopt(long)]
+    pub include_unmatched: bool,
+
+    /// Send a HEAD request to the download URL of every exactly matched file
+    /// and report each API's dead links, redirects and other failures
+    ///
+    /// A URL several APIs returned is only checked once. Can't be combined
+    /// with `--replay`, which has no recording of these requests.
+    #[structopt(long, conflicts_with = "replay")]
+    pub check_download_urls: bool,
+
+    /// Request the changelog of every exactly matched file from the API that
+    /// matched it and report the ones that are empty, missing or garbled
+    ///
+    /// Lists which packages have a readable changelog on one API but not
+    /// another. Sends up to `--max-connections` requests at once.
+    #[structopt(long)]
+    pub check_changelogs: bool,
+
+    /// Download URLs checked at once with `--check-download-urls`
+    #[structopt(long, default_value = "8", requires = "check-download-urls")]
+    pub download_check_concurrency: usize,
+
+    /// Download a sample of the files several APIs exactly matched to the
+    /// same fingerprint and compare their MD5 and SHA-256 hashes
+    ///
+    /// Reports fingerprints whose files aren't byte-identical across the
+    /// APIs. A URL several APIs returned is only downloaded once. Can't be
+    /// combined with `--replay`, which has no recording of these requests.
+    #[structopt(long, conflicts_with = "replay")]
+    pub verify_file_hashes: bool,
+
+    /// Fingerprints whose files are downloaded with `--verify-file-hashes`
+    #[structopt(long, default_value = "20", requires = "verify-file-hashes")]
+    pub verify_sample_size: usize,
+
+    /// Directory to download files to with `--verify-file-hashes`
+    ///
+    /// The files go in a new directory of the run inside it, which is
+    /// removed afterwards. Defaults to the system's temporary directory.
+    #[structopt(long, parse(from_os_str), requires = "verify-file-hashes")]
+    pub verify_dir: Option<PathBuf>,
+
+    /// Keep the files downloaded with `--verify-file-hashes` instead of
+    /// removing them
+    #[structopt(long, requires = "verify-file-hashes")]
+    pub keep_downloads: bool,
+
+    /// Download a sample of the matched files, fingerprint their folders
+    /// locally and compare the result with the fingerprints the APIs
+    /// advertised
+    ///
+    /// Tells whether a drop in matches comes from how fingerprints are
+    /// computed or from an API's index. `--seed` reproduces the sample. Can't
+    /// be combined with `--replay`, which has no recording of these requests.
+    #[structopt(long, conflicts_with = "replay")]
+    pub recompute_fingerprints: bool,
+
+    /// Files downloaded with `--recompute-fingerprints`
+    #[structopt(long, default_value = "5", requires = "recompute-fingerprints")]
+    pub recompute_sample_size: usize,
+
+    /// Group discrepancies by a fingerprint the packages share instead of
+    /// listing every package under each API
+    ///
+    /// A library bundled in many addons then shows up once, with the
+    /// packages it affects. The report gets a `discrepancy_groups` list.
+    #[structopt(long)]
+    pub dedupe_report: bool,
+
+    /// Explain each discrepancy module by module, telling a file an API is
+    /// missing altogether from one whose modules only partly match
+    ///
+    /// A missing file points at the API's index, while a partly matching
+    /// one points at the fingerprints, so each module is listed with
+    /// whether the API's partial match has it and, if not, the fingerprint
+    /// the API has for the same folder. The report gets a
+    /// `mismatch_explanations` list.
+    #[structopt(long)]
+    pub explain_mismatches: bool,
+
+    /// Attribute what every API matched each submitted fingerprint to, or
+    /// that it missed it, to the packages, files and folders it came from
+    ///
+    /// Lists the fingerprints an API matched to a package they weren't
+    /// submitted for. The report gets a `fingerprint_attribution` list.
+    #[structopt(long)]
+    pub attribute_fingerprints: bool,
+
+    /// Print only the packages some APIs matched and others didn't, and the
+    /// version disagreements, instead of the full text report
+    ///
+    /// Prints a single line when there are none. Exit codes are unaffected.
+    #[structopt(long, conflicts_with = "template")]
+    pub only_discrepancies: bool,
+
+    /// API key sent with Curse search and fingerprint requests
+    ///
+    /// The key is never logged, and `--record` only saves request bodies, so
+    /// it never ends up in recordings either.
+    #[structopt(long, env = "CURSE_API_KEY", hide_env_values = true)]
+    pub api_key: Option<String>,
+
+    /// Token sent as a bearer token with WowUp fingerprint requests, for a
+    /// hub that requires authentication
+    ///
+    /// Like `--api-key` it's never logged, recorded or kept in
+    /// `--state-dir`, and a config profile's `wowup_api_key` is used when
+    /// neither the flag nor `WOWUP_API_KEY` is set.
+    #[structopt(long, env = "WOWUP_API_KEY", hide_env_values = true)]
+    pub wowup_api_key: Option<String>,
+
+    /// Validate each fingerprint response against its embedded JSON Schema
+    /// before deserializing it
+    ///
+    /// Mismatches are reported with the path of the offending field, and fail
+    /// the batch without retrying. This parses every response twice.
+    #[structopt(long)]
+    pub validate_response_schema: bool,
+
+    /// Fail a batch whose fingerprint response has any field this tool
+    /// doesn't know, instead of ignoring it
+    ///
+    /// Catches an API adding or renaming fields early, for CI monitoring.
+    /// The error names the field and where in the body it is, and the batch
+    /// isn't retried. This parses every response twice.
+    #[structopt(long)]
+    pub strict_json: bool,
+
+    /// Compare every fingerprint response with what the response types
+    /// captured, and report fields they drop or that came back null
+    ///
+    /// Unlike `--strict-json` no batch fails, so upstream API changes show up
+    /// in the report before they break Ajour. This parses every response
+    /// twice.
+    #[structopt(long)]
+    pub detect_schema_drift: bool,
+
+    /// Proxy for every request, overriding `HTTPS_PROXY` and `HTTP_PROXY`
+    ///
+    /// Hosts listed in `NO_PROXY` still bypass it.
+    #[structopt(long)]
+    pub proxy: Option<Uri>,
+
+    /// DANGEROUS: accept invalid or self-signed TLS certificates
+    ///
+    /// Only meant for testing against mock servers, never the real APIs.
+    #[structopt(long)]
+    pub insecure: bool,
+
+    /// PEM file of CA certificates to verify TLS connections against instead
+    /// of the system's
+    ///
+    /// For networks whose proxy re-signs TLS traffic with its own CA.
+    #[structopt(long, parse(from_os_str), conflicts_with = "insecure")]
+    pub ca_bundle: Option<PathBuf>,
+
+    /// Seconds to wait for a request to complete, including its response body
+    ///
+    /// Applies to the search request and every fingerprint request.
+    #[structopt(long, default_value = "60")]
+    pub request_timeout: u64,
+
+    /// Abort a request whose transfer stays below this many bytes per
+    /// second for `--low-speed-time` seconds
+    ///
+    /// Catches a stalled response body well before `--request-timeout`. The
+    /// aborted attempt counts as a timeout and is retried like one.
+    #[structopt(long)]
+    pub low_speed_limit: Option<u32>,
+
+    /// Seconds a transfer may stay below `--low-speed-limit` before it's
+    /// aborted
+    #[structopt(long, default_value = "30", requires = "low-speed-limit")]
+    pub low_speed_time: u64,
+
+    /// Abandon a response once its body passes this many bytes
+    ///
+    /// Guards against an endpoint streaming an unbounded body. The batch
+    /// fails without being parsed or retried, and the report counts the
+    /// rejected responses of each API.
+    #[structopt(long, default_value = "67108864")]
+    pub max_response_bytes: u64,
+
+    /// Largest fingerprint request body to send an API, as `api=bytes`,
+    /// like `wowup=200000`
+    ///
+    /// A batch whose body would pass it is split in halves, keeping each
+    /// package's fingerprints together, until every part fits, and the
+    /// parts' matches are merged back into the batch. A batch an API
+    /// rejects with 413 Payload Too Large is split and resent the same way
+    /// whether or not it has a limit. Measured before `--compress-request`.
+    #[structopt(long, use_delimiter = true)]
+    pub max_request_bytes: Vec<RequestLimit>,
+
+    /// Require this HTTP version, `1.1` or `2`, instead of negotiating the
+    /// newest one each API supports
+    ///
+    /// HTTP/2 lets the batches to one host share a single multiplexed
+    /// connection. The report lists the versions each API's responses
+    /// actually came over.
+    #[structopt(long)]
+    pub http_version: Option<HttpVersion>,
+
+    /// Gzip the fingerprint request bodies, resending a batch uncompressed
+    /// if an API rejects it with 415 Unsupported Media Type
+    ///
+    /// The report includes how far each API's request bodies shrank. Not
+    /// applied with `--replay`, whose recordings are keyed by the body.
+    #[structopt(long)]
+    pub compress_request: bool,
+
+    /// Ask the APIs to compress responses with only this encoding, `gzip`
+    /// or `deflate`, or not at all with `identity`, instead of offering
+    /// every encoding curl supports
+    ///
+    /// The report includes the bytes each API's responses took over the
+    /// wire and the encodings they came in, which shows whether an API
+    /// compresses at all.
+    #[structopt(long)]
+    pub response_encoding: Option<ResponseEncoding>,
+
+    /// Send one untimed request to each API's host before fingerprinting,
+    /// so the first batches don't pay for connection setup
+    ///
+    /// Warm-up requests are left out of every metric, and only logged with
+    /// `--verbose`.
+    #[structopt(long)]
+    pub warm_up: bool,
+
+    /// Save every request and response to this directory for later `--replay`
+    #[structopt(long, parse(from_os_str), conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+
+    /// Keep the raw body of every response, search pages and fingerprint
+    /// batches of every API alike, gzipped in this directory
+    ///
+    /// Files are named after when the response arrived and the endpoint it
+    /// came from, and `index.ndjson` lists each with its full url. Unlike
+    /// `--record` the bytes are kept exactly as received, including those
+    /// that failed to deserialize. A body that can't be archived is logged
+    /// without failing the request.
+    #[structopt(long, parse(from_os_str), conflicts_with = "replay")]
+    pub archive_dir: Option<PathBuf>,
+
+    /// Serve responses recorded with `--record` from this directory instead of
+    /// hitting the network
+    ///
+    /// Requests are matched by URI and body, so the replayed run must use the
+    /// same options as the recorded one.
+    #[structopt(long, parse(from_os_str))]
+    pub replay: Option<PathBuf>,
+
+    /// Delay each replayed response by its recorded latency times this
+    /// factor, or serve them as fast as possible with 0
+    ///
+    /// 1 reproduces the original timing, which timing-sensitive behaviour
+    /// such as `--adaptive` needs. Recordings made before latencies were
+    /// kept are always served immediately.
+    #[structopt(long, default_value = "0", requires = "replay")]
+    pub replay_speed: f64,
+
+    /// Keep every completed request of the run in this directory, so a run
+    /// that dies halfway can continue with `--resume`
+    ///
+    /// The directory must be empty unless resuming. The seed of `--sample`
+    /// and `--search-sampling` is kept with it, so a resumed run picks the
+    /// same packages.
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["record", "replay"])]
+    pub state_dir: Option<PathBuf>,
+
+    /// Continue the run kept in `--state-dir`, sending only the search pages
+    /// and batches it hadn't completed, for each API separately
+    ///
+    /// Requests are matched by URI and body, so the resumed run must use the
+    /// same options. Resumed batches report no latency.
+    #[structopt(long, requires = "state-dir")]
+    pub resume: bool,
+
+    /// Adjust the number of in-flight fingerprint requests per API while the
+    /// audit runs instead of relying on a fixed connection limit
+    ///
+    /// Starts at `--max-connections` requests, up to 16, and grows by one
+    /// after each window of requests with stable latency, halving on a
+    /// failure, a retried timeout or 429, or a latency spike (AIMD). The
+    /// level each API settled on is reported.
+    #[structopt(long, conflicts_with = "stream-search")]
+    pub adaptive: bool,
+
+    /// Number of times a failed fingerprint batch is retried
+    #[structopt(long, default_value = "2")]
+    pub retries: usize,
+
+    /// Delay in milliseconds before the first retry of a failed batch,
+    /// doubling with every further retry
+    ///
+    /// Each delay is randomly shortened by up to half, so batches that
+    /// failed together are retried apart.
+    #[structopt(long, default_value = "1000")]
+    pub retry_delay_ms: u64,
+
+    /// Longest delay in milliseconds between retries of a failed batch
+    ///
+    /// A rate limited API's `Retry-After` is capped to it too.
+    #[structopt(long, default_value = "30000")]
+    pub max_retry_delay_ms: u64,
+
+    /// Number of times a rate limited fingerprint batch is resent before it
+    /// fails
+    ///
+    /// These resends wait out the API's pause, and don't count towards
+    /// `--retries` or `--retry-budget`.
+    #[structopt(long, default_value = "10")]
+    pub rate_limit_retries: usize,
+
+    /// Maximum number of retries across the whole run
+    ///
+    /// Once exhausted, failing batches are reported as failed without being
+    /// retried.
+    #[structopt(long)]
+    pub retry_budget: Option<usize>,
+
+    /// Send every batch that succeeded without any exact match once more,
+    /// to tell genuine misses from flaky empty responses
+    ///
+    /// If the second response has matches it's used instead and a warning
+    /// is logged. Doesn't count towards `--retries` or `--retry-budget`.
+    #[structopt(long)]
+    pub verify_empty: bool,
+
+    /// Send a random sample of the batches N more times to every API and
+    /// report the ones whose matches changed between sends
+    ///
+    /// Every API gets the same sample, which `--seed` reproduces. Doesn't
+    /// count towards `--retries` or `--retry-budget`, and can't be combined
+    /// with recording or replaying, which are keyed by the request.
+    #[structopt(long, conflicts_with_all = &["replay", "record", "state-dir"])]
+    pub repeat: Option<usize>,
+
+    /// Batches to sample for `--repeat`
+    #[structopt(long, default_value = "10", requires = "repeat")]
+    pub repeat_batches: usize,
+
+    /// Exit with code 2 if either API's share of successful fingerprint batches
+    /// falls below this percentage
+    #[structopt(long)]
+    pub min_success_rate: Option<f64>,
+
+    /// Exit with code 2 if any API matched less than this percentage of the
+    /// audited packages
+    ///
+    /// Like every check that fails the run, it's evaluated after the report
+    /// is written, and the failed checks are then summarised on stderr as a
+    /// json line like
+    /// `{"status":"failed","exit_code":2,"failures":[{"check":"--fail-if-match-rate-below","apis":["wowup"],"message":"..."}]}`.
+    #[structopt(long)]
+    pub fail_if_match_rate_below: Option<f64>,
+
+    /// Exit with code 2 if any fingerprint batch of any API failed, even
+    /// after its retries
+    #[structopt(long)]
+    pub fail_on_api_error: bool,
+
+    /// Slack or Discord compatible webhook to post a summary to when a run
+    /// crosses a `--notify-*` threshold, or with `--watch` changes
+    ///
+    /// The summary lists every API and game that crossed one. Failing to
+    /// deliver it is logged without failing the run, and the url is never
+    /// logged since it holds the webhook's token.
+    #[structopt(
+        long,
+        env = "AJOUR_API_TEST_WEBHOOK_URL",
+        hide_env_values = true,
+        conflicts_with = "jobs-file"
+    )]
+    pub webhook_url: Option<Uri>,
+
+    /// Notify `--webhook-url` if any API matched less than this percentage
+    /// of the audited packages
+    #[structopt(long, requires = "webhook-url")]
+    pub notify_below_match_rate: Option<f64>,
+
+    /// Notify `--webhook-url` if more than this percentage of any API's
+    /// requests failed, retries included
+    #[structopt(long, requires = "webhook-url")]
+    pub notify_above_error_rate: Option<f64>,
+
+    /// Report whether each API's p95 batch latency stayed under this many
+    /// milliseconds
+    #[structopt(long)]
+    pub latency_sla: Option<u64>,
+
+    /// Exit with code 2 if any API missed `--latency-sla`
+    #[structopt(long, requires = "latency-sla")]
+    pub enforce_sla: bool,
+
+    /// Fail the run if any package matched by Curse wasn't also matched by
+    /// WowUp, listing the offending packages
+    #[structopt(long)]
+    pub require_wowup_parity: bool,
+
+    /// Compare every pair of `--apis`, counting the packages both, one or
+    /// neither of them matched
+    ///
+    /// Printed as a table with a row and column per API, and included in the
+    /// json report. Packages whose batch failed on either API of a pair are
+    /// left out of its counts.
+    #[structopt(long)]
+    pub compare_apis_pairwise: bool,
+
+    /// Fail the run if any package matched by several APIs resolved to a
+    /// different file version on each, listing the mismatches
+    ///
+    /// This is separate from package level discrepancies, which don't fail
+    /// the run on their own.
+    #[structopt(long)]
+    pub strict_versions: bool,
+
+    /// Also look up every batch through ajour-core's own Curse fingerprint
+    /// request and fail the run if it matched any package differently
+    ///
+    /// Each divergence is listed with the package and both results. This
+    /// checks ajour-core rather than the APIs, and can't be replayed.
+    #[structopt(long, conflicts_with_all = &["replay", "addon-dir"])]
+    pub compare_against_ajour_core: bool,
+
+    /// Compare per-package match status against a previously saved json report
+    /// or `--save-baseline` snapshot and exit with code 2 if a package that
+    /// matched there no longer matches
+    ///
+    /// Also lists how many packages each API lost, and the unique package
+    /// and match counts that changed.
+    #[structopt(long, alias = "compare-baseline", parse(from_os_str))]
+    pub baseline: Option<PathBuf>,
+
+    /// Save the unique packages, match counts and per-package match status
+    /// of the run to this file, for a later `--compare-baseline`
+    ///
+    /// With `--compare-baseline` the previous baseline is read first, so the
+    /// same file can be compared against and then replaced. It's written
+    /// even if the comparison fails.
+    #[structopt(long, parse(from_os_str))]
+    pub save_baseline: Option<PathBuf>,
+
+    /// With `--baseline`, also fail when a package matches that didn't in the
+    /// baseline
+    #[structopt(long, requires = "baseline")]
+    pub baseline_strict: bool,
+
+    /// With `--baseline`, only fail when at least this many packages changed
+    ///
+    /// Fewer changes are still listed but reported as within tolerance, so a
+    /// single package flapping doesn't fail the run. It only gates the
+    /// baseline comparison, not `--require-wowup-parity` or
+    /// `--min-success-rate`.
+    #[structopt(long, requires = "baseline")]
+    pub diff_threshold: Option<usize>,
+
+    /// Leave the addons and fingerprints listed in this toml file out of the
+    /// checks that fail the run, listing them as known issues instead
+    ///
+    /// Each `[[ignore]]` entry has an `addon` id or a `fingerprint`, and a
+    /// `reason`. Known issues don't count towards the match rate, and aren't
+    /// reported as `--baseline` regressions, `--require-wowup-parity` misses
+    /// or `--strict-versions` disagreements.
+    #[structopt(long, parse(from_os_str))]
+    pub ignore_file: Option<PathBuf>,
+
+    /// Write a one-line `key=value` status summary to this file
+    ///
+    /// For example `status=ok curse=412 wowup=408 discrepancies=7 discrepancy_pct=1.7`.
+    /// `status` is `error` if any fingerprint batch failed.
+    #[structopt(long, parse(from_os_str))]
+    pub summary_file: Option<PathBuf>,
+
+    /// Write every exact match to this file as ndjson
+    ///
+    /// Each line describes one fingerprint of a matched file:
+    /// `{"api": "curse", "package_id": 1234, "package_name": "Details", "file_id": 5678, "fingerprint": 91011}`.
+    /// `package_name` is null if the API matched a package that wasn't audited.
+    #[structopt(long, parse(from_os_str))]
+    pub raw_matches_file: Option<PathBuf>,
+
+    /// Print how long each API's batches were queued before being sent
+    /// versus how long they took to execute
+    ///
+    /// Long queueing means batches waited for a slot under `--adaptive`
+    /// rather than on the API. Without it every batch is handed to the HTTP
+    /// client at once, so waiting on one of its connections per host counts
+    /// as executing and the batches all show as executing at once.
+    #[structopt(long)]
+    pub profile: bool,
+
+    /// Write the `--profile` timestamps of every batch to this file as CSV
+    ///
+    /// Columns are `game_id,api,batch,enqueued_ms,started_ms,completed_ms,queued_ms,executing_ms,attempts`,
+    /// with times relative to the first batch of the game being enqueued.
+    #[structopt(
+        long,
+        parse(from_os_str),
+        requires = "profile",
+        conflicts_with = "jobs-file"
+    )]
+    pub profile_file: Option<PathBuf>,
+
+    /// Write a CSV row per fingerprint batch of every API to this file
+    ///
+    /// Columns are `game_id,api,batch,fingerprints,attempts,succeeded,error,latency_ms,total_latency_ms`,
+    /// where `error` is the kind of the final attempt's error.
+    #[structopt(long, parse(from_os_str), conflicts_with = "jobs-file")]
+    pub batches_file: Option<PathBuf>,
+
+    /// Write the latency of every fingerprint request, retries included, to
+    /// this file as a json array
+    ///
+    /// Each entry is like
+    /// `{"game_id": 1, "api": "curse", "batch": 0, "attempt": 1, "latency_ms": 412, "succeeded": true}`,
+    /// for benchmarking the APIs beyond the report's percentiles.
+    #[structopt(long, parse(from_os_str), conflicts_with = "jobs-file")]
+    pub timings_file: Option<PathBuf>,
+
+    /// Spill fingerprint matches to a temporary file once the ones held in
+    /// memory pass this many megabytes
+    ///
+    /// Spilled matches are read back without each match's latest files for
+    /// the report, and the file is removed afterwards.
+    #[structopt(long)]
+    pub max_memory_mb: Option<usize>,
+
+    /// Append the audit to this SQLite database, creating its tables if the
+    /// file is new
+    ///
+    /// Runs, per-API counts and latency percentiles, packages, matches and
+    /// batch latencies each go into their own table, keyed by run id and
+    /// game id. Only available when built with the `sqlite` feature.
+    #[cfg(feature = "sqlite")]
+    #[structopt(long, parse(from_os_str))]
+    pub export_sqlite: Option<PathBuf>,
+
+    /// Keep every run in a store to follow match rates over time, as
+    /// `sqlite:<path>`
+    ///
+    /// Each run's summary, per-API counts and the APIs that matched every
+    /// package are appended like --export-sqlite does, and the `history`
+    /// subcommand prints the match-rate trends of the runs kept. Only
+    /// available when built with the `sqlite` feature.
+    #[structopt(long)]
+    pub store: Option<Store>,
+
+    /// Keep running and audit again every `--interval`, only reporting what
+    /// changed since the previous run
+    ///
+    /// The first run writes each game's summary line, later ones the
+    /// changes in match counts and the packages that gained or lost a match.
+    /// Every run is appended to `--store`, and `--webhook-url` is sent the
+    /// changes along with the `--notify-*` thresholds newly crossed.
+    #[structopt(
+        long,
+        conflicts_with_all = &["jobs-file", "addon-dir", "replay", "state-dir", "output-dir", "baseline"]
+    )]
+    pub watch: bool,
+
+    /// Time between the start of `--watch` runs, as a number with a unit of
+    /// s, m, h or d, e.g. `6h`
+    #[structopt(long, default_value = "1h", requires = "watch")]
+    pub interval: Interval,
+
+    /// With `--watch`, serve Prometheus metrics of the latest runs at
+    /// `/metrics` on this address, e.g. `0.0.0.0:9185`
+    ///
+    /// Besides the gauges `--metrics-file` writes, labelled with the game,
+    /// there are counters of runs and of failed batches by error category,
+    /// and a histogram of request durations, summed since the monitor
+    /// started.
+    #[structopt(long, requires = "watch")]
+    pub metrics_listen: Option<SocketAddr>,
+
+    /// With `--watch`, push the same metrics as `--metrics-listen` to the
+    /// Prometheus Pushgateway at this url after every run
+    ///
+    /// They replace the previous push under the job `ajour_api_test`. A push
+    /// that fails is logged without stopping the monitor.
+    #[structopt(long, requires = "watch")]
+    pub pushgateway_url: Option<Uri>,
+
+    /// Browse the finished audit in an interactive table of packages
+    /// instead of scrolling through the report
+    ///
+    /// Packages can be filtered by name or id, or to just discrepancies, and
+    /// opened to see each fingerprint and the APIs that matched it, or the
+    /// raw entries each API returned for them. A pane above shows each
+    /// API's matches, failed batches and latency. The report is still
+    /// written first. Only available when built with the
+    /// `tui` feature.
+    #[cfg(feature = "tui")]
+    #[structopt(long, conflicts_with = "jobs-file")]
+    pub tui: bool,
+
+    /// Write the run's key numbers in OpenMetrics text format to this file,
+    /// for the Prometheus node_exporter textfile collector
+    ///
+    /// The file is written to a temporary path and renamed into place, so a
+    /// scrape never sees a partial file.
+    #[structopt(long, parse(from_os_str))]
+    pub metrics_file: Option<PathBuf>,
+
+    /// Write a self-contained HTML report to this file, for sharing the
+    /// audit
+    ///
+    /// It holds the summary, each API's match rate and latency percentiles,
+    /// a chart of batch latencies, and the discrepancies and version
+    /// disagreements linked to their CurseForge pages. Charts are inline
+    /// SVG, so the file needs nothing else to display.
+    #[structopt(long, parse(from_os_str), conflicts_with = "jobs-file")]
+    pub report_html: Option<PathBuf>,
+
+    /// Write a JUnit XML report to this file, for CI test report tooling
+    ///
+    /// Every audited package is a test case that passes if each API matched
+    /// it. One an API didn't match fails with what every API matched it to,
+    /// one whose batch failed is an error, and one listed by
+    /// `--ignore-file` is skipped.
+    #[structopt(long, parse(from_os_str), conflicts_with = "jobs-file")]
+    pub report_junit: Option<PathBuf>,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+
+    /// Name the search is narrowed to, set by the `search` subcommand.
+    #[structopt(skip)]
+    pub search_filter: Option<String>,
+
+    /// Whether the audit runs through an `AuditRunner`, where failures are
+    /// returned as errors rather than exiting the process.
+    #[structopt(skip)]
+    pub embedded: bool,
+}
+
+/// Flags whose values are credentials, left out of anything that keeps the
+/// command line.
+const SECRET_FLAGS: [&str; 4] = [
+    "--api-key",
+    "--wowup-api-key",
+    "--webhook-url",
+    "--github-token",
+];
+
+/// `args` with the values of credential flags replaced, for keeping or
+/// logging the command line.
+pub fn redact_args(args: &[String]) -> Vec<String> {
+    let mut redacted = Vec::with_capacity(args.len());
+    let mut secret_next = false;
+
+    for arg in args {
+        if secret_next {
+            redacted.push("<redacted>".to_owned());
+            secret_next = false;
+            continue;
+        }
+
+        match arg.split_once('=') {
+            Some((flag, _)) if SECRET_FLAGS.contains(&flag) => {
+                redacted.push(format!("{}=<redacted>", flag));
+            }
+            _ => {
+                secret_next = SECRET_FLAGS.contains(&arg.as_str());
+                redacted.push(arg.clone());
+            }
+        }
+    }
+
+    redacted
+}
+
+/// What to do, auditing when no subcommand is given. Options go before the
+/// subcommand, e.g. `ajour-api-test --apis curse,wowup fingerprint 1234`.
+#[derive(Debug, Clone, StructOpt)]
+pub enum Command {
+    /// Audit the searched packages, the default
+    Audit,
+    /// Audit only the packages the search API finds for a name, listing how
+    /// each API matched every one of them
+    Search { query: String },
+    /// Send these fingerprints to every API and show what each matched them
+    /// to, flagging the fingerprints the APIs disagree on
+    Fingerprint {
+        #[structopt(required = true)]
+        fingerprints: Vec<u32>,
+    },
+    /// Send every API requests a client shouldn't, such as empty, duplicated,
+    /// oversized or malformed fingerprint lists, and show how each answered
+    ///
+    /// The cases are built around this fingerprint, which the APIs should
+    /// match, and sent once each without retries.
+    EdgeCases {
+        fingerprint: u32,
+        /// Fingerprints in the oversized batch
+        #[structopt(long, default_value = "10000")]
+        oversized: usize,
+    },
+    /// Show the latest files of the package with this CurseForge id next to
+    /// the files every API resolves their fingerprints to
+    Resolve { id: i32 },
+    /// Run the same query against the Curse search API and the WowUp hub's
+    /// search, listing the results only one returned and those they rank or
+    /// attribute differently
+    SearchCompare {
+        term: String,
+        /// Game type of the WowUp hub search, such as retail or classic
+        #[structopt(long, default_value = "retail")]
+        game_type: String,
+        /// Results to request from each API
+        #[structopt(long, default_value = "50")]
+        limit: usize,
+    },
+    /// Check the latest release of these GitHub or GitLab repositories the
+    /// way Ajour resolves it, listing the ones that would fail to resolve
+    ///
+    /// Checks that the release's tag holds a version and which zip asset
+    /// each game flavor gets, from the release's `release.json` if it has
+    /// one or else from the asset names. Exits with code 2 if any
+    /// repository would fail to resolve.
+    Releases {
+        /// Repository URLs, such as https://github.com/owner/repo
+        repos: Vec<GitRepo>,
+        /// File listing a repository URL per line
+        #[structopt(long, parse(from_os_str))]
+        repos_file: Option<PathBuf>,
+        /// Token sent to the GitHub API, which allows far more requests an
+        /// hour than anonymous ones
+        #[structopt(long, env = "GITHUB_TOKEN", hide_env_values = true)]
+        github_token: Option<String>,
+    },
+    /// Check the endpoints Tukui serves ElvUI and Tukui through on every
+    /// flavor, listing the ones without a usable version or download URL
+    /// and those that disagree with the flavor's addon list
+    ///
+    /// Exits with code 2 if any endpoint has a problem.
+    Tukui,
+    /// Compare the latest release of the searched packages with the same
+    /// addons on WowInterface, listing those WowInterface lags behind
+    ///
+    /// Addons are paired by `--mapping-file`, or else by name and author.
+    /// Exits with code 2 if any paired addon is more than `--max-lag-days`
+    /// behind.
+    Wowinterface {
+        /// File with a `curse_id,wowinterface_id` pair per line, for addons
+        /// published under different names or authors
+        #[structopt(long, parse(from_os_str))]
+        mapping_file: Option<PathBuf>,
+        /// Days WowInterface's latest release may be older than Curse's
+        #[structopt(long, default_value = "7")]
+        max_lag_days: u64,
+    },
+    /// Check the required dependencies of the searched packages, listing
+    /// those whose dependencies resolve on one API but not another
+    ///
+    /// A dependency resolves if its package exists and the API matches the
+    /// fingerprints of its latest files. Exits with code 2 if any package's
+    /// dependencies resolve differently across the APIs.
+    Dependencies,
+    /// Print the match rate of every API over the runs kept in --store,
+    /// with the change since each game's previous run
+    History {
+        /// Only show each game's last this many runs
+        #[structopt(long, default_value = "20")]
+        last: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            _ => Err(format!(
+                "unknown format `{}`, expected text, json or csv",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputKind {
+    Id,
+    Fingerprint,
+}
+
+impl FromStr for InputKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "id" => Ok(InputKind::Id),
+            "fingerprint" => Ok(InputKind::Fingerprint),
+            _ => Err(format!(
+                "unknown input kind `{}`, expected id or fingerprint",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format `{}`, expected text or json", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    Auto,
+    Bar,
+    Lines,
+    None,
+}
+
+impl FromStr for Progress {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Progress::Auto),
+            "bar" => Ok(Progress::Bar),
+            "lines" => Ok(Progress::Lines),
+            "none" => Ok(Progress::None),
+            _ => Err(format!(
+                "unknown progress `{}`, expected auto, bar, lines or none",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchSampling {
+    Top,
+    RandomPages,
+    Stratified,
+}
+
+impl SearchSampling {
+    pub fn name(self) -> &'static str {
+        match self {
+            SearchSampling::Top => "top",
+            SearchSampling::RandomPages => "random-pages",
+            SearchSampling::Stratified => "stratified",
+        }
+    }
+}
+
+impl FromStr for SearchSampling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "top" => Ok(SearchSampling::Top),
+            "random-pages" => Ok(SearchSampling::RandomPages),
+            "stratified" => Ok(SearchSampling::Stratified),
+            _ => Err(format!(
+                "unknown search sampling `{}`, expected top, random-pages or stratified",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Store {
+    Sqlite(PathBuf),
+}
+
+impl FromStr for Store {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("sqlite", path)) if !path.is_empty() => Ok(Store::Sqlite(PathBuf::from(path))),
+            _ => Err(format!("unknown store `{}`, expected sqlite:<path>", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Ndjson,
+}
+
+impl FromStr for Stream {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ndjson" => Ok(Stream::Ndjson),
+            _ => Err(format!("unknown stream format `{}`, expected ndjson", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval(pub Duration);
+
+impl FromStr for Interval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid interval `{}`, expected a number followed by s, m, h or d",
+                s
+            )
+        };
+
+        let unit = match s.chars().last().ok_or_else(invalid)? {
+            's' => 1,
+            'm' => 60,
+            'h' => 3_600,
+            'd' => 86_400,
+            _ => return Err(invalid()),
+        };
+        let count = s[..s.len() - 1].parse::<u64>().map_err(|_| invalid())?;
+
+        if count == 0 {
+            return Err(invalid());
+        }
+
+        Ok(Interval(Duration::from_secs(count * unit)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Forge {
+    GitHub,
+    GitLab,
+}
+
+impl Forge {
+    pub fn name(self) -> &'static str {
+        match self {
+            Forge::GitHub => "github",
+            Forge::GitLab => "gitlab",
+        }
+    }
+}
+
+/// A repository on GitHub or GitLab, given by its URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitRepo {
+    pub forge: Forge,
+    /// `owner/repo`, or with GitLab subgroups `group/subgroup/repo`.
+    pub path: String,
+}
+
+impl FromStr for GitRepo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "unknown repository `{}`, expected a github.com or gitlab.com URL",
+                s
+            )
+        };
+
+        let rest = s
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .trim_start_matches("www.");
+        let (host, path) = rest.split_once('/').ok_or_else(invalid)?;
+        let path = path.trim_end_matches('/').trim_end_matches(".git");
+
+        let forge = match host {
+            "github.com" if path.split('/').count() == 2 => Forge::GitHub,
+            "gitlab.com" if path.split('/').count() >= 2 => Forge::GitLab,
+            _ => return Err(invalid()),
+        };
+
+        if path.split('/').any(str::is_empty) {
+            return Err(invalid());
+        }
+
+        Ok(GitRepo {
+            forge,
+            path: path.to_owned(),
+        })
+    }
+}
+
+/// A `--max-request-bytes` value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestLimit {
+    /// Key of the API, checked against the known APIs once parsed.
+    pub api: String,
+    pub bytes: usize,
+}
+
+impl FromStr for RequestLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (api, bytes) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected `api=bytes`, got `{}`", s))?;
+        let bytes = match bytes.trim().parse() {
+            Ok(bytes) if bytes > 0 => bytes,
+            _ => return Err(format!("invalid byte limit `{}`", bytes)),
+        };
+
+        Ok(RequestLimit {
+            api: api.trim().to_owned(),
+            bytes,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpVersion {
+    Http11,
+    Http2,
+}
+
+impl FromStr for HttpVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1.1" => Ok(HttpVersion::Http11),
+            "2" => Ok(HttpVersion::Http2),
+            _ => Err(format!("unknown http version `{}`, expected 1.1 or 2", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseEncoding {
+    Gzip,
+    Deflate,
+    Identity,
+}
+
+impl ResponseEncoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            ResponseEncoding::Gzip => "gzip",
+            ResponseEncoding::Deflate => "deflate",
+            ResponseEncoding::Identity => "identity",
+        }
+    }
+}
+
+impl FromStr for ResponseEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gzip" => Ok(ResponseEncoding::Gzip),
+            "deflate" => Ok(ResponseEncoding::Deflate),
+            "identity" => Ok(ResponseEncoding::Identity),
+            _ => Err(format!(
+                "unknown response encoding `{}`, expected gzip, deflate or identity",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiVersion {
+    V1,
+    V2,
+}
+
+impl FromStr for ApiVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "v1" | "core" => Ok(ApiVersion::V1),
+            "v2" | "legacy" => Ok(ApiVersion::V2),
+            _ => Err(format!(
+                "unknown api version `{}`, expected v1, v2, core or legacy",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CurseSort {
+    DateCreated = 1,
+    LastUpdated = 2,
+    Name = 3,
+    Popularity = 4,
+    TotalDownloads = 5,
+}
+
+impl CurseSort {
+    /// The `sortField` v1 uses for the same order, since its numbering
+    /// differs from v2.
+    pub const fn v1_field(self) -> u8 {
+        match self {
+            // v1 has no creation date order, release date is the closest
+            CurseSort::DateCreated => 11,
+            CurseSort::LastUpdated => 3,
+            CurseSort::Name => 4,
+            CurseSort::Popularity => 2,
+            CurseSort::TotalDownloads => 6,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CurseSort::DateCreated => "date-created",
+            CurseSort::LastUpdated => "last-updated",
+            CurseSort::Name => "name",
+            CurseSort::Popularity => "popularity",
+            CurseSort::TotalDownloads => "total-downloads",
+        }
+    }
+}
+
+impl FromStr for CurseSort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "date-created" => Ok(CurseSort::DateCreated),
+            "last-updated" => Ok(CurseSort::LastUpdated),
+            "name" => Ok(CurseSort::Name),
+            "popularity" => Ok(CurseSort::Popularity),
+            "total-downloads" => Ok(CurseSort::TotalDownloads),
+            _ => Err(format!(
+                "unknown sort `{}`, expected popularity, total-downloads, last-updated, name or date-created",
+                s
+            )),
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! Named profiles of options read from `--config` for `--config-profile`,
+//! so audits that are run regularly don't need their flags repeated.
+//!
+//! Each profile is a table under `profile` whose keys are flag names with
+//! underscores, for example
+//!
+//! ```toml
+//! [profile.nightly-classic]
+//! flavor = ["wow_classic"]
+//! page_size = 100
+//! apis = ["curse", "wowup"]
+//! min_success_rate = 99.0
+//! output_file = "nightly-classic-{timestamp}.json"
+//! ```
+//!
+//! A flag given on the command line wins over the profile's value.
+
+use crate::cli::Opts;
+
+use serde::Deserialize;
+use structopt::clap::ArgMatches;
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    profile: BTreeMap<String, Profile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    game_id: Option<Vec<u32>>,
+    total: Option<usize>,
+    page_size: Option<usize>,
+    batch_size: Option<usize>,
+    max_connections: Option<usize>,
+    flavor: Option<Vec<String>>,
+    apis: Option<Vec<String>>,
+    format: Option<String>,
+    output_file: Option<PathBuf>,
+    output_dir: Option<PathBuf>,
+    retries: Option<usize>,
+    min_success_rate: Option<f64>,
+    fail_if_match_rate_below: Option<f64>,
+    fail_on_api_error: Option<bool>,
+    latency_sla: Option<u64>,
+    enforce_sla: Option<bool>,
+    baseline: Option<PathBuf>,
+    ignore_file: Option<PathBuf>,
+    api_key: Option<String>,
+    wowup_api_key: Option<String>,
+}
+
+impl Profile {
+    /// Reads the profile called `name` from the file at `path`.
+    pub fn load(path: &Path, name: &str) -> Result<Profile, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {} - {}", path.display(), e))?;
+        let mut config: ConfigFile = toml::from_str(&contents)
+            .map_err(|e| format!("invalid config file {} - {}", path.display(), e))?;
+
+        config.profile.remove(name).ok_or_else(|| {
+            let names = config.profile.keys().cloned().collect::<Vec<_>>();
+
+            format!(
+                "no profile `{}` in {}, expected one of {}",
+                name,
+                path.display(),
+                if names.is_empty() {
+                    "none".to_owned()
+                } else {
+                    names.join(", ")
+                }
+            )
+        })
+    }
+
+    /// The options of the run, which are `opts` with the profile's values
+    /// replacing the flags that weren't given.
+    pub fn apply(&self, mut opts: Opts, matches: &ArgMatches<'_>) -> Result<Opts, String> {
+        let unset = |arg: &str| matches.occurrences_of(arg) == 0;
+
+        if unset("game-id") {
+            opts.game_ids = self.game_id.clone().unwrap_or(opts.game_ids);
+        }
+        if unset("total") {
+            opts.total = self.total.unwrap_or(opts.total);
+        }
+        if unset("page-size") {
+            opts.page_size = self.page_size.unwrap_or(opts.page_size);
+        }
+        if unset("batch-size") {
+            opts.batch_size = self.batch_size.unwrap_or(opts.batch_size);
+        }
+        if unset("max-connections") {
+            opts.max_connections = self.max_connections.unwrap_or(opts.max_connections);
+        }
+        if unset("flavor") {
+            opts.flavors = self.flavor.clone().unwrap_or(opts.flavors);
+        }
+        if unset("apis") {
+            opts.apis = self.apis.clone().unwrap_or(opts.apis);
+        }
+        if let Some(format) = self.format.as_ref().filter(|_| unset("format")) {
+            opts.format = format.parse()?;
+        }
+        if unset("output-file") {
+            opts.output_file = self.output_file.clone().or(opts.output_file);
+        }
+        if unset("output-dir") {
+            opts.output_dir = self.output_dir.clone().or(opts.output_dir);
+        }
+        if unset("retries") {
+            opts.retries = self.retries.unwrap_or(opts.retries);
+        }
+        if unset("min-success-rate") {
+            opts.min_success_rate = self.min_success_rate.or(opts.min_success_rate);
+        }
+        if unset("fail-if-match-rate-below") {
+            opts.fail_if_match_rate_below = self
+                .fail_if_match_rate_below
+                .or(opts.fail_if_match_rate_below);
+        }
+        if unset("fail-on-api-error") {
+            opts.fail_on_api_error = self.fail_on_api_error.unwrap_or(opts.fail_on_api_error);
+        }
+        if unset("latency-sla") {
+            opts.latency_sla = self.latency_sla.or(opts.latency_sla);
+        }
+        if unset("enforce-sla") {
+            opts.enforce_sla = self.enforce_sla.unwrap_or(opts.enforce_sla);
+        }
+        if unset("baseline") {
+            opts.baseline = self.baseline.clone().or(opts.baseline);
+        }
+        if unset("ignore-file") {
+            opts.ignore_file = self.ignore_file.clone().or(opts.ignore_file);
+        }
+        // Credentials from the environment also win over the profile's
+        opts.api_key = opts.api_key.or_else(|| self.api_key.clone());
+        opts.wowup_api_key = opts.wowup_api_key.or_else(|| self.wowup_api_key.clone());
+
+        // Checked here as the flags' `requires` only sees the command line
+        if opts.enforce_sla && opts.latency_sla.is_none() {
+            return Err("enforce_sla needs latency_sla".to_owned());
+        }
+
+        Ok(opts)
+    }
+}
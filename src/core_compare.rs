@@ -0,0 +1,138 @@
+//! Checks ajour-core's own fingerprint lookup against the direct Curse
+//! requests for `--compare-against-ajour-core`.
+
+use crate::{ApiResults, Batch};
+
+use ajour_core::repository::curse;
+use futures::future;
+use tracing::warn;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::{self, Display};
+
+pub enum Divergence {
+    /// Only one side matched the package.
+    Missing {
+        package: String,
+        id: i32,
+        matched_by: &'static str,
+        file: String,
+    },
+    /// Both matched the package, but to different files.
+    File {
+        package: String,
+        id: i32,
+        direct: String,
+        core: String,
+    },
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::Missing {
+                package,
+                id,
+                matched_by,
+                file,
+            } => write!(
+                f,
+                "{} ({}) only matched by {}: {}",
+                package, id, matched_by, file
+            ),
+            Divergence::File {
+                package,
+                id,
+                direct,
+                core,
+            } => write!(
+                f,
+                "{} ({}) matched {} directly but {} through ajour-core",
+                package, id, direct, core
+            ),
+        }
+    }
+}
+
+/// Looks up every batch's fingerprints through ajour-core and compares the
+/// matched file of each package with the direct Curse results.
+///
+/// Batches that failed on either side are skipped, returning how many were.
+pub async fn compare(
+    packages: &[curse::Package],
+    batches: &[Batch<'_>],
+    curse: &ApiResults,
+) -> (Vec<Divergence>, usize) {
+    let core = future::join_all(batches.iter().map(|batch| async move {
+        let fingerprints = batch.fingerprints.iter().cloned().collect::<Vec<_>>();
+        curse::fetch_remote_packages_by_fingerprint(&fingerprints).await
+    }))
+    .await;
+
+    let names = packages
+        .iter()
+        .map(|p| (p.id, p.name.as_str()))
+        .collect::<HashMap<_, _>>();
+    let name = |id: &i32| names.get(id).copied().unwrap_or("unknown").to_owned();
+
+    let mut divergences = vec![];
+    let mut skipped = 0;
+
+    for (direct, core) in curse.responses.iter().zip(core) {
+        let (direct, core) = match (&direct.result, core) {
+            (Ok(direct), Ok(core)) => (matched_files(direct), matched_files(&core)),
+            (_, Err(e)) => {
+                warn!("ajour-core fingerprint lookup failed - {}", e);
+                skipped += 1;
+                continue;
+            }
+            _ => {
+                skipped += 1;
+                continue;
+            }
+        };
+
+        let ids = direct.keys().chain(core.keys()).collect::<BTreeSet<_>>();
+
+        for id in ids {
+            let divergence = match (direct.get(id), core.get(id)) {
+                (Some(direct), Some(core)) if direct != core => Divergence::File {
+                    package: name(id),
+                    id: *id,
+                    direct: direct.clone(),
+                    core: core.clone(),
+                },
+                (Some(file), None) => Divergence::Missing {
+                    package: name(id),
+                    id: *id,
+                    matched_by: "the direct request",
+                    file: file.clone(),
+                },
+                (None, Some(file)) => Divergence::Missing {
+                    package: name(id),
+                    id: *id,
+                    matched_by: "ajour-core",
+                    file: file.clone(),
+                },
+                _ => continue,
+            };
+
+            divergences.push(divergence);
+        }
+    }
+
+    (divergences, skipped)
+}
+
+/// Display name of the first exactly matched file of each package.
+fn matched_files(info: &curse::FingerprintInfo) -> BTreeMap<i32, String> {
+    let mut files = BTreeMap::new();
+
+    for m in &info.exact_matches {
+        files
+            .entry(m.id)
+            .or_insert_with(|| m.file.display_name.clone());
+    }
+
+    files
+}
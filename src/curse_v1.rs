@@ -0,0 +1,119 @@
+//! Support for the CurseForge v1 API with `--api-version v1`.
+//!
+//! v1 wraps every response in a `data` envelope and renames a few fields.
+//! Rather than duplicating the `curse` types, responses are rewritten into
+//! the legacy v2 shape first, so the rest of the audit stays unaware of the
+//! version.
+
+use crate::error::AuditError;
+
+use serde_json::{Map, Value};
+
+pub const SEARCH_URL: &str = "https://api.curseforge.com/v1/mods/search";
+pub const MOD_URL: &str = "https://api.curseforge.com/v1/mods";
+pub const FINGERPRINT_URL: &str = "https://api.curseforge.com/v1/fingerprints";
+
+/// Module type v2 reports for addon folders, which v1 no longer sends.
+const FOLDER_MODULE_TYPE: u64 = 3;
+
+/// Rewrites a fingerprint response into the v2 shape.
+pub fn fingerprint_response(response: Value) -> Result<Value, AuditError> {
+    unwrap_data(response, |data| {
+        for key in &["exactMatches", "partialMatches"] {
+            for info in array_mut(data, key) {
+                if let Some(file) = info.get_mut("file") {
+                    file_to_v2(file);
+                }
+
+                for file in array_mut(info, "latestFiles") {
+                    file_to_v2(file);
+                }
+            }
+        }
+    })
+}
+
+/// Rewrites a search response, a list of mods, into the v2 shape.
+pub fn search_response(body: &[u8]) -> Result<Vec<u8>, AuditError> {
+    rewrite(body, |data| {
+        if let Value::Array(mods) = data {
+            mods.iter_mut().for_each(mod_to_v2);
+        }
+    })
+}
+
+/// Rewrites a single mod response into the v2 shape.
+pub fn mod_response(body: &[u8]) -> Result<Vec<u8>, AuditError> {
+    rewrite(body, mod_to_v2)
+}
+
+fn rewrite(body: &[u8], f: impl FnOnce(&mut Value)) -> Result<Vec<u8>, AuditError> {
+    let value =
+        serde_json::from_slice::<Value>(body).map_err(|e| AuditError::Deserialize(e.into()))?;
+    let data = unwrap_data(value, f)?;
+
+    serde_json::to_vec(&data).map_err(AuditError::Serialize)
+}
+
+/// Takes the `data` envelope out of a parsed response and rewrites it.
+fn unwrap_data(mut value: Value, f: impl FnOnce(&mut Value)) -> Result<Value, AuditError> {
+    let mut data = value
+        .get_mut("data")
+        .map(Value::take)
+        .ok_or_else(|| AuditError::InvalidResponse("missing `data` envelope".to_owned()))?;
+
+    f(&mut data);
+
+    Ok(data)
+}
+
+fn mod_to_v2(package: &mut Value) {
+    let website_url = package
+        .get("links")
+        .and_then(|links| links.get("websiteUrl"))
+        .cloned();
+
+    if let (Some(url), Value::Object(package)) = (website_url, &mut *package) {
+        package.insert("websiteUrl".to_owned(), url);
+    }
+
+    for file in array_mut(package, "latestFiles") {
+        file_to_v2(file);
+    }
+}
+
+fn file_to_v2(file: &mut Value) {
+    if let Value::Object(file) = file {
+        rename(file, "gameVersions", "gameVersion");
+    }
+
+    for module in array_mut(file, "modules") {
+        if let Value::Object(module) = module {
+            rename(module, "name", "foldername");
+            module
+                .entry("type".to_owned())
+                .or_insert_with(|| FOLDER_MODULE_TYPE.into());
+        }
+    }
+
+    for dependency in array_mut(file, "dependencies") {
+        if let Value::Object(dependency) = dependency {
+            rename(dependency, "modId", "addonId");
+            rename(dependency, "relationType", "type");
+        }
+    }
+}
+
+fn rename(object: &mut Map<String, Value>, from: &str, to: &str) {
+    if let Some(value) = object.remove(from) {
+        object.insert(to.to_owned(), value);
+    }
+}
+
+fn array_mut<'a>(value: &'a mut Value, key: &str) -> impl Iterator<Item = &'a mut Value> {
+    value
+        .get_mut(key)
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+}
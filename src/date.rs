@@ -0,0 +1,174 @@
+//! Just enough date parsing for `--since` and the search's timestamps, which
+//! avoids pulling in a date crate for one comparison.
+
+use anyhow::anyhow;
+
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A UTC instant with second precision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timestamp(i64);
+
+impl Timestamp {
+    pub fn now() -> Timestamp {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+
+        Timestamp(seconds)
+    }
+
+    /// The timestamp this many seconds after the Unix epoch.
+    pub fn from_unix(seconds: i64) -> Timestamp {
+        Timestamp(seconds)
+    }
+
+    /// Parses an HTTP date in the preferred IMF-fixdate format, like
+    /// `Sun, 06 Nov 1994 08:49:37 GMT`.
+    pub fn from_http_date(s: &str) -> Option<Timestamp> {
+        const MONTHS: [&str; 12] = [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ];
+
+        let fields = s.split_whitespace().collect::<Vec<_>>();
+
+        let (day, month, year, time) = match fields.as_slice() {
+            [_, day, month, year, time, "GMT"] => (day, month, year, time),
+            _ => return None,
+        };
+
+        let month = MONTHS.iter().position(|m| m == month)? + 1;
+        let date = format!("{}-{:02}-{}T{}Z", year, month, day, time);
+
+        date.parse().ok()
+    }
+
+    /// Time from `earlier` until this timestamp, or `None` if `earlier` is
+    /// later.
+    pub fn duration_since(&self, earlier: Timestamp) -> Option<Duration> {
+        let seconds = self.0 - earlier.0;
+
+        if seconds < 0 {
+            None
+        } else {
+            Some(Duration::from_secs(seconds as u64))
+        }
+    }
+
+    /// The timestamp without separators, like `20201201T184412Z`, for use in
+    /// file names.
+    pub fn compact(&self) -> String {
+        self.to_string().replace(&['-', ':'][..], "")
+    }
+}
+
+impl Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let time = self.0.rem_euclid(86_400);
+        let (year, month, day) = civil_from_days(self.0.div_euclid(86_400));
+
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            year,
+            month,
+            day,
+            time / 3600,
+            time / 60 % 60,
+            time % 60
+        )
+    }
+}
+
+/// Parses either a bare `YYYY-MM-DD` date, taken as midnight UTC, or an
+/// RFC 3339 timestamp. Fractional seconds are dropped, and a missing offset
+/// is taken as UTC since the Curse API omits it on some fields.
+impl FromStr for Timestamp {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || anyhow!("expected YYYY-MM-DD or an RFC 3339 timestamp, got `{}`", s);
+
+        let (date, time) = match s.find(&['T', 't', ' '][..]) {
+            Some(i) => (&s[..i], Some(&s[i + 1..])),
+            None => (s, None),
+        };
+
+        let mut parts = date.splitn(3, '-');
+        let mut next = || -> Result<i64, anyhow::Error> {
+            parts
+                .next()
+                .and_then(|p| p.parse().ok())
+                .ok_or_else(invalid)
+        };
+        let (year, month, day) = (next()?, next()?, next()?);
+
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return Err(invalid());
+        }
+
+        let mut seconds = days_from_civil(year, month, day) * 86_400;
+
+        if let Some(time) = time {
+            let (clock, offset) = match time.find(&['Z', 'z', '+', '-'][..]) {
+                Some(i) => (&time[..i], &time[i..]),
+                None => (time, ""),
+            };
+
+            let clock = clock.split('.').next().unwrap_or_default();
+            let fields = clock
+                .split(':')
+                .map(|p| p.parse::<i64>().map_err(|_| invalid()))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            match fields.as_slice() {
+                [h, m, s] if *h < 24 && *m < 60 && *s <= 60 => seconds += h * 3600 + m * 60 + s,
+                [h, m] if *h < 24 && *m < 60 => seconds += h * 3600 + m * 60,
+                _ => return Err(invalid()),
+            }
+
+            if offset.len() > 1 {
+                let sign = if offset.starts_with('-') { -1 } else { 1 };
+                let mut fields = offset[1..].splitn(2, ':').map(|p| p.parse::<i64>());
+
+                match (fields.next(), fields.next()) {
+                    (Some(Ok(h)), Some(Ok(m))) => seconds -= sign * (h * 3600 + m * 60),
+                    _ => return Err(invalid()),
+                }
+            }
+        }
+
+        Ok(Timestamp(seconds))
+    }
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date, from Howard Hinnant's
+/// `days_from_civil`.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Inverse of `days_from_civil`.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
@@ -0,0 +1,326 @@
+//! Checks the required dependencies of the searched packages for the
+//! `dependencies` subcommand, listing addons whose dependencies would
+//! install through one API but not another.
+//!
+//! Ajour installs a dependency by matching its fingerprints like any other
+//! addon, so a dependency resolves on an API if its package exists and the
+//! API exactly matches the fingerprints of its latest files. Dependencies
+//! the search didn't return are fetched by id, then every dependency is
+//! fingerprinted against every API. A dependency whose batch failed on an
+//! API is left out of that API's comparison rather than counted against it.
+
+use crate::cli::{Format, Opts};
+use crate::rate_limit::RateLimits;
+use crate::retry::RetryBudget;
+use crate::spill::Spill;
+use crate::{
+    build_batches, exit_on_search_failure, fetch_packages, package_batch_ranges, sample,
+    search_all_packages, send_batches, write_json, ApiChoice, ApiResults, SearchPlan,
+};
+
+use ajour_core::repository::curse;
+use isahc::HttpClient;
+use serde::Serialize;
+use tracing::info;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{self, Write};
+
+/// Curse's relation type of a dependency the addon can't run without.
+const REQUIRED_DEPENDENCY: u32 = 3;
+
+#[derive(Serialize)]
+pub struct DependencyReport {
+    /// Searched packages whose latest files require another package.
+    pub packages: usize,
+    /// Distinct packages they require.
+    pub dependencies: usize,
+    /// Required packages that don't exist or have no latest file for the
+    /// flavors, which no API can resolve.
+    pub unavailable: Vec<i32>,
+    /// Required packages each API didn't match, keyed by API.
+    pub unmatched: BTreeMap<&'static str, Vec<i32>>,
+    /// Packages whose required dependencies resolve on some APIs but not on
+    /// others.
+    pub inconsistent: Vec<Inconsistent>,
+}
+
+#[derive(Serialize)]
+pub struct Inconsistent {
+    pub id: i32,
+    pub name: String,
+    /// Required dependencies each API couldn't resolve, keyed by API.
+    pub unresolved: BTreeMap<&'static str, Vec<i32>>,
+}
+
+/// How a required package resolves on an API.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Resolution {
+    Matched,
+    Unmatched,
+    /// The batch with its fingerprints failed.
+    Unknown,
+}
+
+/// Builds the dependency graph of the searched packages, fingerprints every
+/// dependency against every API and writes the packages whose dependencies
+/// resolve differently across them, returning how many do.
+pub async fn check(
+    client: &HttpClient,
+    opts: &Opts,
+    apis: &[ApiChoice],
+    retry_budget: &RetryBudget,
+    rate_limits: &RateLimits,
+    spill: &Spill,
+    out: &mut impl Write,
+) -> Result<usize, anyhow::Error> {
+    let seed = opts.seed.unwrap_or_else(sample::random_seed);
+    let plan = SearchPlan::new(opts, seed);
+    let packages = search_all_packages(client, opts, opts.game_ids[0], &plan).await;
+    let mut packages = exit_on_search_failure(opts, packages)?;
+    retain_flavors(opts, &mut packages);
+
+    let graph = packages
+        .iter()
+        .map(|package| (package, required_dependencies(package)))
+        .filter(|(_, dependencies)| !dependencies.is_empty())
+        .collect::<Vec<_>>();
+    let required = graph
+        .iter()
+        .flat_map(|(_, dependencies)| dependencies.iter().copied())
+        .collect::<BTreeSet<_>>();
+
+    let searched = packages
+        .iter()
+        .map(|package| (package.id, package))
+        .collect::<HashMap<_, _>>();
+    let unsearched = required
+        .iter()
+        .copied()
+        .filter(|id| !searched.contains_key(id))
+        .collect::<Vec<_>>();
+
+    info!(
+        "{} packages require {} others, fetching the {} the search didn't return",
+        graph.len(),
+        required.len(),
+        unsearched.len()
+    );
+
+    let mut dependencies = fetch_packages(client, opts, &unsearched).await?;
+    retain_flavors(opts, &mut dependencies);
+    dependencies.extend(
+        required
+            .iter()
+            .filter_map(|id| searched.get(id))
+            .map(|&package| package.clone()),
+    );
+
+    let unavailable = required
+        .iter()
+        .copied()
+        .filter(|id| !dependencies.iter().any(|package| package.id == *id))
+        .collect::<Vec<_>>();
+
+    let batches = build_batches(&dependencies, &package_batch_ranges(opts, &dependencies));
+    let (responses, _) = send_batches(
+        client,
+        opts,
+        retry_budget,
+        rate_limits,
+        spill,
+        apis,
+        &batches,
+    )
+    .await;
+
+    let mut results = apis
+        .iter()
+        .zip(responses)
+        .map(|(&api_choice, responses)| ApiResults {
+            api_choice,
+            failed_packages: responses
+                .iter()
+                .zip(&batches)
+                .filter(|(r, _)| r.result.is_err())
+                .flat_map(|(_, batch)| batch.answers_for.iter().map(|p| p.id))
+                .collect(),
+            responses,
+        })
+        .collect::<Vec<_>>();
+
+    if spill.spilled() {
+        spill.restore(&mut results)?;
+    }
+
+    // How every required package resolves on each API
+    let resolutions = results
+        .iter()
+        .map(|result| {
+            let matched = result.matched_files();
+            let resolutions = required
+                .iter()
+                .map(|&id| {
+                    let resolution = if result.failed_packages.contains(&id) {
+                        Resolution::Unknown
+                    } else if matched.contains_key(&id) {
+                        Resolution::Matched
+                    } else {
+                        Resolution::Unmatched
+                    };
+
+                    (id, resolution)
+                })
+                .collect::<HashMap<_, _>>();
+
+            (result.api_choice.key(), resolutions)
+        })
+        .collect::<Vec<_>>();
+
+    let mut report = DependencyReport {
+        packages: graph.len(),
+        dependencies: required.len(),
+        unmatched: resolutions
+            .iter()
+            .map(|(api, resolutions)| {
+                let unmatched = required
+                    .iter()
+                    .copied()
+                    .filter(|id| !unavailable.contains(id))
+                    .filter(|id| resolutions[id] == Resolution::Unmatched)
+                    .collect();
+
+                (*api, unmatched)
+            })
+            .collect(),
+        unavailable,
+        inconsistent: vec![],
+    };
+
+    for (package, dependencies) in &graph {
+        // Only dependencies every API answered for can be compared
+        let comparable = dependencies
+            .iter()
+            .copied()
+            .filter(|id| {
+                resolutions
+                    .iter()
+                    .all(|(_, resolutions)| resolutions[id] != Resolution::Unknown)
+            })
+            .collect::<Vec<_>>();
+
+        let unresolved = resolutions
+            .iter()
+            .map(|(api, resolutions)| {
+                let unresolved = comparable
+                    .iter()
+                    .copied()
+                    .filter(|id| resolutions[id] == Resolution::Unmatched)
+                    .collect::<Vec<_>>();
+
+                (*api, unresolved)
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let mut sets = unresolved.values();
+        let first = sets.next();
+
+        if sets.any(|set| Some(set) != first) {
+            report.inconsistent.push(Inconsistent {
+                id: package.id,
+                name: package.name.clone(),
+                unresolved,
+            });
+        }
+    }
+
+    report.inconsistent.sort_by_key(|package| package.id);
+
+    match opts.format {
+        Format::Json => write_json(out, &report, opts.compact)?,
+        _ => write_text(out, &report)?,
+    }
+
+    Ok(report.inconsistent.len())
+}
+
+/// Keeps only the latest files for `--flavor`, as an audit does, dropping
+/// packages left without any.
+fn retain_flavors(opts: &Opts, packages: &mut Vec<curse::Package>) {
+    if opts.flavors.is_empty() {
+        return;
+    }
+
+    for package in packages.iter_mut() {
+        package
+            .latest_files
+            .retain(|f| opts.flavors.contains(&f.game_version_flavor));
+    }
+    packages.retain(|p| !p.latest_files.is_empty());
+}
+
+/// Ids of the packages the latest files of `package` require, other than
+/// itself.
+fn required_dependencies(package: &curse::Package) -> BTreeSet<i32> {
+    package
+        .latest_files
+        .iter()
+        .flat_map(|file| file.dependencies.iter())
+        .filter(|dependency| dependency.r#type == REQUIRED_DEPENDENCY)
+        .map(|dependency| dependency.addon_id)
+        .filter(|&id| id != package.id)
+        .collect()
+}
+
+fn write_text(out: &mut impl Write, report: &DependencyReport) -> io::Result<()> {
+    for package in &report.inconsistent {
+        writeln!(out, "{} ({}):", package.name, package.id)?;
+
+        for (api, unresolved) in &package.unresolved {
+            let ids = unresolved.iter().map(i32::to_string).collect::<Vec<_>>();
+
+            writeln!(
+                out,
+                "  {}: {}",
+                api,
+                if ids.is_empty() {
+                    "every dependency resolves".to_owned()
+                } else {
+                    format!("can't resolve {}", ids.join(", "))
+                }
+            )?;
+        }
+    }
+
+    if !report.unavailable.is_empty() {
+        let ids = report
+            .unavailable
+            .iter()
+            .map(i32::to_string)
+            .collect::<Vec<_>>();
+
+        writeln!(
+            out,
+            "No API can resolve {} required packages that don't exist or have no latest file: {}",
+            ids.len(),
+            ids.join(", ")
+        )?;
+    }
+
+    for (api, unmatched) in &report.unmatched {
+        writeln!(
+            out,
+            "{} didn't match {} of {} required packages",
+            api,
+            unmatched.len(),
+            report.dependencies
+        )?;
+    }
+
+    writeln!(
+        out,
+        "{} of {} packages with required dependencies resolve them differently across the APIs",
+        report.inconsistent.len(),
+        report.packages
+    )
+}
@@ -0,0 +1,117 @@
+//! Checks the download URL of every exactly matched file with a HEAD
+//! request for `--check-download-urls`, since a match is no use if its file
+//! can't be downloaded.
+
+use crate::cli::Opts;
+use crate::ApiResults;
+
+use async_std::future::timeout;
+use futures::{stream, StreamExt};
+use isahc::HttpClient;
+use serde::Serialize;
+use tracing::debug;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+#[derive(Default, Serialize)]
+pub struct DownloadUrlCheck {
+    /// Matched files whose download URL was checked.
+    pub checked: usize,
+    /// URLs that answered with a 2xx status.
+    pub ok: usize,
+    /// URLs that answered with a 3xx status, which downloads follow but
+    /// points at a moved file.
+    pub redirected: usize,
+    /// URLs that answered 404 or 410, and files without a URL.
+    pub dead: usize,
+    /// URLs that answered with any other status, keyed by status code.
+    pub other_statuses: BTreeMap<u16, usize>,
+    /// URLs that got no response, such as after a timeout.
+    pub failed: usize,
+    /// Every URL that wasn't ok or redirected, sorted by package id.
+    pub broken: Vec<BrokenUrl>,
+}
+
+#[derive(Serialize)]
+pub struct BrokenUrl {
+    pub package_id: i32,
+    pub url: String,
+    /// Absent if the request got no response or the file had no URL.
+    pub status: Option<u16>,
+}
+
+/// Checks the download URL of every API's matched files, returning the
+/// outcome per API in `results` order. A URL several APIs returned is only
+/// requested once.
+pub async fn check(
+    client: &HttpClient,
+    opts: &Opts,
+    results: &[ApiResults],
+) -> Vec<DownloadUrlCheck> {
+    let matched_files = results
+        .iter()
+        .map(ApiResults::matched_files)
+        .collect::<Vec<_>>();
+
+    let urls = matched_files
+        .iter()
+        .flat_map(|files| files.values().map(|file| file.download_url.as_str()))
+        .filter(|url| !url.is_empty())
+        .collect::<BTreeSet<_>>();
+
+    let request_timeout = Duration::from_secs(opts.request_timeout);
+
+    let statuses = stream::iter(urls)
+        .map(|url| async move {
+            let status = match timeout(request_timeout, client.head_async(url)).await {
+                Ok(Ok(response)) => Some(response.status().as_u16()),
+                Ok(Err(e)) => {
+                    debug!(url, "download URL check failed - {}", e);
+                    None
+                }
+                Err(_) => {
+                    debug!(url, "download URL check timed out");
+                    None
+                }
+            };
+
+            (url, status)
+        })
+        .buffer_unordered(opts.download_check_concurrency)
+        .collect::<BTreeMap<_, _>>()
+        .await;
+
+    matched_files
+        .iter()
+        .map(|files| {
+            let mut check = DownloadUrlCheck::default();
+
+            for (&package_id, file) in files {
+                let status = statuses.get(file.download_url.as_str()).copied().flatten();
+
+                check.checked += 1;
+
+                match status {
+                    Some(200..=299) => check.ok += 1,
+                    Some(300..=399) => check.redirected += 1,
+                    Some(404) | Some(410) => check.dead += 1,
+                    Some(code) => *check.other_statuses.entry(code).or_default() += 1,
+                    // A file without a URL can't be downloaded at all
+                    None if file.download_url.is_empty() => check.dead += 1,
+                    None => check.failed += 1,
+                }
+
+                if !matches!(status, Some(200..=399)) {
+                    check.broken.push(BrokenUrl {
+                        package_id,
+                        url: file.download_url.clone(),
+                        status,
+                    });
+                }
+            }
+
+            check
+        })
+        .collect()
+}
@@ -0,0 +1,110 @@
+//! Compares each fingerprint response with what its types captured for
+//! `--detect-schema-drift`, since the types silently drop fields they don't
+//! know and default fields that come back null.
+//!
+//! Unlike `--strict-json`, which fails the batch on the first unknown field,
+//! this only reports, and it follows the types in use instead of a fixed
+//! list of fields.
+
+use ajour_core::repository::curse;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Fields of an API's responses the types didn't capture or that came back
+/// null, as paths like `exactMatches[].file.modules[].type`.
+#[derive(Clone, Default, Serialize)]
+pub struct SchemaDrift {
+    /// Responses compared.
+    pub responses: usize,
+    /// Fields the types don't have, with the number of responses each was
+    /// in. Fields nested in one are left out.
+    pub uncaptured: BTreeMap<String, usize>,
+    /// Fields the types have that came back null, with the number of
+    /// responses each was null in.
+    pub null: BTreeMap<String, usize>,
+}
+
+impl SchemaDrift {
+    /// Compares a normalized response with the info decoded from it.
+    pub fn detect(raw: &Value, info: &curse::FingerprintInfo) -> SchemaDrift {
+        // What `FingerprintInfo` captures, serialized back with the same
+        // field names
+        let mut typed = Map::new();
+        typed.insert(
+            "exactMatches".to_owned(),
+            serde_json::to_value(&info.exact_matches).unwrap_or(Value::Null),
+        );
+        typed.insert(
+            "partialMatches".to_owned(),
+            serde_json::to_value(&info.partial_matches).unwrap_or(Value::Null),
+        );
+
+        let mut uncaptured = BTreeSet::new();
+        let mut null = BTreeSet::new();
+        walk(raw, &Value::Object(typed), "", &mut uncaptured, &mut null);
+
+        SchemaDrift {
+            responses: 1,
+            uncaptured: uncaptured.into_iter().map(|path| (path, 1)).collect(),
+            null: null.into_iter().map(|path| (path, 1)).collect(),
+        }
+    }
+
+    pub fn merge(&mut self, other: &SchemaDrift) {
+        self.responses += other.responses;
+
+        for (path, count) in &other.uncaptured {
+            *self.uncaptured.entry(path.clone()).or_default() += count;
+        }
+
+        for (path, count) in &other.null {
+            *self.null.entry(path.clone()).or_default() += count;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.uncaptured.is_empty() && self.null.is_empty()
+    }
+}
+
+/// Collects the paths of `raw` missing from `typed` or null in `raw`. Array
+/// elements are compared in order, which serializing keeps.
+fn walk(
+    raw: &Value,
+    typed: &Value,
+    path: &str,
+    uncaptured: &mut BTreeSet<String>,
+    null: &mut BTreeSet<String>,
+) {
+    match (raw, typed) {
+        (Value::Object(raw), Value::Object(typed)) => {
+            for (key, value) in raw {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+
+                match typed.get(key) {
+                    None => {
+                        uncaptured.insert(path);
+                    }
+                    Some(_) if value.is_null() => {
+                        null.insert(path);
+                    }
+                    Some(typed) => walk(value, typed, &path, uncaptured, null),
+                }
+            }
+        }
+        (Value::Array(raw), Value::Array(typed)) => {
+            let path = format!("{}[]", path);
+
+            for (raw, typed) in raw.iter().zip(typed) {
+                walk(raw, typed, &path, uncaptured, null);
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,299 @@
+//! Sends each API's fingerprint endpoint requests a client shouldn't send,
+//! for the `edge-cases` subcommand, so Ajour knows which of them every API
+//! tolerates before relying on that.
+//!
+//! Every case goes to every API once, without retries. An API tolerates a
+//! case if it answers with a response that decodes, and the output flags
+//! the cases the APIs answered differently.
+
+use crate::cli::{ApiVersion, Format, Opts};
+use crate::error::AuditError;
+use crate::{parse_json, send_request, write_json, ApiChoice};
+
+use async_std::future::timeout;
+use isahc::http::header::{HeaderValue, CONTENT_TYPE};
+use isahc::HttpClient;
+use serde::Serialize;
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+#[derive(Serialize)]
+pub struct EdgeCase {
+    pub case: &'static str,
+    pub description: &'static str,
+    /// How each API answered, keyed by API.
+    pub outcomes: BTreeMap<&'static str, Outcome>,
+    /// Whether the APIs answered differently, either one tolerating the
+    /// case and another not, or with different statuses or match counts.
+    pub differs: bool,
+}
+
+#[derive(Serialize)]
+pub struct Outcome {
+    /// Status of a response the API refused the request with, absent if it
+    /// accepted it or never answered.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// Exactly matched files of a response that decoded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exact_matches: Option<usize>,
+    /// `AuditError::kind` of why the request failed or didn't decode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<&'static str>,
+    #[serde(skip)]
+    pub latency: Duration,
+}
+
+/// What a case sends, for an API that takes the fingerprints bare or
+/// wrapped in an object.
+enum Body {
+    Fingerprints(Vec<u32>),
+    /// The body sent to APIs that take the fingerprints bare, and the one
+    /// sent to those that take them wrapped.
+    Raw {
+        to_bare: String,
+        to_wrapped: String,
+    },
+}
+
+/// Probes every API with every case around `fingerprint`, which the APIs
+/// are expected to match, and writes how they answered.
+pub async fn check(
+    client: &HttpClient,
+    opts: &Opts,
+    apis: &[ApiChoice],
+    fingerprint: u32,
+    oversized: usize,
+    out: &mut impl Write,
+) -> Result<(), anyhow::Error> {
+    let mut cases = vec![];
+
+    for (case, description, body, content_type) in cases_around(fingerprint, oversized) {
+        let mut outcomes = BTreeMap::new();
+
+        for &api_choice in apis {
+            let outcome = send(client, opts, api_choice, &body, content_type).await;
+            outcomes.insert(api_choice.key(), outcome);
+        }
+
+        let differs = outcomes
+            .values()
+            .zip(outcomes.values().skip(1))
+            .any(|(a, b)| {
+                (a.status, a.exact_matches, a.error) != (b.status, b.exact_matches, b.error)
+            });
+
+        cases.push(EdgeCase {
+            case,
+            description,
+            outcomes,
+            differs,
+        });
+    }
+
+    match opts.format {
+        Format::Json => write_json(out, &cases, opts.compact)?,
+        _ => write_text(out, &cases)?,
+    }
+
+    Ok(())
+}
+
+/// Each case with its description, body and the content type to send it
+/// with instead of json, if any.
+fn cases_around(
+    fingerprint: u32,
+    oversized: usize,
+) -> Vec<(&'static str, &'static str, Body, Option<&'static str>)> {
+    let raw = |bare: &str| Body::Raw {
+        to_bare: bare.to_owned(),
+        to_wrapped: format!("{{\"fingerprints\":{}}}", bare),
+    };
+
+    vec![
+        (
+            "baseline",
+            "the fingerprint on its own, which every API should match",
+            Body::Fingerprints(vec![fingerprint]),
+            None,
+        ),
+        (
+            "empty",
+            "no fingerprints at all",
+            Body::Fingerprints(vec![]),
+            None,
+        ),
+        (
+            "duplicates",
+            "the fingerprint three times over",
+            Body::Fingerprints(vec![fingerprint; 3]),
+            None,
+        ),
+        (
+            "oversized",
+            "the fingerprint among more than a batch ever holds",
+            Body::Fingerprints(
+                (0..oversized as u32)
+                    .map(|i| fingerprint.wrapping_add(i))
+                    .collect(),
+            ),
+            None,
+        ),
+        (
+            "malformed",
+            "json cut off halfway",
+            Body::Raw {
+                to_bare: format!("[{},", fingerprint),
+                to_wrapped: format!("{{\"fingerprints\":[{},", fingerprint),
+            },
+            None,
+        ),
+        (
+            "wrong-shape",
+            "the body the other API expects, bare instead of wrapped or the other way around",
+            Body::Raw {
+                to_bare: format!("{{\"fingerprints\":[{}]}}", fingerprint),
+                to_wrapped: format!("[{}]", fingerprint),
+            },
+            None,
+        ),
+        (
+            "strings",
+            "the fingerprint as a json string",
+            raw(&format!("[\"{}\"]", fingerprint)),
+            None,
+        ),
+        (
+            "negative",
+            "a negative fingerprint, which no u32 hash can be",
+            raw("[-1]"),
+            None,
+        ),
+        (
+            "out-of-range",
+            "a fingerprint past u32::MAX",
+            raw("[4294967296]"),
+            None,
+        ),
+        (
+            "text-content-type",
+            "a valid body sent as text/plain",
+            Body::Fingerprints(vec![fingerprint]),
+            Some("text/plain"),
+        ),
+    ]
+}
+
+/// Sends one case to one API and describes the answer.
+async fn send(
+    client: &HttpClient,
+    opts: &Opts,
+    api_choice: ApiChoice,
+    body: &Body,
+    content_type: Option<&'static str>,
+) -> Outcome {
+    let start = Instant::now();
+    let request_timeout = Duration::from_secs(opts.request_timeout);
+
+    let result = async {
+        let body = match body {
+            Body::Fingerprints(fingerprints) => api_choice
+                .api()
+                .encode_request(opts.api_version, fingerprints.clone())?,
+            Body::Raw { to_bare, .. } if takes_bare(api_choice, opts.api_version) => {
+                to_bare.clone().into_bytes()
+            }
+            Body::Raw { to_wrapped, .. } => to_wrapped.clone().into_bytes(),
+        };
+
+        let mut request = api_choice.api().build_request(opts, body, false)?;
+
+        if let Some(content_type) = content_type {
+            request
+                .headers_mut()
+                .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        }
+
+        let (body, _, _) = timeout(request_timeout, send_request(client, opts, request))
+            .await
+            .map_err(|_| AuditError::Timeout(request_timeout))??;
+
+        let response = api_choice
+            .api()
+            .normalize_response(opts.api_version, parse_json(&body)?)?;
+
+        api_choice.api().decode_response(&response, &[])
+    }
+    .await;
+
+    let latency = start.elapsed();
+
+    match result {
+        Ok(info) => Outcome {
+            status: None,
+            exact_matches: Some(info.exact_matches.len()),
+            error: None,
+            latency,
+        },
+        Err(e) => Outcome {
+            status: match &e {
+                AuditError::BadStatus { code, .. } => Some(*code),
+                AuditError::RateLimited { .. } => Some(429),
+                _ => None,
+            },
+            exact_matches: None,
+            error: Some(e.kind()),
+            latency,
+        },
+    }
+}
+
+/// Whether the API takes a bare json array of fingerprints rather than an
+/// object holding them.
+fn takes_bare(api_choice: ApiChoice, api_version: ApiVersion) -> bool {
+    matches!(
+        (api_choice, api_version),
+        (ApiChoice::Curse, ApiVersion::V2)
+    )
+}
+
+fn write_text(out: &mut impl Write, cases: &[EdgeCase]) -> io::Result<()> {
+    for case in cases {
+        let outcomes = case
+            .outcomes
+            .iter()
+            .map(|(api, outcome)| {
+                let answer = match (outcome.status, outcome.exact_matches, outcome.error) {
+                    (_, Some(matches), _) => format!("{} exact matches", matches),
+                    (Some(status), _, _) => format!("refused with status {}", status),
+                    (None, _, Some(error)) => error.to_owned(),
+                    _ => "no answer".to_owned(),
+                };
+
+                format!("{} {} in {}ms", api, answer, outcome.latency.as_millis())
+            })
+            .collect::<Vec<_>>();
+
+        let marker = if case.differs { " (differs)" } else { "" };
+
+        writeln!(
+            out,
+            "{}{}: {}\n  {}",
+            case.case,
+            marker,
+            case.description,
+            outcomes.join("\n  ")
+        )?;
+    }
+
+    let differing = cases.iter().filter(|c| c.differs).count();
+
+    writeln!(
+        out,
+        "The APIs answered {} of {} cases differently",
+        differing,
+        cases.len()
+    )
+}
@@ -0,0 +1,158 @@
+//! Failure modes of the requests an audit sends, so retries and exit codes
+//! can tell them apart.
+
+use std::io;
+use std::time::Duration;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AuditError {
+    #[error("request failed: {0}")]
+    Network(#[from] isahc::Error),
+    #[error("invalid request: {0}")]
+    Request(#[from] isahc::http::Error),
+    /// Carries the response's request id header, if it had one, for support
+    /// tickets, as does `BadStatus`.
+    #[error("rate limited, retry after {retry_after:?}{}", request_id_suffix(.request_id))]
+    RateLimited {
+        retry_after: Option<Duration>,
+        request_id: Option<String>,
+    },
+    #[error("unexpected status {code}{}", request_id_suffix(.request_id))]
+    BadStatus {
+        code: u16,
+        request_id: Option<String>,
+    },
+    #[error("failed to serialize request: {0}")]
+    Serialize(serde_json::Error),
+    #[error("failed to deserialize response: {0}")]
+    Deserialize(Box<dyn std::error::Error + Send + Sync>),
+    /// The response didn't match its schema under `--validate-response-schema`.
+    #[error("response failed schema validation: {}", errors.join("; "))]
+    Schema { errors: Vec<String> },
+    /// The response had a field `--strict-json` doesn't know.
+    #[error("response failed strict parsing: {0}")]
+    Strict(serde_json::Error),
+    /// A successful status with an empty `{}` body, which some API errors
+    /// return and which would otherwise parse as a batch with no matches.
+    #[error("empty response envelope")]
+    EmptyResponse,
+    /// The response parsed but its contents don't add up.
+    #[error("invalid response: {0}")]
+    InvalidResponse(String),
+    /// The body passed `--max-response-bytes` and was abandoned unparsed.
+    #[error("response exceeded {limit} bytes")]
+    ResponseTooLarge { limit: u64 },
+    #[error("request timed out after {0:?}")]
+    Timeout(Duration),
+    #[error("failed to read response: {0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Replay(anyhow::Error),
+    /// Ctrl-C or SIGTERM cancelled the request or it was never sent.
+    #[error("interrupted")]
+    Interrupted,
+}
+
+impl AuditError {
+    /// Whether sending the same request again could succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            AuditError::Network(_)
+            | AuditError::RateLimited { .. }
+            | AuditError::EmptyResponse
+            | AuditError::Timeout(_)
+            | AuditError::Io(_) => true,
+            AuditError::BadStatus { code, .. } => *code >= 500,
+            AuditError::Request(_)
+            | AuditError::Serialize(_)
+            | AuditError::Deserialize(_)
+            | AuditError::Schema { .. }
+            | AuditError::Strict(_)
+            | AuditError::InvalidResponse(_)
+            | AuditError::ResponseTooLarge { .. }
+            | AuditError::Replay(_)
+            | AuditError::Interrupted => false,
+        }
+    }
+
+    /// Short name of the variant, for counting failures by kind.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            // Also how curl aborts a response slower than --low-speed-limit
+            AuditError::Network(isahc::Error::Timeout) => "timeout",
+            AuditError::Network(_) => "network",
+            AuditError::Request(_) => "invalid request",
+            AuditError::RateLimited { .. } => "rate limited",
+            AuditError::BadStatus { .. } => "bad status",
+            AuditError::Serialize(_) => "serialize",
+            AuditError::Deserialize(_) => "deserialize",
+            AuditError::Schema { .. } => "schema",
+            AuditError::Strict(_) => "strict json",
+            AuditError::EmptyResponse => "empty response",
+            AuditError::InvalidResponse(_) => "invalid response",
+            AuditError::ResponseTooLarge { .. } => "response too large",
+            AuditError::Timeout(_) => "timeout",
+            AuditError::Io(_) => "io",
+            AuditError::Replay(_) => "replay",
+            AuditError::Interrupted => "interrupted",
+        }
+    }
+
+    /// Coarse category of the variant, for aggregating failures across
+    /// backends whose errors differ in detail: `transport`, `timeout`,
+    /// `rate_limited`, `upstream_error`, `decode` or `internal`.
+    pub fn category(&self) -> &'static str {
+        match self {
+            AuditError::Network(isahc::Error::Timeout) | AuditError::Timeout(_) => "timeout",
+            AuditError::Network(_) | AuditError::Io(_) => "transport",
+            AuditError::RateLimited { .. } => "rate_limited",
+            AuditError::BadStatus { .. } => "upstream_error",
+            AuditError::Deserialize(_)
+            | AuditError::Schema { .. }
+            | AuditError::Strict(_)
+            | AuditError::EmptyResponse
+            | AuditError::InvalidResponse(_)
+            | AuditError::ResponseTooLarge { .. } => "decode",
+            AuditError::Request(_)
+            | AuditError::Serialize(_)
+            | AuditError::Replay(_)
+            | AuditError::Interrupted => "internal",
+        }
+    }
+
+    /// Status the API answered with, for upstream errors.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            AuditError::BadStatus { code, .. } => Some(*code),
+            AuditError::RateLimited { .. } => Some(429),
+            _ => None,
+        }
+    }
+
+    /// Request id header of the response the error came from, if any.
+    pub fn request_id(&self) -> Option<&str> {
+        match self {
+            AuditError::RateLimited { request_id, .. }
+            | AuditError::BadStatus { request_id, .. } => request_id.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Whether the failure was in reaching the API rather than in what it
+    /// returned.
+    pub fn is_network_failure(&self) -> bool {
+        matches!(
+            self,
+            AuditError::Network(_) | AuditError::Timeout(_) | AuditError::Io(_)
+        )
+    }
+}
+
+fn request_id_suffix(request_id: &Option<String>) -> String {
+    request_id
+        .as_ref()
+        .map(|id| format!(" (request id {})", id))
+        .unwrap_or_default()
+}
@@ -0,0 +1,207 @@
+//! Prints the settings a run would use for `--explain`, along with where
+//! each one came from, instead of running it.
+
+use crate::cli::{ApiVersion, HttpVersion, Opts};
+use crate::{curse_v1, env_var, ApiChoice, CONNECTION_TIMEOUT_SECONDS, CURSE_SEARCH_URL};
+
+use structopt::clap::ArgMatches;
+
+const PROXY_VARS: [&str; 4] = ["HTTPS_PROXY", "https_proxy", "HTTP_PROXY", "http_proxy"];
+
+pub fn print(opts: &Opts, matches: &ArgMatches<'_>, apis: &[ApiChoice]) {
+    let source = |arg: &str| {
+        if matches.occurrences_of(arg) > 0 {
+            "flag".to_owned()
+        } else if opts.config_profile.is_some() {
+            "profile or default".to_owned()
+        } else {
+            "default".to_owned()
+        }
+    };
+    let built_in = || "built in".to_owned();
+
+    let game_ids = opts
+        .game_ids
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    let api_keys = apis.iter().map(ApiChoice::key).collect::<Vec<_>>();
+
+    let mut settings = vec![
+        (
+            "config profile",
+            opts.config_profile.as_ref().map_or_else(
+                || "none".to_owned(),
+                |name| format!("{} from {}", name, opts.config.display()),
+            ),
+            source("config-profile"),
+        ),
+        ("game ids", game_ids.join(","), source("game-id")),
+        ("total", opts.total.to_string(), source("total")),
+        ("page size", opts.page_size.to_string(), source("page-size")),
+        ("offset", opts.offset.to_string(), source("offset")),
+        ("max pages", opts.max_pages.to_string(), source("max-pages")),
+        ("sort", opts.sort.name().to_owned(), source("sort")),
+        (
+            "search sampling",
+            opts.search_sampling.name().to_owned(),
+            source("search-sampling"),
+        ),
+        (
+            "search cache ttl",
+            format!("{}s", opts.search_cache_ttl),
+            source("search-cache-ttl"),
+        ),
+        ("refresh", opts.refresh.to_string(), source("refresh")),
+        (
+            "batch size",
+            opts.batch_size.to_string(),
+            source("batch-size"),
+        ),
+        (
+            "max batch fingerprints",
+            opts.max_batch_fingerprints
+                .map_or_else(|| "none".to_owned(), |max| max.to_string()),
+            source("max-batch-fingerprints"),
+        ),
+        (
+            "connections per host",
+            opts.max_connections.to_string(),
+            source("max-connections"),
+        ),
+        ("adaptive", opts.adaptive.to_string(), source("adaptive")),
+        (
+            "request timeout",
+            format!("{}s", opts.request_timeout),
+            source("request-timeout"),
+        ),
+        (
+            "low speed limit",
+            opts.low_speed_limit.map_or_else(
+                || "none".to_owned(),
+                |limit| format!("{}B/s for {}s", limit, opts.low_speed_time),
+            ),
+            source("low-speed-limit"),
+        ),
+        (
+            "connection timeout",
+            format!("{}s", CONNECTION_TIMEOUT_SECONDS),
+            built_in(),
+        ),
+        (
+            "http version",
+            match opts.http_version {
+                Some(HttpVersion::Http11) => "1.1",
+                Some(HttpVersion::Http2) => "2",
+                None => "negotiated",
+            }
+            .to_owned(),
+            source("http-version"),
+        ),
+        (
+            "max response bytes",
+            opts.max_response_bytes.to_string(),
+            source("max-response-bytes"),
+        ),
+        (
+            "max request bytes",
+            if opts.max_request_bytes.is_empty() {
+                "none".to_owned()
+            } else {
+                opts.max_request_bytes
+                    .iter()
+                    .map(|limit| format!("{}={}", limit.api, limit.bytes))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            },
+            source("max-request-bytes"),
+        ),
+        ("retries", opts.retries.to_string(), source("retries")),
+        (
+            "retry delay",
+            format!("{}ms", opts.retry_delay_ms),
+            source("retry-delay-ms"),
+        ),
+        (
+            "max retry delay",
+            format!("{}ms", opts.max_retry_delay_ms),
+            source("max-retry-delay-ms"),
+        ),
+        (
+            "rate limit retries",
+            opts.rate_limit_retries.to_string(),
+            source("rate-limit-retries"),
+        ),
+        (
+            "retry budget",
+            opts.retry_budget
+                .map_or_else(|| "unlimited".to_owned(), |b| b.to_string()),
+            source("retry-budget"),
+        ),
+        ("apis", api_keys.join(","), source("apis")),
+        (
+            "api version",
+            format!("{:?}", opts.api_version).to_lowercase(),
+            source("api-version"),
+        ),
+    ];
+
+    let search_url = match opts.api_version {
+        ApiVersion::V1 => curse_v1::SEARCH_URL,
+        ApiVersion::V2 => CURSE_SEARCH_URL,
+    };
+    settings.push(("search url", search_url.to_owned(), built_in()));
+
+    for api in apis {
+        settings.push((
+            api.name(),
+            api.api().fingerprint_url(opts.api_version).to_owned(),
+            built_in(),
+        ));
+    }
+
+    // Never print the keys themselves
+    let credential = |value: &Option<String>, arg: &str, var: &str| {
+        let source = match (value, matches.occurrences_of(arg)) {
+            (None, _) => "unset".to_owned(),
+            (Some(_), 0) if env_var(&[var]).is_some() => format!("env {}", var),
+            (Some(_), 0) => "profile".to_owned(),
+            (Some(_), _) => "flag".to_owned(),
+        };
+        let value = if value.is_some() { "set" } else { "none" };
+
+        (value.to_owned(), source)
+    };
+
+    let (api_key, api_key_source) = credential(&opts.api_key, "api-key", "CURSE_API_KEY");
+    settings.push(("api key", api_key, api_key_source));
+
+    let (wowup_api_key, wowup_api_key_source) =
+        credential(&opts.wowup_api_key, "wowup-api-key", "WOWUP_API_KEY");
+    settings.push(("wowup api key", wowup_api_key, wowup_api_key_source));
+
+    let (proxy, proxy_source) = match &opts.proxy {
+        Some(proxy) => (proxy.to_string(), "flag".to_owned()),
+        None => PROXY_VARS
+            .iter()
+            .find_map(|&name| env_var(&[name]).map(|proxy| (proxy, format!("env {}", name))))
+            .unwrap_or_else(|| ("none".to_owned(), "unset".to_owned())),
+    };
+    settings.push(("proxy", proxy, proxy_source));
+
+    let ca_bundle = match &opts.ca_bundle {
+        Some(path) => path.display().to_string(),
+        None => "system".to_owned(),
+    };
+    settings.push(("ca bundle", ca_bundle, source("ca-bundle")));
+
+    let width = settings
+        .iter()
+        .map(|(name, _, _)| name.len())
+        .max()
+        .unwrap_or(0);
+
+    for (name, value, source) in settings {
+        println!("{:<width$}  {} ({})", name, value, source, width = width);
+    }
+}
@@ -0,0 +1,325 @@
+//! Downloads a sample of the files several APIs exactly matched for
+//! `--verify-file-hashes` and compares their MD5 and SHA-256 hashes, since
+//! agreeing metadata doesn't prove the APIs point at the same artifact.
+//!
+//! Files are downloaded into a directory of the run under `--verify-dir`,
+//! or the system's temporary directory, which is removed once they're
+//! hashed unless `--keep-downloads` is given.
+
+use crate::cli::Opts;
+use crate::{sample, ApiResults};
+
+use ajour_core::repository::curse;
+use async_std::future::timeout;
+use futures::{stream, AsyncReadExt, StreamExt};
+use isahc::config::RedirectPolicy;
+use isahc::prelude::*;
+use md5::Md5;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Serialize)]
+pub struct HashVerification {
+    /// Seed the fingerprints were sampled with.
+    pub seed: u64,
+    /// Fingerprints several APIs exactly matched, counting those matched to
+    /// the same files once, which the sample was picked from.
+    pub candidates: usize,
+    pub sampled: usize,
+    /// Sampled fingerprints whose files are byte-identical on every API.
+    pub identical: usize,
+    /// Sampled fingerprints with a file that couldn't be downloaded.
+    pub failed: usize,
+    /// Sampled fingerprints whose files differ, sorted by fingerprint.
+    pub mismatches: Vec<HashMismatch>,
+    /// Where the files were kept with `--keep-downloads`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kept_in: Option<PathBuf>,
+}
+
+#[derive(Serialize)]
+pub struct HashMismatch {
+    pub fingerprint: u32,
+    pub folder: String,
+    /// The file each API matched the fingerprint to, keyed by API.
+    pub files: BTreeMap<&'static str, FileHash>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct FileHash {
+    pub package_id: i32,
+    pub file_id: i64,
+    pub download_url: String,
+    pub bytes: u64,
+    pub md5: String,
+    pub sha256: String,
+}
+
+#[derive(Clone, PartialEq)]
+struct Hashes {
+    bytes: u64,
+    md5: String,
+    sha256: String,
+}
+
+/// A fingerprint and the first file each API exactly matched it to.
+type Candidate<'a> = (u32, Vec<(&'static str, i32, &'a curse::File)>);
+
+/// Downloads the files of a sample of the fingerprints several APIs in
+/// `results` exactly matched and compares them. A URL several APIs returned
+/// is only downloaded once.
+pub async fn verify(client: &HttpClient, opts: &Opts, results: &[ApiResults]) -> HashVerification {
+    let (candidates, mut folders) = candidates(results);
+    let seed = sample::random_seed();
+    let total = candidates.len();
+    let sampled = sample::sample(candidates, opts.verify_sample_size, seed);
+
+    let dir = DownloadDir::new(opts);
+
+    let names = sampled
+        .iter()
+        .flat_map(|(_, files)| files.iter().map(|(_, _, file)| file))
+        .filter(|file| !file.download_url.is_empty())
+        .map(|file| (file.download_url.as_str(), file.file_name.as_str()))
+        .collect::<BTreeMap<_, _>>();
+
+    info!(
+        "Downloading {} files for {} of {} fingerprints matched by several APIs",
+        names.len(),
+        sampled.len(),
+        total
+    );
+
+    let request_timeout = Duration::from_secs(opts.request_timeout);
+    let dir_ref = &dir;
+
+    let hashes = stream::iter(names.into_iter().enumerate())
+        .map(|(index, (url, name))| async move {
+            let path = dir_ref.file(index, name);
+            let hashes = match timeout(request_timeout, download(client, url, &path)).await {
+                Ok(Ok(hashes)) => Some(hashes),
+                Ok(Err(e)) => {
+                    debug!(url, "file download failed - {}", e);
+                    None
+                }
+                Err(_) => {
+                    debug!(url, "file download timed out");
+                    None
+                }
+            };
+
+            (url, hashes)
+        })
+        .buffer_unordered(opts.max_connections)
+        .collect::<HashMap<_, _>>()
+        .await;
+
+    let mut verification = HashVerification {
+        seed,
+        candidates: total,
+        sampled: sampled.len(),
+        identical: 0,
+        failed: 0,
+        mismatches: vec![],
+        kept_in: dir.kept(),
+    };
+
+    for (fingerprint, files) in sampled {
+        let hashed = files
+            .iter()
+            .map(|(api, package_id, file)| {
+                let hashes = hashes.get(file.download_url.as_str()).cloned().flatten()?;
+
+                Some((*api, *package_id, *file, hashes))
+            })
+            .collect::<Option<Vec<_>>>();
+
+        let hashed = match hashed {
+            Some(hashed) => hashed,
+            None => {
+                verification.failed += 1;
+                continue;
+            }
+        };
+
+        if hashed.iter().all(|(.., hashes)| *hashes == hashed[0].3) {
+            verification.identical += 1;
+            continue;
+        }
+
+        verification.mismatches.push(HashMismatch {
+            fingerprint,
+            folder: folders.remove(&fingerprint).unwrap_or_default(),
+            files: hashed
+                .into_iter()
+                .map(|(api, package_id, file, hashes)| {
+                    let hash = FileHash {
+                        package_id,
+                        file_id: file.id,
+                        download_url: file.download_url.clone(),
+                        bytes: hashes.bytes,
+                        md5: hashes.md5,
+                        sha256: hashes.sha256,
+                    };
+
+                    (api, hash)
+                })
+                .collect(),
+        });
+    }
+
+    verification
+}
+
+/// Fingerprints several APIs exactly matched, with the folder of each. Only
+/// the first fingerprint matched to the same files is kept, as every
+/// fingerprint of a file would otherwise download it again.
+fn candidates(results: &[ApiResults]) -> (Vec<Candidate<'_>>, HashMap<u32, String>) {
+    let mut files = BTreeMap::<u32, Vec<(&'static str, i32, &curse::File)>>::new();
+    let mut folders = HashMap::new();
+
+    for result in results {
+        let mut seen = BTreeSet::new();
+
+        for info in result.exact_matches() {
+            for module in &info.file.modules {
+                if !seen.insert(module.fingerprint) {
+                    continue;
+                }
+
+                files.entry(module.fingerprint).or_default().push((
+                    result.api_choice.key(),
+                    info.id,
+                    &info.file,
+                ));
+                folders
+                    .entry(module.fingerprint)
+                    .or_insert_with(|| module.foldername.clone());
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+
+    let candidates = files
+        .into_iter()
+        .filter(|(_, files)| files.len() > 1)
+        .filter(|(_, files)| {
+            seen.insert(
+                files
+                    .iter()
+                    .map(|(api, package_id, file)| (*api, *package_id, file.id))
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect();
+
+    (candidates, folders)
+}
+
+/// Streams the file at `url` to `path`, hashing it on the way. Download URLs
+/// commonly redirect to a CDN, so unlike the API requests redirects are
+/// followed.
+async fn download(client: &HttpClient, url: &str, path: &Path) -> Result<Hashes, anyhow::Error> {
+    let request = Request::get(url)
+        .redirect_policy(RedirectPolicy::Limit(10))
+        .body(())?;
+    let mut response = client.send_async(request).await?;
+
+    if !response.status().is_success() {
+        anyhow::bail!("status {}", response.status());
+    }
+
+    let mut file = std::fs::File::create(path)?;
+    let mut md5 = Md5::new();
+    let mut sha256 = Sha256::new();
+    let mut bytes = 0;
+    let mut buffer = vec![0; 64 * 1024];
+
+    loop {
+        let read = response.body_mut().read(&mut buffer).await?;
+
+        if read == 0 {
+            break;
+        }
+
+        md5.update(&buffer[..read]);
+        sha256.update(&buffer[..read]);
+        file.write_all(&buffer[..read])?;
+        bytes += read as u64;
+    }
+
+    Ok(Hashes {
+        bytes,
+        md5: format!("{:x}", md5.finalize()),
+        sha256: format!("{:x}", sha256.finalize()),
+    })
+}
+
+/// The directory of the run's downloads, removed when dropped unless
+/// `--keep-downloads` was given. It's always a new directory named after a
+/// random id, so removing it can't touch anything under `--verify-dir` that was
+/// there before.
+struct DownloadDir {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl DownloadDir {
+    fn new(opts: &Opts) -> DownloadDir {
+        let path = opts
+            .verify_dir
+            .clone()
+            .unwrap_or_else(std::env::temp_dir)
+            .join(format!("ajour-api-test-files-{}", sample::run_id()));
+
+        if let Err(e) = std::fs::create_dir_all(&path) {
+            // Each download then fails on its own and counts as failed
+            warn!("failed to create {} - {}", path.display(), e);
+        }
+
+        DownloadDir {
+            path,
+            keep: opts.keep_downloads,
+        }
+    }
+
+    /// Path to download the `index`th file to, keeping its name readable
+    /// for `--keep-downloads`.
+    fn file(&self, index: usize, name: &str) -> PathBuf {
+        let name = name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+
+        self.path.join(format!("{}-{}", index, name))
+    }
+
+    fn kept(&self) -> Option<PathBuf> {
+        Some(self.path.clone()).filter(|_| self.keep)
+    }
+}
+
+impl Drop for DownloadDir {
+    fn drop(&mut self) {
+        if self.keep {
+            return;
+        }
+
+        if let Err(e) = std::fs::remove_dir_all(&self.path) {
+            warn!("failed to remove {} - {}", self.path.display(), e);
+        }
+    }
+}
@@ -0,0 +1,313 @@
+//! The Curse fingerprint of an addon folder, as ajour-core and the Curse
+//! client compute it, over the files of a downloaded archive rather than a
+//! folder on disk.
+//!
+//! A folder's fingerprint covers its toc files, `Bindings.xml`, and every
+//! lua and xml file those include, following includes recursively. Each
+//! file is hashed with MurmurHash2 after dropping its whitespace, and the
+//! sorted hashes are joined as decimal and hashed once more.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+/// Suffixes of the toc files for a single flavor, like `Addon-Classic.toc`.
+const TOC_FLAVORS: [&str; 7] = [
+    "mainline", "bcc", "tbc", "classic", "vanilla", "wrath", "wotlkc",
+];
+
+/// Files of an archive keyed by their lowercased path with `/` separators,
+/// since the game and Curse resolve includes without regard to case.
+pub type ArchiveFiles = BTreeMap<String, Vec<u8>>;
+
+/// Fingerprints `folder`, a top level folder of `files`, failing if one of
+/// the files it includes is missing like ajour-core does.
+pub fn folder(files: &ArchiveFiles, folder: &str) -> Result<u32, String> {
+    let folder = folder.to_lowercase();
+    let prefix = format!("{}/", folder);
+
+    let mut to_fingerprint = BTreeSet::new();
+    let mut to_parse = VecDeque::new();
+
+    for path in files.keys().filter(|path| path.starts_with(&prefix)) {
+        let relative = &path[prefix.len()..];
+
+        if is_toc(&folder, relative) {
+            to_parse.push_back(path.clone());
+        } else if relative == "bindings.xml" {
+            to_fingerprint.insert(path.clone());
+        }
+    }
+
+    while let Some(path) = to_parse.pop_front() {
+        if !to_fingerprint.insert(path.clone()) {
+            continue;
+        }
+
+        let text = String::from_utf8_lossy(&files[&path]).to_lowercase();
+        let includes = if path.ends_with(".toc") {
+            toc_includes(&text)
+        } else if path.ends_with(".xml") {
+            xml_includes(&text)
+        } else {
+            continue;
+        };
+
+        let dir = path.rsplit_once('/').map_or("", |(dir, _)| dir);
+
+        for include in includes {
+            let included = resolve(dir, &include);
+
+            if !files.contains_key(&included) {
+                return Err(format!("{} includes missing file {}", path, include));
+            }
+
+            to_parse.push_back(included);
+        }
+    }
+
+    if to_fingerprint.is_empty() {
+        return Err(format!("no toc file in {}", folder));
+    }
+
+    let mut hashes = to_fingerprint
+        .iter()
+        .map(|path| {
+            let stripped = files[path]
+                .iter()
+                .copied()
+                .filter(|&b| !matches!(b, b' ' | b'\t' | b'\n' | b'\r'))
+                .collect::<Vec<_>>();
+
+            murmur2(&stripped, 1)
+        })
+        .collect::<Vec<_>>();
+    hashes.sort_unstable();
+
+    let joined = hashes.iter().map(u32::to_string).collect::<String>();
+
+    Ok(murmur2(joined.as_bytes(), 1))
+}
+
+/// Whether `relative`, a lowercased path inside the folder, is one of its
+/// toc files.
+fn is_toc(folder: &str, relative: &str) -> bool {
+    let stem = match relative.strip_suffix(".toc") {
+        Some(stem) if stem.starts_with(folder) => &stem[folder.len()..],
+        _ => return false,
+    };
+
+    stem.is_empty()
+        || stem
+            .strip_prefix(&['-', '_'][..])
+            .map_or(false, |flavor| TOC_FLAVORS.contains(&flavor))
+}
+
+/// Lua and xml files a toc lists, one per line after `#` comments.
+fn toc_includes(text: &str) -> Vec<String> {
+    text.split(&['\n', '\r'][..])
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| line.ends_with(".lua") || line.ends_with(".xml"))
+        .filter(|line| !line.contains(".."))
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Files an xml includes through self-closing `<Include file="...">` and
+/// `<Script file="...">` tags outside of comments.
+fn xml_includes(text: &str) -> Vec<String> {
+    let mut uncommented = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("<!--") {
+        uncommented.push_str(&rest[..start]);
+
+        rest = match rest[start..].find("-->") {
+            Some(end) => &rest[start + end + 3..],
+            None => "",
+        };
+    }
+    uncommented.push_str(rest);
+
+    let mut includes = vec![];
+
+    for line in uncommented.split(&['\n', '\r'][..]) {
+        let mut rest = line;
+
+        while let Some(start) = rest.find('<') {
+            rest = &rest[start + 1..];
+
+            if let Some((path, after)) = include_tag(rest) {
+                includes.push(path.to_owned());
+                rest = after;
+            }
+        }
+    }
+
+    includes
+}
+
+/// The path of an include tag starting right after its `<`, with what
+/// follows the tag.
+fn include_tag(tag: &str) -> Option<(&str, &str)> {
+    let rest = tag
+        .strip_prefix("include")
+        .or_else(|| tag.strip_prefix("script"))?;
+
+    let trimmed = rest.trim_start();
+    if trimmed.len() == rest.len() {
+        return None;
+    }
+
+    let rest = trimmed.strip_prefix("file=")?;
+    let rest = rest.strip_prefix(&['"', '\''][..])?;
+    let end = rest.find(&['"', '\''][..])?;
+    let (path, after) = (&rest[..end], &rest[end + 1..]);
+
+    let after = after.trim_start().strip_prefix("/>")?;
+
+    if path.is_empty() || path.contains("..") {
+        return None;
+    }
+
+    Some((path, after))
+}
+
+/// The archive path of `include`, relative to the directory of the file
+/// that included it, with Windows separators and `.` segments resolved.
+fn resolve(dir: &str, include: &str) -> String {
+    let include = include.replace('\\', "/");
+
+    dir.split('/')
+        .chain(include.split('/'))
+        .filter(|segment| !segment.is_empty() && *segment != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// 32-bit MurmurHash2, which Curse fingerprints are built from.
+///
+/// ajour-core has its own, but only fingerprints folders on disk through
+/// `fingerprint_addon_dir` and keeps its hash private, so it can't hash the
+/// files of an archive held in memory without unpacking it first.
+fn murmur2(data: &[u8], seed: u32) -> u32 {
+    const M: u32 = 0x5bd1_e995;
+    const R: u32 = 24;
+
+    let mut h = seed ^ data.len() as u32;
+    let mut chunks = data.chunks_exact(4);
+
+    for chunk in &mut chunks {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+
+        h = h.wrapping_mul(M);
+        h ^= k;
+    }
+
+    let tail = chunks.remainder();
+
+    if !tail.is_empty() {
+        for (i, &byte) in tail.iter().enumerate() {
+            h ^= u32::from(byte) << (8 * i);
+        }
+
+        h = h.wrapping_mul(M);
+    }
+
+    h ^= h >> 13;
+    h = h.wrapping_mul(M);
+    h ^= h >> 15;
+
+    h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn murmur2_matches_reference_vectors() {
+        // Every tail length, with the seed fingerprints use
+        assert_eq!(murmur2(b"", 1), 0x5bd1_5e36);
+        assert_eq!(murmur2(b"a", 1), 0x2550_b18c);
+        assert_eq!(murmur2(b"ab", 1), 0x64e1_50ee);
+        assert_eq!(murmur2(b"abc", 1), 0x60a4_fcc1);
+        assert_eq!(murmur2(b"abcd", 1), 0xc93f_7a16);
+        assert_eq!(murmur2(b"Hello, world!", 1), 0x63a2_7f49);
+    }
+
+    #[test]
+    fn murmur2_passes_smhasher_verification() {
+        // SMHasher hashes 0, 1, ... 255 byte keys with seeds 256 down to 1,
+        // then hashes those hashes with seed 0
+        let key = (0..=255).collect::<Vec<u8>>();
+        let hashes = (0..256)
+            .flat_map(|i| murmur2(&key[..i], 256 - i as u32).to_le_bytes())
+            .collect::<Vec<_>>();
+
+        assert_eq!(murmur2(&hashes, 0), 0x2786_4c1e);
+    }
+
+    fn archive(files: &[(&str, &str)]) -> ArchiveFiles {
+        files
+            .iter()
+            .map(|(path, contents)| (path.to_lowercase(), contents.as_bytes().to_vec()))
+            .collect()
+    }
+
+    /// What ajour-core fingerprints `folder` of `files` as once they're
+    /// unpacked to disk.
+    fn ajour_core_fingerprint(files: &[(&str, &str)], folder: &str) -> u32 {
+        let dir =
+            std::env::temp_dir().join(format!("ajour-api-test-fingerprint-{}", std::process::id()));
+
+        for (path, contents) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+
+        let fingerprint = ajour_core::parse::fingerprint_addon_dir(&dir.join(folder));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        fingerprint.unwrap()
+    }
+
+    #[test]
+    fn folder_follows_toc_and_xml_includes() {
+        let files = [
+            (
+                "Addon/Addon.toc",
+                "## Interface: 90002\n## Title: Addon\n# Old.lua\nCore.lua\nUI\\Frames.xml\n",
+            ),
+            ("Addon/Core.lua", "local addon = {}\n"),
+            (
+                "Addon/UI/Frames.xml",
+                "<Ui>\n  <!-- <Include file=\"Old.xml\"/> -->\n  <Script file=\"Frames.lua\"/>\n</Ui>\n",
+            ),
+            ("Addon/UI/Frames.lua", "addon.frames = {}\n"),
+            // Not included, so not fingerprinted
+            ("Addon/Unused.lua", "print(\"unused\")\n"),
+            ("Other/Other.toc", "Other.lua\n"),
+        ];
+
+        assert_eq!(
+            folder(&archive(&files), "Addon"),
+            Ok(ajour_core_fingerprint(&files, "Addon"))
+        );
+    }
+
+    #[test]
+    fn folder_fails_on_a_missing_include() {
+        let files = archive(&[
+            ("addon/addon.toc", "Core.lua\nMissing.lua\n"),
+            ("addon/core.lua", ""),
+        ]);
+
+        assert_eq!(
+            folder(&files, "Addon"),
+            Err("addon/addon.toc includes missing file missing.lua".to_owned())
+        );
+    }
+}
@@ -0,0 +1,493 @@
+//! The APIs an audit compares, each implementing `FingerprintApi` so batches
+//! are sent and their responses read the same way whichever API they go to.
+//! Besides the fingerprint APIs, that covers the repositories Ajour matches
+//! by toc metadata, through the addon list each serves.
+
+use crate::cli::{ApiVersion, Opts};
+use crate::error::AuditError;
+use crate::{
+    curse_v1, decode_json, schema, strict, tukui, wowinterface, BatchResult,
+    WowUpFingerprintRequest, WowUpFingerprintResponse, CURSE_API_KEY_HEADER, CURSE_FINGERPRINT_URL,
+    SELF_TEST_FINGERPRINTS, WOWUP_FINGERPRINT_URL,
+};
+
+use ajour_core::repository::curse;
+use isahc::http::request::Builder;
+use isahc::http::Request;
+use serde::Deserialize;
+use serde_json::Value;
+
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+
+const TUKUI_ADDONS_URL: &str = "https://www.tukui.org/api.php?addons";
+const TOWNLONG_YAK_ADDONS_URL: &str = "https://hub.wowup.io/addons/author/foxlit";
+
+/// How to build one API's requests and read its responses.
+pub trait FingerprintApi: Sync {
+    /// Short identifier used in machine-readable output.
+    fn key(&self) -> &'static str;
+
+    fn name(&self) -> &'static str;
+
+    /// Where batches are sent.
+    fn fingerprint_url(&self, api_version: ApiVersion) -> &'static str;
+
+    /// Adds the API's key to a request, if one was given.
+    fn authorize(&self, opts: &Opts, request: Builder) -> Builder;
+
+    /// Serializes already sorted fingerprints into a request body, which is
+    /// empty for an API that doesn't take them.
+    fn request_body(
+        &self,
+        api_version: ApiVersion,
+        fingerprints: Vec<u32>,
+    ) -> serde_json::Result<Vec<u8>>;
+
+    /// Where `request_body` put the fingerprints in a parsed request.
+    fn request_fingerprints<'a>(
+        &self,
+        api_version: ApiVersion,
+        request: &'a Value,
+    ) -> Option<&'a Value>;
+
+    /// Reads a parsed response into the common match type. `packages` are
+    /// the ones the batch answers for, which only an API matching by name
+    /// reads.
+    fn decode_response(&self, response: &Value, packages: &[&curse::Package]) -> BatchResult;
+
+    /// The schema in `schemas/` responses are validated against.
+    fn response_schema(&self) -> &'static str;
+
+    /// A response bundled for `--self-test`, matching `SELF_TEST_FINGERPRINTS`.
+    fn sample_response(&self, api_version: ApiVersion) -> &'static str;
+
+    /// Rewrites a fingerprint response into the shape `decode_response`
+    /// expects.
+    fn normalize_response(
+        &self,
+        _api_version: ApiVersion,
+        response: Value,
+    ) -> Result<Value, AuditError> {
+        Ok(response)
+    }
+
+    /// Whether the API matches a batch's packages by name rather than by
+    /// fingerprint, so its responses aren't fingerprint matches that
+    /// `--strict-json` and `--detect-schema-drift` could check.
+    fn matches_by_name(&self) -> bool {
+        false
+    }
+
+    /// Builds the request for an encoded body, marking it as gzipped if
+    /// `gzipped` is set.
+    fn build_request(
+        &self,
+        opts: &Opts,
+        body: Vec<u8>,
+        gzipped: bool,
+    ) -> Result<Request<Vec<u8>>, AuditError> {
+        let mut request = Request::builder()
+            .uri(self.fingerprint_url(opts.api_version))
+            .method("POST")
+            .header("content-type", "application/json");
+
+        if gzipped {
+            request = request.header("content-encoding", "gzip");
+        }
+
+        Ok(self.authorize(opts, request).body(body)?)
+    }
+
+    /// Builds the fingerprint request body.
+    fn encode_request(
+        &self,
+        api_version: ApiVersion,
+        mut fingerprints: Vec<u32>,
+    ) -> Result<Vec<u8>, AuditError> {
+        // Keeps the request body stable across runs, which replay relies on
+        fingerprints.sort_unstable();
+
+        self.request_body(api_version, fingerprints)
+            .map_err(AuditError::Serialize)
+    }
+
+    /// Checks the request encoding and response decoding against bundled
+    /// samples, without any network access.
+    fn self_test(&self, api_version: ApiVersion) -> Result<(), String> {
+        let body = self
+            .encode_request(api_version, SELF_TEST_FINGERPRINTS.to_vec())
+            .map_err(|e| e.to_string())?;
+
+        let value = serde_json::from_slice::<Value>(&body)
+            .map_err(|e| format!("request isn't valid json: {}", e))?;
+        let round_tripped = self
+            .request_fingerprints(api_version, &value)
+            .and_then(|v| serde_json::from_value::<Vec<u32>>(v.clone()).ok())
+            .ok_or_else(|| format!("unexpected request body {}", value))?;
+
+        let mut expected = SELF_TEST_FINGERPRINTS.to_vec();
+        expected.sort_unstable();
+
+        if round_tripped != expected {
+            return Err(format!(
+                "request encoded {:?} instead of {:?}",
+                round_tripped, expected
+            ));
+        }
+
+        let sample = serde_json::from_str::<Value>(self.sample_response(api_version))
+            .map_err(|e| format!("bundled sample isn't valid json: {}", e))?;
+        let response = self
+            .normalize_response(api_version, sample)
+            .map_err(|e| e.to_string())?;
+
+        let errors = schema::validate_response(self.response_schema(), &response);
+
+        if !errors.is_empty() {
+            return Err(format!(
+                "sample failed schema validation: {}",
+                errors.join("; ")
+            ));
+        }
+
+        strict::check_fingerprint_response(&response)
+            .map_err(|e| format!("sample failed strict parsing: {}", e))?;
+
+        let info = self
+            .decode_response(&response, &[])
+            .map_err(|e| e.to_string())?;
+        let matched = info
+            .exact_matches
+            .iter()
+            .flat_map(|m| m.file.modules.iter().map(|m| m.fingerprint))
+            .collect::<BTreeSet<_>>();
+
+        if !expected.iter().all(|f| matched.contains(f)) {
+            return Err(format!(
+                "sample decoded to fingerprints {:?}, expected {:?}",
+                matched, expected
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for dyn FingerprintApi + '_ {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}_api", self.key())
+    }
+}
+
+pub struct Curse;
+
+impl FingerprintApi for Curse {
+    fn key(&self) -> &'static str {
+        "curse"
+    }
+
+    fn name(&self) -> &'static str {
+        "Curse"
+    }
+
+    fn fingerprint_url(&self, api_version: ApiVersion) -> &'static str {
+        match api_version {
+            ApiVersion::V1 => curse_v1::FINGERPRINT_URL,
+            ApiVersion::V2 => CURSE_FINGERPRINT_URL,
+        }
+    }
+
+    fn authorize(&self, opts: &Opts, request: Builder) -> Builder {
+        match &opts.api_key {
+            Some(api_key) => request.header(CURSE_API_KEY_HEADER, api_key.as_str()),
+            None => request,
+        }
+    }
+
+    fn request_body(
+        &self,
+        api_version: ApiVersion,
+        fingerprints: Vec<u32>,
+    ) -> serde_json::Result<Vec<u8>> {
+        match api_version {
+            ApiVersion::V2 => serde_json::to_vec(&fingerprints),
+            // v1 takes the same body as WowUp
+            ApiVersion::V1 => serde_json::to_vec(&WowUpFingerprintRequest { fingerprints }),
+        }
+    }
+
+    fn request_fingerprints<'a>(
+        &self,
+        api_version: ApiVersion,
+        request: &'a Value,
+    ) -> Option<&'a Value> {
+        match api_version {
+            ApiVersion::V2 => Some(request),
+            ApiVersion::V1 => request.get("fingerprints"),
+        }
+    }
+
+    fn normalize_response(
+        &self,
+        api_version: ApiVersion,
+        response: Value,
+    ) -> Result<Value, AuditError> {
+        match api_version {
+            ApiVersion::V1 => curse_v1::fingerprint_response(response),
+            ApiVersion::V2 => Ok(response),
+        }
+    }
+
+    fn decode_response(&self, response: &Value, _packages: &[&curse::Package]) -> BatchResult {
+        decode_json::<curse::FingerprintInfo>(response)
+    }
+
+    fn response_schema(&self) -> &'static str {
+        schema::CURSE_FINGERPRINT
+    }
+
+    fn sample_response(&self, api_version: ApiVersion) -> &'static str {
+        match api_version {
+            ApiVersion::V1 => include_str!("../samples/curse_v1_fingerprint_response.json"),
+            ApiVersion::V2 => include_str!("../samples/curse_fingerprint_response.json"),
+        }
+    }
+}
+
+/// The WowUp hub, which mirrors the Curse fingerprint endpoint whatever the
+/// `--api-version`.
+pub struct WowUp;
+
+impl FingerprintApi for WowUp {
+    fn key(&self) -> &'static str {
+        "wowup"
+    }
+
+    fn name(&self) -> &'static str {
+        "WowUp"
+    }
+
+    fn fingerprint_url(&self, _api_version: ApiVersion) -> &'static str {
+        WOWUP_FINGERPRINT_URL
+    }
+
+    fn authorize(&self, opts: &Opts, request: Builder) -> Builder {
+        match &opts.wowup_api_key {
+            Some(api_key) => request.header("authorization", format!("Bearer {}", api_key)),
+            None => request,
+        }
+    }
+
+    fn request_body(
+        &self,
+        _api_version: ApiVersion,
+        fingerprints: Vec<u32>,
+    ) -> serde_json::Result<Vec<u8>> {
+        serde_json::to_vec(&WowUpFingerprintRequest { fingerprints })
+    }
+
+    fn request_fingerprints<'a>(
+        &self,
+        _api_version: ApiVersion,
+        request: &'a Value,
+    ) -> Option<&'a Value> {
+        request.get("fingerprints")
+    }
+
+    fn decode_response(&self, response: &Value, _packages: &[&curse::Package]) -> BatchResult {
+        decode_json::<WowUpFingerprintResponse>(response)
+            .and_then(WowUpFingerprintResponse::into_fingerprint_info)
+    }
+
+    fn response_schema(&self) -> &'static str {
+        schema::WOWUP_FINGERPRINT
+    }
+
+    fn sample_response(&self, _api_version: ApiVersion) -> &'static str {
+        include_str!("../samples/wowup_fingerprint_response.json")
+    }
+}
+
+/// A repository without a fingerprint endpoint, which Ajour matches addons
+/// to by their toc metadata instead. A batch's packages count as matched
+/// when the repository's addon list has an addon of the same name.
+///
+/// The list is fetched again for every batch, so a `--batch-size` spanning
+/// the whole search keeps it to a single request.
+pub struct AddonList {
+    key: &'static str,
+    name: &'static str,
+    url: &'static str,
+    schema: &'static str,
+    sample: &'static str,
+    /// Reads the names of the listed addons out of a parsed list.
+    names: fn(&Value) -> Result<Vec<String>, AuditError>,
+}
+
+/// Tukui's retail addons. ElvUI and Tukui themselves are served apart from
+/// the list, which the `tukui` subcommand checks.
+pub static TUKUI: AddonList = AddonList {
+    key: "tukui",
+    name: "Tukui",
+    url: TUKUI_ADDONS_URL,
+    schema: schema::TUKUI_ADDONS,
+    sample: include_str!("../samples/tukui_addons.json"),
+    names: tukui_names,
+};
+
+pub static WOWINTERFACE: AddonList = AddonList {
+    key: "wowinterface",
+    name: "WowInterface",
+    url: wowinterface::WOWINTERFACE_FILE_LIST_URL,
+    schema: schema::WOWINTERFACE_FILE_LIST,
+    sample: include_str!("../samples/wowinterface_file_list.json"),
+    names: wowinterface_names,
+};
+
+/// Townlong-Yak's addons, which the WowUp hub lists under their author.
+pub static TOWNLONG_YAK: AddonList = AddonList {
+    key: "townlong-yak",
+    name: "Townlong-Yak",
+    url: TOWNLONG_YAK_ADDONS_URL,
+    schema: schema::TOWNLONG_YAK_ADDONS,
+    sample: include_str!("../samples/townlong_yak_addons.json"),
+    names: townlong_yak_names,
+};
+
+#[derive(Deserialize)]
+struct TownlongYakList {
+    addons: Vec<TownlongYakAddon>,
+}
+
+#[derive(Deserialize)]
+struct TownlongYakAddon {
+    repository_name: String,
+}
+
+fn tukui_names(list: &Value) -> Result<Vec<String>, AuditError> {
+    let addons = decode_json::<Vec<tukui::TukuiAddon>>(list)?;
+
+    Ok(addons.into_iter().map(|addon| addon.name).collect())
+}
+
+fn wowinterface_names(list: &Value) -> Result<Vec<String>, AuditError> {
+    let addons = decode_json::<Vec<wowinterface::WowiAddon>>(list)?;
+
+    Ok(addons.into_iter().map(|addon| addon.name).collect())
+}
+
+fn townlong_yak_names(list: &Value) -> Result<Vec<String>, AuditError> {
+    let list = decode_json::<TownlongYakList>(list)?;
+
+    Ok(list
+        .addons
+        .into_iter()
+        .map(|addon| addon.repository_name)
+        .collect())
+}
+
+impl FingerprintApi for AddonList {
+    fn key(&self) -> &'static str {
+        self.key
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn fingerprint_url(&self, _api_version: ApiVersion) -> &'static str {
+        self.url
+    }
+
+    fn authorize(&self, _opts: &Opts, request: Builder) -> Builder {
+        request
+    }
+
+    fn request_body(
+        &self,
+        _api_version: ApiVersion,
+        _fingerprints: Vec<u32>,
+    ) -> serde_json::Result<Vec<u8>> {
+        Ok(vec![])
+    }
+
+    fn request_fingerprints<'a>(
+        &self,
+        _api_version: ApiVersion,
+        _request: &'a Value,
+    ) -> Option<&'a Value> {
+        None
+    }
+
+    fn decode_response(&self, response: &Value, packages: &[&curse::Package]) -> BatchResult {
+        let listed = (self.names)(response)?
+            .iter()
+            .map(|name| wowinterface::key(name))
+            .collect::<HashSet<_>>();
+
+        // A listed package is matched on its own latest files, so it counts
+        // towards the API the way a fingerprint match would
+        let exact_matches = packages
+            .iter()
+            .filter(|package| listed.contains(&wowinterface::key(&package.name)))
+            .filter_map(|package| {
+                Some(curse::AddonFingerprintInfo {
+                    id: package.id,
+                    file: package.latest_files.first()?.clone(),
+                    latest_files: package.latest_files.clone(),
+                })
+            })
+            .collect();
+
+        Ok(curse::FingerprintInfo {
+            exact_matches,
+            partial_matches: vec![],
+        })
+    }
+
+    fn response_schema(&self) -> &'static str {
+        self.schema
+    }
+
+    fn sample_response(&self, _api_version: ApiVersion) -> &'static str {
+        self.sample
+    }
+
+    fn matches_by_name(&self) -> bool {
+        true
+    }
+
+    fn build_request(
+        &self,
+        _opts: &Opts,
+        body: Vec<u8>,
+        _gzipped: bool,
+    ) -> Result<Request<Vec<u8>>, AuditError> {
+        Ok(Request::builder().method("GET").uri(self.url).body(body)?)
+    }
+
+    /// Checks the bundled sample list against its schema and that it names
+    /// addons, as there are no fingerprints to round trip.
+    fn self_test(&self, _api_version: ApiVersion) -> Result<(), String> {
+        let sample = serde_json::from_str::<Value>(self.sample)
+            .map_err(|e| format!("bundled sample isn't valid json: {}", e))?;
+
+        let errors = schema::validate_response(self.schema, &sample);
+
+        if !errors.is_empty() {
+            return Err(format!(
+                "sample failed schema validation: {}",
+                errors.join("; ")
+            ));
+        }
+
+        let names = (self.names)(&sample).map_err(|e| e.to_string())?;
+
+        if names.iter().all(String::is_empty) {
+            return Err("sample lists no named addons".to_owned());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+//! Downloads a sample of the matched files for `--recompute-fingerprints`
+//! and fingerprints their folders locally, to tell whether a regression in
+//! matches comes from how the fingerprints are computed or from the API's
+//! index.
+//!
+//! Every folder the API lists for a file is recomputed from the archive and
+//! compared with the fingerprint the API advertised for it. If most agree,
+//! the hashing is sound and mismatches point at the index.
+
+use crate::cli::Opts;
+use crate::fingerprint::{self, ArchiveFiles};
+use crate::{sample, ApiResults};
+
+use async_std::future::timeout;
+use futures::{stream, AsyncReadExt, StreamExt};
+use isahc::config::RedirectPolicy;
+use isahc::prelude::*;
+use serde::Serialize;
+use tracing::{debug, info};
+
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::time::Duration;
+
+#[derive(Serialize)]
+pub struct FingerprintSelfTest {
+    /// Seed the files were sampled with.
+    pub seed: u64,
+    /// Distinct matched files the sample was picked from.
+    pub candidates: usize,
+    pub sampled: usize,
+    /// Sampled files that couldn't be downloaded or unpacked.
+    pub failed: usize,
+    /// Folders of the downloaded files that were recomputed.
+    pub folders: usize,
+    /// Folders whose recomputed fingerprint is the advertised one.
+    pub agreed: usize,
+    /// Folders that recomputed to another fingerprint or couldn't be
+    /// recomputed, sorted by package and folder.
+    pub mismatches: Vec<RecomputedMismatch>,
+}
+
+#[derive(Serialize)]
+pub struct RecomputedMismatch {
+    pub package_id: i32,
+    pub file_id: i64,
+    pub folder: String,
+    /// APIs that matched the file.
+    pub apis: Vec<&'static str>,
+    pub advertised: u32,
+    /// Absent if the folder couldn't be fingerprinted, such as when it's
+    /// missing from the archive.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub computed: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A matched file and the folders and fingerprints the APIs advertised.
+struct Candidate {
+    package_id: i32,
+    file_id: i64,
+    download_url: String,
+    apis: Vec<&'static str>,
+    modules: BTreeMap<String, u32>,
+}
+
+/// Downloads a sample of the files the APIs in `results` exactly matched
+/// and recomputes the fingerprints of their folders.
+pub async fn check(
+    client: &HttpClient,
+    opts: &Opts,
+    results: &[ApiResults],
+) -> FingerprintSelfTest {
+    let candidates = candidates(results);
+    let seed = opts.seed.unwrap_or_else(sample::random_seed);
+    let total = candidates.len();
+    let sampled = sample::sample(candidates, opts.recompute_sample_size, seed);
+
+    info!(
+        "Downloading {} of {} matched files to recompute their fingerprints",
+        sampled.len(),
+        total
+    );
+
+    let request_timeout = Duration::from_secs(opts.request_timeout);
+
+    let archives = stream::iter(&sampled)
+        .map(|candidate| async move {
+            let url = candidate.download_url.as_str();
+
+            let files = match timeout(request_timeout, download(client, url)).await {
+                Ok(Ok(bytes)) => unpack(bytes),
+                Ok(Err(e)) => Err(e),
+                Err(_) => Err(anyhow::anyhow!("timed out")),
+            };
+
+            if let Err(e) = &files {
+                debug!(url, "failed to fetch file to fingerprint - {}", e);
+            }
+
+            files.ok()
+        })
+        .buffered(opts.max_connections)
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut test = FingerprintSelfTest {
+        seed,
+        candidates: total,
+        sampled: sampled.len(),
+        failed: 0,
+        folders: 0,
+        agreed: 0,
+        mismatches: vec![],
+    };
+
+    for (candidate, files) in sampled.iter().zip(archives) {
+        let files = match files {
+            Some(files) => files,
+            None => {
+                test.failed += 1;
+                continue;
+            }
+        };
+
+        for (folder, &advertised) in &candidate.modules {
+            test.folders += 1;
+
+            let (computed, error) = match fingerprint::folder(&files, folder) {
+                Ok(computed) if computed == advertised => {
+                    test.agreed += 1;
+                    continue;
+                }
+                Ok(computed) => (Some(computed), None),
+                Err(e) => (None, Some(e)),
+            };
+
+            test.mismatches.push(RecomputedMismatch {
+                package_id: candidate.package_id,
+                file_id: candidate.file_id,
+                folder: folder.clone(),
+                apis: candidate.apis.clone(),
+                advertised,
+                computed,
+                error,
+            });
+        }
+    }
+
+    test.mismatches
+        .sort_by(|a, b| (a.package_id, &a.folder).cmp(&(b.package_id, &b.folder)));
+
+    test
+}
+
+/// Every distinct file the APIs exactly matched, by download URL, with the
+/// APIs that matched it.
+fn candidates(results: &[ApiResults]) -> Vec<Candidate> {
+    let mut files = BTreeMap::<&str, Candidate>::new();
+
+    for result in results {
+        for (package_id, file) in result.matched_files() {
+            if file.download_url.is_empty() {
+                continue;
+            }
+
+            let candidate = files
+                .entry(file.download_url.as_str())
+                .or_insert_with(|| Candidate {
+                    package_id,
+                    file_id: file.id,
+                    download_url: file.download_url.clone(),
+                    apis: vec![],
+                    modules: file
+                        .modules
+                        .iter()
+                        .map(|m| (m.foldername.clone(), m.fingerprint))
+                        .collect(),
+                });
+
+            candidate.apis.push(result.api_choice.key());
+        }
+    }
+
+    files.into_iter().map(|(_, candidate)| candidate).collect()
+}
+
+/// Reads the file at `url` into memory. Download URLs commonly redirect to a
+/// CDN, so unlike the API requests redirects are followed.
+async fn download(client: &HttpClient, url: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let request = Request::get(url)
+        .redirect_policy(RedirectPolicy::Limit(10))
+        .body(())?;
+    let mut response = client.send_async(request).await?;
+
+    if !response.status().is_success() {
+        anyhow::bail!("status {}", response.status());
+    }
+
+    let mut bytes = vec![];
+    response.body_mut().read_to_end(&mut bytes).await?;
+
+    Ok(bytes)
+}
+
+/// Every file of a zip archive, keyed the way `fingerprint::folder` looks
+/// them up.
+fn unpack(bytes: Vec<u8>) -> Result<ArchiveFiles, anyhow::Error> {
+    // Only here, as the response body is read asynchronously above
+    use std::io::Read;
+
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))?;
+    let mut files = ArchiveFiles::new();
+
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+
+        if file.is_dir() {
+            continue;
+        }
+
+        let path = file.name().replace('\\', "/").to_lowercase();
+        let mut contents = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut contents)?;
+
+        files.insert(path, contents);
+    }
+
+    Ok(files)
+}
@@ -0,0 +1,129 @@
+//! Maps every fingerprint of the audited packages back to the package,
+//! file and folder it came from.
+//!
+//! Several packages can ship the same folder, such as a bundled library,
+//! so a fingerprint may have several origins. Batches send each one once,
+//! and the index attributes what the APIs matched it to, or that they
+//! missed it, to every package that submitted it.
+
+use crate::ApiResults;
+
+use ajour_core::repository::curse;
+use serde::Serialize;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+#[derive(Clone, Serialize)]
+pub struct Origin {
+    pub package_id: i32,
+    pub file_id: i64,
+    pub folder: String,
+}
+
+pub struct FingerprintIndex {
+    origins: BTreeMap<u32, Vec<Origin>>,
+}
+
+#[derive(Serialize)]
+pub struct FingerprintAttribution {
+    pub fingerprint: u32,
+    /// Every package file and folder the fingerprint was submitted for.
+    pub origins: Vec<Origin>,
+    /// Package each API exactly matched the fingerprint to, keyed by API.
+    pub matched_to: BTreeMap<&'static str, i32>,
+    /// APIs that didn't match the fingerprint, leaving out those whose
+    /// batch failed for every package it came from.
+    pub missed_by: Vec<&'static str>,
+}
+
+impl FingerprintAttribution {
+    /// Whether an API matched the fingerprint to a package none of its
+    /// origins belong to.
+    pub fn foreign(&self) -> bool {
+        self.matched_to
+            .values()
+            .any(|id| !self.origins.iter().any(|o| o.package_id == *id))
+    }
+}
+
+impl FingerprintIndex {
+    pub fn new(packages: &[curse::Package]) -> FingerprintIndex {
+        let mut origins = BTreeMap::<u32, Vec<Origin>>::new();
+
+        for package in packages {
+            for file in &package.latest_files {
+                for module in &file.modules {
+                    origins.entry(module.fingerprint).or_default().push(Origin {
+                        package_id: package.id,
+                        file_id: file.id,
+                        folder: module.foldername.clone(),
+                    });
+                }
+            }
+        }
+
+        FingerprintIndex { origins }
+    }
+
+    /// Fingerprints submitted for more than one package.
+    pub fn shared(&self) -> usize {
+        self.origins
+            .values()
+            .filter(|origins| {
+                origins
+                    .iter()
+                    .map(|o| o.package_id)
+                    .collect::<BTreeSet<_>>()
+                    .len()
+                    > 1
+            })
+            .count()
+    }
+
+    /// What every API made of each fingerprint, sorted by fingerprint.
+    pub fn attribute(&self, results: &[ApiResults]) -> Vec<FingerprintAttribution> {
+        let matched = results
+            .iter()
+            .map(|result| {
+                let mut matched = HashMap::new();
+
+                for info in result.exact_matches() {
+                    for module in &info.file.modules {
+                        matched.entry(module.fingerprint).or_insert(info.id);
+                    }
+                }
+
+                matched
+            })
+            .collect::<Vec<_>>();
+
+        self.origins
+            .iter()
+            .map(|(&fingerprint, origins)| {
+                let mut matched_to = BTreeMap::new();
+                let mut missed_by = vec![];
+
+                for (result, matched) in results.iter().zip(&matched) {
+                    let key = result.api_choice.key();
+
+                    match matched.get(&fingerprint) {
+                        Some(&id) => {
+                            matched_to.insert(key, id);
+                        }
+                        None if origins
+                            .iter()
+                            .all(|o| result.failed_packages.contains(&o.package_id)) => {}
+                        None => missed_by.push(key),
+                    }
+                }
+
+                FingerprintAttribution {
+                    fingerprint,
+                    origins: origins.clone(),
+                    matched_to,
+                    missed_by,
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,460 @@
+//! Self-contained HTML report for `--report-html`, for sharing an audit
+//! with people who won't read the text report.
+//!
+//! Everything, charts included, is inline in the one file, so it can be
+//! attached or pasted anywhere without the page reaching out for assets.
+
+use crate::report::{AuditReport, PackageStatus};
+use crate::ApiResults;
+
+use std::cmp::Ordering;
+use std::fmt::{self, Write as _};
+use std::io;
+use std::path::Path;
+
+/// Upper bounds in milliseconds of the latency chart's buckets, the last
+/// bucket holding everything slower.
+const LATENCY_BUCKETS_MS: [u64; 6] = [100, 250, 500, 1_000, 2_500, 5_000];
+
+/// Colors of the APIs in charts, in `--apis` order.
+const COLORS: [&str; 4] = ["#f16436", "#3b82f6", "#10b981", "#a855f7"];
+
+const STYLE: &str = "
+body { font-family: -apple-system, 'Segoe UI', Helvetica, Arial, sans-serif; margin: 2em auto; max-width: 72em; padding: 0 1em; color: #1f2328; }
+h1 { font-size: 1.5em; margin-bottom: 0.2em; }
+h2 { font-size: 1.25em; margin-top: 2em; border-bottom: 1px solid #d0d7de; padding-bottom: 0.3em; }
+h3 { font-size: 1em; margin-top: 1.5em; }
+.meta { color: #656d76; }
+table { border-collapse: collapse; margin: 0.5em 0; }
+th, td { border: 1px solid #d0d7de; padding: 0.3em 0.7em; text-align: left; }
+th { background: #f6f8fa; }
+td.num { text-align: right; font-variant-numeric: tabular-nums; }
+.bar { background: #eaeef2; width: 10em; height: 0.8em; display: inline-block; margin-right: 0.5em; }
+.bar span { background: #2da44e; height: 100%; display: block; }
+.warning { color: #9a6700; }
+.missing { color: #cf222e; }
+";
+
+/// Renders every game's report, with the latencies of its batches from
+/// `results`.
+pub fn render(games: &[(u32, &AuditReport, &[ApiResults])]) -> String {
+    let mut out = String::new();
+    // Writing to a String can't fail
+    let _ = document(&mut out, games);
+
+    out
+}
+
+/// Writes the report to `path`.
+pub fn write(path: &Path, games: &[(u32, &AuditReport, &[ApiResults])]) -> io::Result<()> {
+    std::fs::write(path, render(games))
+}
+
+fn document(out: &mut String, games: &[(u32, &AuditReport, &[ApiResults])]) -> fmt::Result {
+    let first = match games.first() {
+        Some((_, report, _)) => report,
+        None => return Ok(()),
+    };
+
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html lang=\"en\">")?;
+    writeln!(out, "<head>")?;
+    writeln!(out, "<meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>API audit {}</title>", escape(&first.run_id))?;
+    writeln!(out, "<style>{}</style>", STYLE)?;
+    writeln!(out, "</head>")?;
+    writeln!(out, "<body>")?;
+    writeln!(out, "<h1>API audit</h1>")?;
+    writeln!(
+        out,
+        "<p class=\"meta\">Run {} started {}</p>",
+        escape(&first.run_id),
+        escape(&first.started_at)
+    )?;
+
+    for (game_id, report, results) in games {
+        if games.len() > 1 {
+            writeln!(out, "<h2>Game {}</h2>", game_id)?;
+        }
+
+        game(out, report, results)?;
+    }
+
+    writeln!(out, "</body>")?;
+    writeln!(out, "</html>")
+}
+
+fn game(out: &mut String, report: &AuditReport, results: &[ApiResults]) -> fmt::Result {
+    if !report.warnings.is_empty() {
+        writeln!(out, "<ul>")?;
+
+        for warning in &report.warnings {
+            writeln!(out, "<li class=\"warning\">{}</li>", escape(warning))?;
+        }
+
+        writeln!(out, "</ul>")?;
+    }
+
+    writeln!(out, "<h3>Summary</h3>")?;
+    writeln!(out, "<table>")?;
+
+    let mut row = |label: &str, value: String| {
+        writeln!(
+            out,
+            "<tr><th>{}</th><td class=\"num\">{}</td></tr>",
+            label, value
+        )
+    };
+
+    row("Packages audited", report.packages_audited.to_string())?;
+    row("Matched by an API", report.unique_packages.to_string())?;
+    row(
+        "Discrepancies",
+        format!("{} ({:.1}%)", report.discrepancies, report.discrepancy_pct),
+    )?;
+    row(
+        "Indeterminate packages",
+        report.indeterminate_packages.to_string(),
+    )?;
+    row(
+        "Version disagreements",
+        report.version_disagreements.len().to_string(),
+    )?;
+    row(
+        "Attribution conflicts",
+        report.attribution_conflicts.len().to_string(),
+    )?;
+    row("Retries used", report.retries_used.to_string())?;
+
+    if let Some(seed) = report.sample_seed {
+        row("Sample seed", seed.to_string())?;
+    }
+
+    writeln!(out, "</table>")?;
+
+    apis(out, report)?;
+    latency_chart(out, report, results)?;
+    discrepancies(out, report)?;
+    version_disagreements(out, report)
+}
+
+fn apis(out: &mut String, report: &AuditReport) -> fmt::Result {
+    writeln!(out, "<h3>APIs</h3>")?;
+    writeln!(out, "<table>")?;
+    writeln!(
+        out,
+        "<tr><th>API</th><th>Match rate</th><th>Matched packages</th><th>Fingerprint matches</th>\
+         <th>Failed batches</th><th>Success rate</th><th>p50</th><th>p95</th><th>p99</th><th>Error rate</th></tr>"
+    )?;
+
+    for api in &report.apis {
+        let match_rate = report.match_rate(api);
+
+        writeln!(
+            out,
+            "<tr><td>{}</td><td><span class=\"bar\"><span style=\"width: {:.1}%\"></span></span>{:.1}%</td>\
+             <td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}/{}</td>\
+             <td class=\"num\">{:.1}%</td><td class=\"num\">{}</td><td class=\"num\">{}</td>\
+             <td class=\"num\">{}</td><td class=\"num\">{:.1}%</td></tr>",
+            escape(api.name),
+            match_rate,
+            match_rate,
+            api.matched_packages,
+            api.fingerprint_matches,
+            api.failed_batches,
+            api.batches,
+            api.success_rate,
+            millis(api.latency_p50_ms),
+            millis(api.latency_p95_ms),
+            millis(api.latency_p99_ms),
+            api.error_rate
+        )?;
+    }
+
+    writeln!(out, "</table>")
+}
+
+/// A bar chart of each API's share of successful batches per latency
+/// bucket, as shares rather than counts so APIs sent different numbers of
+/// batches still compare.
+fn latency_chart(out: &mut String, report: &AuditReport, results: &[ApiResults]) -> fmt::Result {
+    let buckets = LATENCY_BUCKETS_MS.len() + 1;
+
+    let shares = results
+        .iter()
+        .map(|result| {
+            let latencies = result
+                .responses
+                .iter()
+                .filter(|r| r.result.is_ok())
+                .map(|r| r.latency.as_millis() as u64)
+                .collect::<Vec<_>>();
+
+            let mut counts = vec![0usize; buckets];
+
+            for latency in &latencies {
+                let bucket = LATENCY_BUCKETS_MS
+                    .iter()
+                    .position(|bound| latency < bound)
+                    .unwrap_or(buckets - 1);
+                counts[bucket] += 1;
+            }
+
+            let shares = counts
+                .into_iter()
+                .map(|count| count as f64 / latencies.len().max(1) as f64)
+                .collect::<Vec<_>>();
+
+            (result.api_choice.name(), shares)
+        })
+        .collect::<Vec<_>>();
+
+    if shares.is_empty() || report.apis.iter().all(|api| api.latency_p50_ms.is_none()) {
+        return Ok(());
+    }
+
+    let (width, height, top, bottom, left) = (720.0, 240.0, 10.0, 40.0, 40.0);
+    let plot_height = height - top - bottom;
+    let group_width = (width - left) / buckets as f64;
+    let bar_width = group_width * 0.8 / shares.len() as f64;
+
+    writeln!(out, "<h3>Latency of successful batches</h3>")?;
+    writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-size=\"11\">",
+        width, height
+    )?;
+
+    for tick in &[0.0, 0.25, 0.5, 0.75, 1.0] {
+        let y = top + plot_height * (1.0 - tick);
+
+        writeln!(
+            out,
+            "<line x1=\"{}\" x2=\"{}\" y1=\"{:.1}\" y2=\"{:.1}\" stroke=\"#eaeef2\"/>\
+             <text x=\"{}\" y=\"{:.1}\" text-anchor=\"end\">{:.0}%</text>",
+            left,
+            width,
+            y,
+            y,
+            left - 4.0,
+            y + 4.0,
+            tick * 100.0
+        )?;
+    }
+
+    for bucket in 0..buckets {
+        let x = left + group_width * bucket as f64 + group_width * 0.1;
+
+        for (index, (name, shares)) in shares.iter().enumerate() {
+            let bar_height = plot_height * shares[bucket];
+
+            writeln!(
+                out,
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\">\
+                 <title>{}: {:.1}%</title></rect>",
+                x + bar_width * index as f64,
+                top + plot_height - bar_height,
+                bar_width,
+                bar_height,
+                COLORS[index % COLORS.len()],
+                escape(name),
+                shares[bucket] * 100.0
+            )?;
+        }
+
+        writeln!(
+            out,
+            "<text x=\"{:.1}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+            left + group_width * (bucket as f64 + 0.5),
+            height - bottom + 16.0,
+            bucket_label(bucket)
+        )?;
+    }
+
+    for (index, (name, _)) in shares.iter().enumerate() {
+        let x = left + 120.0 * index as f64;
+
+        writeln!(
+            out,
+            "<rect x=\"{}\" y=\"{}\" width=\"10\" height=\"10\" fill=\"{}\"/>\
+             <text x=\"{}\" y=\"{}\">{}</text>",
+            x,
+            height - 12.0,
+            COLORS[index % COLORS.len()],
+            x + 14.0,
+            height - 3.0,
+            escape(name)
+        )?;
+    }
+
+    writeln!(out, "</svg>")
+}
+
+fn bucket_label(bucket: usize) -> String {
+    let duration = |ms: u64| {
+        if ms >= 1_000 {
+            format!("{}s", ms as f64 / 1_000.0)
+        } else {
+            format!("{}ms", ms)
+        }
+    };
+
+    match bucket {
+        0 => format!("&lt; {}", duration(LATENCY_BUCKETS_MS[0])),
+        bucket if bucket == LATENCY_BUCKETS_MS.len() => {
+            format!("&ge; {}", duration(LATENCY_BUCKETS_MS[bucket - 1]))
+        }
+        bucket => format!(
+            "{}-{}",
+            duration(LATENCY_BUCKETS_MS[bucket - 1]),
+            duration(LATENCY_BUCKETS_MS[bucket])
+        ),
+    }
+}
+
+/// Packages some APIs matched and others didn't, most downloaded first.
+fn discrepancies(out: &mut String, report: &AuditReport) -> fmt::Result {
+    let mut packages = report
+        .packages
+        .iter()
+        .filter(|package| !missing_on(report, package).is_empty())
+        .collect::<Vec<_>>();
+    packages.sort_by(|a, b| {
+        b.download_count
+            .partial_cmp(&a.download_count)
+            .unwrap_or(Ordering::Equal)
+    });
+
+    writeln!(out, "<h3>Discrepancies ({})</h3>", packages.len())?;
+
+    if packages.is_empty() {
+        return writeln!(out, "<p>No discrepancies between the APIs</p>");
+    }
+
+    writeln!(out, "<table>")?;
+    writeln!(
+        out,
+        "<tr><th>Package</th><th>Id</th><th>Downloads</th><th>Matched by</th><th>Missing on</th></tr>"
+    )?;
+
+    for package in packages {
+        writeln!(
+            out,
+            "<tr><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{:.0}</td><td>{}</td>\
+             <td class=\"missing\">{}</td></tr>",
+            package_link(package.id, &package.name),
+            package.id,
+            package.download_count,
+            escape(&api_names(
+                report,
+                package.matched_by.iter().map(String::as_str)
+            )),
+            escape(&api_names(report, missing_on(report, package).into_iter()))
+        )?;
+    }
+
+    writeln!(out, "</table>")
+}
+
+fn version_disagreements(out: &mut String, report: &AuditReport) -> fmt::Result {
+    if report.version_disagreements.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(
+        out,
+        "<h3>Version disagreements ({})</h3>",
+        report.version_disagreements.len()
+    )?;
+    writeln!(out, "<table>")?;
+    write!(out, "<tr><th>Package</th><th>Id</th>")?;
+
+    for api in &report.apis {
+        write!(out, "<th>{}</th>", escape(api.name))?;
+    }
+
+    writeln!(out, "</tr>")?;
+
+    for disagreement in &report.version_disagreements {
+        write!(
+            out,
+            "<tr><td>{}</td><td class=\"num\">{}</td>",
+            package_link(disagreement.id, &disagreement.name),
+            disagreement.id
+        )?;
+
+        for api in &report.apis {
+            let version = disagreement
+                .versions
+                .get(api.api)
+                .map_or("-", String::as_str);
+
+            write!(out, "<td>{}</td>", escape(version))?;
+        }
+
+        writeln!(out, "</tr>")?;
+    }
+
+    writeln!(out, "</table>")
+}
+
+/// Keys of the APIs that didn't match a package another API matched,
+/// leaving out those whose batch with it failed.
+fn missing_on<'a>(report: &'a AuditReport, package: &PackageStatus) -> Vec<&'a str> {
+    if package.matched_by.is_empty() {
+        return vec![];
+    }
+
+    report
+        .apis
+        .iter()
+        .map(|api| api.api)
+        .filter(|api| {
+            !package.matched_by.iter().any(|m| m == api)
+                && !package.failed_on.iter().any(|f| f == api)
+        })
+        .collect()
+}
+
+fn api_names<'a>(report: &AuditReport, keys: impl Iterator<Item = &'a str>) -> String {
+    keys.map(|key| {
+        report
+            .apis
+            .iter()
+            .find(|api| api.api == key)
+            .map_or(key, |api| api.name)
+    })
+    .collect::<Vec<_>>()
+    .join(", ")
+}
+
+/// The package's name linked to its CurseForge page, which the project id
+/// redirects to.
+fn package_link(id: i32, name: &str) -> String {
+    format!(
+        "<a href=\"https://www.curseforge.com/projects/{}\">{}</a>",
+        id,
+        escape(name)
+    )
+}
+
+fn millis(ms: Option<u64>) -> String {
+    ms.map_or_else(|| "-".to_owned(), |ms| format!("{}ms", ms))
+}
+
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
@@ -0,0 +1,82 @@
+//! Addons and fingerprints with known upstream issues, read from
+//! `--ignore-file`, so that one permanently broken addon doesn't fail every
+//! scheduled run. The checks that fail a run leave them out, and the report
+//! lists them as known issues instead, for example
+//!
+//! ```toml
+//! [[ignore]]
+//! addon = 12345
+//! reason = "Its fingerprints change on every release, reported upstream"
+//!
+//! [[ignore]]
+//! fingerprint = 3147283429
+//! reason = "Bundled library that WowUp never indexed"
+//! ```
+//!
+//! An ignored fingerprint covers every package that has it in a latest file.
+
+use ajour_core::repository::curse;
+use serde::Deserialize;
+
+use std::path::Path;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct IgnoreFile {
+    #[serde(default)]
+    ignore: Vec<IgnoreEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct IgnoreEntry {
+    addon: Option<i32>,
+    fingerprint: Option<u32>,
+    reason: String,
+}
+
+pub struct IgnoreList {
+    entries: Vec<IgnoreEntry>,
+}
+
+impl IgnoreList {
+    /// Reads the list from the file at `path`, checking that every entry
+    /// names either an addon or a fingerprint.
+    pub fn load(path: &Path) -> Result<IgnoreList, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {} - {}", path.display(), e))?;
+        let file: IgnoreFile = toml::from_str(&contents)
+            .map_err(|e| format!("invalid ignore file {} - {}", path.display(), e))?;
+
+        for (index, entry) in file.ignore.iter().enumerate() {
+            if entry.addon.is_some() == entry.fingerprint.is_some() {
+                return Err(format!(
+                    "entry {} of {} needs either addon or fingerprint",
+                    index + 1,
+                    path.display()
+                ));
+            }
+        }
+
+        Ok(IgnoreList {
+            entries: file.ignore,
+        })
+    }
+
+    /// Why `package` is ignored, if it is, going by the first entry that
+    /// covers it.
+    pub fn reason(&self, package: &curse::Package) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|entry| match (entry.addon, entry.fingerprint) {
+                (Some(id), _) => id == package.id,
+                (None, Some(fingerprint)) => package
+                    .latest_files
+                    .iter()
+                    .flat_map(|file| &file.modules)
+                    .any(|module| module.fingerprint == fingerprint),
+                (None, None) => false,
+            })
+            .map(|entry| entry.reason.as_str())
+    }
+}
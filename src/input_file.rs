@@ -0,0 +1,37 @@
+//! Reads the ids or fingerprints listed in `--input-file`.
+
+use std::path::Path;
+use std::str::FromStr;
+
+/// Parses every line of the file at `path` as a `T`, ignoring blank lines
+/// and anything after a `#`, so a curated list can note why each entry is
+/// in it.
+pub fn read<T: FromStr>(path: &Path) -> Result<Vec<T>, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {} - {}", path.display(), e))?;
+    let mut values = vec![];
+
+    for (index, line) in contents.lines().enumerate() {
+        let value = line.split('#').next().unwrap_or_default().trim();
+
+        if value.is_empty() {
+            continue;
+        }
+
+        let value = value.parse().map_err(|_| {
+            format!(
+                "invalid entry `{}` on line {} of {}",
+                value,
+                index + 1,
+                path.display()
+            )
+        })?;
+        values.push(value);
+    }
+
+    if values.is_empty() {
+        return Err(format!("no entries in {}", path.display()));
+    }
+
+    Ok(values)
+}
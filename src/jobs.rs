@@ -0,0 +1,223 @@
+//! Runs independent audits described by an ndjson file for `--jobs-file`,
+//! writing one report per job as ndjson.
+//!
+//! Each line of the file is a job that overrides some of the command line
+//! options, for example
+//! `{"id": "classic", "game_id": 1, "apis": ["curse"], "total": 500}`.
+//! A job that fails is written as an error line without stopping the rest.
+
+use crate::cli::{Format, Opts};
+use crate::date::Timestamp;
+use crate::rate_limit::RateLimits;
+use crate::report::AuditReport;
+use crate::retry::RetryBudget;
+use crate::spill::Spill;
+use crate::{audit_and_report, open_output, ApiChoice};
+
+use anyhow::{anyhow, bail};
+use futures::{stream, StreamExt};
+use isahc::HttpClient;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::process;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Job {
+    /// Echoed back in the job's output line, so a scheduler can match them.
+    id: Option<String>,
+    game_id: Option<u32>,
+    apis: Option<Vec<String>>,
+    total: Option<usize>,
+    name_contains: Option<String>,
+    category: Option<Vec<String>>,
+    exclude_category: Option<Vec<String>>,
+    flavor: Option<Vec<String>>,
+    since: Option<String>,
+    sample: Option<usize>,
+    seed: Option<u64>,
+    package_limit: Option<usize>,
+}
+
+impl Job {
+    /// The options of the job's audit, which are `opts` with the job's
+    /// fields replacing their flags.
+    fn apply(&self, opts: &Opts) -> Result<Opts, anyhow::Error> {
+        let mut opts = opts.clone();
+        // Reports go out as ndjson, so nothing else may reach stdout
+        opts.format = Format::Json;
+
+        if let Some(game_id) = self.game_id {
+            opts.game_ids = vec![game_id];
+        }
+
+        if opts.game_ids.len() != 1 {
+            bail!("a job needs a single game id");
+        }
+
+        if let Some(apis) = &self.apis {
+            opts.apis = apis.clone();
+        }
+
+        if let Some(since) = &self.since {
+            opts.since = Some(since.parse()?);
+        }
+
+        opts.total = self.total.unwrap_or(opts.total);
+        opts.name_contains = self.name_contains.clone().or(opts.name_contains);
+        opts.categories = self.category.clone().unwrap_or(opts.categories);
+        opts.exclude_categories = self
+            .exclude_category
+            .clone()
+            .unwrap_or(opts.exclude_categories);
+        opts.flavors = self.flavor.clone().unwrap_or(opts.flavors);
+        opts.sample = self.sample.or(opts.sample);
+        opts.seed = self.seed.or(opts.seed);
+        opts.package_limit = self.package_limit.or(opts.package_limit);
+
+        Ok(opts)
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+enum JobOutput<'a> {
+    Ok {
+        /// Line of the job in the jobs file.
+        line: usize,
+        id: Option<&'a str>,
+        report: &'a AuditReport,
+    },
+    Error {
+        line: usize,
+        id: Option<&'a str>,
+        error: String,
+    },
+}
+
+/// Reads every non-blank line as a job, keeping lines that aren't valid
+/// jobs as errors so they're reported in place.
+fn read(path: &Path) -> io::Result<Vec<(usize, Result<Job, String>)>> {
+    let reader = BufReader::new(std::fs::File::open(path)?);
+    let mut jobs = vec![];
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let job = serde_json::from_str(&line).map_err(|e| format!("invalid job - {}", e));
+        jobs.push((index + 1, job));
+    }
+
+    Ok(jobs)
+}
+
+/// Runs every job of `path`, `--job-concurrency` at a time, writing their
+/// output lines in file order and returning how many failed.
+#[allow(clippy::too_many_arguments)]
+pub async fn run(
+    client: &HttpClient,
+    opts: &Opts,
+    retry_budget: &RetryBudget,
+    rate_limits: &RateLimits,
+    spill: &Spill,
+    path: &Path,
+    run_id: &str,
+    started_at: Timestamp,
+) -> Result<usize, anyhow::Error> {
+    let jobs = match read(path) {
+        Ok(jobs) => jobs,
+        Err(e) => {
+            error!("failed to read {} - {}", path.display(), e);
+            process::exit(crate::EXIT_ARGUMENT_ERROR);
+        }
+    };
+
+    let (output_path, mut out) = open_output(opts, run_id, started_at)?;
+
+    let mut outcomes = Box::pin(
+        stream::iter(&jobs)
+            .map(|(line, job)| async move {
+                let report = match job {
+                    Ok(job) => run_job(client, opts, retry_budget, rate_limits, spill, job).await,
+                    Err(e) => Err(anyhow!("{}", e)),
+                };
+                let id = job.as_ref().ok().and_then(|j| j.id.as_deref());
+
+                (*line, id, report)
+            })
+            .buffered(opts.job_concurrency),
+    );
+
+    let mut failed = 0;
+
+    while let Some((line, id, mut report)) = outcomes.next().await {
+        let output = match &mut report {
+            Ok(report) => {
+                report.run_id = run_id.to_owned();
+                report.started_at = started_at.to_string();
+
+                JobOutput::Ok { line, id, report }
+            }
+            Err(e) => {
+                error!("job on line {} failed - {}", line, e);
+                failed += 1;
+
+                JobOutput::Error {
+                    line,
+                    id,
+                    error: e.to_string(),
+                }
+            }
+        };
+
+        serde_json::to_writer(&mut out, &output)?;
+        writeln!(out)?;
+        out.flush()?;
+    }
+
+    info!(
+        "{} of {} jobs succeeded, {} failed",
+        jobs.len() - failed,
+        jobs.len(),
+        failed
+    );
+
+    if let Some(path) = &output_path {
+        info!("Wrote reports to {}", path.display());
+    }
+
+    Ok(failed)
+}
+
+async fn run_job(
+    client: &HttpClient,
+    opts: &Opts,
+    retry_budget: &RetryBudget,
+    rate_limits: &RateLimits,
+    spill: &Spill,
+    job: &Job,
+) -> Result<AuditReport, anyhow::Error> {
+    let opts = job.apply(opts)?;
+    let apis = ApiChoice::parse_list(&opts.apis).map_err(|e| anyhow!("{}", e))?;
+
+    let (game, _) = audit_and_report(
+        client,
+        &opts,
+        &apis,
+        retry_budget,
+        rate_limits,
+        spill,
+        opts.game_ids[0],
+        None,
+    )
+    .await?;
+
+    Ok(game.report)
+}
@@ -0,0 +1,211 @@
+//! JUnit XML report for `--report-junit`, so CI test report tooling can
+//! show an audit without a parser of its own.
+//!
+//! Each audited game, or flavor with `--all-flavors`, is a test suite and
+//! each of its packages a test case, which passes if every API matched it.
+//! A package an API didn't match fails with what each API matched it to,
+//! one whose batch failed on an API is an error, and one `--ignore-file`
+//! lists is skipped with its reason.
+
+use crate::html::escape;
+use crate::report::AuditReport;
+use crate::ApiResults;
+
+use ajour_core::repository::curse;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Write as _};
+use std::io;
+use std::path::Path;
+
+/// Outcome of one package's test case.
+enum Case {
+    Passed,
+    Skipped(String),
+    /// Some API didn't match the package, with the summary and the line of
+    /// every API.
+    Failed(String, Vec<String>),
+    /// Some API's batch with the package failed.
+    Errored(String, Vec<String>),
+}
+
+/// What one API matched, gathered once per game rather than per package.
+struct ApiMatches<'a> {
+    name: &'static str,
+    matched: BTreeMap<i32, &'a curse::File>,
+    partial: BTreeSet<i32>,
+    failed: &'a BTreeSet<i32>,
+}
+
+/// Renders a test suite per game, named by the first element.
+pub fn render(games: &[(String, &[curse::Package], &AuditReport, &[ApiResults])]) -> String {
+    let mut out = String::new();
+    // Writing to a String can't fail
+    let _ = document(&mut out, games);
+
+    out
+}
+
+/// Writes the report to `path`.
+pub fn write(
+    path: &Path,
+    games: &[(String, &[curse::Package], &AuditReport, &[ApiResults])],
+) -> io::Result<()> {
+    std::fs::write(path, render(games))
+}
+
+fn document(
+    out: &mut String,
+    games: &[(String, &[curse::Package], &AuditReport, &[ApiResults])],
+) -> fmt::Result {
+    let suites = games
+        .iter()
+        .map(|(name, packages, report, results)| {
+            let apis = results
+                .iter()
+                .map(|result| ApiMatches {
+                    name: result.api_choice.name(),
+                    matched: result.matched_files(),
+                    partial: result
+                        .partial_matches()
+                        .iter()
+                        .map(|info| info.id)
+                        .collect(),
+                    failed: &result.failed_packages,
+                })
+                .collect::<Vec<_>>();
+
+            let cases = packages
+                .iter()
+                .map(|package| (package, case(package, report, &apis)))
+                .collect::<Vec<_>>();
+
+            (name, cases)
+        })
+        .collect::<Vec<_>>();
+
+    let count = |matches: fn(&Case) -> bool| {
+        suites
+            .iter()
+            .flat_map(|(_, cases)| cases)
+            .filter(|(_, case)| matches(case))
+            .count()
+    };
+
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(
+        out,
+        "<testsuites name=\"ajour-api-test\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\">",
+        count(|_| true),
+        count(|case| matches!(case, Case::Failed(..))),
+        count(|case| matches!(case, Case::Errored(..))),
+        count(|case| matches!(case, Case::Skipped(_)))
+    )?;
+
+    for (name, cases) in &suites {
+        let count = |matches: fn(&Case) -> bool| cases.iter().filter(|(_, c)| matches(c)).count();
+
+        writeln!(
+            out,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\">",
+            escape(name),
+            cases.len(),
+            count(|case| matches!(case, Case::Failed(..))),
+            count(|case| matches!(case, Case::Errored(..))),
+            count(|case| matches!(case, Case::Skipped(_)))
+        )?;
+
+        for (package, case) in cases {
+            write!(
+                out,
+                "    <testcase classname=\"{}\" name=\"{}\"",
+                escape(name),
+                escape(&format!("{} ({})", package.name, package.id))
+            )?;
+
+            match case {
+                Case::Passed => writeln!(out, "/>")?,
+                Case::Skipped(reason) => {
+                    writeln!(out, ">")?;
+                    writeln!(out, "      <skipped message=\"{}\"/>", escape(reason))?;
+                    writeln!(out, "    </testcase>")?;
+                }
+                Case::Failed(message, detail) | Case::Errored(message, detail) => {
+                    let element = match case {
+                        Case::Failed(..) => "failure",
+                        _ => "error",
+                    };
+
+                    writeln!(out, ">")?;
+                    writeln!(
+                        out,
+                        "      <{} message=\"{}\">{}</{}>",
+                        element,
+                        escape(message),
+                        escape(&detail.join("\n")),
+                        element
+                    )?;
+                    writeln!(out, "    </testcase>")?;
+                }
+            }
+        }
+
+        writeln!(out, "  </testsuite>")?;
+    }
+
+    writeln!(out, "</testsuites>")
+}
+
+/// Decides the test case of `package` from what each API matched it to.
+fn case(package: &curse::Package, report: &AuditReport, apis: &[ApiMatches<'_>]) -> Case {
+    if let Some(issue) = report.known_issues.iter().find(|i| i.id == package.id) {
+        return Case::Skipped(issue.reason.clone());
+    }
+
+    let mut unmatched = vec![];
+    let mut failed = vec![];
+    let mut detail = vec![];
+
+    for api in apis {
+        let name = api.name;
+
+        let line = if api.failed.contains(&package.id) {
+            failed.push(name);
+            "its batch failed".to_owned()
+        } else if let Some(file) = api.matched.get(&package.id) {
+            format!("matched {} ({})", file.display_name, file.id)
+        } else if report.count_partial && api.partial.contains(&package.id) {
+            "matched partially".to_owned()
+        } else {
+            unmatched.push(name);
+
+            if api.partial.contains(&package.id) {
+                "only matched partially".to_owned()
+            } else {
+                "no match".to_owned()
+            }
+        };
+
+        detail.push(format!("{}: {}", name, line));
+    }
+
+    detail.extend(latest_files(package));
+
+    if !failed.is_empty() {
+        Case::Errored(format!("batch failed on {}", failed.join(", ")), detail)
+    } else if !unmatched.is_empty() {
+        Case::Failed(format!("not matched by {}", unmatched.join(", ")), detail)
+    } else {
+        Case::Passed
+    }
+}
+
+/// A line per latest file of `package`, to compare the APIs' matches with.
+fn latest_files(package: &curse::Package) -> impl Iterator<Item = String> + '_ {
+    package.latest_files.iter().map(|file| {
+        format!(
+            "latest {}: {} ({})",
+            file.game_version_flavor, file.display_name, file.id
+        )
+    })
+}
@@ -0,0 +1,4602 @@
+//! Audits how the Curse and WowUp fingerprint APIs match the packages of a
+//! Curse search.
+//!
+//! The `ajour-api-test` binary is a thin wrapper around [`run`]. Other tools
+//! embed an audit with [`AuditRunner`], which returns the [`AuditReport`]
+//! instead of writing it and exiting with a code.
+
+use ajour_core::repository::curse;
+use anyhow::{anyhow, bail};
+use async_lock::Semaphore;
+use async_std::future::timeout;
+use async_std::task;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures::channel::mpsc;
+use futures::stream::FuturesUnordered;
+use futures::{future, stream, AsyncReadExt, StreamExt};
+use isahc::config::{CaCertificate, SslOption, VersionNegotiation};
+use isahc::http::StatusCode;
+use isahc::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use structopt::StructOpt;
+use tracing::{debug, error, info, warn};
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::env;
+use std::fmt::{self, Display};
+use std::io::{self, BufWriter, IsTerminal, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+mod adaptive;
+mod addon_dir;
+mod all_flavors;
+mod archive;
+mod baseline;
+mod batch_csv;
+mod changelog_check;
+mod checks;
+pub mod cli;
+mod config;
+mod core_compare;
+mod curse_v1;
+mod date;
+mod dependencies;
+mod download_check;
+mod drift;
+mod edge_cases;
+mod error;
+mod explain;
+mod file_hashes;
+mod fingerprint;
+mod fingerprint_api;
+mod fingerprint_check;
+mod fingerprint_index;
+mod fnv;
+mod html;
+mod ignore;
+mod input_file;
+mod jobs;
+mod junit;
+mod logging;
+mod lookup;
+mod metrics;
+mod metrics_export;
+mod notify;
+mod package_stream;
+mod profile;
+mod rate_limit;
+mod raw_matches;
+mod releases;
+mod repeat_check;
+mod replay;
+pub mod report;
+mod report_csv;
+mod retry;
+mod runner;
+mod sample;
+mod schema;
+mod search_cache;
+mod search_compare;
+mod shutdown;
+mod spill;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod state;
+mod strict;
+mod timings;
+#[cfg(feature = "tui")]
+mod tui;
+mod tukui;
+mod watch;
+mod wowinterface;
+
+use adaptive::Aimd;
+use baseline::{Baseline, Regression};
+use checks::CheckFailure;
+use cli::{
+    ApiVersion, Command, CurseSort, Format, HttpVersion, InputKind, LogFormat, Opts, Progress,
+    ResponseEncoding, SearchSampling,
+};
+use config::Profile;
+use date::Timestamp;
+use drift::SchemaDrift;
+use error::AuditError;
+use fingerprint_api::FingerprintApi;
+use ignore::IgnoreList;
+use package_stream::PackageStream;
+use rate_limit::RateLimits;
+use report::{GameReport, KnownIssue, MultiGameReport, SlaStatus, Totals};
+use retry::RetryBudget;
+use search_cache::SearchCache;
+use spill::Spill;
+
+pub use report::AuditReport;
+pub use runner::AuditRunner;
+
+const CURSE_SEARCH_URL: &str = "https://addons-ecs.forgesvc.net/api/v2/addon/search";
+const CURSE_ADDON_URL: &str = "https://addons-ecs.forgesvc.net/api/v2/addon";
+const CURSE_FINGERPRINT_URL: &str = "https://addons-ecs.forgesvc.net/api/v2/fingerprint";
+const WOWUP_FINGERPRINT_URL: &str = "https://hub.wowup.io/curseforge/addons/fingerprint";
+const CURSE_API_KEY_HEADER: &str = "x-api-key";
+/// Response headers identifying a request to its API's support, first match
+/// wins.
+const REQUEST_ID_HEADERS: [&str; 4] = [
+    "x-request-id",
+    "x-trace-id",
+    "x-amzn-requestid",
+    "x-amzn-trace-id",
+];
+const CONNECTION_TIMEOUT_SECONDS: u64 = 30;
+const SLOWEST_PACKAGES_SHOWN: usize = 10;
+/// Fingerprints of the bundled sample responses used by `--self-test`.
+const SELF_TEST_FINGERPRINTS: [u32; 2] = [3_095_035_474, 1_652_237_858];
+/// Every API and Curse API version checked by `--self-test`.
+const SELF_TESTS: [(ApiChoice, ApiVersion); 6] = [
+    (ApiChoice::Curse, ApiVersion::V2),
+    (ApiChoice::Curse, ApiVersion::V1),
+    (ApiChoice::WowUp, ApiVersion::V2),
+    (ApiChoice::Tukui, ApiVersion::V2),
+    (ApiChoice::WowInterface, ApiVersion::V2),
+    (ApiChoice::TownlongYak, ApiVersion::V2),
+];
+
+/// Exit code used when the audit ran but failed one of the requested checks.
+const EXIT_AUDIT_FAILURE: i32 = 2;
+/// Exit code used when a request the whole run depends on fails.
+const EXIT_NETWORK_FAILURE: i32 = 3;
+/// Exit code used when the given arguments can't describe a useful run.
+const EXIT_ARGUMENT_ERROR: i32 = 4;
+/// Exit code used when the search left fewer packages than `--assert-total`.
+const EXIT_TOO_FEW_PACKAGES: i32 = 5;
+/// Exit code used when Ctrl-C or SIGTERM cut the run short, as shells report
+/// a process killed by SIGINT.
+const EXIT_INTERRUPTED: i32 = 130;
+
+type BatchResult = Result<curse::FingerprintInfo, AuditError>;
+
+/// Runs the command line tool with the process's arguments, exiting with
+/// one of its exit codes whenever the run can't succeed.
+pub async fn run() -> Result<(), anyhow::Error> {
+    let matches = Opts::clap().get_matches();
+    let mut opts = Opts::from_clap(&matches);
+    logging::init(&opts);
+
+    if let Some(name) = opts.config_profile.clone() {
+        let profile = match Profile::load(&opts.config, &name) {
+            Ok(profile) => profile,
+            Err(e) => {
+                error!("{}", e);
+                process::exit(EXIT_ARGUMENT_ERROR);
+            }
+        };
+
+        opts = match profile.apply(opts, &matches) {
+            Ok(opts) => opts,
+            Err(e) => {
+                error!("profile `{}` - {}", name, e);
+                process::exit(EXIT_ARGUMENT_ERROR);
+            }
+        };
+    }
+
+    if opts.self_test {
+        let mut failed = false;
+
+        for &(api_choice, api_version) in &SELF_TESTS {
+            let name = match (api_choice, api_version) {
+                (ApiChoice::Curse, ApiVersion::V1) => "Curse v1",
+                _ => api_choice.name(),
+            };
+
+            match api_choice.api().self_test(api_version) {
+                Ok(()) => println!("{} self-test passed", name),
+                Err(e) => {
+                    error!("{} - self-test failed: {}", name, e);
+                    failed = true;
+                }
+            }
+        }
+
+        process::exit(if failed { EXIT_AUDIT_FAILURE } else { 0 });
+    }
+
+    let run_id = sample::run_id();
+    let started_at = Timestamp::now();
+
+    if !opts.explain {
+        info!("Run {} started at {}", run_id, started_at);
+    }
+
+    if let Err(e) = shutdown::install(|| process::exit(EXIT_INTERRUPTED)) {
+        warn!(
+            "failed to handle Ctrl-C, interrupting the run will lose it - {}",
+            e
+        );
+    }
+
+    let apis = match ApiChoice::parse_list(&opts.apis) {
+        Ok(apis) => apis,
+        Err(e) => {
+            error!("{}", e);
+            process::exit(EXIT_ARGUMENT_ERROR);
+        }
+    };
+
+    if let Err(e) = check_options(&opts, &apis) {
+        error!("{}", e);
+        process::exit(EXIT_ARGUMENT_ERROR);
+    }
+
+    if let Some(Command::Search { query }) = &opts.command {
+        opts.search_filter = Some(query.clone());
+    }
+
+    if opts.explain {
+        explain::print(&opts, &matches, &apis);
+        return Ok(());
+    }
+
+    #[cfg(feature = "sqlite")]
+    {
+        if let (Some(Command::History { last }), Some(cli::Store::Sqlite(path))) =
+            (&opts.command, &opts.store)
+        {
+            return show_history(&opts, path, *last, &run_id, started_at);
+        }
+    }
+
+    if let Err(e) = state::prepare(&mut opts, sample::random_seed()) {
+        error!("{}", e);
+        process::exit(EXIT_ARGUMENT_ERROR);
+    }
+
+    let output_dir = match opts.output_dir.clone() {
+        Some(dir) => Some(use_output_dir(&mut opts, &dir, &run_id, started_at)?),
+        None => None,
+    };
+
+    let client = build_client(&opts)?;
+
+    let retry_budget = RetryBudget::new(opts.retry_budget);
+    let rate_limits = RateLimits::new();
+    let spill = Spill::new(opts.max_memory_mb);
+
+    if opts.warm_up && opts.replay.is_none() {
+        warm_up(&client, &opts, &apis).await;
+    }
+
+    let fingerprints = match (&opts.command, &opts.input_file) {
+        (Some(Command::Fingerprint { fingerprints }), _) => Some(fingerprints.clone()),
+        (_, Some(path)) if opts.input_kind == InputKind::Fingerprint => {
+            Some(read_input_file(&opts, path)?)
+        }
+        _ => None,
+    };
+
+    let session = Session {
+        opts,
+        apis,
+        client,
+        retry_budget,
+        rate_limits,
+        spill,
+        run_id,
+        started_at,
+        output_dir,
+    };
+    let opts = &session.opts;
+
+    match (&opts.command, fingerprints) {
+        (_, Some(fingerprints)) => session.fingerprint(&fingerprints).await,
+        (
+            Some(Command::SearchCompare {
+                term,
+                game_type,
+                limit,
+            }),
+            None,
+        ) => session.search_compare(term, game_type, *limit).await,
+        (
+            Some(Command::Releases {
+                repos,
+                repos_file,
+                github_token,
+            }),
+            None,
+        ) => {
+            session
+                .releases(repos, repos_file.as_deref(), github_token.as_deref())
+                .await
+        }
+        (Some(Command::Tukui), None) => session.tukui().await,
+        (
+            Some(Command::Wowinterface {
+                mapping_file,
+                max_lag_days,
+            }),
+            None,
+        ) => {
+            session
+                .wowinterface(mapping_file.as_deref(), *max_lag_days)
+                .await
+        }
+        (Some(Command::Dependencies), None) => session.dependencies().await,
+        (
+            Some(Command::EdgeCases {
+                fingerprint,
+                oversized,
+            }),
+            None,
+        ) => session.edge_cases(*fingerprint, *oversized).await,
+        (Some(Command::Resolve { id }), None) => session.resolve(*id).await,
+        _ => match (&opts.addon_dir, &opts.jobs_file) {
+            (Some(dir), _) => session.addon_dir(dir).await,
+            (_, Some(path)) => session.jobs(path).await,
+            _ if opts.watch => watch::run(&session.client, opts, &session.apis).await,
+            _ => session.audit().await,
+        },
+    }
+}
+
+/// Checks the options against each other and the APIs, failing if they
+/// can't make a run.
+fn check_options(opts: &Opts, apis: &[ApiChoice]) -> Result<(), anyhow::Error> {
+    for limit in &opts.max_request_bytes {
+        if let Err(e) = ApiChoice::parse_list(&[limit.api.clone()]) {
+            bail!("--max-request-bytes - {}", e);
+        }
+    }
+
+    if opts.require_wowup_parity
+        && !(apis.contains(&ApiChoice::Curse) && apis.contains(&ApiChoice::WowUp))
+    {
+        bail!("--require-wowup-parity needs both curse and wowup in --apis");
+    }
+
+    if let Some(template) = &opts.template {
+        let keys = apis.iter().map(ApiChoice::key).collect::<Vec<_>>();
+        let placeholders = report::template_placeholders(&keys);
+
+        if let Err(unknown) = report::validate_template(template, &placeholders) {
+            bail!(
+                "unknown --template placeholders {}, expected any of {}",
+                unknown.join(", "),
+                placeholders.join(", ")
+            );
+        }
+    }
+
+    if opts.compare_against_ajour_core
+        && !(apis.contains(&ApiChoice::Curse) && opts.api_version == ApiVersion::V2)
+    {
+        bail!(
+            "--compare-against-ajour-core needs curse in --apis and --api-version v2, which ajour-core uses"
+        );
+    }
+
+    // These need a fingerprint endpoint, rather than packages to look up by
+    // name
+    if let Some(api) = apis.iter().find(|api| api.api().matches_by_name()) {
+        let fingerprint_only = [
+            ("--addon-dir", opts.addon_dir.is_some()),
+            (
+                "--input-kind fingerprint",
+                opts.input_file.is_some() && opts.input_kind == InputKind::Fingerprint,
+            ),
+            (
+                "the fingerprint subcommand",
+                matches!(opts.command, Some(Command::Fingerprint { .. })),
+            ),
+            (
+                "the edge-cases subcommand",
+                matches!(opts.command, Some(Command::EdgeCases { .. })),
+            ),
+            (
+                "the resolve subcommand",
+                matches!(opts.command, Some(Command::Resolve { .. })),
+            ),
+            ("--check-changelogs", opts.check_changelogs),
+            ("--verify-file-hashes", opts.verify_file_hashes),
+        ];
+
+        if let Some((what, _)) = fingerprint_only.iter().find(|(_, set)| *set) {
+            bail!(
+                "{} matches packages by name, so it can't be audited with {}",
+                api.key(),
+                what
+            );
+        }
+    }
+
+    if let Some(command) = &opts.command {
+        let (name, lookup) = match command {
+            Command::Audit => ("audit", false),
+            Command::Search { .. } => ("search", false),
+            Command::Fingerprint { .. } => ("fingerprint", true),
+            Command::EdgeCases { .. } => ("edge-cases", true),
+            Command::Resolve { .. } => ("resolve", true),
+            Command::History { .. } => ("history", true),
+            Command::SearchCompare { .. } => ("search-compare", true),
+            Command::Releases { .. } => ("releases", true),
+            Command::Tukui => ("tukui", true),
+            Command::Wowinterface { .. } => ("wowinterface", true),
+            Command::Dependencies => ("dependencies", true),
+        };
+
+        let audit_only = [
+            ("--addon-dir", opts.addon_dir.is_some()),
+            ("--jobs-file", opts.jobs_file.is_some()),
+            ("--package-id", opts.package_id.is_some()),
+            ("--packages-stdin", opts.packages_stdin),
+            ("--input-file", opts.input_file.is_some()),
+        ];
+
+        if name != "audit" {
+            if let Some((flag, _)) = audit_only.iter().find(|(_, set)| *set) {
+                bail!("{} can't be combined with the {} subcommand", flag, name);
+            }
+        }
+
+        if opts.stream.is_some() && lookup {
+            bail!("--stream can't be combined with the {} subcommand", name);
+        }
+
+        if opts.watch && lookup {
+            bail!("--watch can't be combined with the {} subcommand", name);
+        }
+
+        if name == "history" && opts.store.is_none() {
+            bail!("the history subcommand needs --store to read the runs from");
+        }
+
+        if (name == "search" || name == "search-compare" || name == "wowinterface")
+            && opts.game_ids.len() > 1
+        {
+            bail!("the {} subcommand only searches a single --game-id", name);
+        }
+
+        if lookup && opts.format == Format::Csv {
+            bail!(
+                "--format csv can't be combined with the {} subcommand",
+                name
+            );
+        }
+    }
+
+    if opts.format == Format::Csv
+        && opts.input_file.is_some()
+        && opts.input_kind == InputKind::Fingerprint
+    {
+        bail!("--format csv can't be combined with --input-kind fingerprint");
+    }
+
+    if opts.format == Format::Csv && opts.addon_dir.is_some() {
+        bail!("--format csv can't be combined with --addon-dir");
+    }
+
+    if opts.stream.is_some()
+        && opts.output_dir.is_none()
+        && opts
+            .output_file
+            .as_ref()
+            .map_or(true, |p| p.as_os_str() == "-")
+    {
+        bail!(
+            "--stream takes over stdout, so it needs --output-file or --output-dir for the report"
+        );
+    }
+
+    if opts.only_discrepancies && opts.format != Format::Text {
+        bail!("--only-discrepancies only applies to --format text");
+    }
+
+    #[cfg(feature = "sqlite")]
+    {
+        if opts.store.is_some() && opts.export_sqlite.is_some() {
+            bail!("--store can't be combined with --export-sqlite, which it already does");
+        }
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    {
+        if opts.store.is_some() {
+            bail!("--store needs a build with the sqlite feature");
+        }
+    }
+
+    #[cfg(feature = "tui")]
+    {
+        if opts.tui && (opts.game_ids.len() > 1 || opts.all_flavors) {
+            bail!("--tui only browses a single game");
+        }
+    }
+
+    if opts.all_flavors && opts.game_ids.len() > 1 {
+        bail!("--all-flavors only audits a single --game-id");
+    }
+
+    if opts.strict_versions && apis.len() < 2 {
+        bail!("--strict-versions needs at least two APIs in --apis");
+    }
+
+    if opts.compare_apis_pairwise && apis.len() < 2 {
+        bail!("--compare-apis-pairwise needs at least two APIs in --apis");
+    }
+
+    if !(opts.replay_speed >= 0.0 && opts.replay_speed.is_finite()) {
+        bail!("--replay-speed must be a finite number of at least 0");
+    }
+
+    if opts.page_size == 0 || opts.batch_size == 0 || opts.max_connections == 0 {
+        bail!("--page-size, --batch-size and --max-connections must be at least 1");
+    }
+
+    if opts.max_batch_fingerprints == Some(0) {
+        bail!("--max-batch-fingerprints must be at least 1");
+    }
+
+    if opts.search_sampling == SearchSampling::Top {
+        if opts.seed.is_some() && opts.sample.is_none() {
+            bail!("--seed needs --sample or --search-sampling random-pages or stratified");
+        }
+    } else {
+        if opts.offset > 0 {
+            bail!("--offset can't be combined with --search-sampling, which picks its own pages");
+        }
+
+        if opts.strata == 0 || opts.catalog_size < opts.page_size {
+            bail!("--strata must be at least 1 and --catalog-size at least --page-size");
+        }
+    }
+
+    if opts.download_check_concurrency == 0 {
+        bail!("--download-check-concurrency must be at least 1");
+    }
+
+    if opts.verify_sample_size == 0 {
+        bail!("--verify-sample-size must be at least 1");
+    }
+
+    if opts.recompute_sample_size == 0 {
+        bail!("--recompute-sample-size must be at least 1");
+    }
+
+    if let Some(path) = &opts.ignore_file {
+        if let Err(e) = IgnoreList::load(path) {
+            bail!("{}", e);
+        }
+    }
+
+    if opts.webhook_url.is_some()
+        && opts.notify_below_match_rate.is_none()
+        && opts.notify_above_error_rate.is_none()
+        && !opts.watch
+    {
+        bail!(
+            "--webhook-url needs --notify-below-match-rate, --notify-above-error-rate or --watch"
+        );
+    }
+
+    if opts.job_concurrency == 0 {
+        bail!("--job-concurrency must be at least 1");
+    }
+
+    // Jobs restore spilled matches from a single shared file, which only
+    // works one job at a time
+    if opts.job_concurrency > 1 && opts.max_memory_mb.is_some() {
+        bail!("--job-concurrency above 1 can't be combined with --max-memory-mb");
+    }
+
+    // v1 rejects every request without a key, so fail before sending any
+    if opts.api_version == ApiVersion::V1 && opts.api_key.is_none() && opts.replay.is_none() {
+        bail!("--api-version v1 needs --api-key");
+    }
+
+    Ok(())
+}
+
+/// Writes the runs `--store` kept for the `history` subcommand.
+#[cfg(feature = "sqlite")]
+fn show_history(
+    opts: &Opts,
+    path: &Path,
+    last: usize,
+    run_id: &str,
+    started_at: Timestamp,
+) -> Result<(), anyhow::Error> {
+    let history = match sqlite::history(path, &opts.game_ids, last) {
+        Ok(history) => history,
+        Err(e) => {
+            error!("failed to read runs from {} - {}", path.display(), e);
+            process::exit(EXIT_ARGUMENT_ERROR);
+        }
+    };
+
+    let (output_path, mut out) = open_output(opts, run_id, started_at)?;
+
+    match opts.format {
+        Format::Json => write_json(&mut out, &history, opts.compact)?,
+        _ => sqlite::write_history(&mut out, &history)?,
+    }
+    out.flush()?;
+
+    if let Some(path) = &output_path {
+        info!("Wrote history to {}", path.display());
+    }
+
+    Ok(())
+}
+
+/// What every mode of a run shares once its options are settled.
+struct Session {
+    opts: Opts,
+    apis: Vec<ApiChoice>,
+    client: HttpClient,
+    retry_budget: RetryBudget,
+    rate_limits: RateLimits,
+    spill: Spill,
+    run_id: String,
+    started_at: Timestamp,
+    output_dir: Option<PathBuf>,
+}
+
+impl Session {
+    /// Opens `--output-file`, or stdout without one, for a mode's report.
+    fn open_report(&self) -> io::Result<(Option<PathBuf>, Box<dyn Write>)> {
+        open_output(&self.opts, &self.run_id, self.started_at)
+    }
+
+    /// Flushes a report `open_report` opened and logs where it went.
+    fn finish_report(
+        &self,
+        output_path: Option<PathBuf>,
+        mut out: Box<dyn Write>,
+    ) -> Result<(), anyhow::Error> {
+        out.flush()?;
+
+        if let Some(path) = &output_path {
+            info!("Wrote report to {}", path.display());
+        }
+
+        print_finished(&self.run_id, self.output_dir.as_deref());
+
+        Ok(())
+    }
+
+    async fn fingerprint(&self, fingerprints: &[u32]) -> Result<(), anyhow::Error> {
+        let (output_path, mut out) = self.open_report()?;
+
+        lookup::fingerprint(
+            &self.client,
+            &self.opts,
+            &self.apis,
+            &self.retry_budget,
+            &self.rate_limits,
+            &self.spill,
+            fingerprints,
+            &mut out,
+        )
+        .await?;
+
+        self.finish_report(output_path, out)
+    }
+
+    async fn search_compare(
+        &self,
+        term: &str,
+        game_type: &str,
+        limit: usize,
+    ) -> Result<(), anyhow::Error> {
+        let (output_path, mut out) = self.open_report()?;
+
+        search_compare::compare(&self.client, &self.opts, term, game_type, limit, &mut out).await?;
+
+        self.finish_report(output_path, out)
+    }
+
+    async fn releases(
+        &self,
+        repos: &[cli::GitRepo],
+        repos_file: Option<&Path>,
+        github_token: Option<&str>,
+    ) -> Result<(), anyhow::Error> {
+        let mut repos = repos.to_vec();
+
+        if let Some(path) = repos_file {
+            repos.extend(read_input_file::<cli::GitRepo>(&self.opts, path)?);
+        }
+
+        if repos.is_empty() {
+            error!("the releases subcommand needs repository URLs or --repos-file");
+            process::exit(EXIT_ARGUMENT_ERROR);
+        }
+
+        let (output_path, mut out) = self.open_report()?;
+
+        let failing =
+            releases::check(&self.client, &self.opts, &repos, github_token, &mut out).await?;
+
+        self.finish_report(output_path, out)?;
+
+        exit_if_failing(failing);
+        Ok(())
+    }
+
+    async fn tukui(&self) -> Result<(), anyhow::Error> {
+        let (output_path, mut out) = self.open_report()?;
+
+        let failing = tukui::check(&self.client, &self.opts, &mut out).await?;
+
+        self.finish_report(output_path, out)?;
+
+        exit_if_failing(failing);
+        Ok(())
+    }
+
+    async fn wowinterface(
+        &self,
+        mapping_file: Option<&Path>,
+        max_lag_days: u64,
+    ) -> Result<(), anyhow::Error> {
+        let (output_path, mut out) = self.open_report()?;
+
+        let lagging = wowinterface::check(
+            &self.client,
+            &self.opts,
+            mapping_file,
+            max_lag_days,
+            &mut out,
+        )
+        .await?;
+
+        self.finish_report(output_path, out)?;
+
+        exit_if_failing(lagging);
+        Ok(())
+    }
+
+    async fn dependencies(&self) -> Result<(), anyhow::Error> {
+        let (output_path, mut out) = self.open_report()?;
+
+        let inconsistent = dependencies::check(
+            &self.client,
+            &self.opts,
+            &self.apis,
+            &self.retry_budget,
+            &self.rate_limits,
+            &self.spill,
+            &mut out,
+        )
+        .await?;
+
+        self.finish_report(output_path, out)?;
+
+        exit_if_failing(inconsistent);
+        Ok(())
+    }
+
+    async fn edge_cases(&self, fingerprint: u32, oversized: usize) -> Result<(), anyhow::Error> {
+        let (output_path, mut out) = self.open_report()?;
+
+        edge_cases::check(
+            &self.client,
+            &self.opts,
+            &self.apis,
+            fingerprint,
+            oversized,
+            &mut out,
+        )
+        .await?;
+
+        self.finish_report(output_path, out)
+    }
+
+    async fn resolve(&self, id: i32) -> Result<(), anyhow::Error> {
+        let (output_path, mut out) = self.open_report()?;
+
+        lookup::resolve(
+            &self.client,
+            &self.opts,
+            &self.apis,
+            &self.retry_budget,
+            &self.rate_limits,
+            &self.spill,
+            id,
+            &mut out,
+        )
+        .await?;
+
+        self.finish_report(output_path, out)
+    }
+
+    async fn addon_dir(&self, dir: &Path) -> Result<(), anyhow::Error> {
+        let (output_path, mut out) = self.open_report()?;
+
+        audit_addon_dir(
+            &self.client,
+            &self.opts,
+            &self.apis,
+            &self.retry_budget,
+            &self.rate_limits,
+            &self.spill,
+            dir,
+            &mut out,
+        )
+        .await?;
+
+        self.finish_report(output_path, out)
+    }
+
+    async fn jobs(&self, path: &Path) -> Result<(), anyhow::Error> {
+        let failed = jobs::run(
+            &self.client,
+            &self.opts,
+            &self.retry_budget,
+            &self.rate_limits,
+            &self.spill,
+            path,
+            &self.run_id,
+            self.started_at,
+        )
+        .await?;
+
+        print_finished(&self.run_id, self.output_dir.as_deref());
+
+        exit_if_failing(failed);
+        Ok(())
+    }
+
+    /// Audits every `--game-id`, or every flavor, and writes their reports.
+    async fn audit(&self) -> Result<(), anyhow::Error> {
+        let Session {
+            opts,
+            apis,
+            client,
+            retry_budget,
+            rate_limits,
+            spill,
+            run_id,
+            started_at,
+            output_dir,
+        } = self;
+
+        let multi_game = opts.game_ids.len() > 1 || opts.all_flavors;
+
+        if multi_game {
+            let single_game_only = [
+                ("--package-id", opts.package_id.is_some()),
+                ("--packages-stdin", opts.packages_stdin),
+                ("--input-file", opts.input_file.is_some()),
+                ("--baseline", opts.baseline.is_some()),
+                ("--save-baseline", opts.save_baseline.is_some()),
+                ("--summary-file", opts.summary_file.is_some()),
+                ("--metrics-file", opts.metrics_file.is_some()),
+            ];
+
+            if let Some((flag, _)) = single_game_only.iter().find(|(_, set)| *set) {
+                let e = anyhow!(
+                    "{} can't be combined with several --game-id values or --all-flavors",
+                    flag
+                );
+                return Err(exit_unless_embedded(opts, EXIT_ARGUMENT_ERROR, e));
+            }
+        }
+
+        let mut raw_matches_out = match &opts.raw_matches_file {
+            Some(path) => Some(BufWriter::new(std::fs::File::create(path)?)),
+            None => None,
+        };
+
+        let mut batches_out = match &opts.batches_file {
+            Some(path) => {
+                let mut out = BufWriter::new(std::fs::File::create(path)?);
+                batch_csv::write_header(&mut out)?;
+                Some(out)
+            }
+            None => None,
+        };
+
+        let mut profile_out = match &opts.profile_file {
+            Some(path) => {
+                let mut out = BufWriter::new(std::fs::File::create(path)?);
+                profile::write_header(&mut out)?;
+                Some(out)
+            }
+            None => None,
+        };
+
+        let mut games = vec![];
+        let mut divergences = vec![];
+        let mut request_timings = vec![];
+
+        let mut audited = vec![];
+
+        if opts.all_flavors {
+            audited = all_flavors::audit(client, opts, apis, retry_budget, rate_limits).await?;
+        } else {
+            for &game_id in &opts.game_ids {
+                if multi_game && opts.format == Format::Text {
+                    print_status(opts, format_args!("Auditing game {}", game_id));
+                }
+
+                let (game, found) = audit_and_report(
+                    client,
+                    opts,
+                    apis,
+                    retry_budget,
+                    rate_limits,
+                    spill,
+                    game_id,
+                    None,
+                )
+                .await?;
+                divergences.extend(found);
+                audited.push(game);
+            }
+        }
+
+        for mut game in audited {
+            let game_id = game.game_id;
+            game.report.run_id = run_id.clone();
+            game.report.started_at = started_at.to_string();
+
+            if let Some(out) = &mut raw_matches_out {
+                raw_matches::write(out, &game.packages, &game.results)?;
+            }
+
+            if let Some(out) = &mut batches_out {
+                batch_csv::write(out, game_id, &game.results)?;
+            }
+
+            if let Some(out) = &mut profile_out {
+                profile::write(out, game_id, &game.results)?;
+            }
+
+            if opts.timings_file.is_some() {
+                request_timings.extend(timings::collect(game_id, &game.results));
+            }
+
+            #[cfg(feature = "sqlite")]
+            {
+                let store = opts.store.as_ref().map(|cli::Store::Sqlite(path)| path);
+
+                if let Some(path) = opts.export_sqlite.as_ref().or(store) {
+                    sqlite::export(path, game_id, &game.report, &game.results)?;
+                }
+            }
+
+            games.push(game);
+        }
+
+        let (output_path, mut out) = open_output(opts, run_id, *started_at)?;
+
+        let reports = games.iter().map(|g| &g.report).collect::<Vec<_>>();
+
+        match (opts.format, games.as_slice()) {
+            (Format::Text, games) if opts.only_discrepancies => {
+                for (index, game) in games.iter().enumerate() {
+                    if opts.all_flavors {
+                        writeln!(out, "\n{}:", all_flavors::FLAVORS[index].1)?;
+                    } else if multi_game {
+                        writeln!(out, "\nGame {}:", game.game_id)?;
+                    }
+
+                    game.report.write_discrepancies(&mut out, opts.top)?;
+                }
+            }
+            (format, games) if opts.all_flavors => {
+                all_flavors::write(&mut out, format, opts, games)?;
+            }
+            (Format::Text, [game]) => {
+                game.report
+                    .write_text(&mut out, opts.top, opts.template.as_deref())?;
+
+                if opts.package_id.is_some() {
+                    write_fingerprint_detail(&mut out, &game.packages[0], &game.results)?;
+                }
+
+                if opts.search_filter.is_some() {
+                    game.report.write_package_list(&mut out, opts.top)?;
+                }
+            }
+            (Format::Text, games) => {
+                for game in games {
+                    writeln!(out, "\nGame {}:", game.game_id)?;
+                    game.report
+                        .write_text(&mut out, opts.top, opts.template.as_deref())?;
+                }
+
+                Totals::new(&reports).write_text(&mut out)?;
+            }
+            (Format::Json, [game]) => {
+                write_json(&mut out, &game.report, opts.compact)?;
+            }
+            (Format::Json, games) => {
+                let report = MultiGameReport {
+                    games: games
+                        .iter()
+                        .map(|g| GameReport {
+                            game_id: g.game_id,
+                            report: &g.report,
+                        })
+                        .collect(),
+                    totals: Totals::new(&reports),
+                };
+
+                write_json(&mut out, &report, opts.compact)?;
+            }
+            (Format::Csv, games) => {
+                if let Some(game) = games.first() {
+                    report_csv::write_header(&mut out, &game.report)?;
+                }
+
+                for game in games {
+                    report_csv::write(&mut out, game.game_id, &game.report)?;
+                }
+            }
+        }
+
+        out.flush()?;
+
+        if let Some(mut out) = raw_matches_out {
+            out.flush()?;
+        }
+
+        if let Some(mut out) = batches_out {
+            out.flush()?;
+        }
+
+        if let Some(mut out) = profile_out {
+            out.flush()?;
+        }
+
+        if let Some(path) = &opts.timings_file {
+            let mut out = BufWriter::new(std::fs::File::create(path)?);
+            write_json(&mut out, &request_timings, opts.compact)?;
+            out.flush()?;
+        }
+
+        if let Some(path) = &opts.report_html {
+            let games = games
+                .iter()
+                .map(|g| (g.game_id, &g.report, g.results.as_slice()))
+                .collect::<Vec<_>>();
+
+            html::write(path, &games)?;
+            info!("Wrote HTML report to {}", path.display());
+        }
+
+        if let Some(path) = &opts.report_junit {
+            let games = games
+                .iter()
+                .enumerate()
+                .map(|(index, g)| {
+                    let name = if opts.all_flavors {
+                        all_flavors::FLAVORS[index].1.to_owned()
+                    } else {
+                        format!("game {}", g.game_id)
+                    };
+
+                    (name, g.packages.as_slice(), &g.report, g.results.as_slice())
+                })
+                .collect::<Vec<_>>();
+
+            junit::write(path, &games)?;
+            info!("Wrote JUnit report to {}", path.display());
+        }
+
+        if let Some(path) = &output_path {
+            info!("Wrote report to {}", path.display());
+        }
+
+        #[cfg(feature = "tui")]
+        {
+            if opts.tui {
+                let game = &games[0];
+                tui::run(&game.report, &game.packages, &game.results)?;
+            }
+        }
+
+        print_finished(run_id, output_dir.as_deref());
+
+        // Checks, alerts and baselines of a partial report would only mislead
+        if shutdown::requested() {
+            match &opts.state_dir {
+                Some(dir) => warn!(
+                    "run {} was interrupted, continue it with --resume --state-dir {}",
+                    run_id,
+                    dir.display()
+                ),
+                None => warn!("run {} was interrupted, its report is partial", run_id),
+            }
+
+            let e = AuditError::Interrupted.into();
+            return Err(exit_unless_embedded(opts, EXIT_INTERRUPTED, e));
+        }
+
+        self.check_games(&games, &divergences).await
+    }
+
+    /// Sends the alerts of the audited games, writes their summary, metrics
+    /// and baseline, and exits if they failed any check they're held to.
+    async fn check_games(
+        &self,
+        games: &[GameAudit],
+        divergences: &[core_compare::Divergence],
+    ) -> Result<(), anyhow::Error> {
+        let Session {
+            opts,
+            client,
+            run_id,
+            ..
+        } = self;
+        let reports = games.iter().map(|g| &g.report).collect::<Vec<_>>();
+
+        let alerts = notify::alerts(
+            opts,
+            &games
+                .iter()
+                .map(|g| (g.game_id, &g.report))
+                .collect::<Vec<_>>(),
+        );
+        notify::send(client, opts, run_id, &alerts).await;
+
+        // Only a single game is audited when these are set. Without any
+        // audited game there's nothing for them or the checks below to look at
+        let report = match reports.first() {
+            Some(&report) => report,
+            None => return Ok(()),
+        };
+
+        if let Some(path) = &opts.summary_file {
+            std::fs::write(path, report.summary_line())?;
+        }
+
+        if let Some(path) = &opts.metrics_file {
+            metrics::write(path, report)?;
+        }
+
+        let mut failures = vec![];
+
+        // Read before --save-baseline possibly replaces the same file
+        let previous = match &opts.baseline {
+            Some(path) => Some((path, Baseline::load(path)?)),
+            None => None,
+        };
+
+        if let Some(path) = &opts.save_baseline {
+            Baseline::new(report).save(path)?;
+            info!("Saved baseline to {}", path.display());
+        }
+
+        if let Some((path, previous)) = &previous {
+            let api_name = |key: &str| {
+                report
+                    .apis
+                    .iter()
+                    .find(|api| api.api == key)
+                    .map_or_else(|| key.to_owned(), |api| api.name.to_owned())
+            };
+
+            for change in previous.count_changes(report) {
+                let subject = match &change.api {
+                    Some(api) => format!("{} {}", api_name(api), change.what),
+                    None => change.what.to_owned(),
+                };
+
+                info!(
+                    "{} went from {} in the baseline to {}",
+                    subject, change.before, change.after
+                );
+            }
+
+            let mut regressions = previous.compare(report, opts.baseline_strict);
+            regressions.retain(|regression| !report.is_known_issue(regression.id()));
+
+            for (api, lost) in baseline::lost_by_api(&regressions) {
+                info!(
+                    "{} packages that matched in the baseline no longer match on {}",
+                    lost,
+                    api_name(api)
+                );
+            }
+
+            if !regressions.is_empty() {
+                let changed = regressions
+                    .iter()
+                    .map(Regression::id)
+                    .collect::<HashSet<_>>()
+                    .len();
+                let within_tolerance = matches!(opts.diff_threshold, Some(n) if changed < n);
+                let list = regressions
+                    .iter()
+                    .map(|regression| format!("\n  {}", regression))
+                    .collect::<String>();
+
+                if within_tolerance {
+                    warn!(
+                        "{} regressions across {} packages against baseline {}, within --diff-threshold:{}",
+                        regressions.len(),
+                        changed,
+                        path.display(),
+                        list
+                    );
+                } else {
+                    error!(
+                        "{} regressions against baseline {}:{}",
+                        regressions.len(),
+                        path.display(),
+                        list
+                    );
+                }
+
+                if !within_tolerance {
+                    failures.push(CheckFailure {
+                        check: "--baseline",
+                        apis: vec![],
+                        message: format!(
+                            "{} regressions across {} packages against baseline {}",
+                            regressions.len(),
+                            changed,
+                            path.display()
+                        ),
+                    });
+                }
+            }
+        }
+
+        if opts.enforce_sla {
+            let missed = reports
+                .iter()
+                .flat_map(|report| &report.apis)
+                .filter(|api| api.sla == Some(SlaStatus::Fail))
+                .collect::<Vec<_>>();
+
+            if !missed.is_empty() {
+                let names = missed.iter().map(|api| api.name).collect::<Vec<_>>();
+                let message = format!("p95 latency exceeded --latency-sla on {}", names.join(", "));
+                error!("{}", message);
+
+                failures.push(CheckFailure {
+                    check: "--enforce-sla",
+                    apis: missed.iter().map(|api| api.api).collect(),
+                    message,
+                });
+            }
+        }
+
+        if let Some(min_success_rate) = opts.min_success_rate {
+            for api in reports.iter().flat_map(|report| &report.apis) {
+                if api.success_rate < min_success_rate {
+                    let message = format!(
+                        "{} - batch success rate {:.1}% is below --min-success-rate {}%",
+                        api.name, api.success_rate, min_success_rate
+                    );
+                    error!("{}", message);
+
+                    failures.push(CheckFailure {
+                        check: "--min-success-rate",
+                        apis: vec![api.api],
+                        message,
+                    });
+                }
+            }
+        }
+
+        if let Some(min_match_rate) = opts.fail_if_match_rate_below {
+            for report in &reports {
+                for api in &report.apis {
+                    let match_rate = report.match_rate(api);
+
+                    if match_rate < min_match_rate {
+                        let message = format!(
+                            "{} - matched {:.1}% of {} audited packages, below --fail-if-match-rate-below {}%",
+                            api.name, match_rate, report.packages_audited, min_match_rate
+                        );
+                        error!("{}", message);
+
+                        failures.push(CheckFailure {
+                            check: "--fail-if-match-rate-below",
+                            apis: vec![api.api],
+                            message,
+                        });
+                    }
+                }
+            }
+        }
+
+        if opts.fail_on_api_error {
+            for report in &reports {
+                for api in report.apis.iter().filter(|api| api.failed_batches > 0) {
+                    let message = format!(
+                        "{} - {} of {} batches failed",
+                        api.name, api.failed_batches, api.batches
+                    );
+                    error!("{}", message);
+
+                    failures.push(CheckFailure {
+                        check: "--fail-on-api-error",
+                        apis: vec![api.api],
+                        message,
+                    });
+                }
+            }
+        }
+
+        if opts.require_wowup_parity {
+            let curse = ApiChoice::Curse.key();
+            let wowup = ApiChoice::WowUp.key();
+
+            let missing = reports
+                .iter()
+                .flat_map(|report| report.packages.iter().map(move |p| (report, p)))
+                .filter(|(report, p)| {
+                    !report.is_known_issue(p.id)
+                        && p.matched_by.iter().any(|api| api == curse)
+                        && !p.matched_by.iter().any(|api| api == wowup)
+                        && !p.failed_on.iter().any(|api| api == wowup)
+                })
+                .collect::<Vec<_>>();
+
+            if !missing.is_empty() {
+                let message = format!(
+                    "{} packages matched by {} are missing from {}",
+                    missing.len(),
+                    ApiChoice::Curse.name(),
+                    ApiChoice::WowUp.name()
+                );
+                let list = missing
+                    .iter()
+                    .map(|(_, package)| format!("\n  {} ({})", package.name, package.id))
+                    .collect::<String>();
+                error!("{}:{}", message, list);
+
+                failures.push(CheckFailure {
+                    check: "--require-wowup-parity",
+                    apis: vec![wowup],
+                    message,
+                });
+            }
+        }
+
+        if opts.compare_against_ajour_core {
+            if divergences.is_empty() {
+                info!("ajour-core agreed with the direct Curse requests");
+            } else {
+                let message = format!(
+                    "{} packages differ between ajour-core and the direct Curse requests",
+                    divergences.len()
+                );
+                let list = divergences
+                    .iter()
+                    .map(|divergence| format!("\n  {}", divergence))
+                    .collect::<String>();
+                error!("{}:{}", message, list);
+
+                failures.push(CheckFailure {
+                    check: "--compare-against-ajour-core",
+                    apis: vec![ApiChoice::Curse.key()],
+                    message,
+                });
+            }
+        }
+
+        if opts.strict_versions {
+            let mismatches = reports
+                .iter()
+                .flat_map(|report| {
+                    report
+                        .version_disagreements
+                        .iter()
+                        .filter(move |d| !report.is_known_issue(d.id))
+                })
+                .collect::<Vec<_>>();
+
+            if !mismatches.is_empty() {
+                let message = format!(
+                    "{} packages resolved to different file versions across APIs",
+                    mismatches.len()
+                );
+                let list = mismatches
+                    .iter()
+                    .map(|mismatch| {
+                        let versions = mismatch
+                            .versions
+                            .iter()
+                            .map(|(api, version)| format!("{} {}", api, version))
+                            .collect::<Vec<_>>();
+
+                        format!(
+                            "\n  {} ({}) - {}",
+                            mismatch.name,
+                            mismatch.id,
+                            versions.join(", ")
+                        )
+                    })
+                    .collect::<String>();
+                error!("{}:{}", message, list);
+
+                failures.push(CheckFailure {
+                    check: "--strict-versions",
+                    apis: vec![],
+                    message,
+                });
+            }
+        }
+
+        if !failures.is_empty() {
+            checks::write_summary(&failures, EXIT_AUDIT_FAILURE)?;
+
+            let e = anyhow!("{} checks failed", failures.len());
+            return Err(exit_unless_embedded(opts, EXIT_AUDIT_FAILURE, e));
+        }
+
+        Ok(())
+    }
+}
+
+/// Logs `error` and exits with `code` from the command line tool, or hands
+/// it back to be returned from an `AuditRunner`, which mustn't exit the
+/// program embedding it.
+fn exit_unless_embedded(opts: &Opts, code: i32, error: anyhow::Error) -> anyhow::Error {
+    if !opts.embedded {
+        error!("{}", error);
+        process::exit(code);
+    }
+
+    error
+}
+
+/// Exits with `EXIT_AUDIT_FAILURE` if a subcommand found any problem.
+fn exit_if_failing(problems: usize) {
+    if problems > 0 {
+        process::exit(EXIT_AUDIT_FAILURE);
+    }
+}
+
+/// Audits one game and builds its report, along with how ajour-core
+/// diverged from the direct Curse requests with
+/// `--compare-against-ajour-core`. The report's run id and start time are
+/// left for the caller to fill in.
+#[allow(clippy::too_many_arguments)]
+async fn audit_and_report(
+    client: &HttpClient,
+    opts: &Opts,
+    apis: &[ApiChoice],
+    retry_budget: &RetryBudget,
+    rate_limits: &RateLimits,
+    spill: &Spill,
+    game_id: u32,
+    searched: Option<&Searched>,
+) -> Result<(GameAudit, Vec<core_compare::Divergence>), anyhow::Error> {
+    let retries_before = retry_budget.used();
+    let audited = audit_game(
+        client,
+        opts,
+        apis,
+        retry_budget,
+        rate_limits,
+        spill,
+        game_id,
+        searched,
+    )
+    .await?;
+
+    let Audited {
+        packages,
+        packages_fetched,
+        sample_seed,
+        search_duration,
+        batch_ranges,
+        responses,
+        concurrency,
+    } = audited;
+
+    let report_start = Instant::now();
+    let mut warnings = vec![];
+    let mut divergences = vec![];
+
+    let searched = opts.package_id.is_none() && !opts.packages_stdin && opts.input_file.is_none();
+
+    if searched && opts.search_filter.is_none() && packages_fetched < opts.total {
+        let warning = format!(
+            "requested {} packages but the search returned {}",
+            opts.total, packages_fetched
+        );
+        warn!("{}", warning);
+        warnings.push(warning);
+    }
+
+    // Rebuilt the way they were sent, so they line up with the responses
+    let batches = batch_ranges
+        .iter()
+        .flat_map(|ranges| build_batches(&packages, ranges))
+        .collect::<Vec<_>>();
+
+    let mut results = apis
+        .iter()
+        .zip(responses)
+        .map(|(&api_choice, responses)| ApiResults {
+            api_choice,
+            failed_packages: responses
+                .iter()
+                .zip(&batches)
+                .filter(|(r, _)| r.result.is_err())
+                .flat_map(|(_, batch)| batch.answers_for.iter().map(|p| p.id))
+                .collect(),
+            responses,
+        })
+        .collect::<Vec<_>>();
+
+    let spilled = spill.spilled();
+
+    if spilled {
+        warn!(
+            "matches passed --max-memory-mb, spilled them to {} and read them back without their latest files",
+            spill.path().display()
+        );
+        spill.restore(&mut results)?;
+    }
+
+    if opts.compare_against_ajour_core {
+        if let Some(curse) = results.iter().find(|r| r.api_choice == ApiChoice::Curse) {
+            let (found, skipped) = core_compare::compare(&packages, &batches, curse).await;
+
+            if skipped > 0 {
+                let warning = format!(
+                    "{} batches failed directly or through ajour-core and weren't compared",
+                    skipped
+                );
+                warn!("{}", warning);
+                warnings.push(warning);
+            }
+
+            divergences.extend(found);
+        }
+    }
+
+    let mut report = AuditReport::new(opts, &packages, &results);
+    report.packages_fetched = packages_fetched;
+    report.spilled_to_disk = spilled;
+    report.sample_seed = sample_seed;
+    report.warnings = warnings;
+    report.retries_used = retry_budget.used() - retries_before;
+    report.retry_budget = retry_budget.limit();
+
+    // Read again for every run, so edits apply to the next --watch cycle
+    if let Some(path) = &opts.ignore_file {
+        let ignored = IgnoreList::load(path).map_err(anyhow::Error::msg)?;
+
+        report.known_issues = packages
+            .iter()
+            .filter_map(|package| {
+                ignored.reason(package).map(|reason| KnownIssue {
+                    id: package.id,
+                    name: package.name.clone(),
+                    reason: reason.to_owned(),
+                })
+            })
+            .collect();
+        report.known_issues.sort_by_key(|issue| issue.id);
+    }
+
+    for (api, concurrency) in report.apis.iter_mut().zip(concurrency) {
+        api.concurrency = concurrency;
+    }
+
+    if opts.check_download_urls {
+        let checks = download_check::check(client, opts, &results).await;
+
+        for (api, check) in report.apis.iter_mut().zip(checks) {
+            api.download_urls = Some(check);
+        }
+    }
+
+    if opts.check_changelogs {
+        let checks = changelog_check::check(client, opts, &results).await;
+
+        for (api, check) in report.apis.iter_mut().zip(checks) {
+            api.changelogs = Some(check);
+        }
+    }
+
+    if let Some(repeats) = opts.repeat {
+        let seed = opts.seed.unwrap_or_else(sample::random_seed);
+        info!(
+            "Sending {} sampled batches {} more times with seed {}",
+            opts.repeat_batches.min(batches.len()),
+            repeats,
+            seed
+        );
+
+        let checks = repeat_check::check(client, opts, &batches, &results, seed).await;
+
+        for (api, check) in report.apis.iter_mut().zip(checks) {
+            if !check.flaky.is_empty() {
+                let warning = format!(
+                    "{} returned different matches for {} of {} batches sent again",
+                    api.name,
+                    check.flaky.len(),
+                    check.batches
+                );
+                warn!("{}", warning);
+                report.warnings.push(warning);
+            }
+
+            api.repeats = Some(check);
+        }
+    }
+
+    if opts.recompute_fingerprints {
+        let test = fingerprint_check::check(client, opts, &results).await;
+
+        if !test.mismatches.is_empty() {
+            let warning = format!(
+                "{} of {} folders of the downloaded files didn't recompute to the fingerprint the API advertised",
+                test.mismatches.len(),
+                test.folders
+            );
+            warn!("{}", warning);
+            report.warnings.push(warning);
+        }
+
+        report.fingerprint_self_test = Some(test);
+    }
+
+    if opts.verify_file_hashes {
+        let verification = file_hashes::verify(client, opts, &results).await;
+
+        if !verification.mismatches.is_empty() {
+            let warning = format!(
+                "{} of {} sampled fingerprints were matched to files that aren't byte-identical across the APIs",
+                verification.mismatches.len(),
+                verification.sampled
+            );
+            warn!("{}", warning);
+            report.warnings.push(warning);
+        }
+
+        report.file_hashes = Some(verification);
+    }
+
+    let algorithms = report
+        .apis
+        .iter()
+        .map(|api| api.fingerprint_algorithm.as_str())
+        .collect::<BTreeSet<_>>();
+
+    if algorithms.len() > 1 && !algorithms.contains(report::UNKNOWN_ALGORITHM) {
+        let reported = report
+            .apis
+            .iter()
+            .map(|api| format!("{} {}", api.name, api.fingerprint_algorithm))
+            .collect::<Vec<_>>();
+        let warning = format!(
+            "the APIs use different fingerprint algorithms ({}), so some discrepancies are expected",
+            reported.join(", ")
+        );
+        warn!("{}", warning);
+        report.warnings.push(warning);
+    }
+
+    for api in &report.apis {
+        if let Some(drift) = api.schema_drift.as_ref().filter(|d| !d.is_empty()) {
+            let warning = format!(
+                "{} responses have {} fields the response types don't capture and {} that came back null",
+                api.name,
+                drift.uncaptured.len(),
+                drift.null.len()
+            );
+            warn!("{}", warning);
+            report.warnings.push(warning);
+        }
+
+        let uncompressed = api.response_bytes.as_ref().map_or(false, |bytes| {
+            bytes
+                .encodings
+                .keys()
+                .all(|encoding| encoding == "identity")
+        });
+
+        if uncompressed && opts.response_encoding != Some(ResponseEncoding::Identity) {
+            let warning = format!(
+                "{} sent every response uncompressed although compression was offered",
+                api.name
+            );
+            warn!("{}", warning);
+            report.warnings.push(warning);
+        }
+    }
+
+    if !report.attribution_conflicts.is_empty() {
+        let warning = format!(
+            "{} fingerprints were matched to different packages by different APIs",
+            report.attribution_conflicts.len()
+        );
+        warn!("{}", warning);
+        report.warnings.push(warning);
+    }
+
+    report.phases.search_ms = search_duration.as_millis() as u64;
+    report.phases.report_ms = report_start.elapsed().as_millis() as u64;
+
+    if opts.profile {
+        profile::print_summary(&results);
+    }
+
+    if opts.verbose > 0 {
+        print_batch_sizes(opts, &packages, &batches);
+
+        for result in &results {
+            print_slowest_matched_packages(&batches, result);
+        }
+
+        for api in &report.apis {
+            if let (Some(slowest), Some(fastest)) = (&api.slowest_batch, &api.fastest_batch) {
+                debug!(
+                    "{} slowest batch #{} with {} fingerprints took {}ms, fastest #{} with {} took {}ms",
+                    api.name,
+                    slowest.index,
+                    slowest.fingerprints,
+                    slowest.latency_ms,
+                    fastest.index,
+                    fastest.fingerprints,
+                    fastest.latency_ms
+                );
+            }
+        }
+
+        for result in &results {
+            print_retried_batches(result);
+        }
+    }
+
+    let game = GameAudit {
+        game_id,
+        packages,
+        results,
+        report,
+    };
+
+    Ok((game, divergences))
+}
+
+/// Sends a throwaway request to each API's host, so the timed batches reuse
+/// an established connection instead of paying for the TLS handshake. Its
+/// outcome doesn't matter and isn't counted anywhere.
+async fn warm_up(client: &HttpClient, opts: &Opts, apis: &[ApiChoice]) {
+    future::join_all(apis.iter().map(|&api_choice| async move {
+        let start = Instant::now();
+        let result = client
+            .head_async(api_choice.api().fingerprint_url(opts.api_version))
+            .await;
+
+        match result {
+            Ok(_) => debug!(
+                "{} - warm-up request took {:?}",
+                api_choice,
+                start.elapsed()
+            ),
+            Err(e) => debug!("{} - warm-up request failed - {}", api_choice, e),
+        }
+    }))
+    .await;
+}
+
+/// Fingerprints the addon folders in `dir` and writes which APIs matched
+/// each, bypassing the search.
+#[allow(clippy::too_many_arguments)]
+async fn audit_addon_dir(
+    client: &HttpClient,
+    opts: &Opts,
+    apis: &[ApiChoice],
+    retry_budget: &RetryBudget,
+    rate_limits: &RateLimits,
+    spill: &Spill,
+    dir: &Path,
+    out: &mut impl Write,
+) -> Result<(), anyhow::Error> {
+    let scan_start = Instant::now();
+    let folders = addon_dir::scan(dir, opts.cache_dir.as_deref()).map_err(|e| {
+        let e = anyhow!("failed to read {} - {}", dir.display(), e);
+        exit_unless_embedded(opts, EXIT_ARGUMENT_ERROR, e)
+    })?;
+
+    if folders.is_empty() {
+        let e = anyhow!("no addon folders to audit in {}", dir.display());
+        return Err(exit_unless_embedded(opts, EXIT_ARGUMENT_ERROR, e));
+    }
+
+    info!(
+        "Fingerprinted {} addon folders in {}ms",
+        folders.len(),
+        scan_start.elapsed().as_millis()
+    );
+
+    if opts.format == Format::Text {
+        print_status(
+            opts,
+            format_args!("{} addon folders to audit against", folders.len()),
+        );
+    }
+
+    let ranges = batch_ranges(folders.len(), opts.batch_size);
+    let batches = ranges
+        .iter()
+        .map(|range| Batch {
+            packages: &[],
+            answers_for: vec![],
+            fingerprints: folders[range.clone()]
+                .iter()
+                .map(|f| f.fingerprint)
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+
+    let (responses, _) = send_batches(
+        client,
+        opts,
+        retry_budget,
+        rate_limits,
+        spill,
+        apis,
+        &batches,
+    )
+    .await;
+
+    let failed = responses
+        .iter()
+        .map(|responses| {
+            responses
+                .iter()
+                .zip(&ranges)
+                .filter(|(r, _)| r.result.is_err())
+                .flat_map(|(_, range)| range.clone())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut results = apis
+        .iter()
+        .zip(responses)
+        .map(|(&api_choice, responses)| ApiResults {
+            api_choice,
+            responses,
+            failed_packages: BTreeSet::new(),
+        })
+        .collect::<Vec<_>>();
+
+    if spill.spilled() {
+        spill.restore(&mut results)?;
+    }
+
+    let statuses = addon_dir::statuses(&folders, &results, &failed);
+
+    match opts.format {
+        Format::Text => addon_dir::write_text(out, &statuses, &results)?,
+        Format::Json => write_json(out, &statuses, opts.compact)?,
+        Format::Csv => unreachable!("--format csv is rejected with --addon-dir"),
+    }
+
+    Ok(())
+}
+
+/// Searches, filters and fingerprints the packages of one game, or the
+/// single package or piped list asked for instead. Packages that were
+/// already `searched` are filtered without searching again.
+#[allow(clippy::too_many_arguments)]
+async fn audit_game(
+    client: &HttpClient,
+    opts: &Opts,
+    apis: &[ApiChoice],
+    retry_budget: &RetryBudget,
+    rate_limits: &RateLimits,
+    spill: &Spill,
+    game_id: u32,
+    searched: Option<&Searched>,
+) -> Result<Audited, anyhow::Error> {
+    let search_start = Instant::now();
+    // Shared by `--sample` and `--search-sampling`, so one seed reproduces
+    // both
+    let seed = opts.seed.unwrap_or_else(sample::random_seed);
+
+    let audited = if let Some(id) = opts.package_id {
+        let package = if opts.embedded {
+            fetch_package(client, opts, id).await?
+        } else {
+            fetch_package_or_exit(client, opts, id).await
+        };
+        let packages = vec![package];
+        let search_duration = search_start.elapsed();
+        let batch_ranges = package_batch_ranges(opts, &packages);
+        let batches = build_batches(&packages, &batch_ranges);
+        let (responses, concurrency) = send_batches(
+            client,
+            opts,
+            retry_budget,
+            rate_limits,
+            spill,
+            apis,
+            &batches,
+        )
+        .await;
+
+        Audited {
+            packages,
+            packages_fetched: 1,
+            sample_seed: None,
+            search_duration,
+            batch_ranges: vec![batch_ranges],
+            responses,
+            concurrency,
+        }
+    } else if opts.stream_search {
+        let plan = SearchPlan::new(opts, seed);
+        let audited = stream_audit(
+            client,
+            opts,
+            apis,
+            retry_budget,
+            rate_limits,
+            spill,
+            game_id,
+            &plan,
+        )
+        .await;
+        let audited = exit_on_search_failure(opts, audited)?;
+        check_total(opts, game_id, audited.packages.len())?;
+        exit_if_empty(opts, game_id, &audited.packages);
+
+        if opts.format == Format::Text && !opts.only_discrepancies {
+            print_status(
+                opts,
+                format_args!("{} packages audited against", audited.packages.len()),
+            );
+        }
+
+        audited
+    } else {
+        let mut search_seed = None;
+
+        let mut packages = if let Some(searched) = searched {
+            search_seed = searched.seed;
+            searched.packages.clone()
+        } else if opts.packages_stdin {
+            match read_packages(io::stdin()) {
+                Ok(packages) => {
+                    info!("Read {} packages from stdin", packages.len());
+                    packages
+                }
+                Err(e) if opts.embedded => bail!("invalid package list on stdin - {}", e),
+                Err(e) => {
+                    error!("invalid package list on stdin - {}", e);
+                    process::exit(EXIT_ARGUMENT_ERROR);
+                }
+            }
+        } else if let Some(path) = &opts.input_file {
+            let ids = read_input_file(opts, path)?;
+            let packages = fetch_packages(client, opts, &ids).await;
+            exit_on_search_failure(opts, packages)?
+        } else {
+            let plan = SearchPlan::new(opts, seed);
+            search_seed = plan.seed;
+
+            let packages = search_all_packages(client, opts, game_id, &plan).await;
+            exit_on_search_failure(opts, packages)?
+        };
+
+        let search_duration = searched.map_or_else(|| search_start.elapsed(), |s| s.duration);
+        let packages_fetched = packages.len();
+
+        if let Some(since) = opts.since {
+            packages.retain(|p| updated_since(p, since));
+
+            let excluded = packages_fetched - packages.len();
+            info!("Excluded {} packages not updated since {}", excluded, since);
+        }
+
+        if let Some(needle) = &opts.name_contains {
+            let needle = needle.to_lowercase();
+            let before = packages.len();
+
+            packages.retain(|p| p.name.to_lowercase().contains(&needle));
+
+            info!(
+                "Excluded {} packages whose name doesn't contain \"{}\"",
+                before - packages.len(),
+                needle
+            );
+        }
+
+        if !opts.categories.is_empty() || !opts.exclude_categories.is_empty() {
+            let before = packages.len();
+            let in_any = |package: &curse::Package, names: &[String]| {
+                package.categories.iter().any(|category| {
+                    names
+                        .iter()
+                        .any(|name| name.trim().eq_ignore_ascii_case(&category.name))
+                })
+            };
+
+            packages.retain(|p| {
+                (opts.categories.is_empty() || in_any(p, &opts.categories))
+                    && !in_any(p, &opts.exclude_categories)
+            });
+
+            info!(
+                "Excluded {} packages outside of --category or in --exclude-category",
+                before - packages.len()
+            );
+        }
+
+        if !opts.flavors.is_empty() {
+            let before = packages.len();
+
+            for package in &mut packages {
+                package
+                    .latest_files
+                    .retain(|f| opts.flavors.contains(&f.game_version_flavor));
+            }
+            packages.retain(|p| !p.latest_files.is_empty());
+
+            info!(
+                "Excluded {} packages without a latest file for {}",
+                before - packages.len(),
+                opts.flavors.join(", ")
+            );
+        }
+
+        if let Some(min) = opts.min_fingerprints {
+            let before = packages.len();
+
+            packages.retain(|p| package_fingerprints(p).collect::<HashSet<_>>().len() >= min);
+
+            info!(
+                "Excluded {} packages with fewer than {} fingerprints",
+                before - packages.len(),
+                min
+            );
+        }
+
+        check_total(opts, game_id, packages.len())?;
+
+        let sample_seed = opts.sample.map(|_| seed).or(search_seed);
+
+        if let Some(size) = opts.sample {
+            packages = sample::sample(packages, size, seed);
+            info!(
+                "Sampled {} of {} packages with seed {}",
+                packages.len(),
+                packages_fetched,
+                seed
+            );
+        }
+
+        if let Some(limit) = opts.package_limit {
+            packages.truncate(limit);
+        }
+
+        exit_if_empty(opts, game_id, &packages);
+
+        if opts.format == Format::Text && !opts.only_discrepancies {
+            if packages.len() < packages_fetched {
+                print_status(
+                    opts,
+                    format_args!(
+                        "{} of {} fetched packages to audit against",
+                        packages.len(),
+                        packages_fetched
+                    ),
+                );
+            } else {
+                print_status(
+                    opts,
+                    format_args!("{} packages to audit against", packages.len()),
+                );
+            }
+        }
+
+        let batch_ranges = package_batch_ranges(opts, &packages);
+        let batches = build_batches(&packages, &batch_ranges);
+        let (responses, concurrency) = send_batches(
+            client,
+            opts,
+            retry_budget,
+            rate_limits,
+            spill,
+            apis,
+            &batches,
+        )
+        .await;
+
+        Audited {
+            packages,
+            packages_fetched,
+            sample_seed,
+            search_duration,
+            batch_ranges: vec![batch_ranges],
+            responses,
+            concurrency,
+        }
+    };
+
+    Ok(audited)
+}
+
+/// Writes `value` as pretty json, or on a single line with `compact`.
+fn write_json(out: &mut impl Write, value: &impl Serialize, compact: bool) -> io::Result<()> {
+    if compact {
+        serde_json::to_writer(&mut *out, value)?;
+    } else {
+        serde_json::to_writer_pretty(&mut *out, value)?;
+    }
+
+    writeln!(out)
+}
+
+/// Opens `--output-file`, with its placeholders filled in, or stdout.
+fn open_output(
+    opts: &Opts,
+    run_id: &str,
+    started_at: Timestamp,
+) -> io::Result<(Option<PathBuf>, Box<dyn Write>)> {
+    let output_path = opts
+        .output_file
+        .as_ref()
+        .filter(|p| p.as_os_str() != "-")
+        .map(|p| match p.to_str() {
+            Some(path) => PathBuf::from(
+                path.replace("{run_id}", run_id)
+                    .replace("{timestamp}", &started_at.compact()),
+            ),
+            None => p.clone(),
+        });
+
+    let out: Box<dyn Write> = match &output_path {
+        Some(path) => {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            Box::new(BufWriter::new(std::fs::File::create(path)?))
+        }
+        None => Box::new(io::stdout()),
+    };
+
+    Ok((output_path, out))
+}
+
+/// Creates the run's `--output-dir` bundle and points every artifact flag
+/// that wasn't given into it, returning the bundle's path.
+fn use_output_dir(
+    opts: &mut Opts,
+    dir: &Path,
+    run_id: &str,
+    started_at: Timestamp,
+) -> io::Result<PathBuf> {
+    let bundle = dir.join(format!("{}-{}", started_at.compact(), run_id));
+    std::fs::create_dir_all(&bundle)?;
+
+    let report = match opts.format {
+        Format::Json => "report.json",
+        Format::Text => "report.txt",
+        Format::Csv => "report.csv",
+    };
+    opts.output_file.get_or_insert_with(|| bundle.join(report));
+
+    // These can't be combined with a jobs file, and the last two only apply
+    // to a single game
+    if opts.jobs_file.is_none() {
+        opts.raw_matches_file
+            .get_or_insert_with(|| bundle.join("matches.ndjson"));
+        opts.batches_file
+            .get_or_insert_with(|| bundle.join("per-batch.csv"));
+        opts.timings_file
+            .get_or_insert_with(|| bundle.join("timings.json"));
+
+        if opts.profile {
+            opts.profile_file
+                .get_or_insert_with(|| bundle.join("profile.csv"));
+        }
+
+        if opts.game_ids.len() == 1 {
+            opts.summary_file
+                .get_or_insert_with(|| bundle.join("summary.txt"));
+            opts.metrics_file
+                .get_or_insert_with(|| bundle.join("metrics.prom"));
+        }
+    }
+
+    Ok(bundle)
+}
+
+/// Prints a line of progress ahead of the text report, or to stderr with
+/// `--stream`, which keeps stdout to its json lines.
+fn print_status(opts: &Opts, status: fmt::Arguments<'_>) {
+    if opts.stream.is_some() {
+        eprintln!("{}", status);
+    } else {
+        println!("{}", status);
+    }
+}
+
+fn print_finished(run_id: &str, output_dir: Option<&Path>) {
+    if let Some(dir) = output_dir {
+        info!("Wrote artifacts to {}", dir.display());
+    }
+
+    info!("Run {} finished", run_id);
+}
+
+/// Builds the client shared by every request.
+///
+/// Without `--proxy`, curl picks up `HTTPS_PROXY`, `HTTP_PROXY` and `NO_PROXY`
+/// from the environment on its own.
+fn build_client(opts: &Opts) -> Result<HttpClient, anyhow::Error> {
+    let max_connections = if opts.adaptive {
+        adaptive::MAX_CONCURRENCY
+    } else {
+        opts.max_connections
+    };
+
+    // Metrics give the body bytes as received, before decompression
+    let mut builder = HttpClient::builder()
+        .max_connections_per_host(max_connections)
+        .connect_timeout(Duration::from_secs(CONNECTION_TIMEOUT_SECONDS))
+        .metrics(true);
+
+    // Without --http-version curl negotiates the newest version both sides
+    // support
+    match opts.http_version {
+        Some(HttpVersion::Http11) => {
+            builder = builder.version_negotiation(VersionNegotiation::http11())
+        }
+        Some(HttpVersion::Http2) => {
+            builder = builder.version_negotiation(VersionNegotiation::http2())
+        }
+        None => {}
+    }
+
+    // Without --response-encoding curl offers every encoding it supports
+    match opts.response_encoding {
+        Some(ResponseEncoding::Identity) => {
+            builder = builder
+                .automatic_decompression(false)
+                .default_header("accept-encoding", "identity")
+        }
+        Some(encoding) => builder = builder.default_header("accept-encoding", encoding.name()),
+        None => {}
+    }
+
+    if let Some(proxy) = &opts.proxy {
+        builder = builder.proxy(Some(proxy.clone()));
+
+        if let Some(no_proxy) = env_var(&["NO_PROXY", "no_proxy"]) {
+            builder = builder.proxy_blacklist(
+                no_proxy
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|host| !host.is_empty())
+                    .map(str::to_owned)
+                    .collect::<Vec<_>>(),
+            );
+        }
+    }
+
+    let proxy = opts
+        .proxy
+        .as_ref()
+        .map(ToString::to_string)
+        .or_else(|| env_var(&["HTTPS_PROXY", "https_proxy", "HTTP_PROXY", "http_proxy"]));
+
+    match proxy {
+        Some(proxy) => debug!("Using proxy {}", proxy),
+        None => debug!("Not using a proxy"),
+    }
+
+    if opts.insecure {
+        warn!("TLS certificate verification is disabled");
+
+        builder = builder.ssl_options(
+            SslOption::DANGER_ACCEPT_INVALID_CERTS | SslOption::DANGER_ACCEPT_INVALID_HOSTS,
+        );
+    }
+
+    if let Some(limit) = opts.low_speed_limit {
+        builder = builder.low_speed_timeout(limit, Duration::from_secs(opts.low_speed_time));
+    }
+
+    if let Some(path) = &opts.ca_bundle {
+        // curl only reports a missing file once the first request fails
+        if !path.is_file() {
+            bail!("--ca-bundle {} is not a file", path.display());
+        }
+
+        debug!("Verifying TLS against {}", path.display());
+        builder = builder.ssl_ca_certificate(CaCertificate::file(path));
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Value of the first of `names` that is set to something non-empty.
+fn env_var(names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+}
+
+/// Exits with `--empty-exit-code` before any fingerprint request is sent if
+/// the search and filters left nothing to audit. With several games, an empty
+/// game only gets a warning so the others are still audited, as does an
+/// empty job or audit of an `AuditRunner`.
+fn exit_if_empty(opts: &Opts, game_id: u32, packages: &[curse::Package]) {
+    if let Some(code) = empty_exit_code(opts, game_id, packages) {
+        process::exit(code);
+    }
+}
+
+/// The code `exit_if_empty` exits with, or `None` if the run goes on.
+fn empty_exit_code(opts: &Opts, game_id: u32, packages: &[curse::Package]) -> Option<i32> {
+    if !packages.is_empty() {
+        return None;
+    }
+
+    if opts.game_ids.len() > 1 || opts.all_flavors || opts.jobs_file.is_some() || opts.embedded {
+        warn!("no packages matched the given filters for game {}", game_id);
+        None
+    } else {
+        info!("No packages matched the given filters, nothing to audit");
+        Some(opts.empty_exit_code)
+    }
+}
+
+/// Exits if the search and filters left fewer packages than `--assert-total`,
+/// or only fails the job with `--jobs-file` or the audit of an `AuditRunner`.
+fn check_total(opts: &Opts, game_id: u32, total: usize) -> Result<(), anyhow::Error> {
+    let expected = match opts.assert_total {
+        Some(expected) if total < expected => expected,
+        _ => return Ok(()),
+    };
+
+    let message = format!(
+        "only {} packages left to audit for game {}, expected at least {}",
+        total, game_id, expected
+    );
+
+    if opts.jobs_file.is_some() || opts.embedded {
+        bail!("{}", message);
+    }
+
+    error!("{}", message);
+    process::exit(EXIT_TOO_FEW_PACKAGES);
+}
+
+/// Exits with the network failure code if a search request couldn't reach the
+/// API, otherwise converts any other error for `run`. With `--jobs-file` only
+/// the job fails, as does the audit of an `AuditRunner`, so every error is
+/// converted.
+fn exit_on_search_failure<T>(
+    opts: &Opts,
+    result: Result<T, AuditError>,
+) -> Result<T, anyhow::Error> {
+    match result {
+        Err(e) if e.is_network_failure() && opts.jobs_file.is_none() && !opts.embedded => {
+            error!("search {}", e);
+            process::exit(EXIT_NETWORK_FAILURE);
+        }
+        result => Ok(result?),
+    }
+}
+
+/// Everything kept about one game's audit until the reports are written.
+struct GameAudit {
+    game_id: u32,
+    packages: Vec<curse::Package>,
+    results: Vec<ApiResults>,
+    report: AuditReport,
+}
+
+/// Search results shared by the audits of `--all-flavors`, which each
+/// filter them to their flavor rather than searching again.
+struct Searched {
+    packages: Vec<curse::Package>,
+    seed: Option<u64>,
+    duration: Duration,
+}
+
+/// Searched packages along with every API's response to each of their
+/// batches.
+struct Audited {
+    packages: Vec<curse::Package>,
+    /// Packages returned by the search, before sampling and limits.
+    packages_fetched: usize,
+    sample_seed: Option<u64>,
+    /// Time taken by the search, or fetching or reading the packages. With
+    /// `--stream-search` it overlaps with fingerprinting.
+    search_duration: Duration,
+    /// Index range into `packages` of each batch, in dispatch order, grouped
+    /// by what fingerprints were deduplicated across: every batch, or each
+    /// page with `--stream-search`.
+    batch_ranges: Vec<Vec<Range<usize>>>,
+    /// Per API, in `--apis` order, the response to each batch.
+    responses: Vec<Vec<BatchResponse>>,
+    /// Per API, the concurrency `--adaptive` settled on.
+    concurrency: Vec<Option<usize>>,
+}
+
+/// Splits `len` packages into ranges of `batch_size`.
+fn batch_ranges(len: usize, batch_size: usize) -> Vec<Range<usize>> {
+    (0..len)
+        .step_by(batch_size)
+        .map(|start| start..(start + batch_size).min(len))
+        .collect()
+}
+
+/// Splits `packages` into ranges of `--batch-size`, ending a range early
+/// once the next package would take its fingerprints past
+/// `--max-batch-fingerprints`.
+fn package_batch_ranges(opts: &Opts, packages: &[curse::Package]) -> Vec<Range<usize>> {
+    let max_fingerprints = match opts.max_batch_fingerprints {
+        Some(max_fingerprints) => max_fingerprints,
+        None => return batch_ranges(packages.len(), opts.batch_size),
+    };
+
+    let mut ranges = vec![];
+    let mut start = 0;
+    let mut fingerprints = 0;
+
+    for (index, package) in packages.iter().enumerate() {
+        let count = package_fingerprints(package).count();
+
+        if index > start
+            && (index - start == opts.batch_size || fingerprints + count > max_fingerprints)
+        {
+            ranges.push(start..index);
+            start = index;
+            fingerprints = 0;
+        }
+
+        fingerprints += count;
+    }
+
+    if start < packages.len() {
+        ranges.push(start..packages.len());
+    }
+
+    ranges
+}
+
+/// Chunks `packages` along `ranges`, sending each fingerprint only once
+/// across the batches where possible.
+///
+/// A package whose fingerprints were all sent by one earlier batch is
+/// answered by that batch instead. One that only shares some of them sends
+/// all of its own again, so a package's fingerprints are always matched
+/// together. A range left without any fingerprint to send gets no batch.
+fn build_batches<'a>(packages: &'a [curse::Package], ranges: &[Range<usize>]) -> Vec<Batch<'a>> {
+    let mut sent_by = HashMap::<u32, usize>::new();
+    let mut batches: Vec<Batch<'a>> = Vec::with_capacity(ranges.len());
+
+    for range in ranges {
+        let index = batches.len();
+        let packages = &packages[range.clone()];
+        let mut fingerprints = BTreeSet::new();
+        let mut answers_for = vec![];
+
+        for package in packages {
+            let own = package_fingerprints(package).collect::<BTreeSet<_>>();
+            let senders = own
+                .iter()
+                .map(|f| sent_by.get(f).copied())
+                .collect::<Option<BTreeSet<_>>>();
+
+            match senders {
+                Some(senders) if senders.len() == 1 => {
+                    let sender = senders.into_iter().next().unwrap_or(index);
+                    batches[sender].answers_for.push(package);
+                }
+                _ => {
+                    fingerprints.extend(own);
+                    answers_for.push(package);
+                }
+            }
+        }
+
+        if fingerprints.is_empty() {
+            continue;
+        }
+
+        for &fingerprint in &fingerprints {
+            sent_by.entry(fingerprint).or_insert(index);
+        }
+
+        batches.push(Batch {
+            packages,
+            fingerprints,
+            answers_for,
+        });
+    }
+
+    batches
+}
+
+/// Sends every batch to every API concurrently, up to `--max-connections` in
+/// flight per API, returning the responses per API in batch order.
+///
+/// With `--adaptive` each API's limit is adjusted separately, and the
+/// concurrency each settled on is returned alongside its responses.
+async fn send_batches(
+    client: &HttpClient,
+    opts: &Opts,
+    retry_budget: &RetryBudget,
+    rate_limits: &RateLimits,
+    spill: &Spill,
+    apis: &[ApiChoice],
+    batches: &[Batch<'_>],
+) -> (Vec<Vec<BatchResponse>>, Vec<Option<usize>>) {
+    let progress = &BatchProgress::new(opts, apis, batches.len());
+    let stream = opts.stream.map(|_| PackageStream::new(apis.len(), batches));
+    let stream = stream.as_ref();
+
+    if opts.adaptive {
+        let sent = future::join_all(apis.iter().map(|&api_choice| {
+            send_batches_adaptive(
+                client,
+                opts,
+                retry_budget,
+                rate_limits,
+                spill,
+                progress,
+                stream,
+                api_choice,
+                batches,
+            )
+        }))
+        .await;
+
+        return sent
+            .into_iter()
+            .map(|(responses, limit)| (responses, Some(limit)))
+            .unzip();
+    }
+
+    let responses = future::join_all(apis.iter().map(|&api_choice| async move {
+        // Batches past `--max-connections` wait here for a slot rather than
+        // queueing inside the client, so their wait shows up in `--profile`
+        let slots = Semaphore::new(opts.max_connections);
+        let slots = &slots;
+
+        future::join_all(batches.iter().enumerate().map(move |(index, batch)| {
+            let enqueued = Instant::now();
+
+            async move {
+                let _slot = slots.acquire().await;
+                let mut response = send_batch(
+                    client,
+                    opts,
+                    retry_budget,
+                    rate_limits,
+                    spill,
+                    api_choice.api(),
+                    batch,
+                )
+                .await;
+                response.enqueued = enqueued;
+                progress.record(api_choice, &response);
+
+                if let Some(stream) = stream {
+                    stream.record(index, api_choice, &response);
+                }
+
+                response
+            }
+        }))
+        .await
+    }))
+    .await;
+
+    (responses, vec![None; apis.len()])
+}
+
+/// Sends every batch to one API while an AIMD limiter adjusts how many are in
+/// flight, returning the responses in batch order and the final limit.
+#[allow(clippy::too_many_arguments)]
+async fn send_batches_adaptive(
+    client: &HttpClient,
+    opts: &Opts,
+    retry_budget: &RetryBudget,
+    rate_limits: &RateLimits,
+    spill: &Spill,
+    progress: &BatchProgress,
+    stream: Option<&PackageStream<'_>>,
+    api_choice: ApiChoice,
+    batches: &[Batch<'_>],
+) -> (Vec<BatchResponse>, usize) {
+    let mut aimd = Aimd::new(opts.max_connections);
+    // Every batch is waiting for a slot from the start
+    let enqueued = Instant::now();
+    let mut pending = batches.iter().enumerate();
+    let mut in_flight = FuturesUnordered::new();
+    let mut responses = Vec::with_capacity(batches.len());
+
+    loop {
+        while in_flight.len() < aimd.limit() {
+            match pending.next() {
+                Some((index, batch)) => in_flight.push(async move {
+                    (
+                        index,
+                        send_batch(
+                            client,
+                            opts,
+                            retry_budget,
+                            rate_limits,
+                            spill,
+                            api_choice.api(),
+                            batch,
+                        )
+                        .await,
+                    )
+                }),
+                None => break,
+            }
+        }
+
+        match in_flight.next().await {
+            Some((index, mut response)) => {
+                response.enqueued = enqueued;
+                aimd.record(
+                    response.latency,
+                    response.result.is_ok() && response.attempts == 1,
+                );
+                progress.record(api_choice, &response);
+
+                if let Some(stream) = stream {
+                    stream.record(index, api_choice, &response);
+                }
+
+                responses.push((index, response));
+            }
+            None => break,
+        }
+    }
+
+    debug!(
+        "{} - adaptive concurrency settled at {}",
+        api_choice,
+        aimd.limit()
+    );
+
+    responses.sort_by_key(|(index, _)| *index);
+
+    (
+        responses.into_iter().map(|(_, r)| r).collect(),
+        aimd.limit(),
+    )
+}
+
+/// Overlaps the search with fingerprinting by batching and sending each search
+/// page as soon as it arrives, while later pages are still being fetched.
+///
+/// Batches never span pages, so fingerprints are only deduplicated within a
+/// page.
+#[allow(clippy::too_many_arguments)]
+async fn stream_audit(
+    client: &HttpClient,
+    opts: &Opts,
+    apis: &[ApiChoice],
+    retry_budget: &RetryBudget,
+    rate_limits: &RateLimits,
+    spill: &Spill,
+    game_id: u32,
+    plan: &SearchPlan,
+) -> Result<Audited, AuditError> {
+    let start = Instant::now();
+    let (sender, receiver) = mpsc::unbounded();
+
+    let search = async {
+        let mut pages = 0;
+        let mut first_page_at = None;
+
+        let result = search_pages(client, opts, game_id, plan, |page| {
+            first_page_at.get_or_insert_with(|| start.elapsed());
+            // The receiver outlives the search, so this can't fail
+            let _ = sender.unbounded_send((pages, page));
+            pages += 1;
+        })
+        .await;
+
+        sender.close_channel();
+
+        result.map(|_| (start.elapsed(), first_page_at))
+    };
+
+    let fingerprint = receiver
+        .map(|(index, page): (usize, Vec<curse::Package>)| async move {
+            let ranges = package_batch_ranges(opts, &page);
+            let batches = build_batches(&page, &ranges);
+            let (responses, _) = send_batches(
+                client,
+                opts,
+                retry_budget,
+                rate_limits,
+                spill,
+                apis,
+                &batches,
+            )
+            .await;
+
+            (index, page, ranges, responses)
+        })
+        .buffer_unordered(opts.max_connections)
+        .collect::<Vec<_>>();
+
+    let (search, mut pages) = future::join(search, fingerprint).await;
+    let (search_duration, first_page_at) = search?;
+
+    if let Some(first_page_at) = first_page_at {
+        info!(
+            "Search finished after {:?} and fingerprinting after {:?}, overlapping by {:?}",
+            search_duration,
+            start.elapsed(),
+            search_duration - first_page_at
+        );
+    }
+
+    pages.sort_by_key(|(index, ..)| *index);
+
+    let mut audited = Audited {
+        packages: vec![],
+        packages_fetched: 0,
+        sample_seed: plan.seed,
+        search_duration,
+        batch_ranges: vec![],
+        responses: apis.iter().map(|_| vec![]).collect(),
+        concurrency: vec![None; apis.len()],
+    };
+
+    for (_, page, ranges, responses) in pages {
+        let offset = audited.packages.len();
+
+        audited.batch_ranges.push(
+            ranges
+                .into_iter()
+                .map(|r| r.start + offset..r.end + offset)
+                .collect(),
+        );
+        audited.packages.extend(page);
+
+        for (all, responses) in audited.responses.iter_mut().zip(responses) {
+            all.extend(responses);
+        }
+    }
+
+    audited.packages_fetched = audited.packages.len();
+
+    Ok(audited)
+}
+
+/// Whether `package` was modified at or after `since`. Packages with an
+/// unparseable date are kept, so a format change can't silently empty the
+/// audit.
+fn updated_since(package: &curse::Package, since: Timestamp) -> bool {
+    package
+        .date_modified
+        .parse::<Timestamp>()
+        .map_or(true, |modified| modified >= since)
+}
+
+fn package_fingerprints(package: &curse::Package) -> impl Iterator<Item = u32> + '_ {
+    package
+        .latest_files
+        .iter()
+        .flat_map(|f| f.modules.iter().map(|m| m.fingerprint))
+}
+
+/// Shows how far every API got through its batches, with `--progress bar`
+/// as a single redrawn line or with `--progress lines` as a line per
+/// completed batch, along with the failures so far and an estimate of the
+/// time left.
+struct BatchProgress {
+    mode: Progress,
+    total: usize,
+    start: Instant,
+    /// Completed and failed batches of each API, in `--apis` order.
+    apis: Mutex<Vec<(ApiChoice, usize, usize)>>,
+}
+
+impl BatchProgress {
+    fn new(opts: &Opts, apis: &[ApiChoice], total: usize) -> BatchProgress {
+        let mode = match opts.progress {
+            _ if opts.no_progress => Progress::None,
+            // A bar would break up json log lines
+            Progress::Auto if io::stderr().is_terminal() && opts.log_format == LogFormat::Text => {
+                Progress::Bar
+            }
+            Progress::Auto => Progress::Lines,
+            mode => mode,
+        };
+
+        BatchProgress {
+            mode,
+            total,
+            start: Instant::now(),
+            apis: Mutex::new(apis.iter().map(|&api| (api, 0, 0)).collect()),
+        }
+    }
+
+    fn record(&self, api_choice: ApiChoice, response: &BatchResponse) {
+        if self.mode == Progress::None {
+            return;
+        }
+
+        let mut apis = self.apis.lock().unwrap();
+
+        if let Some((_, completed, failed)) = apis.iter_mut().find(|(api, ..)| *api == api_choice) {
+            *completed += 1;
+
+            if response.result.is_err() {
+                *failed += 1;
+            }
+        }
+
+        if self.mode == Progress::Bar {
+            let done = apis
+                .iter()
+                .all(|(_, completed, _)| *completed == self.total);
+            let status = apis
+                .iter()
+                .map(|&(api, completed, failed)| self.status(api, completed, failed))
+                .collect::<Vec<_>>();
+
+            // Clears the rest of the line, as the status may have shrunk
+            eprint!("\r{}\x1b[K", status.join(" | "));
+
+            if done {
+                eprintln!();
+            }
+
+            return;
+        }
+
+        if let Some(&(_, completed, failed)) = apis.iter().find(|(api, ..)| *api == api_choice) {
+            info!(
+                "batch {}/{} {} {} {}ms{}{}",
+                completed,
+                self.total,
+                api_choice.key(),
+                if response.result.is_ok() {
+                    "ok"
+                } else {
+                    "failed"
+                },
+                response.total_latency.as_millis(),
+                if failed > 0 {
+                    format!(" {} failed so far", failed)
+                } else {
+                    String::new()
+                },
+                self.eta(completed)
+            );
+        }
+    }
+
+    fn status(&self, api_choice: ApiChoice, completed: usize, failed: usize) -> String {
+        const WIDTH: usize = 20;
+
+        let filled = (completed * WIDTH).checked_div(self.total).unwrap_or(WIDTH);
+
+        format!(
+            "{} [{}{}] {}/{}{}{}",
+            api_choice.key(),
+            "#".repeat(filled),
+            "-".repeat(WIDTH - filled),
+            completed,
+            self.total,
+            if failed > 0 {
+                format!(" {} failed", failed)
+            } else {
+                String::new()
+            },
+            self.eta(completed)
+        )
+    }
+
+    fn eta(&self, completed: usize) -> String {
+        let remaining = self.total.saturating_sub(completed);
+
+        // Mean latency over the effective concurrency works out to the
+        // elapsed time per completed batch, which converges as more of them
+        // complete
+        if completed > 0 && remaining > 0 {
+            let eta = self.start.elapsed() / completed as u32 * remaining as u32;
+            format!(" eta {}s", eta.as_secs())
+        } else {
+            String::new()
+        }
+    }
+}
+
+/// A chunk of packages whose fingerprints are sent in a single request.
+struct Batch<'a> {
+    packages: &'a [curse::Package],
+    fingerprints: BTreeSet<u32>,
+    /// Packages whose match status the request decides, which leaves out
+    /// the chunk's packages an earlier batch already sent every fingerprint
+    /// of, and adds the later ones this batch did.
+    answers_for: Vec<&'a curse::Package>,
+}
+
+impl Batch<'_> {
+    /// The fingerprints each package the batch answers for needs matched
+    /// together, or every fingerprint on its own for a batch of addon
+    /// folders or bare fingerprints, so a split batch keeps them whole.
+    fn fingerprint_groups(&self) -> Vec<BTreeSet<u32>> {
+        if self.answers_for.is_empty() {
+            return self
+                .fingerprints
+                .iter()
+                .map(|&fingerprint| BTreeSet::from([fingerprint]))
+                .collect();
+        }
+
+        self.answers_for
+            .iter()
+            .map(|package| package_fingerprints(package).collect())
+            .collect()
+    }
+}
+
+/// Outcome of sending one batch to an API.
+struct BatchResponse {
+    /// When the batch was handed to the dispatcher, which `send_batches`
+    /// sets for `--profile`.
+    enqueued: Instant,
+    /// When the first attempt was sent.
+    started: Instant,
+    /// Unique fingerprints sent in the batch.
+    fingerprints: usize,
+    /// Time taken by the final attempt.
+    latency: Duration,
+    /// Time across every attempt, including the delays between them.
+    total_latency: Duration,
+    attempts: usize,
+    /// Latency of every attempt, in the order they were sent.
+    attempt_latencies: Vec<Duration>,
+    /// Attempts the API rate limited.
+    rate_limited: usize,
+    /// Time spent waiting for the API's rate limit pauses to end.
+    rate_limit_wait: Duration,
+    /// Fingerprint algorithm the response reported, if it did.
+    fingerprint_algorithm: Option<String>,
+    /// HTTP version the final attempt's response came over, absent if it
+    /// failed or was replayed.
+    http_version: Option<String>,
+    /// Request body bytes of the final attempt before and after
+    /// `--compress-request`, absent if it failed.
+    request_bytes: Option<(usize, usize)>,
+    /// Response body bytes of the final attempt as received and once
+    /// decompressed, absent if it failed or was replayed.
+    response_bytes: Option<(u64, usize)>,
+    /// `Content-Encoding` of the final attempt's response, absent if it
+    /// wasn't compressed, failed or was replayed.
+    content_encoding: Option<String>,
+    /// Fields of the response the types dropped or that came back null,
+    /// with `--detect-schema-drift` if it succeeded.
+    schema_drift: Option<SchemaDrift>,
+    /// Set while the matches are in the `--max-memory-mb` spill file.
+    spill_id: Option<usize>,
+    result: BatchResult,
+}
+
+/// Sends a batch, retrying retryable failures up to `--retries` times with
+/// exponential backoff while the run's retry budget lasts.
+///
+/// A rate limited request pauses every request to its API for the
+/// `Retry-After` the API asked for, up to `--max-retry-delay-ms`, or the
+/// backoff if it didn't say, then is resent. Those resends only count towards
+/// `--rate-limit-retries`.
+async fn send_batch(
+    client: &HttpClient,
+    opts: &Opts,
+    retry_budget: &RetryBudget,
+    rate_limits: &RateLimits,
+    spill: &Spill,
+    api: &dyn FingerprintApi,
+    batch: &Batch<'_>,
+) -> BatchResponse {
+    let start = Instant::now();
+    let mut attempts = 0;
+    let mut attempt_latencies = vec![];
+    let mut rate_limited = 0;
+    let mut rate_limit_wait = Duration::default();
+
+    let (result, latency) = loop {
+        rate_limit_wait += rate_limits.wait(api).await;
+        attempts += 1;
+
+        let attempt_start = Instant::now();
+        let result = shutdown::cancellable(send_fingerprint_groups(
+            client,
+            opts,
+            api,
+            batch.fingerprint_groups(),
+            &batch.answers_for,
+        ))
+        .await;
+        let latency = attempt_start.elapsed();
+        attempt_latencies.push(latency);
+
+        let backoff = |attempt| {
+            retry::backoff(
+                Duration::from_millis(opts.retry_delay_ms),
+                Duration::from_millis(opts.max_retry_delay_ms),
+                attempt,
+            )
+        };
+
+        let delay = match &result {
+            Err(AuditError::RateLimited { retry_after, .. }) => {
+                rate_limited += 1;
+
+                if rate_limited > opts.rate_limit_retries {
+                    error!(
+                        "{} - still rate limited after {} retries, giving up on the batch",
+                        api, opts.rate_limit_retries
+                    );
+                    break (result, latency);
+                }
+
+                // An API asking for longer than --max-retry-delay-ms would
+                // otherwise stall every batch to it for that long
+                let delay = retry_after
+                    .map(|d| d.min(Duration::from_millis(opts.max_retry_delay_ms)))
+                    .unwrap_or_else(|| backoff(rate_limited));
+
+                if rate_limits.pause(api, delay) {
+                    warn!(
+                        "{} - rate limited, pausing its requests for {:?}",
+                        api, delay
+                    );
+                }
+
+                continue;
+            }
+            Err(e) if e.is_retryable() => backoff(attempts - rate_limited),
+            _ => break (result, latency),
+        };
+
+        if attempts - rate_limited > opts.retries || !retry_budget.try_acquire() {
+            break (result, latency);
+        }
+
+        task::sleep(delay).await;
+    };
+
+    let (result, latency) = match &result {
+        Ok(response) if opts.verify_empty && response.info.exact_matches.is_empty() => {
+            rate_limit_wait += rate_limits.wait(api).await;
+            attempts += 1;
+
+            let attempt_start = Instant::now();
+            let verified = shutdown::cancellable(send_fingerprint_groups(
+                client,
+                opts,
+                api,
+                batch.fingerprint_groups(),
+                &batch.answers_for,
+            ))
+            .await;
+            attempt_latencies.push(attempt_start.elapsed());
+
+            match verified {
+                Ok(verified) if !verified.info.exact_matches.is_empty() => {
+                    warn!(
+                        "{} - batch of {} fingerprints returned no exact matches, then {} when verified",
+                        api,
+                        batch.fingerprints.len(),
+                        verified.info.exact_matches.len()
+                    );
+
+                    (Ok(verified), attempt_start.elapsed())
+                }
+                _ => (result, latency),
+            }
+        }
+        _ => (result, latency),
+    };
+
+    let (mut result, fingerprint_algorithm, transfer, request_bytes, schema_drift) = match result {
+        Ok(response) => (
+            Ok(response.info),
+            response.fingerprint_algorithm,
+            response.transfer,
+            Some((response.request_bytes, response.sent_bytes)),
+            response.schema_drift,
+        ),
+        Err(e) => (Err(e), None, None, None, None),
+    };
+
+    let spill_id = match spill.keep(&mut result) {
+        Ok(spill_id) => spill_id,
+        Err(e) => {
+            warn!(
+                "failed to spill matches to {}, keeping them in memory - {}",
+                spill.path().display(),
+                e
+            );
+            None
+        }
+    };
+
+    BatchResponse {
+        enqueued: start,
+        started: start,
+        fingerprints: batch.fingerprints.len(),
+        latency,
+        total_latency: start.elapsed(),
+        attempts,
+        attempt_latencies,
+        rate_limited,
+        rate_limit_wait,
+        fingerprint_algorithm,
+        http_version: transfer.as_ref().map(|t| t.http_version.clone()),
+        request_bytes,
+        response_bytes: transfer
+            .as_ref()
+            .map(|t| (t.received_bytes, t.decoded_bytes)),
+        content_encoding: transfer.and_then(|t| t.content_encoding),
+        schema_drift,
+        spill_id,
+        result,
+    }
+}
+
+/// Sends the fingerprints of `groups` in one request, or split in halves of
+/// whole groups for as long as the request body would pass the API's
+/// `--max-request-bytes` or the API rejects it with 413 Payload Too Large,
+/// merging the halves' responses. Both halves carry all of `packages`, for
+/// an API that matches them by name.
+fn send_fingerprint_groups<'a>(
+    client: &'a HttpClient,
+    opts: &'a Opts,
+    api: &'a dyn FingerprintApi,
+    mut groups: Vec<BTreeSet<u32>>,
+    packages: &'a [&'a curse::Package],
+) -> future::LocalBoxFuture<'a, Result<FingerprintResponse, AuditError>> {
+    Box::pin(async move {
+        let fingerprints = groups.iter().flatten().copied().collect::<BTreeSet<_>>();
+        let limit = opts
+            .max_request_bytes
+            .iter()
+            .rev()
+            .find(|limit| limit.api == api.key())
+            .map(|limit| limit.bytes);
+
+        let fits = match limit {
+            Some(limit) if groups.len() > 1 => {
+                let body =
+                    api.encode_request(opts.api_version, fingerprints.iter().copied().collect())?;
+                body.len() <= limit
+            }
+            _ => true,
+        };
+
+        if fits {
+            match get_fingerprint_respose(client, opts, api, &fingerprints, packages).await {
+                Err(AuditError::BadStatus { code: 413, .. }) if groups.len() > 1 => {
+                    warn!(
+                        "{} - rejected {} fingerprints as too large, splitting them",
+                        api,
+                        fingerprints.len()
+                    );
+                }
+                result => return result,
+            }
+        } else {
+            debug!(
+                "{} - {} fingerprints pass --max-request-bytes, splitting them",
+                api,
+                fingerprints.len()
+            );
+        }
+
+        let second = groups.split_off(groups.len() / 2);
+        let (first, second) = future::try_join(
+            send_fingerprint_groups(client, opts, api, groups, packages),
+            send_fingerprint_groups(client, opts, api, second, packages),
+        )
+        .await?;
+
+        Ok(first.merge(second))
+    })
+}
+
+/// Attributes each batch's latency to the packages it matched, then prints
+/// the packages whose batches took longest.
+fn print_slowest_matched_packages(batches: &[Batch], results: &ApiResults) {
+    let mut matched = vec![];
+
+    for (batch, response) in batches.iter().zip(&results.responses) {
+        if let Ok(info) = &response.result {
+            let ids = info
+                .exact_matches
+                .iter()
+                .map(|m| m.id)
+                .collect::<HashSet<_>>();
+
+            matched.extend(
+                batch
+                    .answers_for
+                    .iter()
+                    .filter(|p| ids.contains(&p.id))
+                    .map(|&p| (p, response.latency)),
+            );
+        }
+    }
+
+    matched.sort_by_key(|(package, latency)| (Reverse(*latency), package.id));
+
+    debug!("Slowest matched packages on {}:", results.api_choice.name());
+
+    for (package, latency) in matched.iter().take(SLOWEST_PACKAGES_SHOWN) {
+        debug!(
+            "  {:?} - {} ({}), {} fingerprints",
+            latency,
+            package.name,
+            package.id,
+            package_fingerprints(package).count()
+        );
+    }
+}
+
+/// Writes every fingerprint of `package`'s latest files along with the APIs
+/// that exactly matched it.
+fn write_fingerprint_detail(
+    out: &mut impl Write,
+    package: &curse::Package,
+    results: &[ApiResults],
+) -> io::Result<()> {
+    let matched = results
+        .iter()
+        .map(|result| {
+            let fingerprints = result
+                .exact_matches()
+                .iter()
+                .filter(|m| m.id == package.id)
+                .flat_map(|m| m.file.modules.iter().map(|m| m.fingerprint))
+                .collect::<HashSet<_>>();
+
+            (result.api_choice.name(), fingerprints)
+        })
+        .collect::<Vec<_>>();
+
+    writeln!(out, "\n{} ({})", package.name, package.id)?;
+
+    for file in &package.latest_files {
+        writeln!(
+            out,
+            "  {} ({})",
+            file.display_name, file.game_version_flavor
+        )?;
+
+        for module in &file.modules {
+            let matched_by = matched
+                .iter()
+                .filter(|(_, fingerprints)| fingerprints.contains(&module.fingerprint))
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>();
+
+            writeln!(
+                out,
+                "    {:>10} {} - {}",
+                module.fingerprint,
+                module.foldername,
+                if matched_by.is_empty() {
+                    "unmatched".to_owned()
+                } else {
+                    matched_by.join(", ")
+                }
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the min/max/mean number of fingerprints actually sent per batch,
+/// and how many fingerprints deduplication removed.
+fn print_batch_sizes(opts: &Opts, packages: &[curse::Package], batches: &[Batch<'_>]) {
+    let sizes = batches.iter().map(|b| b.fingerprints.len());
+
+    let (min, max) = match (sizes.clone().min(), sizes.clone().max()) {
+        (Some(min), Some(max)) => (min, max),
+        _ => return,
+    };
+    let sent = sizes.sum::<usize>();
+
+    let raw = packages.iter().flat_map(package_fingerprints).count();
+    let unique = packages
+        .iter()
+        .flat_map(package_fingerprints)
+        .collect::<BTreeSet<_>>()
+        .len();
+
+    debug!(
+        "Batch sizes of {} packages: {} min, {} max, {:.1} mean fingerprints",
+        opts.batch_size,
+        min,
+        max,
+        sent as f64 / batches.len() as f64
+    );
+    debug!(
+        "Sent {} of {} raw fingerprints ({} unique across all batches)",
+        sent, raw, unique
+    );
+}
+
+/// Prints the final attempt and total latency of every retried batch.
+fn print_retried_batches(results: &ApiResults) {
+    let retried = results
+        .responses
+        .iter()
+        .enumerate()
+        .filter(|(_, r)| r.attempts > 1)
+        .collect::<Vec<_>>();
+
+    if retried.is_empty() {
+        return;
+    }
+
+    debug!("Retried batches on {}:", results.api_choice.name());
+
+    for (index, response) in retried {
+        debug!(
+            "  #{} - last attempt {:?}, {:?} total over {} attempts",
+            index, response.latency, response.total_latency, response.attempts
+        );
+    }
+}
+
+/// Every fingerprint batch response from one API, in batch order.
+struct ApiResults {
+    api_choice: ApiChoice,
+    responses: Vec<BatchResponse>,
+    /// Packages of the batches that failed, whose match status is unknown.
+    failed_packages: BTreeSet<i32>,
+}
+
+impl ApiResults {
+    fn exact_matches(&self) -> Vec<&curse::AddonFingerprintInfo> {
+        self.responses
+            .iter()
+            .filter_map(|r| r.result.as_ref().ok())
+            .flat_map(|i| i.exact_matches.iter())
+            .collect()
+    }
+
+    /// The first exactly matched file of each matched package.
+    fn matched_files(&self) -> BTreeMap<i32, &curse::File> {
+        let mut files = BTreeMap::new();
+
+        for info in self.exact_matches() {
+            files.entry(info.id).or_insert(&info.file);
+        }
+
+        files
+    }
+
+    fn partial_matches(&self) -> Vec<&curse::AddonFingerprintInfo> {
+        self.responses
+            .iter()
+            .filter_map(|r| r.result.as_ref().ok())
+            .flat_map(|i| i.partial_matches.iter())
+            .collect()
+    }
+
+    /// Ids of the exactly matched packages, plus the partially matched ones
+    /// with `count_partial`.
+    fn package_ids(&self, count_partial: bool) -> BTreeSet<i32> {
+        let mut ids = self
+            .exact_matches()
+            .iter()
+            .map(|i| i.id)
+            .collect::<BTreeSet<_>>();
+
+        if count_partial {
+            ids.extend(self.partial_matches().iter().map(|i| i.id));
+        }
+
+        ids
+    }
+
+    /// Time from the first batch sent until the last response, which the
+    /// batches of every other API overlap with.
+    fn fingerprint_duration(&self) -> Duration {
+        let first = self.responses.iter().map(|r| r.started).min();
+        let last = self
+            .responses
+            .iter()
+            .map(|r| r.started + r.total_latency)
+            .max();
+
+        match (first, last) {
+            (Some(first), Some(last)) => last - first,
+            _ => Duration::default(),
+        }
+    }
+
+    fn failed_batches(&self) -> usize {
+        self.responses.iter().filter(|r| r.result.is_err()).count()
+    }
+
+    /// Failed batches counted by error kind.
+    fn error_kinds(&self) -> BTreeMap<&'static str, usize> {
+        let mut kinds = BTreeMap::new();
+
+        for response in &self.responses {
+            if let Err(e) = &response.result {
+                *kinds.entry(e.kind()).or_default() += 1;
+            }
+        }
+
+        kinds
+    }
+
+    /// Failed batches counted by error category.
+    fn error_categories(&self) -> BTreeMap<&'static str, usize> {
+        let mut categories = BTreeMap::new();
+
+        for response in &self.responses {
+            if let Err(e) = &response.result {
+                *categories.entry(e.category()).or_default() += 1;
+            }
+        }
+
+        categories
+    }
+
+    /// Failed batches counted by the status the API answered with.
+    fn error_statuses(&self) -> BTreeMap<u16, usize> {
+        let mut statuses = BTreeMap::new();
+
+        for response in &self.responses {
+            if let Some(status) = response.result.as_ref().err().and_then(AuditError::status) {
+                *statuses.entry(status).or_default() += 1;
+            }
+        }
+
+        statuses
+    }
+
+    /// Batches that failed because their response passed
+    /// `--max-response-bytes`.
+    fn oversized_responses(&self) -> usize {
+        self.responses
+            .iter()
+            .filter(|r| matches!(r.result, Err(AuditError::ResponseTooLarge { .. })))
+            .count()
+    }
+
+    /// Percentage of batches that succeeded, treating an empty run as fully
+    /// successful.
+    fn success_rate(&self) -> f64 {
+        if self.responses.is_empty() {
+            return 100.0;
+        }
+
+        let succeeded = self.responses.len() - self.failed_batches();
+
+        succeeded as f64 / self.responses.len() as f64 * 100.0
+    }
+}
+
+/// Fetches a single package by id from the CurseForge addon endpoint.
+async fn fetch_package(
+    client: &HttpClient,
+    opts: &Opts,
+    id: i32,
+) -> Result<curse::Package, AuditError> {
+    let url = match opts.api_version {
+        ApiVersion::V1 => curse_v1::MOD_URL,
+        ApiVersion::V2 => CURSE_ADDON_URL,
+    };
+
+    let mut request = Request::builder()
+        .method("GET")
+        .uri(&format!("{}/{}", url, id));
+
+    if let Some(api_key) = &opts.api_key {
+        request = request.header(CURSE_API_KEY_HEADER, api_key.as_str());
+    }
+
+    let request = request.body(vec![])?;
+
+    let request_timeout = Duration::from_secs(opts.request_timeout);
+    let (body, _, _) = timeout(request_timeout, send_request(client, opts, request))
+        .await
+        .map_err(|_| AuditError::Timeout(request_timeout))??;
+
+    let body = match opts.api_version {
+        ApiVersion::V1 => curse_v1::mod_response(&body)?,
+        ApiVersion::V2 => body,
+    };
+
+    serde_json::from_slice(&body).map_err(|e| AuditError::Deserialize(e.into()))
+}
+
+/// Fetches a single package, exiting if it doesn't exist or can't be
+/// fetched.
+async fn fetch_package_or_exit(client: &HttpClient, opts: &Opts, id: i32) -> curse::Package {
+    match fetch_package(client, opts, id).await {
+        Ok(package) => package,
+        Err(AuditError::BadStatus { code: 404, .. }) => {
+            error!("no package exists with id {}", id);
+            process::exit(EXIT_ARGUMENT_ERROR);
+        }
+        Err(e) => {
+            error!("failed to fetch package {} - {}", id, e);
+
+            if e.is_network_failure() {
+                process::exit(EXIT_NETWORK_FAILURE);
+            }
+            process::exit(EXIT_ARGUMENT_ERROR);
+        }
+    }
+}
+
+/// Fetches the packages with the given ids, `--max-connections` at a time,
+/// skipping ids without a package.
+async fn fetch_packages(
+    client: &HttpClient,
+    opts: &Opts,
+    ids: &[i32],
+) -> Result<Vec<curse::Package>, AuditError> {
+    let fetched = stream::iter(ids)
+        .map(|&id| async move { (id, fetch_package(client, opts, id).await) })
+        .buffered(opts.max_connections)
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut packages = vec![];
+
+    for (id, result) in fetched {
+        match result {
+            Ok(package) => packages.push(package),
+            Err(AuditError::BadStatus { code: 404, .. }) => {
+                warn!("no package exists with id {}, skipping it", id);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    info!(
+        "Fetched {} of {} listed packages",
+        packages.len(),
+        ids.len()
+    );
+
+    Ok(packages)
+}
+
+/// Reads `--input-file`, failing if it can't be read or lists anything that
+/// isn't a `T`.
+fn read_input_file<T: FromStr>(opts: &Opts, path: &Path) -> Result<Vec<T>, anyhow::Error> {
+    input_file::read(path)
+        .map_err(|e| exit_unless_embedded(opts, EXIT_ARGUMENT_ERROR, anyhow::Error::msg(e)))
+}
+
+/// Reads a json array of packages, as produced by the search API.
+fn read_packages(mut reader: impl Read) -> Result<Vec<curse::Package>, anyhow::Error> {
+    let mut body = vec![];
+    reader.read_to_end(&mut body)?;
+
+    Ok(serde_json::from_slice(&body)?)
+}
+
+/// The search pages to request and the order to search in.
+struct SearchPlan {
+    sort: CurseSort,
+    /// Search index of each page, in the order they're requested.
+    starts: Vec<usize>,
+    /// Seed the pages were picked with by `--search-sampling`.
+    seed: Option<u64>,
+}
+
+impl SearchPlan {
+    /// Plans the pages holding `--total` packages, which are consecutive
+    /// from `--offset` on or picked at random with `seed`.
+    fn new(opts: &Opts, seed: u64) -> SearchPlan {
+        let needed = opts.total.div_ceil(opts.page_size);
+
+        let (sort, strata) = match opts.search_sampling {
+            SearchSampling::Top => {
+                return SearchPlan {
+                    sort: opts.sort,
+                    starts: (0..needed)
+                        .map(|page| opts.offset + page * opts.page_size)
+                        .collect(),
+                    seed: None,
+                };
+            }
+            SearchSampling::RandomPages => (opts.sort, 1),
+            // Equal ranges of the download order are download count buckets
+            // holding the same number of packages
+            SearchSampling::Stratified => (CurseSort::TotalDownloads, opts.strata),
+        };
+
+        let starts = sample::page_starts(needed, opts.page_size, opts.catalog_size, strata, seed);
+
+        if starts.len() < needed {
+            warn!(
+                "--catalog-size {} only holds {} pages to sample, fewer than --total needs",
+                opts.catalog_size,
+                starts.len()
+            );
+        }
+
+        info!(
+            "Sampled {} search pages with --search-sampling {} and seed {}",
+            starts.len(),
+            opts.search_sampling.name(),
+            seed
+        );
+
+        SearchPlan {
+            sort,
+            starts,
+            seed: Some(seed),
+        }
+    }
+}
+
+async fn search_all_packages(
+    client: &HttpClient,
+    opts: &Opts,
+    game_id: u32,
+    plan: &SearchPlan,
+) -> Result<Vec<curse::Package>, AuditError> {
+    let mut packages = vec![];
+
+    search_pages(client, opts, game_id, plan, |page| packages.extend(page)).await?;
+
+    Ok(packages)
+}
+
+/// Pages through the planned search pages until `--total` packages are
+/// gathered, the API runs out of results, or `--max-pages` requests have
+/// been issued. Each page is handed to `on_page` in order as it arrives.
+///
+/// Up to `--max-connections` pages are requested at once, so a few pages
+/// past the last one may be requested and discarded.
+async fn search_pages(
+    client: &HttpClient,
+    opts: &Opts,
+    game_id: u32,
+    plan: &SearchPlan,
+    mut on_page: impl FnMut(Vec<curse::Package>),
+) -> Result<(), AuditError> {
+    let request_timeout = Duration::from_secs(opts.request_timeout);
+
+    let needed = plan.starts.len();
+    let requested = needed.min(opts.max_pages);
+
+    let mut pages = Box::pin(
+        stream::iter(plan.starts[..requested].iter().enumerate())
+            .map(|(page, &start)| {
+                let page_size = opts.page_size.min(opts.total - page * opts.page_size);
+                let search = search_packages(client, opts, game_id, plan.sort, start, page_size);
+
+                async move {
+                    let page = timeout(request_timeout, search)
+                        .await
+                        .map_err(|_| AuditError::Timeout(request_timeout))??;
+
+                    Ok::<_, AuditError>((page, start, page_size))
+                }
+            })
+            .buffered(opts.max_connections),
+    );
+
+    while let Some(result) = pages.next().await {
+        let (page, start, page_size) = result?;
+        let is_last_page = page.len() < page_size;
+        let end = start + page.len();
+        on_page(page);
+
+        if is_last_page {
+            if opts.search_sampling != SearchSampling::Top {
+                warn!(
+                    "the search ran out of packages at index {}, so --catalog-size {} is larger than the catalog",
+                    end,
+                    opts.catalog_size
+                );
+            }
+
+            return Ok(());
+        }
+    }
+
+    if requested < needed {
+        warn!(
+            "stopped after --max-pages {} search requests, results may be truncated",
+            opts.max_pages
+        );
+    }
+
+    Ok(())
+}
+
+async fn search_packages(
+    client: &HttpClient,
+    opts: &Opts,
+    game_id: u32,
+    sort_type: CurseSort,
+    index: usize,
+    num_results: usize,
+) -> Result<Vec<curse::Package>, AuditError> {
+    let mut url = match opts.api_version {
+        ApiVersion::V1 => format!(
+            "{}?gameId={}&sortField={}&sortOrder=desc&index={}&pageSize={}",
+            curse_v1::SEARCH_URL,
+            game_id,
+            sort_type.v1_field(),
+            index,
+            num_results,
+        ),
+        ApiVersion::V2 => format!(
+            "{}?gameId={}&sort={}&index={}&pageSize={}",
+            CURSE_SEARCH_URL, game_id, sort_type as u8, index, num_results,
+        ),
+    };
+
+    if let Some(filter) = &opts.search_filter {
+        url.push_str(&format!("&searchFilter={}", percent_encode(filter)));
+    }
+
+    let cache = SearchCache::new(opts);
+    let cached = cache.as_ref().and_then(|cache| cache.load(&url));
+
+    let body = match cached {
+        Some(body) => {
+            debug!("Using cached search page at index {}", index);
+
+            body
+        }
+        None => {
+            let mut request = Request::builder().method("GET").uri(&url);
+
+            if let Some(api_key) = &opts.api_key {
+                request = request.header(CURSE_API_KEY_HEADER, api_key.as_str());
+            }
+
+            let request = request.body(vec![])?;
+
+            let (body, _, _) = send_request(client, opts, request).await?;
+
+            if let Some(cache) = &cache {
+                cache.save(&url, &body);
+            }
+
+            body
+        }
+    };
+
+    let body = match opts.api_version {
+        ApiVersion::V1 => curse_v1::search_response(&body)?,
+        ApiVersion::V2 => body,
+    };
+
+    serde_json::from_slice(&body).map_err(|e| AuditError::Deserialize(e.into()))
+}
+
+/// Percent-encodes everything but unreserved characters, for a query
+/// parameter value.
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// A decoded fingerprint response and what it revealed about how it was
+/// served.
+struct FingerprintResponse {
+    info: curse::FingerprintInfo,
+    fingerprint_algorithm: Option<String>,
+    transfer: Option<Transfer>,
+    /// Set with `--detect-schema-drift`.
+    schema_drift: Option<SchemaDrift>,
+    /// Size of the request body before and after `--compress-request`.
+    request_bytes: usize,
+    sent_bytes: usize,
+}
+
+impl FingerprintResponse {
+    /// Combines the responses to the two halves of a split batch, keeping a
+    /// match both halves returned once.
+    fn merge(mut self, other: FingerprintResponse) -> FingerprintResponse {
+        for (matches, others) in [
+            (&mut self.info.exact_matches, other.info.exact_matches),
+            (&mut self.info.partial_matches, other.info.partial_matches),
+        ] {
+            for info in others {
+                if !matches
+                    .iter()
+                    .any(|m| m.id == info.id && m.file.id == info.file.id)
+                {
+                    matches.push(info);
+                }
+            }
+        }
+
+        self.fingerprint_algorithm = self.fingerprint_algorithm.or(other.fingerprint_algorithm);
+        self.transfer = match (self.transfer, other.transfer) {
+            (Some(mut transfer), Some(other)) => {
+                transfer.received_bytes += other.received_bytes;
+                transfer.decoded_bytes += other.decoded_bytes;
+                Some(transfer)
+            }
+            (transfer, other) => transfer.or(other),
+        };
+        self.schema_drift = match (self.schema_drift, other.schema_drift) {
+            (Some(mut drift), Some(other)) => {
+                drift.merge(&other);
+                Some(drift)
+            }
+            (drift, other) => drift.or(other),
+        };
+        self.request_bytes += other.request_bytes;
+        self.sent_bytes += other.sent_bytes;
+
+        self
+    }
+}
+
+/// Compresses a request body for `--compress-request`.
+fn gzip(body: &[u8]) -> Result<Vec<u8>, AuditError> {
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(body)?;
+
+    Ok(encoder.finish()?)
+}
+
+async fn get_fingerprint_respose(
+    client: &HttpClient,
+    opts: &Opts,
+    api: &dyn FingerprintApi,
+    fingerprints: impl IntoIterator<Item = &u32>,
+    packages: &[&curse::Package],
+) -> Result<FingerprintResponse, AuditError> {
+    let fingerprints = fingerprints.into_iter().cloned().collect::<Vec<_>>();
+    debug!(
+        api = api.key(),
+        fingerprints = fingerprints.len(),
+        "sending fingerprint batch"
+    );
+    let body = api.encode_request(opts.api_version, fingerprints)?;
+    let request_bytes = body.len();
+
+    // Recordings are keyed by the request body, so replays and kept state
+    // stay uncompressed
+    let compress = opts.compress_request
+        && opts.replay.is_none()
+        && opts.state_dir.is_none()
+        && !body.is_empty();
+
+    let (request, mut sent_bytes) = if compress {
+        let compressed = gzip(&body)?;
+        let sent_bytes = compressed.len();
+
+        (api.build_request(opts, compressed, true)?, sent_bytes)
+    } else {
+        (api.build_request(opts, body.clone(), false)?, request_bytes)
+    };
+
+    let request_timeout = Duration::from_secs(opts.request_timeout);
+
+    let mut result = timeout(request_timeout, send_request(client, opts, request)).await;
+
+    if compress && matches!(result, Ok(Err(AuditError::BadStatus { code: 415, .. }))) {
+        debug!(
+            "{} - rejected the gzipped request, resending it uncompressed",
+            api
+        );
+
+        sent_bytes = request_bytes;
+        let request = api.build_request(opts, body, false)?;
+        result = timeout(request_timeout, send_request(client, opts, request)).await;
+    }
+
+    let (body, transfer) = match result {
+        Ok(Ok((body, transfer, request_id))) => {
+            if let Some(request_id) = request_id {
+                debug!("{} - request id {}", api, request_id);
+            }
+
+            (body, transfer)
+        }
+        Ok(Err(e)) => {
+            // send_batch logs rate limits along with how it handles them
+            if !matches!(e, AuditError::RateLimited { .. }) {
+                error!("{} - {}", api, e);
+            }
+            return Err(e);
+        }
+        Err(_) => {
+            error!("{} - request timed out after {:?}", api, request_timeout);
+            return Err(AuditError::Timeout(request_timeout));
+        }
+    };
+
+    let parse_start = Instant::now();
+
+    // Parsed once and shared by every check below, rather than each
+    // reparsing the body
+    let response = match parse_json::<Value>(&body) {
+        Ok(response) => response,
+        Err(e) => {
+            error!(
+                "{} - failed to deserialize fingerprint request, got body: {}",
+                api,
+                String::from_utf8_lossy(&body)
+            );
+            return Err(e);
+        }
+    };
+
+    let parse_time = parse_start.elapsed();
+
+    if is_empty_envelope(&response) {
+        warn!(
+            "{} - got an empty {{}} response, treating it as a failed batch",
+            api
+        );
+        return Err(AuditError::EmptyResponse);
+    }
+
+    let response = match api.normalize_response(opts.api_version, response) {
+        Ok(response) => response,
+        Err(e) => {
+            error!("{} - {}", api, e);
+            return Err(e);
+        }
+    };
+
+    if opts.validate_response_schema {
+        let errors = schema::validate_response(api.response_schema(), &response);
+
+        if !errors.is_empty() {
+            let list = errors
+                .iter()
+                .map(|error| format!("\n  {}", error))
+                .collect::<String>();
+            error!("{} - response failed schema validation:{}", api, list);
+
+            return Err(AuditError::Schema { errors });
+        }
+    }
+
+    if opts.strict_json && !api.matches_by_name() {
+        if let Err(e) = strict::check_fingerprint_response(&response) {
+            error!("{} - {}", api, e);
+            return Err(e);
+        }
+    }
+
+    let decode_start = Instant::now();
+
+    match api.decode_response(&response, packages) {
+        Ok(info) => {
+            debug!(
+                "{} - parsed {} byte response in {:?}",
+                api,
+                body.len(),
+                parse_time + decode_start.elapsed()
+            );
+
+            let schema_drift = (opts.detect_schema_drift && !api.matches_by_name())
+                .then(|| SchemaDrift::detect(&response, &info));
+
+            Ok(FingerprintResponse {
+                info,
+                fingerprint_algorithm: fingerprint_algorithm(&response),
+                transfer,
+                schema_drift,
+                request_bytes,
+                sent_bytes,
+            })
+        }
+        Err(e) => {
+            error!(
+                "{} - failed to deserialize fingerprint request, got body: {}",
+                api,
+                String::from_utf8_lossy(&body)
+            );
+            Err(e)
+        }
+    }
+}
+
+/// How a response came over the wire, which replayed and resumed responses
+/// don't have.
+struct Transfer {
+    http_version: String,
+    /// Absent if the response wasn't compressed.
+    content_encoding: Option<String>,
+    /// Body bytes as received, before curl decompressed them.
+    received_bytes: u64,
+    decoded_bytes: usize,
+}
+
+/// Sends `request` and reads the full response body, so that a timeout
+/// wrapping this future also covers a body that stalls mid-transfer. Also
+/// returns how the response came over the wire and its request id header,
+/// if any.
+///
+/// With `--replay` the body is served from a recording instead, after its
+/// recorded latency scaled by `--replay-speed`, and with `--record` every
+/// response is saved for later replay. `--state-dir` saves them the same way,
+/// and serves the ones it has with `--resume`. `--archive-dir` keeps every
+/// body as received.
+async fn send_request(
+    client: &HttpClient,
+    opts: &Opts,
+    request: Request<Vec<u8>>,
+) -> Result<(Vec<u8>, Option<Transfer>, Option<String>), AuditError> {
+    let uri = request.uri().to_string();
+
+    if let Some(dir) = &opts.replay {
+        let (body, latency) = replay::load(dir, &uri, request.body())
+            .await
+            .map_err(AuditError::Replay)?;
+
+        if let Some(latency) = latency.filter(|_| opts.replay_speed > 0.0) {
+            task::sleep(latency.mul_f64(opts.replay_speed)).await;
+        }
+
+        debug!(url = %uri, response_bytes = body.len(), "replayed response");
+
+        return Ok((body, None, None));
+    }
+
+    if let Some(dir) = opts.state_dir.as_ref().filter(|_| opts.resume) {
+        // A request without a recording hadn't completed yet
+        if let Ok((body, _)) = replay::load(dir, &uri, request.body()).await {
+            debug!(url = %uri, response_bytes = body.len(), "resumed response");
+
+            return Ok((body, None, None));
+        }
+    }
+
+    let recording = opts
+        .record
+        .as_ref()
+        .or(opts.state_dir.as_ref())
+        .map(|dir| (dir, request.body().clone()));
+
+    let (parts, body) = request.into_parts();
+    let method = parts.method.clone();
+    let request_bytes = body.len();
+    let body = if body.is_empty() {
+        isahc::Body::empty()
+    } else {
+        isahc::Body::from(body)
+    };
+
+    let start = Instant::now();
+    let mut response = client.send_async(Request::from_parts(parts, body)).await?;
+
+    let status = response.status();
+    let http_version = format!("{:?}", response.version());
+    let content_encoding = response
+        .headers()
+        .get("content-encoding")
+        .and_then(|v| v.to_str().ok())
+        .filter(|encoding| !encoding.eq_ignore_ascii_case("identity"))
+        .map(str::to_lowercase);
+    let request_id = REQUEST_ID_HEADERS
+        .iter()
+        .find_map(|&name| response.headers().get(name))
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_owned);
+
+    debug!(
+        %method,
+        url = %uri,
+        status = status.as_u16(),
+        latency_ms = start.elapsed().as_millis() as u64,
+        request_bytes,
+        request_id = request_id.as_deref(),
+        "received response"
+    );
+
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = response
+            .headers()
+            .get("retry-after")
+            .and_then(|v| v.to_str().ok())
+            .and_then(rate_limit::parse_retry_after);
+
+        return Err(AuditError::RateLimited {
+            retry_after,
+            request_id,
+        });
+    }
+
+    if !status.is_success() {
+        return Err(AuditError::BadStatus {
+            code: status.as_u16(),
+            request_id,
+        });
+    }
+
+    let limit = opts.max_response_bytes;
+
+    if matches!(response.body().len(), Some(len) if len > limit) {
+        return Err(AuditError::ResponseTooLarge { limit });
+    }
+
+    // Reading one byte past the limit tells a body that's exactly at it
+    // apart from one that's over
+    let mut body = vec![];
+    response
+        .body_mut()
+        .take(limit + 1)
+        .read_to_end(&mut body)
+        .await?;
+
+    if body.len() as u64 > limit {
+        return Err(AuditError::ResponseTooLarge { limit });
+    }
+
+    if let Some(dir) = &opts.archive_dir {
+        if let Err(e) = archive::save(dir, &uri, &body) {
+            warn!("failed to archive the response of {} - {}", uri, e);
+        }
+    }
+
+    if let Some((dir, request_body)) = recording {
+        replay::save(dir, &uri, &request_body, &body, start.elapsed())
+            .await
+            .map_err(AuditError::Replay)?;
+    }
+
+    let received_bytes = response
+        .metrics()
+        .map_or(body.len() as u64, |m| m.download_progress().0);
+
+    let transfer = Transfer {
+        http_version,
+        content_encoding,
+        received_bytes,
+        decoded_bytes: body.len(),
+    };
+
+    Ok((body, Some(transfer), request_id))
+}
+
+/// Fingerprint algorithm or version the response says it used, if any.
+///
+/// Neither API documents such a field, so this looks for the likely names
+/// and anything else is treated as unknown.
+fn fingerprint_algorithm(response: &Value) -> Option<String> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct FingerprintMeta {
+        #[serde(default)]
+        fingerprint_algorithm: Option<Value>,
+        #[serde(default)]
+        fingerprint_version: Option<Value>,
+    }
+
+    let meta = FingerprintMeta::deserialize(response).ok()?;
+
+    // A response with both names is taken at its algorithm
+    match meta.fingerprint_algorithm.or(meta.fingerprint_version)? {
+        Value::String(algorithm) => Some(algorithm),
+        Value::Null => None,
+        other => Some(other.to_string()),
+    }
+}
+
+/// Whether the response is a json object without any fields.
+fn is_empty_envelope(response: &Value) -> bool {
+    response.as_object().map_or(false, Map::is_empty)
+}
+
+#[cfg(not(feature = "simd"))]
+use parse_serde_json as parse_json;
+#[cfg(feature = "simd")]
+use parse_simd_json as parse_json;
+
+/// Reads an already parsed response into `T`.
+fn decode_json<T: DeserializeOwned>(response: &Value) -> Result<T, AuditError> {
+    T::deserialize(response).map_err(|e| AuditError::Deserialize(e.into()))
+}
+
+fn parse_serde_json<T: DeserializeOwned>(body: &[u8]) -> Result<T, AuditError> {
+    serde_json::from_slice(body).map_err(|e| AuditError::Deserialize(e.into()))
+}
+
+/// `simd-json` parses in place, so it works on a copy to keep the original
+/// body intact for error reporting.
+#[cfg(feature = "simd")]
+fn parse_simd_json<T: DeserializeOwned>(body: &[u8]) -> Result<T, AuditError> {
+    let mut body = body.to_vec();
+
+    simd_json::serde::from_slice(&mut body).map_err(|e| AuditError::Deserialize(e.into()))
+}
+
+/// Hooks for `benches/parse.rs`, which can only reach public items.
+#[doc(hidden)]
+pub mod bench {
+    use super::*;
+
+    /// The bundled fingerprint responses, named and normalized for parsing.
+    pub fn samples() -> Vec<(&'static str, ApiChoice, Vec<u8>)> {
+        [
+            ("curse", ApiChoice::Curse, ApiVersion::V2),
+            ("curse-v1", ApiChoice::Curse, ApiVersion::V1),
+            ("wowup", ApiChoice::WowUp, ApiVersion::V2),
+        ]
+        .iter()
+        .map(|&(name, api, version)| {
+            let response = serde_json::from_str(api.api().sample_response(version)).unwrap();
+            let response = api.api().normalize_response(version, response).unwrap();
+            let body = serde_json::to_vec(&response).unwrap();
+
+            (name, api, body)
+        })
+        .collect()
+    }
+
+    /// Parses a response of `api` with `serde_json`.
+    pub fn serde_json(api: ApiChoice, body: &[u8]) -> Result<(), AuditError> {
+        match api {
+            ApiChoice::Curse => parse_serde_json::<curse::FingerprintInfo>(body).map(|_| ()),
+            ApiChoice::WowUp => parse_serde_json::<WowUpFingerprintResponse>(body).map(|_| ()),
+            // Addon lists are only ever parsed into a `Value`
+            ApiChoice::Tukui | ApiChoice::WowInterface | ApiChoice::TownlongYak => {
+                parse_serde_json::<Value>(body).map(|_| ())
+            }
+        }
+    }
+
+    /// Parses a response of `api` with `simd-json`.
+    #[cfg(feature = "simd")]
+    pub fn simd_json(api: ApiChoice, body: &[u8]) -> Result<(), AuditError> {
+        match api {
+            ApiChoice::Curse => parse_simd_json::<curse::FingerprintInfo>(body).map(|_| ()),
+            ApiChoice::WowUp => parse_simd_json::<WowUpFingerprintResponse>(body).map(|_| ()),
+            ApiChoice::Tukui | ApiChoice::WowInterface | ApiChoice::TownlongYak => {
+                parse_simd_json::<Value>(body).map(|_| ())
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApiChoice {
+    Curse,
+    WowUp,
+    Tukui,
+    WowInterface,
+    TownlongYak,
+}
+
+impl ApiChoice {
+    const ALL: [ApiChoice; 5] = [
+        ApiChoice::Curse,
+        ApiChoice::WowUp,
+        ApiChoice::Tukui,
+        ApiChoice::WowInterface,
+        ApiChoice::TownlongYak,
+    ];
+
+    /// Parses the `--apis` values, rejecting an empty selection or unknown
+    /// names.
+    pub fn parse_list(values: &[String]) -> Result<Vec<ApiChoice>, String> {
+        let valid = ApiChoice::ALL
+            .iter()
+            .map(ApiChoice::key)
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let mut apis = vec![];
+
+        for value in values.iter().map(|v| v.trim()).filter(|v| !v.is_empty()) {
+            let api_choice = ApiChoice::ALL
+                .iter()
+                .find(|api| api.key() == value)
+                .copied()
+                .ok_or_else(|| format!("unknown api `{}`, valid values are: {}", value, valid))?;
+
+            if !apis.contains(&api_choice) {
+                apis.push(api_choice);
+            }
+        }
+
+        if apis.is_empty() {
+            return Err(format!("no api selected, valid values are: {}", valid));
+        }
+
+        Ok(apis)
+    }
+
+    /// How the API's requests are built and its responses read.
+    fn api(&self) -> &'static dyn FingerprintApi {
+        match self {
+            ApiChoice::Curse => &fingerprint_api::Curse,
+            ApiChoice::WowUp => &fingerprint_api::WowUp,
+            ApiChoice::Tukui => &fingerprint_api::TUKUI,
+            ApiChoice::WowInterface => &fingerprint_api::WOWINTERFACE,
+            ApiChoice::TownlongYak => &fingerprint_api::TOWNLONG_YAK,
+        }
+    }
+
+    /// Short identifier used in machine-readable output.
+    pub fn key(&self) -> &'static str {
+        self.api().key()
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.api().name()
+    }
+}
+
+impl Display for ApiChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.api().fmt(f)
+    }
+}
+
+#[derive(Serialize)]
+struct WowUpFingerprintRequest {
+    fingerprints: Vec<u32>,
+}
+
+/// The WowUp hub's fingerprint response, which mirrors the Curse response with
+/// a few extra fields.
+///
+/// `exact_matches` is required so a renamed field fails deserialization
+/// instead of silently producing zero matches.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WowUpFingerprintResponse {
+    exact_matches: Vec<curse::AddonFingerprintInfo>,
+    #[serde(default)]
+    exact_fingerprints: Vec<u32>,
+    #[serde(default)]
+    partial_matches: Vec<curse::AddonFingerprintInfo>,
+}
+
+impl WowUpFingerprintResponse {
+    /// Maps the response into the common match type shared with Curse.
+    fn into_fingerprint_info(self) -> Result<curse::FingerprintInfo, AuditError> {
+        if self.exact_matches.is_empty() && !self.exact_fingerprints.is_empty() {
+            return Err(AuditError::InvalidResponse(format!(
+                "response lists {} exact fingerprints but no exact matches",
+                self.exact_fingerprints.len()
+            )));
+        }
+
+        Ok(curse::FingerprintInfo {
+            exact_matches: self.exact_matches,
+            partial_matches: self.partial_matches,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn wowup_sample_maps_to_fingerprint_info() {
+        let body = include_bytes!("../samples/wowup_fingerprint_response.json");
+        let response: WowUpFingerprintResponse = serde_json::from_slice(body).unwrap();
+        let info = response.into_fingerprint_info().unwrap();
+
+        assert_eq!(info.exact_matches.len(), 1);
+        assert!(info.partial_matches.is_empty());
+
+        let matched = &info.exact_matches[0];
+        assert_eq!(matched.id, 61284);
+        assert_eq!(matched.file.id, 3120586);
+        assert_eq!(matched.file.display_name, "Details-v9.0.2.8154");
+        assert_eq!(
+            matched
+                .file
+                .modules
+                .iter()
+                .map(|m| m.fingerprint)
+                .collect::<Vec<_>>(),
+            vec![3095035474, 1652237858]
+        );
+    }
+
+    #[test]
+    fn wowup_exact_fingerprints_without_matches_is_invalid() {
+        let body =
+            br#"{"exactMatches": [], "exactFingerprints": [3095035474], "partialMatches": []}"#;
+        let response: WowUpFingerprintResponse = serde_json::from_slice(body).unwrap();
+
+        match response.into_fingerprint_info() {
+            Err(AuditError::InvalidResponse(message)) => {
+                assert!(message.contains("1 exact fingerprints"), "{}", message)
+            }
+            other => panic!("expected an invalid response, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn wowup_response_without_exact_matches_field_fails() {
+        let body = br#"{"exactFingerprints": [], "partialMatches": []}"#;
+
+        assert!(serde_json::from_slice::<WowUpFingerprintResponse>(body).is_err());
+    }
+
+    fn parse_apis(values: &[&str]) -> Result<Vec<ApiChoice>, String> {
+        ApiChoice::parse_list(&values.iter().map(|v| v.to_string()).collect::<Vec<_>>())
+    }
+
+    fn assert_lists_valid(result: Result<Vec<ApiChoice>, String>) -> String {
+        match result {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => {
+                assert!(
+                    e.contains("valid values are: curse, wowup, tukui, wowinterface, townlong-yak"),
+                    "{}",
+                    e
+                );
+                e
+            }
+        }
+    }
+
+    #[test]
+    fn empty_apis_are_rejected() {
+        let e = assert_lists_valid(parse_apis(&[]));
+        assert!(e.starts_with("no api selected"), "{}", e);
+
+        let e = assert_lists_valid(parse_apis(&[""]));
+        assert!(e.starts_with("no api selected"), "{}", e);
+    }
+
+    #[test]
+    fn unknown_api_is_rejected() {
+        let e = assert_lists_valid(parse_apis(&["bogus"]));
+        assert!(e.starts_with("unknown api `bogus`"), "{}", e);
+    }
+
+    #[test]
+    fn known_apis_parse_in_order() {
+        let apis = parse_apis(&["curse", "wowup"]).unwrap();
+        assert_eq!(apis, vec![ApiChoice::Curse, ApiChoice::WowUp]);
+
+        let apis = parse_apis(&["wowup", " curse ", "wowup"]).unwrap();
+        assert_eq!(apis, vec![ApiChoice::WowUp, ApiChoice::Curse]);
+    }
+
+    #[test]
+    fn addon_list_apis_parse() {
+        let apis = parse_apis(&["townlong-yak", "tukui", "wowinterface"]).unwrap();
+        assert_eq!(
+            apis,
+            vec![
+                ApiChoice::TownlongYak,
+                ApiChoice::Tukui,
+                ApiChoice::WowInterface
+            ]
+        );
+    }
+
+    #[test]
+    fn fingerprint_algorithm_prefers_algorithm_over_version() {
+        let response = json!({"fingerprintVersion": 1, "fingerprintAlgorithm": "murmur2"});
+        assert_eq!(fingerprint_algorithm(&response), Some("murmur2".to_owned()));
+
+        let response = json!({"fingerprintVersion": 2, "exactMatches": []});
+        assert_eq!(fingerprint_algorithm(&response), Some("2".to_owned()));
+
+        let response = json!({"fingerprintAlgorithm": null, "fingerprintVersion": "v2"});
+        assert_eq!(fingerprint_algorithm(&response), Some("v2".to_owned()));
+
+        assert_eq!(fingerprint_algorithm(&json!({"exactMatches": []})), None);
+    }
+
+    /// The recorded Details! package, under `id`.
+    fn package(id: i32) -> curse::Package {
+        let recording = include_str!("../tests/fixtures/replay/7f993255d5b8df8e.json");
+        let recording = serde_json::from_str::<Value>(recording).unwrap();
+        let mut package =
+            serde_json::from_str::<Value>(recording["response_body"].as_str().unwrap()).unwrap();
+        package["id"] = json!(id);
+
+        serde_json::from_value(package).unwrap()
+    }
+
+    #[test]
+    fn range_of_already_sent_fingerprints_gets_no_batch() {
+        // Both packages have the same files, so the second range has nothing
+        // left to send
+        let packages = [package(1), package(2)];
+        let batches = build_batches(&packages, &[0..1, 1..2]);
+
+        assert_eq!(batches.len(), 1);
+        assert_eq!(
+            batches[0].fingerprints,
+            BTreeSet::from([1_652_237_858, 3_095_035_474])
+        );
+        assert_eq!(
+            batches[0]
+                .answers_for
+                .iter()
+                .map(|p| p.id)
+                .collect::<Vec<_>>(),
+            [1, 2]
+        );
+    }
+
+    #[test]
+    fn addon_list_matches_packages_by_name() {
+        let details = package(61284);
+        let mut other = package(1);
+        other.name = "Some Other Addon".to_owned();
+
+        let list = json!([{"name": "details damage meter"}, {"name": "AddOnSkins"}]);
+        let info = ApiChoice::Tukui
+            .api()
+            .decode_response(&list, &[&details, &other])
+            .unwrap();
+
+        assert_eq!(
+            info.exact_matches.iter().map(|m| m.id).collect::<Vec<_>>(),
+            [61284]
+        );
+        assert_eq!(info.exact_matches[0].file.id, details.latest_files[0].id);
+    }
+
+    #[test]
+    fn addon_list_samples_pass_self_test() {
+        for api in &[
+            ApiChoice::Tukui,
+            ApiChoice::WowInterface,
+            ApiChoice::TownlongYak,
+        ] {
+            assert_eq!(api.api().self_test(ApiVersion::V2), Ok(()), "{}", api);
+        }
+    }
+
+    #[test]
+    fn empty_search_exits_with_empty_exit_code() {
+        let opts = Opts::from_iter(&["ajour-api-test"]);
+        assert_eq!(empty_exit_code(&opts, 1, &[]), Some(0));
+
+        let opts = Opts::from_iter(&["ajour-api-test", "--empty-exit-code", "7"]);
+        assert_eq!(empty_exit_code(&opts, 1, &[]), Some(7));
+    }
+
+    #[test]
+    fn empty_game_of_several_only_warns() {
+        let opts = Opts::from_iter(&["ajour-api-test", "--game-id", "1,2"]);
+        assert_eq!(empty_exit_code(&opts, 1, &[]), None);
+    }
+}
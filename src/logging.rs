@@ -0,0 +1,34 @@
+//! Sends the run's log events to stderr, as plain lines or with
+//! `--log-format json` as a json object per line.
+//!
+//! `-v` adds debug events, such as every request sent and the response it
+//! got, and `-vv` adds trace events from the HTTP client too. `RUST_LOG`
+//! overrides both.
+
+use crate::cli::{LogFormat, Opts};
+
+use tracing_subscriber::EnvFilter;
+
+use std::io::{self, IsTerminal};
+
+pub fn init(opts: &Opts) {
+    let directives = match opts.verbose {
+        0 => "ajour_api_test=info",
+        1 => "ajour_api_test=debug",
+        _ => "trace",
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(directives));
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .with_target(false);
+
+    match opts.log_format {
+        LogFormat::Text => builder
+            .without_time()
+            .with_ansi(io::stderr().is_terminal())
+            .init(),
+        LogFormat::Json => builder.json().init(),
+    }
+}
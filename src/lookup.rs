@@ -0,0 +1,404 @@
+//! Targeted lookups for the `fingerprint` and `resolve` subcommands, which
+//! show what each API matched a few fingerprints to instead of auditing a
+//! whole search.
+
+use crate::cli::{Format, Opts};
+use crate::rate_limit::RateLimits;
+use crate::retry::RetryBudget;
+use crate::spill::Spill;
+use crate::{
+    batch_ranges, build_batches, fetch_package_or_exit, package_batch_ranges, send_batches,
+    write_json, ApiChoice, ApiResults, Batch,
+};
+
+use ajour_core::repository::curse;
+use isahc::HttpClient;
+use serde::Serialize;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+
+#[derive(Serialize)]
+struct FingerprintLookup {
+    fingerprint: u32,
+    /// Files each API matched the fingerprint to, keyed by API, empty for
+    /// APIs that didn't match it.
+    matches: BTreeMap<&'static str, Vec<MatchedFile>>,
+    /// Keys of the APIs whose batch with the fingerprint failed.
+    failed_on: Vec<&'static str>,
+    /// Whether the APIs that answered matched it to different files.
+    differs: bool,
+}
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+struct MatchedFile {
+    package_id: i32,
+    file_id: i64,
+    display_name: String,
+    /// Whether the file only matched partially.
+    partial: bool,
+}
+
+#[derive(Serialize)]
+struct Resolution<'a> {
+    id: i32,
+    name: &'a str,
+    /// The package's latest files as the search API lists them.
+    latest: Vec<ResolvedFile>,
+    /// Files each API exactly matched the latest files' fingerprints to,
+    /// keyed by API, absent for APIs whose batch failed.
+    apis: BTreeMap<&'static str, Option<Vec<ResolvedFile>>>,
+}
+
+#[derive(Serialize)]
+struct ResolvedFile {
+    /// Id of the package the file belongs to, which differs from the
+    /// resolved package when an API attributed its fingerprints elsewhere.
+    package_id: i32,
+    file_id: i64,
+    display_name: String,
+    file_name: String,
+    file_date: String,
+    game_version_flavor: String,
+    download_url: String,
+}
+
+impl ResolvedFile {
+    fn new(package_id: i32, file: &curse::File) -> ResolvedFile {
+        ResolvedFile {
+            package_id,
+            file_id: file.id,
+            display_name: file.display_name.clone(),
+            file_name: file.file_name.clone(),
+            file_date: file.file_date.clone(),
+            game_version_flavor: file.game_version_flavor.clone(),
+            download_url: file.download_url.clone(),
+        }
+    }
+}
+
+/// Sends `fingerprints` to every API and writes what each matched them to,
+/// flagging the fingerprints the APIs matched differently.
+#[allow(clippy::too_many_arguments)]
+pub async fn fingerprint(
+    client: &HttpClient,
+    opts: &Opts,
+    apis: &[ApiChoice],
+    retry_budget: &RetryBudget,
+    rate_limits: &RateLimits,
+    spill: &Spill,
+    fingerprints: &[u32],
+    out: &mut impl Write,
+) -> Result<(), anyhow::Error> {
+    let mut unique = vec![];
+
+    for &fingerprint in fingerprints {
+        if !unique.contains(&fingerprint) {
+            unique.push(fingerprint);
+        }
+    }
+
+    let batches = batch_ranges(unique.len(), opts.batch_size)
+        .into_iter()
+        .map(|range| Batch {
+            packages: &[],
+            fingerprints: unique[range].iter().copied().collect(),
+            answers_for: vec![],
+        })
+        .collect::<Vec<_>>();
+
+    let results = send(
+        client,
+        opts,
+        apis,
+        retry_budget,
+        rate_limits,
+        spill,
+        &batches,
+    )
+    .await?;
+
+    let lookups = unique
+        .iter()
+        .map(|&fingerprint| {
+            let mut matches = BTreeMap::new();
+            let mut failed_on = vec![];
+
+            for result in &results {
+                let response = batches
+                    .iter()
+                    .zip(&result.responses)
+                    .find(|(batch, _)| batch.fingerprints.contains(&fingerprint))
+                    .map(|(_, response)| &response.result);
+
+                let info = match response {
+                    Some(Ok(info)) => info,
+                    _ => {
+                        failed_on.push(result.api_choice.key());
+                        continue;
+                    }
+                };
+
+                let exact = info.exact_matches.iter().map(|m| (m, false));
+                let partial = info.partial_matches.iter().map(|m| (m, true));
+
+                let mut files = exact
+                    .chain(partial)
+                    .filter(|(m, _)| {
+                        m.file
+                            .modules
+                            .iter()
+                            .any(|module| module.fingerprint == fingerprint)
+                    })
+                    .map(|(m, partial)| MatchedFile {
+                        package_id: m.id,
+                        file_id: m.file.id,
+                        display_name: m.file.display_name.clone(),
+                        partial,
+                    })
+                    .collect::<Vec<_>>();
+                files.sort();
+                files.dedup();
+
+                matches.insert(result.api_choice.key(), files);
+            }
+
+            let differs = matches.values().collect::<BTreeSet<_>>().len() > 1;
+
+            FingerprintLookup {
+                fingerprint,
+                matches,
+                failed_on,
+                differs,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    match opts.format {
+        Format::Json => write_json(out, &lookups, opts.compact)?,
+        Format::Text => {
+            for lookup in &lookups {
+                writeln!(
+                    out,
+                    "{}{}",
+                    lookup.fingerprint,
+                    if lookup.differs {
+                        " - the APIs disagree"
+                    } else {
+                        ""
+                    }
+                )?;
+
+                for api in apis {
+                    let line = match lookup.matches.get(api.key()) {
+                        None => "failed".to_owned(),
+                        Some(files) if files.is_empty() => "no match".to_owned(),
+                        Some(files) => files
+                            .iter()
+                            .map(|f| {
+                                format!(
+                                    "{}package {} file {} ({})",
+                                    if f.partial { "partially " } else { "" },
+                                    f.package_id,
+                                    f.file_id,
+                                    f.display_name
+                                )
+                            })
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    };
+
+                    writeln!(out, "  {}: {}", api.name(), line)?;
+                }
+            }
+
+            let differing = lookups.iter().filter(|l| l.differs).count();
+            writeln!(
+                out,
+                "\n{} of {} fingerprints matched differently across the APIs",
+                differing,
+                lookups.len()
+            )?;
+        }
+        Format::Csv => unreachable!("--format csv is rejected with the fingerprint subcommand"),
+    }
+
+    Ok(())
+}
+
+/// Fetches the package with CurseForge id `id`, sends the fingerprints of
+/// its latest files to every API and writes the files each resolved them
+/// to next to the package's own latest files.
+#[allow(clippy::too_many_arguments)]
+pub async fn resolve(
+    client: &HttpClient,
+    opts: &Opts,
+    apis: &[ApiChoice],
+    retry_budget: &RetryBudget,
+    rate_limits: &RateLimits,
+    spill: &Spill,
+    id: i32,
+    out: &mut impl Write,
+) -> Result<(), anyhow::Error> {
+    let package = fetch_package_or_exit(client, opts, id).await;
+    let packages = std::slice::from_ref(&package);
+    let batches = build_batches(packages, &package_batch_ranges(opts, packages));
+
+    let results = send(
+        client,
+        opts,
+        apis,
+        retry_budget,
+        rate_limits,
+        spill,
+        &batches,
+    )
+    .await?;
+
+    let resolution = Resolution {
+        id: package.id,
+        name: &package.name,
+        latest: package
+            .latest_files
+            .iter()
+            .map(|file| ResolvedFile::new(package.id, file))
+            .collect(),
+        apis: results
+            .iter()
+            .map(|result| {
+                let files = if result.failed_batches() > 0 {
+                    None
+                } else {
+                    Some(
+                        result
+                            .exact_matches()
+                            .into_iter()
+                            .map(|m| ResolvedFile::new(m.id, &m.file))
+                            .collect(),
+                    )
+                };
+
+                (result.api_choice.key(), files)
+            })
+            .collect(),
+    };
+
+    match opts.format {
+        Format::Json => write_json(out, &resolution, opts.compact)?,
+        Format::Text => write_resolution(out, &resolution, apis)?,
+        Format::Csv => unreachable!("--format csv is rejected with the resolve subcommand"),
+    }
+
+    Ok(())
+}
+
+/// Writes a table per game version flavor with a column for the package's
+/// own latest file and one per API.
+fn write_resolution(
+    out: &mut impl Write,
+    resolution: &Resolution<'_>,
+    apis: &[ApiChoice],
+) -> std::io::Result<()> {
+    writeln!(out, "{} ({})", resolution.name, resolution.id)?;
+
+    let flavors = resolution
+        .latest
+        .iter()
+        .chain(resolution.apis.values().flatten().flatten())
+        .map(|f| f.game_version_flavor.as_str())
+        .collect::<BTreeSet<_>>();
+
+    let mut columns = vec![("latest", Some(resolution.latest.as_slice()))];
+    columns.extend(apis.iter().map(|api| {
+        let files = resolution.apis.get(api.key()).and_then(Option::as_deref);
+        (api.name(), files)
+    }));
+
+    for flavor in flavors {
+        let cells = columns
+            .iter()
+            .map(|(name, files)| {
+                let file =
+                    files.and_then(|files| files.iter().find(|f| f.game_version_flavor == flavor));
+
+                let rows = match (files, file) {
+                    (None, _) => vec!["failed".to_owned(); 4],
+                    (Some(_), None) => vec!["-".to_owned(); 4],
+                    (Some(_), Some(file)) => vec![
+                        file.package_id.to_string(),
+                        file.file_id.to_string(),
+                        file.display_name.clone(),
+                        file.file_date.clone(),
+                    ],
+                };
+
+                (*name, rows)
+            })
+            .collect::<Vec<_>>();
+
+        let width = cells
+            .iter()
+            .flat_map(|(name, rows)| rows.iter().map(String::len).chain(Some(name.len())))
+            .max()
+            .unwrap_or(0);
+
+        writeln!(out, "\n{}", flavor)?;
+        write!(out, "  {:<8}", "")?;
+
+        for (name, _) in &cells {
+            write!(out, "  {:<width$}", name, width = width)?;
+        }
+
+        writeln!(out)?;
+
+        for (index, label) in ["package", "file", "name", "date"].iter().enumerate() {
+            write!(out, "  {:<8}", label)?;
+
+            for (_, rows) in &cells {
+                write!(out, "  {:<width$}", rows[index], width = width)?;
+            }
+
+            writeln!(out)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends `batches` to every API, with their spilled matches restored.
+async fn send(
+    client: &HttpClient,
+    opts: &Opts,
+    apis: &[ApiChoice],
+    retry_budget: &RetryBudget,
+    rate_limits: &RateLimits,
+    spill: &Spill,
+    batches: &[Batch<'_>],
+) -> Result<Vec<ApiResults>, anyhow::Error> {
+    let (responses, _) = send_batches(
+        client,
+        opts,
+        retry_budget,
+        rate_limits,
+        spill,
+        apis,
+        batches,
+    )
+    .await;
+
+    let mut results = apis
+        .iter()
+        .zip(responses)
+        .map(|(&api_choice, responses)| ApiResults {
+            api_choice,
+            responses,
+            failed_packages: BTreeSet::new(),
+        })
+        .collect::<Vec<_>>();
+
+    if spill.spilled() {
+        spill.restore(&mut results)?;
+    }
+
+    Ok(results)
+}
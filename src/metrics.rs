@@ -0,0 +1,309 @@
+//! OpenMetrics text output for the Prometheus node_exporter textfile
+//! collector, and the metrics a `--watch` monitor serves or pushes.
+
+use crate::report::{ApiReport, AuditReport};
+use crate::ApiResults;
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io;
+use std::path::Path;
+
+const PREFIX: &str = "ajour_api_test";
+
+/// Upper bounds of the buckets of the `--watch` request duration histogram,
+/// in seconds.
+const DURATION_BUCKETS: [f64; 9] = [0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+/// Renders the run's key numbers. Every metric is a gauge since each file
+/// describes a single run.
+pub fn render(report: &AuditReport) -> String {
+    let mut out = String::new();
+
+    write_gauges(&mut out, &[(None, report)]);
+    out.push_str("# EOF\n");
+
+    out
+}
+
+/// Writes a gauge of the key numbers for every report in `reports`,
+/// labelling each sample with its game if it has one.
+fn write_gauges(out: &mut String, reports: &[(Option<u32>, &AuditReport)]) {
+    let mut gauge = |name: &str, help: &str, value: &dyn Fn(&AuditReport) -> f64| {
+        let samples = reports
+            .iter()
+            .map(|&(game_id, report)| (labels(game_id, None, &[]), value(report)))
+            .collect::<Vec<_>>();
+
+        write_family(out, name, "gauge", help, &samples);
+    };
+
+    gauge(
+        "packages_audited",
+        "Packages whose fingerprints were audited.",
+        &|report| report.packages_audited as f64,
+    );
+    gauge(
+        "unique_packages",
+        "Packages matched by at least one API.",
+        &|report| report.unique_packages as f64,
+    );
+    gauge(
+        "discrepancies",
+        "Packages matched by some APIs but not all of them.",
+        &|report| report.discrepancies as f64,
+    );
+    gauge(
+        "discrepancy_ratio",
+        "Share of unique packages that are discrepancies.",
+        &|report| report.discrepancy_pct / 100.0,
+    );
+    gauge(
+        "version_disagreements",
+        "Packages whose matched files differ in version between APIs.",
+        &|report| report.version_disagreements.len() as f64,
+    );
+    gauge(
+        "metadata_mismatches",
+        "Fingerprints exactly matched to files whose metadata differs between APIs.",
+        &|report| report.metadata_mismatches.len() as f64,
+    );
+    gauge(
+        "retries_used",
+        "Retries consumed across every batch.",
+        &|report| report.retries_used as f64,
+    );
+
+    let mut per_api =
+        |name: &str, help: &str, value: &dyn Fn(&AuditReport, &ApiReport) -> Option<f64>| {
+            let samples = reports
+                .iter()
+                .flat_map(|&(game_id, report)| {
+                    report.apis.iter().filter_map(move |api| {
+                        value(report, api).map(|v| (labels(game_id, Some(api.api), &[]), v))
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            write_family(out, name, "gauge", help, &samples);
+        };
+
+    per_api(
+        "api_matched_packages",
+        "Packages exactly matched by the API.",
+        &|_, api| Some(api.matched_packages as f64),
+    );
+    per_api(
+        "api_match_ratio",
+        "Share of the audited packages the API matched, leaving out known issues.",
+        &|report, api| Some(report.match_rate(api) / 100.0),
+    );
+    per_api(
+        "api_fingerprint_matches",
+        "Exact fingerprint matches returned by the API.",
+        &|_, api| Some(api.fingerprint_matches as f64),
+    );
+    per_api(
+        "api_failed_batches",
+        "Batches that failed after every retry.",
+        &|_, api| Some(api.failed_batches as f64),
+    );
+    per_api(
+        "api_success_ratio",
+        "Share of batches that succeeded.",
+        &|_, api| Some(api.success_rate / 100.0),
+    );
+    per_api(
+        "api_latency_p50_seconds",
+        "Median latency of successful batches.",
+        &|_, api| api.latency_p50_ms.map(|ms| ms as f64 / 1000.0),
+    );
+    per_api(
+        "api_latency_p95_seconds",
+        "95th percentile latency of successful batches.",
+        &|_, api| api.latency_p95_ms.map(|ms| ms as f64 / 1000.0),
+    );
+    per_api(
+        "api_latency_p99_seconds",
+        "99th percentile latency of successful batches.",
+        &|_, api| api.latency_p99_ms.map(|ms| ms as f64 / 1000.0),
+    );
+}
+
+/// Writes the help and type of a metric followed by its samples, each
+/// given with its labels.
+fn write_family(out: &mut String, name: &str, kind: &str, help: &str, samples: &[(String, f64)]) {
+    let _ = writeln!(out, "# HELP {}_{} {}", PREFIX, name, help);
+    let _ = writeln!(out, "# TYPE {}_{} {}", PREFIX, name, kind);
+
+    for (labels, value) in samples {
+        let _ = writeln!(out, "{}_{}{} {}", PREFIX, name, labels, value);
+    }
+}
+
+/// The label set of a sample, empty without a game, API or other label.
+fn labels(game_id: Option<u32>, api: Option<&str>, other: &[(&str, String)]) -> String {
+    let labels = game_id
+        .map(|game_id| format!("game=\"{}\"", game_id))
+        .into_iter()
+        .chain(api.map(|api| format!("api=\"{}\"", api)))
+        .chain(
+            other
+                .iter()
+                .map(|(name, value)| format!("{}=\"{}\"", name, value)),
+        )
+        .collect::<Vec<_>>();
+
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels.join(","))
+    }
+}
+
+/// Metrics of a `--watch` monitor, in the Prometheus text format served on
+/// `--metrics-listen` and pushed to `--pushgateway-url`.
+///
+/// The gauges describe each game's latest run, while failed batches and
+/// the duration of every request are summed over all runs, so alerts can
+/// use `rate` and `histogram_quantile` on them.
+#[derive(Default)]
+pub struct WatchMetrics {
+    latest: BTreeMap<u32, AuditReport>,
+    runs: BTreeMap<u32, u64>,
+    /// Failed batches by game, API and error category.
+    errors: BTreeMap<(u32, &'static str, &'static str), u64>,
+    durations: BTreeMap<(u32, &'static str), Histogram>,
+}
+
+#[derive(Default)]
+struct Histogram {
+    /// Requests at or under each of `DURATION_BUCKETS`.
+    buckets: [u64; DURATION_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl WatchMetrics {
+    /// Adds a successful run of `game_id`, replacing its previous report.
+    pub fn record(&mut self, game_id: u32, report: AuditReport, results: &[ApiResults]) {
+        *self.runs.entry(game_id).or_default() += 1;
+
+        for api in &report.apis {
+            for (&category, &count) in &api.error_categories {
+                *self.errors.entry((game_id, api.api, category)).or_default() += count as u64;
+            }
+        }
+
+        for result in results {
+            let histogram = self
+                .durations
+                .entry((game_id, result.api_choice.key()))
+                .or_default();
+
+            for latency in result.responses.iter().flat_map(|r| &r.attempt_latencies) {
+                let seconds = latency.as_secs_f64();
+
+                for (bucket, &le) in histogram.buckets.iter_mut().zip(&DURATION_BUCKETS) {
+                    if seconds <= le {
+                        *bucket += 1;
+                    }
+                }
+
+                histogram.count += 1;
+                histogram.sum += seconds;
+            }
+        }
+
+        self.latest.insert(game_id, report);
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        let reports = self
+            .latest
+            .iter()
+            .map(|(&game_id, report)| (Some(game_id), report))
+            .collect::<Vec<_>>();
+        write_gauges(&mut out, &reports);
+
+        let runs = self
+            .runs
+            .iter()
+            .map(|(&game_id, &runs)| (labels(Some(game_id), None, &[]), runs as f64))
+            .collect::<Vec<_>>();
+        write_family(
+            &mut out,
+            "runs_total",
+            "counter",
+            "Runs that audited the game.",
+            &runs,
+        );
+
+        let errors = self
+            .errors
+            .iter()
+            .map(|(&(game_id, api, category), &count)| {
+                let labels = labels(
+                    Some(game_id),
+                    Some(api),
+                    &[("category", category.to_owned())],
+                );
+
+                (labels, count as f64)
+            })
+            .collect::<Vec<_>>();
+        write_family(
+            &mut out,
+            "api_errors_total",
+            "counter",
+            "Batches that failed after every retry, by error category.",
+            &errors,
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP {}_api_request_duration_seconds Latency of every fingerprint request, retries included.",
+            PREFIX
+        );
+        let _ = writeln!(
+            out,
+            "# TYPE {}_api_request_duration_seconds histogram",
+            PREFIX
+        );
+
+        for (&(game_id, api), histogram) in &self.durations {
+            let name = format!("{}_api_request_duration_seconds", PREFIX);
+            let bucket = |le: String| labels(Some(game_id), Some(api), &[("le", le)]);
+
+            for (count, le) in histogram.buckets.iter().zip(&DURATION_BUCKETS) {
+                let _ = writeln!(out, "{}_bucket{} {}", name, bucket(le.to_string()), count);
+            }
+
+            let _ = writeln!(
+                out,
+                "{}_bucket{} {}",
+                name,
+                bucket("+Inf".to_owned()),
+                histogram.count
+            );
+
+            let labels = labels(Some(game_id), Some(api), &[]);
+            let _ = writeln!(out, "{}_sum{} {}", name, labels, histogram.sum);
+            let _ = writeln!(out, "{}_count{} {}", name, labels, histogram.count);
+        }
+
+        out
+    }
+}
+
+/// Writes the metrics next to `path` and renames them into place, so a scrape
+/// never reads a partially written file.
+pub fn write(path: &Path, report: &AuditReport) -> io::Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+
+    std::fs::write(&temp, render(report))?;
+    std::fs::rename(&temp, path)
+}
@@ -0,0 +1,110 @@
+//! Hands the `--watch` metrics to Prometheus, either by serving them at
+//! `/metrics` on `--metrics-listen` for scraping or by pushing them to the
+//! Pushgateway at `--pushgateway-url` after every run.
+
+use crate::cli::Opts;
+
+use async_std::future::timeout;
+use async_std::net::{TcpListener, TcpStream};
+use async_std::task;
+use futures::{AsyncReadExt, AsyncWriteExt, StreamExt};
+use isahc::prelude::*;
+use tracing::{debug, info, warn};
+
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Job the metrics are pushed under.
+const JOB: &str = "ajour_api_test";
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Answers scrapes on `listener` with the latest metrics in `body` until
+/// the process is stopped.
+pub async fn serve(listener: TcpListener, body: Arc<Mutex<String>>) {
+    let mut incoming = listener.incoming();
+
+    while let Some(stream) = incoming.next().await {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                debug!("failed to accept a metrics connection - {}", e);
+                continue;
+            }
+        };
+        let body = body.clone();
+
+        task::spawn(async move {
+            if let Err(e) = respond(stream, &body).await {
+                debug!("failed to answer a metrics request - {}", e);
+            }
+        });
+    }
+}
+
+/// Reads the request line and answers `GET /metrics`, or 404 for anything
+/// else.
+async fn respond(mut stream: TcpStream, body: &Mutex<String>) -> io::Result<()> {
+    let mut request = [0; 1024];
+    let read = stream.read(&mut request).await?;
+    let request = String::from_utf8_lossy(&request[..read]);
+
+    let mut parts = request.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+    let path = path.split('?').next().unwrap_or_default();
+
+    let (status, body) = if method == "GET" && path == "/metrics" {
+        ("200 OK", body.lock().unwrap().clone())
+    } else {
+        ("404 Not Found", String::new())
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        CONTENT_TYPE,
+        body.len(),
+        body
+    );
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.flush().await
+}
+
+/// Replaces the metrics the Pushgateway holds for this job with `body`,
+/// logging rather than failing the run if it can't.
+pub async fn push(client: &HttpClient, opts: &Opts, body: String) {
+    let url = match &opts.pushgateway_url {
+        Some(url) => url,
+        None => return,
+    };
+
+    let url = format!(
+        "{}/metrics/job/{}",
+        url.to_string().trim_end_matches('/'),
+        JOB
+    );
+    let request = match Request::put(url.as_str())
+        .header("content-type", CONTENT_TYPE)
+        .body(body)
+    {
+        Ok(request) => request,
+        Err(e) => {
+            warn!("failed to build the Pushgateway request - {}", e);
+            return;
+        }
+    };
+
+    let request_timeout = Duration::from_secs(opts.request_timeout);
+
+    match timeout(request_timeout, client.send_async(request)).await {
+        Ok(Ok(response)) if response.status().is_success() => {
+            info!("Pushed metrics to {}", url);
+        }
+        Ok(Ok(response)) => warn!("the Pushgateway answered with status {}", response.status()),
+        Ok(Err(e)) => warn!("failed to push metrics to {} - {}", url, e),
+        Err(_) => warn!("pushing metrics to {} timed out", url),
+    }
+}
@@ -0,0 +1,133 @@
+//! Posts a summary to `--webhook-url` when a run crosses one of the
+//! `--notify-*` thresholds, so scheduled runs raise an alert instead of
+//! waiting for someone to read their logs.
+//!
+//! The payload carries the summary as both `text` and `content`, which is
+//! what Slack and Discord incoming webhooks respectively display, and each
+//! ignores the other's field.
+
+use crate::cli::Opts;
+use crate::report::AuditReport;
+
+use async_std::future::timeout;
+use isahc::prelude::*;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use std::time::Duration;
+
+/// Discord rejects messages longer than this many characters.
+const MAX_MESSAGE_CHARS: usize = 2_000;
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    text: &'a str,
+    content: &'a str,
+    run_id: &'a str,
+    alerts: &'a [Alert],
+}
+
+#[derive(Serialize)]
+pub struct Alert {
+    pub game_id: u32,
+    /// Key of the API that crossed the threshold, empty for a change
+    /// `--watch` found.
+    pub api: &'static str,
+    /// Flag of the threshold, such as `--notify-below-match-rate`, or
+    /// `--watch` for a change since the previous run.
+    pub threshold: &'static str,
+    pub message: String,
+}
+
+/// Every API of every game that crossed a `--notify-*` threshold.
+pub fn alerts(opts: &Opts, games: &[(u32, &AuditReport)]) -> Vec<Alert> {
+    let mut alerts = vec![];
+
+    for (game_id, report) in games {
+        for api in &report.apis {
+            if let Some(min_match_rate) = opts.notify_below_match_rate {
+                let match_rate = report.match_rate(api);
+
+                if match_rate < min_match_rate {
+                    alerts.push(Alert {
+                        game_id: *game_id,
+                        api: api.api,
+                        threshold: "--notify-below-match-rate",
+                        message: format!(
+                            "{} matched {:.1}% of {} packages of game {}, below {}%",
+                            api.name, match_rate, report.packages_audited, game_id, min_match_rate
+                        ),
+                    });
+                }
+            }
+
+            if let Some(max_error_rate) = opts.notify_above_error_rate {
+                if api.error_rate > max_error_rate {
+                    alerts.push(Alert {
+                        game_id: *game_id,
+                        api: api.api,
+                        threshold: "--notify-above-error-rate",
+                        message: format!(
+                            "{} failed {:.1}% of its requests for game {}, above {}%",
+                            api.name, api.error_rate, game_id, max_error_rate
+                        ),
+                    });
+                }
+            }
+        }
+    }
+
+    alerts
+}
+
+/// Posts the alerts to `--webhook-url`. An alert that can't be delivered
+/// shouldn't fail the audit it's about, so failures are only logged.
+pub async fn send(client: &HttpClient, opts: &Opts, run_id: &str, alerts: &[Alert]) {
+    let url = match &opts.webhook_url {
+        Some(url) if !alerts.is_empty() => url,
+        _ => return,
+    };
+
+    let mut message = format!("ajour-api-test run {}:", run_id);
+
+    for alert in alerts {
+        message.push_str("\n- ");
+        message.push_str(&alert.message);
+    }
+
+    if message.chars().count() > MAX_MESSAGE_CHARS {
+        message = message.chars().take(MAX_MESSAGE_CHARS - 3).collect();
+        message.push_str("...");
+    }
+
+    let payload = Payload {
+        text: &message,
+        content: &message,
+        run_id,
+        alerts,
+    };
+
+    let request = Request::post(url.clone())
+        .header("content-type", "application/json")
+        .body(serde_json::to_vec(&payload).expect("payload serializes"));
+
+    let request = match request {
+        Ok(request) => request,
+        Err(e) => {
+            warn!("failed to build the webhook request - {}", e);
+            return;
+        }
+    };
+
+    let request_timeout = Duration::from_secs(opts.request_timeout);
+
+    // The url is left out of logs, as webhook urls hold their token
+    match timeout(request_timeout, client.send_async(request)).await {
+        Ok(Ok(response)) if response.status().is_success() => {
+            info!("Sent {} alerts to the webhook", alerts.len());
+        }
+        Ok(Ok(response)) => warn!("webhook answered with status {}", response.status()),
+        Ok(Err(e)) => warn!("failed to send alerts to the webhook - {}", e),
+        Err(_) => warn!("sending alerts to the webhook timed out"),
+    }
+}
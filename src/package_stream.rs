@@ -0,0 +1,114 @@
+//! Writes each package's outcome to stdout for `--stream ndjson` as soon as
+//! every API answered the batch that sent it, so consumers can process a
+//! large audit while it runs rather than after the report.
+//!
+//! A package counts as matched by an API if the response of the batch
+//! that sent its fingerprints matched it, which is what the report finds
+//! too unless another batch's fingerprints were attributed to it.
+
+use crate::{ApiChoice, Batch, BatchResponse};
+
+use serde::Serialize;
+use tracing::warn;
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+#[derive(Serialize)]
+struct PackageLine<'a> {
+    id: i32,
+    name: &'a str,
+    matched_by: Vec<&'static str>,
+    partial_on: Vec<&'static str>,
+    failed_on: Vec<&'static str>,
+}
+
+/// What one API's response to a batch said about its packages.
+struct Answer {
+    api_choice: ApiChoice,
+    exact: HashSet<i32>,
+    partial: HashSet<i32>,
+    failed: bool,
+}
+
+pub struct PackageStream<'a> {
+    apis: usize,
+    batches: &'a [Batch<'a>],
+    /// Answers of each batch until every API's is in, by batch index.
+    pending: Mutex<HashMap<usize, Vec<Answer>>>,
+}
+
+impl<'a> PackageStream<'a> {
+    pub fn new(apis: usize, batches: &'a [Batch<'a>]) -> PackageStream<'a> {
+        PackageStream {
+            apis,
+            batches,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Keeps an API's response to the batch at `index`, writing the batch's
+    /// packages once it was the last API to answer.
+    pub fn record(&self, index: usize, api_choice: ApiChoice, response: &BatchResponse) {
+        let answer = match &response.result {
+            Ok(info) => Answer {
+                api_choice,
+                exact: info.exact_matches.iter().map(|i| i.id).collect(),
+                partial: info.partial_matches.iter().map(|i| i.id).collect(),
+                failed: false,
+            },
+            Err(_) => Answer {
+                api_choice,
+                exact: HashSet::new(),
+                partial: HashSet::new(),
+                failed: true,
+            },
+        };
+
+        let answers = {
+            let mut pending = self.pending.lock().unwrap();
+            let answers = pending.entry(index).or_default();
+            answers.push(answer);
+
+            if answers.len() < self.apis {
+                return;
+            }
+
+            pending.remove(&index).unwrap_or_default()
+        };
+
+        if let Err(e) = self.write(&self.batches[index], &answers) {
+            warn!("failed to stream batch {} - {}", index, e);
+        }
+    }
+
+    fn write(&self, batch: &Batch<'_>, answers: &[Answer]) -> io::Result<()> {
+        let apis = |matches: &dyn Fn(&Answer) -> bool| {
+            answers
+                .iter()
+                .filter(|answer| matches(answer))
+                .map(|answer| answer.api_choice.key())
+                .collect::<Vec<_>>()
+        };
+
+        // Locked for the whole batch, so its lines stay together
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+
+        for package in &batch.answers_for {
+            let line = PackageLine {
+                id: package.id,
+                name: &package.name,
+                matched_by: apis(&|answer| answer.exact.contains(&package.id)),
+                partial_on: apis(&|answer| answer.partial.contains(&package.id)),
+                failed_on: apis(&|answer| answer.failed),
+            };
+
+            serde_json::to_writer(&mut out, &line)?;
+            writeln!(out)?;
+        }
+
+        out.flush()
+    }
+}
@@ -0,0 +1,119 @@
+//! Queueing and execution times of every fingerprint batch for `--profile`,
+//! which tell batches waiting for a slot apart from batches waiting on the
+//! API.
+//!
+//! A batch is queued from when it's handed to the dispatcher until its first
+//! attempt is sent, and executing from then until its final attempt returns.
+
+use crate::report::percentile;
+use crate::ApiResults;
+
+use tracing::info;
+
+use std::io::{self, Write};
+use std::time::Instant;
+
+/// Prints the queued and executing percentiles of each API's batches, along
+/// with the most batches that were executing at once.
+pub fn print_summary(results: &[ApiResults]) {
+    for result in results {
+        let mut queued = result
+            .responses
+            .iter()
+            .map(|r| (r.started - r.enqueued).as_millis() as u64)
+            .collect::<Vec<_>>();
+        let mut executing = result
+            .responses
+            .iter()
+            .map(|r| r.total_latency.as_millis() as u64)
+            .collect::<Vec<_>>();
+
+        queued.sort_unstable();
+        executing.sort_unstable();
+
+        let stats = |sorted: &[u64]| match (
+            percentile(sorted, 50.0),
+            percentile(sorted, 95.0),
+            sorted.last(),
+        ) {
+            (Some(p50), Some(p95), Some(max)) => {
+                format!("p50 {}ms, p95 {}ms, max {}ms", p50, p95, max)
+            }
+            _ => "none".to_owned(),
+        };
+
+        info!(
+            "{} profile over {} batches: queued {}; executing {}; at most {} executing at once",
+            result.api_choice,
+            result.responses.len(),
+            stats(&queued),
+            stats(&executing),
+            peak_executing(result)
+        );
+    }
+}
+
+/// Most batches of one API whose execution overlapped at any point.
+fn peak_executing(result: &ApiResults) -> usize {
+    // Ends sort before starts at the same instant, so back to back batches
+    // don't count as overlapping
+    let mut events = result
+        .responses
+        .iter()
+        .flat_map(|r| vec![(r.started, 1), (r.started + r.total_latency, -1)])
+        .collect::<Vec<(_, i64)>>();
+    events.sort();
+
+    let mut executing = 0;
+    let mut peak = 0;
+
+    for (_, change) in events {
+        executing += change;
+        peak = peak.max(executing);
+    }
+
+    peak as usize
+}
+
+pub fn write_header(out: &mut impl Write) -> io::Result<()> {
+    writeln!(
+        out,
+        "game_id,api,batch,enqueued_ms,started_ms,completed_ms,queued_ms,executing_ms,attempts"
+    )
+}
+
+/// Writes a row per batch of one game, with times relative to the first batch
+/// of any API being enqueued.
+pub fn write(out: &mut impl Write, game_id: u32, results: &[ApiResults]) -> io::Result<()> {
+    let origin = match results
+        .iter()
+        .flat_map(|r| r.responses.iter().map(|r| r.enqueued))
+        .min()
+    {
+        Some(origin) => origin,
+        None => return Ok(()),
+    };
+    let since = |instant: Instant| (instant - origin).as_millis();
+
+    for result in results {
+        for (index, response) in result.responses.iter().enumerate() {
+            let completed = response.started + response.total_latency;
+
+            writeln!(
+                out,
+                "{},{},{},{},{},{},{},{},{}",
+                game_id,
+                result.api_choice.key(),
+                index,
+                since(response.enqueued),
+                since(response.started),
+                since(completed),
+                (response.started - response.enqueued).as_millis(),
+                response.total_latency.as_millis(),
+                response.attempts
+            )?;
+        }
+    }
+
+    Ok(())
+}
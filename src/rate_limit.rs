@@ -0,0 +1,67 @@
+//! Pauses every request to an API while it's rate limiting the run, so the
+//! batches still queued don't keep running into its limit.
+
+use crate::date::Timestamp;
+use crate::fingerprint_api::FingerprintApi;
+
+use async_std::task;
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// When each API, by key, may be sent requests again.
+pub struct RateLimits {
+    paused_until: Mutex<BTreeMap<&'static str, Instant>>,
+}
+
+impl RateLimits {
+    pub fn new() -> RateLimits {
+        RateLimits {
+            paused_until: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Holds off every further request to the API for `delay`, returning
+    /// `false` if an earlier pause already lasts longer.
+    pub fn pause(&self, api: &dyn FingerprintApi, delay: Duration) -> bool {
+        let until = Instant::now() + delay;
+        let mut paused_until = self.paused_until.lock().unwrap();
+        let current = paused_until.entry(api.key()).or_insert(until);
+
+        if *current < until {
+            *current = until;
+        }
+
+        *current == until
+    }
+
+    /// Waits until the API is no longer paused, returning how long that took.
+    /// A pause extended meanwhile is waited out too.
+    pub async fn wait(&self, api: &dyn FingerprintApi) -> Duration {
+        let start = Instant::now();
+
+        loop {
+            let until = self.paused_until.lock().unwrap().get(api.key()).copied();
+
+            match until {
+                Some(until) if until > Instant::now() => task::sleep(until - Instant::now()).await,
+                _ => return start.elapsed(),
+            }
+        }
+    }
+}
+
+/// Parses a `Retry-After` header, which is either a number of seconds or an
+/// HTTP date. A date in the past means retrying right away.
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = Timestamp::from_http_date(value)?;
+
+    Some(date.duration_since(Timestamp::now()).unwrap_or_default())
+}
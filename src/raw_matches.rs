@@ -0,0 +1,52 @@
+//! Writes every exact match as ndjson for `--raw-matches-file`.
+
+use crate::ApiResults;
+
+use ajour_core::repository::curse;
+use serde::Serialize;
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// One line of the file, for every fingerprint of every exactly matched file.
+#[derive(Serialize)]
+struct RawMatch<'a> {
+    api: &'static str,
+    package_id: i32,
+    /// Absent if the API matched a package that wasn't audited.
+    package_name: Option<&'a str>,
+    file_id: i64,
+    fingerprint: u32,
+}
+
+/// Writes the matches of `results` one line at a time, without collecting
+/// them first.
+pub fn write(
+    out: &mut impl Write,
+    packages: &[curse::Package],
+    results: &[ApiResults],
+) -> io::Result<()> {
+    let names = packages
+        .iter()
+        .map(|p| (p.id, p.name.as_str()))
+        .collect::<HashMap<_, _>>();
+
+    for result in results {
+        for info in result.exact_matches() {
+            for module in &info.file.modules {
+                let line = RawMatch {
+                    api: result.api_choice.key(),
+                    package_id: info.id,
+                    package_name: names.get(&info.id).copied(),
+                    file_id: info.file.id,
+                    fingerprint: module.fingerprint,
+                };
+
+                serde_json::to_writer(&mut *out, &line)?;
+                writeln!(out)?;
+            }
+        }
+    }
+
+    Ok(())
+}
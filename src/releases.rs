@@ -0,0 +1,407 @@
+//! Checks the latest release of GitHub and GitLab repositories for the
+//! `releases` subcommand, the way Ajour resolves addons installed from
+//! them, since a release Ajour can't resolve never updates.
+//!
+//! Ajour takes the version from the release's tag and picks a zip asset
+//! per game flavor. A release with a `release.json` asset, as written by
+//! the BigWigs packager, says which file is for which flavor. Without one,
+//! Ajour goes by the asset names, where a marker like `-classic`, `-bcc` or
+//! `-wrath` picks a classic flavor and anything else is retail.
+
+use crate::cli::{Forge, Format, GitRepo, Opts};
+use crate::error::AuditError;
+use crate::{percent_encode, send_request, write_json};
+
+use async_std::future::timeout;
+use isahc::config::RedirectPolicy;
+use isahc::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::time::Duration;
+
+const GITHUB_API_URL: &str = "https://api.github.com/repos";
+const GITLAB_API_URL: &str = "https://gitlab.com/api/v4/projects";
+const RELEASE_JSON: &str = "release.json";
+
+/// What marks an asset name as one of the classic flavors, without which
+/// it's for retail.
+const FLAVOR_MARKERS: [(&str, &str); 6] = [
+    ("-classic", "classic"),
+    ("-vanilla", "classic"),
+    ("-bcc", "bcc"),
+    ("-tbc", "bcc"),
+    ("-wrath", "wrath"),
+    ("-wotlkc", "wrath"),
+];
+
+#[derive(Deserialize)]
+struct GitHubRelease {
+    tag_name: String,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    prerelease: bool,
+    assets: Vec<GitHubAsset>,
+}
+
+#[derive(Deserialize)]
+struct GitHubAsset {
+    name: String,
+    browser_download_url: String,
+}
+
+#[derive(Deserialize)]
+struct GitLabRelease {
+    tag_name: String,
+    #[serde(default)]
+    upcoming_release: bool,
+    assets: GitLabAssets,
+}
+
+#[derive(Deserialize)]
+struct GitLabAssets {
+    links: Vec<GitLabLink>,
+}
+
+#[derive(Deserialize)]
+struct GitLabLink {
+    name: String,
+    url: String,
+}
+
+/// The BigWigs packager's description of a release's files.
+#[derive(Deserialize)]
+struct ReleaseJson {
+    releases: Vec<ReleaseJsonFile>,
+}
+
+#[derive(Deserialize)]
+struct ReleaseJsonFile {
+    filename: String,
+    #[serde(default)]
+    nolib: bool,
+    metadata: Vec<ReleaseJsonMetadata>,
+}
+
+#[derive(Deserialize)]
+struct ReleaseJsonMetadata {
+    flavor: String,
+}
+
+/// The latest release, whichever forge it's from.
+struct Release {
+    tag: String,
+    /// Asset names with their download URLs.
+    assets: Vec<(String, String)>,
+}
+
+#[derive(Serialize)]
+pub struct ReleaseCheck {
+    pub repo: String,
+    pub forge: &'static str,
+    /// Tag of the latest release, absent if there's none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// Whether the flavors came from the release's `release.json`.
+    pub release_json: bool,
+    /// Asset each flavor resolves to.
+    pub flavors: BTreeMap<&'static str, String>,
+    /// Why the release wouldn't resolve, empty if it would.
+    pub problems: Vec<String>,
+}
+
+/// Checks every repository's latest release and writes the outcome,
+/// returning how many would fail to resolve.
+pub async fn check(
+    client: &HttpClient,
+    opts: &Opts,
+    repos: &[GitRepo],
+    github_token: Option<&str>,
+    out: &mut impl Write,
+) -> Result<usize, anyhow::Error> {
+    let mut checks = vec![];
+
+    for repo in repos {
+        checks.push(check_repo(client, opts, repo, github_token).await);
+    }
+
+    match opts.format {
+        Format::Json => write_json(out, &checks, opts.compact)?,
+        _ => write_text(out, &checks)?,
+    }
+
+    Ok(checks.iter().filter(|c| !c.problems.is_empty()).count())
+}
+
+async fn check_repo(
+    client: &HttpClient,
+    opts: &Opts,
+    repo: &GitRepo,
+    github_token: Option<&str>,
+) -> ReleaseCheck {
+    let mut check = ReleaseCheck {
+        repo: repo.path.clone(),
+        forge: repo.forge.name(),
+        tag: None,
+        release_json: false,
+        flavors: BTreeMap::new(),
+        problems: vec![],
+    };
+
+    let release = match latest_release(client, opts, repo, github_token).await {
+        Ok(Some(release)) => release,
+        Ok(None) => {
+            check.problems.push("no published release".to_owned());
+            return check;
+        }
+        Err(AuditError::BadStatus { code: 404, .. }) => {
+            check
+                .problems
+                .push("repository not found, or it's private".to_owned());
+            return check;
+        }
+        Err(e) => {
+            check
+                .problems
+                .push(format!("failed to fetch releases - {}", e));
+            return check;
+        }
+    };
+
+    if !release.tag.chars().any(|c| c.is_ascii_digit()) {
+        check
+            .problems
+            .push(format!("tag `{}` holds no version", release.tag));
+    }
+
+    check.tag = Some(release.tag.clone());
+
+    let release_json = release
+        .assets
+        .iter()
+        .find(|(name, _)| name == RELEASE_JSON)
+        .map(|(_, url)| url.clone());
+
+    match release_json {
+        Some(url) => {
+            check.release_json = true;
+
+            match fetch_release_json(client, opts, &url).await {
+                Ok(release_json) => resolve_from_release_json(&mut check, &release, &release_json),
+                Err(e) => check
+                    .problems
+                    .push(format!("failed to read {} - {}", RELEASE_JSON, e)),
+            }
+        }
+        None => resolve_from_names(&mut check, &release),
+    }
+
+    check
+}
+
+/// The newest release that isn't a draft or upcoming, preferring stable
+/// releases over prereleases like Ajour's stable channel.
+async fn latest_release(
+    client: &HttpClient,
+    opts: &Opts,
+    repo: &GitRepo,
+    github_token: Option<&str>,
+) -> Result<Option<Release>, AuditError> {
+    match repo.forge {
+        Forge::GitHub => {
+            let url = format!("{}/{}/releases?per_page=20", GITHUB_API_URL, repo.path);
+            let mut request = Request::builder()
+                .method("GET")
+                .uri(&url)
+                .header("accept", "application/vnd.github+json");
+
+            if let Some(token) = github_token {
+                request = request.header("authorization", format!("Bearer {}", token));
+            }
+
+            let releases: Vec<GitHubRelease> = get_json(client, opts, request).await?;
+            let published = releases.into_iter().filter(|r| !r.draft);
+            let (stable, pre): (Vec<_>, Vec<_>) = published.partition(|r| !r.prerelease);
+
+            Ok(stable.into_iter().chain(pre).next().map(|r| Release {
+                tag: r.tag_name,
+                assets: r
+                    .assets
+                    .into_iter()
+                    .map(|a| (a.name, a.browser_download_url))
+                    .collect(),
+            }))
+        }
+        Forge::GitLab => {
+            let url = format!("{}/{}/releases", GITLAB_API_URL, percent_encode(&repo.path));
+            let request = Request::builder().method("GET").uri(&url);
+
+            let releases: Vec<GitLabRelease> = get_json(client, opts, request).await?;
+
+            Ok(releases
+                .into_iter()
+                .find(|r| !r.upcoming_release)
+                .map(|r| Release {
+                    tag: r.tag_name,
+                    assets: r
+                        .assets
+                        .links
+                        .into_iter()
+                        .map(|l| (l.name, l.url))
+                        .collect(),
+                }))
+        }
+    }
+}
+
+async fn fetch_release_json(
+    client: &HttpClient,
+    opts: &Opts,
+    url: &str,
+) -> Result<ReleaseJson, AuditError> {
+    // GitHub serves assets through a redirect to its storage
+    let request = Request::builder()
+        .method("GET")
+        .uri(url)
+        .redirect_policy(RedirectPolicy::Follow);
+
+    get_json(client, opts, request).await
+}
+
+async fn get_json<T: serde::de::DeserializeOwned>(
+    client: &HttpClient,
+    opts: &Opts,
+    request: isahc::http::request::Builder,
+) -> Result<T, AuditError> {
+    let request_timeout = Duration::from_secs(opts.request_timeout);
+
+    let (body, _, _) = timeout(
+        request_timeout,
+        send_request(client, opts, request.body(vec![])?),
+    )
+    .await
+    .map_err(|_| AuditError::Timeout(request_timeout))??;
+
+    serde_json::from_slice(&body).map_err(|e| AuditError::Deserialize(e.into()))
+}
+
+fn resolve_from_release_json(
+    check: &mut ReleaseCheck,
+    release: &Release,
+    release_json: &ReleaseJson,
+) {
+    for file in release_json.releases.iter().filter(|f| !f.nolib) {
+        if !release
+            .assets
+            .iter()
+            .any(|(name, _)| *name == file.filename)
+        {
+            check.problems.push(format!(
+                "{} lists {}, which isn't an asset of the release",
+                RELEASE_JSON, file.filename
+            ));
+            continue;
+        }
+
+        for metadata in &file.metadata {
+            let flavor = match metadata.flavor.as_str() {
+                "mainline" => "retail",
+                "classic" => "classic",
+                "bcc" => "bcc",
+                "wrath" => "wrath",
+                other => {
+                    check
+                        .problems
+                        .push(format!("{} has unknown flavor `{}`", RELEASE_JSON, other));
+                    continue;
+                }
+            };
+
+            if let Some(previous) = check.flavors.insert(flavor, file.filename.clone()) {
+                check.problems.push(format!(
+                    "{} lists both {} and {} for {}",
+                    RELEASE_JSON, previous, file.filename, flavor
+                ));
+            }
+        }
+    }
+
+    if check.flavors.is_empty() && check.problems.is_empty() {
+        check
+            .problems
+            .push(format!("{} lists no file for any flavor", RELEASE_JSON));
+    }
+}
+
+fn resolve_from_names(check: &mut ReleaseCheck, release: &Release) {
+    let mut candidates = BTreeMap::<&'static str, Vec<&str>>::new();
+
+    for (name, _) in &release.assets {
+        let lower = name.to_lowercase();
+
+        if !lower.ends_with(".zip") || lower.contains("nolib") {
+            continue;
+        }
+
+        let stem = lower.trim_end_matches(".zip");
+        let flavor = FLAVOR_MARKERS
+            .iter()
+            .find(|(suffix, _)| stem.contains(suffix))
+            .map_or("retail", |(_, flavor)| flavor);
+
+        candidates.entry(flavor).or_default().push(name);
+    }
+
+    if candidates.is_empty() {
+        check.problems.push("no zip asset".to_owned());
+    }
+
+    for (flavor, names) in candidates {
+        if names.len() > 1 {
+            check.problems.push(format!(
+                "several assets for {}, {}",
+                flavor,
+                names.join(", ")
+            ));
+        }
+
+        check.flavors.insert(flavor, names[0].to_owned());
+    }
+}
+
+fn write_text(out: &mut impl Write, checks: &[ReleaseCheck]) -> io::Result<()> {
+    for check in checks {
+        let subject = match &check.tag {
+            Some(tag) => format!("{} ({}) {}", check.repo, check.forge, tag),
+            None => format!("{} ({})", check.repo, check.forge),
+        };
+
+        let flavors = check
+            .flavors
+            .iter()
+            .map(|(flavor, asset)| format!("{} {}", flavor, asset))
+            .collect::<Vec<_>>();
+
+        if check.problems.is_empty() {
+            writeln!(out, "{}: {}", subject, flavors.join(", "))?;
+        } else {
+            writeln!(
+                out,
+                "{} would fail to resolve: {}",
+                subject,
+                check.problems.join("; ")
+            )?;
+        }
+    }
+
+    let failing = checks.iter().filter(|c| !c.problems.is_empty()).count();
+
+    writeln!(
+        out,
+        "{} of {} repositories would fail to resolve",
+        failing,
+        checks.len()
+    )
+}
@@ -0,0 +1,158 @@
+//! Sends a random sample of the batches again for `--repeat`, to find
+//! fingerprint requests an API answers differently from one send to the
+//! next, which it's been seen to do under load.
+//!
+//! Every API gets the same sample, so a batch that's only flaky on one of
+//! them points at that API rather than the fingerprints. Batches that failed
+//! the first time have nothing to compare against and are skipped.
+
+use crate::cli::Opts;
+use crate::{get_fingerprint_respose, sample, ApiResults, Batch};
+
+use ajour_core::repository::curse;
+use futures::{stream, StreamExt};
+use isahc::HttpClient;
+use serde::Serialize;
+use tracing::debug;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Serialize)]
+pub struct RepeatCheck {
+    /// Sampled batches that succeeded the first time and were sent again.
+    pub batches: usize,
+    /// Sends after the first one, counting those that failed.
+    pub repeats: usize,
+    pub failed_repeats: usize,
+    /// Batches whose matches differed between sends, sorted by index.
+    pub flaky: Vec<FlakyBatch>,
+}
+
+#[derive(Serialize)]
+pub struct FlakyBatch {
+    pub index: usize,
+    pub fingerprints: usize,
+    /// Distinct match sets among the sends that succeeded, the first
+    /// included.
+    pub distinct_results: usize,
+    /// Packages some sends matched and others didn't, sorted by id.
+    pub packages: Vec<i32>,
+}
+
+/// The packages and files a response matched, exactly or partially, which
+/// is what has to stay the same between sends.
+type Matches = BTreeSet<(i32, i64, bool)>;
+
+/// Resends the sampled batches `--repeat` times to every API, returning the
+/// outcome per API in `results` order.
+pub async fn check(
+    client: &HttpClient,
+    opts: &Opts,
+    batches: &[Batch<'_>],
+    results: &[ApiResults],
+    seed: u64,
+) -> Vec<RepeatCheck> {
+    let repeats = opts.repeat.unwrap_or_default();
+    let sampled = sample::sample((0..batches.len()).collect(), opts.repeat_batches, seed);
+
+    let mut checks = vec![];
+
+    for result in results {
+        let originals = sampled
+            .iter()
+            .filter_map(|&index| match &result.responses[index].result {
+                Ok(info) => Some((index, matches(info))),
+                Err(_) => None,
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let sends = originals
+            .keys()
+            .flat_map(|&index| (0..repeats).map(move |_| index))
+            .collect::<Vec<_>>();
+
+        let responses = stream::iter(sends)
+            .map(|index| async move {
+                let response = get_fingerprint_respose(
+                    client,
+                    opts,
+                    result.api_choice.api(),
+                    &batches[index].fingerprints,
+                    &batches[index].answers_for,
+                )
+                .await;
+
+                (index, response.map(|response| matches(&response.info)))
+            })
+            .buffer_unordered(opts.max_connections)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut check = RepeatCheck {
+            batches: originals.len(),
+            repeats: responses.len(),
+            failed_repeats: 0,
+            flaky: vec![],
+        };
+
+        let mut seen = originals
+            .into_iter()
+            .map(|(index, original)| (index, vec![original]))
+            .collect::<BTreeMap<_, _>>();
+
+        for (index, response) in responses {
+            match response {
+                Ok(matches) => seen.entry(index).or_default().push(matches),
+                Err(e) => {
+                    debug!(
+                        index,
+                        "{} - repeated batch failed - {}", result.api_choice, e
+                    );
+                    check.failed_repeats += 1;
+                }
+            }
+        }
+
+        for (index, sends) in seen {
+            let distinct = sends.iter().collect::<BTreeSet<_>>();
+
+            if distinct.len() < 2 {
+                continue;
+            }
+
+            let packages = |matches: &Matches| {
+                matches
+                    .iter()
+                    .map(|(id, _, _)| *id)
+                    .collect::<BTreeSet<_>>()
+            };
+            let everywhere = sends
+                .iter()
+                .map(packages)
+                .reduce(|a, b| a.intersection(&b).copied().collect())
+                .unwrap_or_default();
+            let anywhere = sends.iter().flat_map(packages).collect::<BTreeSet<_>>();
+
+            check.flaky.push(FlakyBatch {
+                index,
+                fingerprints: batches[index].fingerprints.len(),
+                distinct_results: distinct.len(),
+                packages: anywhere.difference(&everywhere).copied().collect(),
+            });
+        }
+
+        checks.push(check);
+    }
+
+    checks
+}
+
+fn matches(info: &curse::FingerprintInfo) -> Matches {
+    let exact = info.exact_matches.iter().map(|m| (m.id, m.file.id, true));
+    let partial = info
+        .partial_matches
+        .iter()
+        .map(|m| (m.id, m.file.id, false));
+
+    exact.chain(partial).collect()
+}
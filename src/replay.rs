@@ -0,0 +1,78 @@
+//! Records request/response pairs to disk and serves them back, so a problematic
+//! run can be reproduced offline and deterministically.
+
+use crate::fnv::Fnv1a;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Serialize, Deserialize)]
+struct Recording {
+    uri: String,
+    request_body: String,
+    response_body: String,
+    /// How long the response took, absent from recordings made before it
+    /// was kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    latency_ms: Option<u64>,
+}
+
+pub async fn save(
+    dir: &Path,
+    uri: &str,
+    request_body: &[u8],
+    response_body: &[u8],
+    latency: Duration,
+) -> Result<(), anyhow::Error> {
+    async_std::fs::create_dir_all(dir).await?;
+
+    let recording = Recording {
+        uri: uri.to_owned(),
+        request_body: String::from_utf8_lossy(request_body).into_owned(),
+        response_body: String::from_utf8_lossy(response_body).into_owned(),
+        latency_ms: Some(latency.as_millis() as u64),
+    };
+
+    // Written aside and renamed into place, so a run killed mid-write
+    // doesn't leave a truncated recording for `--resume` to trip over
+    let path = recording_path(dir, uri, request_body);
+    let partial = path.with_extension("json.partial");
+    async_std::fs::write(&partial, serde_json::to_vec_pretty(&recording)?).await?;
+    async_std::fs::rename(&partial, &path).await?;
+
+    Ok(())
+}
+
+/// Loads the recorded response body along with how long it originally took,
+/// if that was recorded.
+pub async fn load(
+    dir: &Path,
+    uri: &str,
+    request_body: &[u8],
+) -> Result<(Vec<u8>, Option<Duration>), anyhow::Error> {
+    let path = recording_path(dir, uri, request_body);
+
+    let contents = async_std::fs::read(&path)
+        .await
+        .with_context(|| format!("no recording of {} at {}", uri, path.display()))?;
+    let recording: Recording = serde_json::from_slice(&contents)?;
+
+    Ok((
+        recording.response_body.into_bytes(),
+        recording.latency_ms.map(Duration::from_millis),
+    ))
+}
+
+/// Recordings are named by an FNV-1a hash of the URI and request body, which
+/// unlike `DefaultHasher` is stable across Rust releases.
+fn recording_path(dir: &Path, uri: &str, request_body: &[u8]) -> PathBuf {
+    let mut hasher = Fnv1a::default();
+    hasher.write(uri.as_bytes());
+    hasher.write(request_body);
+
+    dir.join(format!("{:016x}.json", hasher.finish()))
+}
@@ -0,0 +1,3189 @@
+use crate::cli::Opts;
+use crate::date::Timestamp;
+use crate::fingerprint_index::FingerprintIndex;
+use crate::shutdown;
+use crate::{package_fingerprints, ApiResults};
+
+use ajour_core::repository::curse;
+use serde::{Deserialize, Serialize};
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{self, Write};
+
+pub use crate::changelog_check::{BrokenChangelog, ChangelogCheck, ChangelogProblem};
+pub use crate::download_check::{BrokenUrl, DownloadUrlCheck};
+pub use crate::drift::SchemaDrift;
+pub use crate::file_hashes::{FileHash, HashMismatch, HashVerification};
+pub use crate::fingerprint_check::{FingerprintSelfTest, RecomputedMismatch};
+pub use crate::fingerprint_index::{FingerprintAttribution, Origin};
+pub use crate::repeat_check::{FlakyBatch, RepeatCheck};
+
+/// Machine-readable results of an audit run.
+///
+/// Every list is sorted, so reports of identical runs are byte-identical
+/// apart from their run id and start time, and can be diffed.
+#[derive(Serialize)]
+pub struct AuditReport {
+    /// Random UUID of the run, also logged at its start and end.
+    pub run_id: String,
+    /// RFC 3339 time the run started.
+    pub started_at: String,
+    /// Whether Ctrl-C or SIGTERM cut the run short, leaving a partial report
+    /// in which the batches that hadn't completed count as failed.
+    pub interrupted: bool,
+    /// Packages returned by the search, before `--package-limit`.
+    pub packages_fetched: usize,
+    pub packages_audited: usize,
+    /// Seed used for `--sample` or `--search-sampling`, which reproduces the
+    /// same sample when passed back with `--seed`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_seed: Option<u64>,
+    /// Whether partial matches counted towards matched packages and
+    /// discrepancies, set by `--count-partial`.
+    pub count_partial: bool,
+    /// Packages matched by at least one API.
+    pub unique_packages: usize,
+    /// Packages matched by some APIs but not all of them, leaving out those
+    /// whose batch failed on an API that didn't match them.
+    pub discrepancies: usize,
+    /// Packages that fell into a failed batch on at least one API, so their
+    /// match status there is unknown.
+    pub indeterminate_packages: usize,
+    pub discrepancy_pct: f64,
+    pub apis: Vec<ApiReport>,
+    /// Retries consumed across every batch of the run.
+    pub retries_used: usize,
+    /// The `--retry-budget`, if one was set.
+    pub retry_budget: Option<usize>,
+    /// Packages matched by several APIs that resolved to files with different
+    /// versions, sorted by package id.
+    pub version_disagreements: Vec<VersionDisagreement>,
+    /// Which API matched the newer file of each version disagreement.
+    pub freshness: Freshness,
+    /// Release types and ages of the files each API matched the packages
+    /// every API matched to, with the APIs that skew from the others.
+    pub file_distribution: FileDistribution,
+    /// Discrepancies grouped by a fingerprint the packages share, sorted by
+    /// fingerprint, only included with `--dedupe-report`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub discrepancy_groups: Option<Vec<DiscrepancyGroup>>,
+    /// Why each discrepancy didn't match on the APIs missing it, module by
+    /// module, sorted by package id and then API, only included with
+    /// `--explain-mismatches`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mismatch_explanations: Option<Vec<MismatchExplanation>>,
+    /// Packages some APIs only partially matched while others matched them
+    /// exactly, sorted by id.
+    pub partial_mismatches: Vec<PartialMismatch>,
+    /// Fingerprints that APIs exactly matched to entirely different
+    /// packages, which points at an indexing bug in one of them, sorted by
+    /// fingerprint.
+    pub attribution_conflicts: Vec<AttributionConflict>,
+    /// Fingerprints submitted for more than one package, which batches
+    /// only send once.
+    pub shared_fingerprints: usize,
+    /// What every API matched each submitted fingerprint to, attributed to
+    /// the packages, files and folders it came from, sorted by fingerprint,
+    /// only included with `--attribute-fingerprints`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fingerprint_attribution: Option<Vec<FingerprintAttribution>>,
+    /// Fingerprints several APIs exactly matched to files whose metadata
+    /// differs, such as a stale file id or download URL, one per distinct
+    /// set of files, sorted by fingerprint.
+    pub metadata_mismatches: Vec<MetadataMismatch>,
+    /// Hashes of a sample of the files several APIs exactly matched, only
+    /// included with `--verify-file-hashes`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_hashes: Option<HashVerification>,
+    /// Fingerprints recomputed locally from a sample of the matched files,
+    /// only included with `--recompute-fingerprints`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fingerprint_self_test: Option<FingerprintSelfTest>,
+    /// Agreement of every pair of APIs, in `--apis` order, only included
+    /// with `--compare-apis-pairwise`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pairwise: Option<Vec<PairAgreement>>,
+    /// Match rate of each API per Curse category, sorted by the widest gap
+    /// between APIs first.
+    pub categories: Vec<CategoryReport>,
+    /// Exact match rate of each API per game version flavor, sorted by
+    /// flavor.
+    pub flavors: Vec<FlavorReport>,
+    /// Match rate of each API per bucket of total downloads, from the least
+    /// downloaded, leaving out empty buckets.
+    pub downloads: Vec<DownloadBucketReport>,
+    /// Match status of every audited package, sorted by id.
+    pub packages: Vec<PackageStatus>,
+    /// Packages `--ignore-file` lists, sorted by id. The match rate and the
+    /// checks that fail the run leave them out.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub known_issues: Vec<KnownIssue>,
+    /// Packages with fingerprints that no API exactly matched, sorted by id,
+    /// only included with `--include-unmatched`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fully_unmatched: Option<Vec<UnmatchedPackage>>,
+    pub phases: PhaseTimings,
+    /// Whether matches passed `--max-memory-mb` and were spilled to disk.
+    pub spilled_to_disk: bool,
+    /// Conditions that may make the results misleading, such as the search
+    /// returning fewer packages than requested.
+    pub warnings: Vec<String>,
+}
+
+/// Lower bounds of the buckets of `AuditReport::downloads` after the first,
+/// which starts at 0.
+const DOWNLOAD_BUCKETS: [u64; 4] = [1_000, 10_000, 100_000, 1_000_000];
+
+/// Packages a comparison of `FileDistribution` has to cover before it's
+/// flagged as a skew, so a handful of packages can't make one.
+const SKEW_MIN_PACKAGES: usize = 20;
+
+/// Share of the packages whose file dates differ that an API has to have
+/// matched the oldest file for to skew.
+const SKEW_OLDEST_SHARE: f64 = 0.75;
+
+/// Points the share of stable files of an API has to be from the other APIs'
+/// to skew.
+const SKEW_STABLE_PCT: f64 = 10.0;
+
+/// `ApiReport::fingerprint_algorithm` of APIs whose responses don't say.
+pub const UNKNOWN_ALGORITHM: &str = "unknown";
+
+#[derive(Serialize)]
+pub struct ApiReport {
+    pub api: &'static str,
+    #[serde(skip)]
+    pub name: &'static str,
+    pub matched_packages: usize,
+    pub fingerprint_matches: usize,
+    /// Partially matched files, which don't count as fingerprint matches.
+    pub partial_matches: usize,
+    /// Packages the API only partially matched, with no exactly matched
+    /// file.
+    pub partially_matched_packages: usize,
+    pub batches: usize,
+    /// Requests sent, including retries.
+    pub attempts: usize,
+    pub failed_batches: usize,
+    /// Failed batches whose response passed `--max-response-bytes`.
+    pub oversized_responses: usize,
+    /// Failed batches by the kind of error their final attempt ended in.
+    pub errors: BTreeMap<&'static str, usize>,
+    /// Failed batches by the category of their error, one of `transport`,
+    /// `timeout`, `rate_limited`, `upstream_error`, `decode` or `internal`,
+    /// which are the same for every API.
+    pub error_categories: BTreeMap<&'static str, usize>,
+    /// Failed batches by the status the API answered with.
+    pub error_statuses: BTreeMap<u16, usize>,
+    /// Every failed batch, sorted by index.
+    pub failures: Vec<BatchFailure>,
+    pub success_rate: f64,
+    /// Latency percentiles of the final attempt of successful batches in
+    /// milliseconds, absent when no batch succeeded.
+    pub latency_p50_ms: Option<u64>,
+    pub latency_p95_ms: Option<u64>,
+    pub latency_p99_ms: Option<u64>,
+    /// Percentage of requests, retries included, that failed.
+    pub error_rate: f64,
+    /// Requests sent and fingerprints of successful batches per second of
+    /// the API's fingerprint phase.
+    pub requests_per_second: f64,
+    pub fingerprints_per_second: f64,
+    /// Time spent on failed attempts and the delays between retries, summed
+    /// over every batch.
+    pub retry_overhead_ms: u64,
+    /// Requests the API answered with 429 Too Many Requests.
+    pub rate_limited: usize,
+    /// Time batches spent waiting for the API's rate limit pauses, summed
+    /// over every batch.
+    pub rate_limit_wait_ms: u64,
+    /// In-flight requests `--adaptive` settled on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub concurrency: Option<usize>,
+    /// The slowest and fastest successful batches, absent when no batch
+    /// succeeded.
+    pub slowest_batch: Option<BatchTiming>,
+    pub fastest_batch: Option<BatchTiming>,
+    /// Fingerprint algorithm the responses reported, `unknown` if they
+    /// didn't and comma-separated if batches disagreed.
+    pub fingerprint_algorithm: String,
+    /// HTTP versions the successful responses came over, such as
+    /// `HTTP/2.0`, empty when replayed.
+    pub http_versions: Vec<String>,
+    /// Bytes sent over the bytes the request bodies of successful batches
+    /// would have taken uncompressed, with `--compress-request`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression_ratio: Option<f64>,
+    /// Sizes and encodings of the responses that came over the wire,
+    /// absent if none did, like with `--replay`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_bytes: Option<ResponseBytes>,
+    /// Whether p95 latency stayed under `--latency-sla`, if one was set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sla: Option<SlaStatus>,
+    /// Sorted fingerprints of every exactly matched file, only included with
+    /// `--include-fingerprints-in-report`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matched_fingerprints: Option<Vec<u32>>,
+    /// Outcome of checking the matched files' download URLs, only included
+    /// with `--check-download-urls`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub download_urls: Option<DownloadUrlCheck>,
+    /// Outcome of requesting the matched files' changelogs, only included
+    /// with `--check-changelogs`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changelogs: Option<ChangelogCheck>,
+    /// Batches sent again with `--repeat` and the ones whose matches
+    /// changed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repeats: Option<RepeatCheck>,
+    /// Fields of the API's responses the response types dropped or that
+    /// came back null, only included with `--detect-schema-drift`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema_drift: Option<SchemaDrift>,
+}
+
+/// Report of a run covering several games.
+#[derive(Serialize)]
+pub struct MultiGameReport<'a> {
+    pub games: Vec<GameReport<'a>>,
+    pub totals: Totals,
+}
+
+#[derive(Serialize)]
+pub struct GameReport<'a> {
+    pub game_id: u32,
+    #[serde(flatten)]
+    pub report: &'a AuditReport,
+}
+
+/// Sums across the games of a run.
+#[derive(Serialize)]
+pub struct Totals {
+    pub games: usize,
+    pub packages_audited: usize,
+    pub unique_packages: usize,
+    pub discrepancies: usize,
+    pub discrepancy_pct: f64,
+    pub apis: Vec<ApiTotals>,
+}
+
+#[derive(Serialize)]
+pub struct ApiTotals {
+    pub api: &'static str,
+    #[serde(skip)]
+    pub name: &'static str,
+    pub matched_packages: usize,
+    pub fingerprint_matches: usize,
+    pub batches: usize,
+    pub failed_batches: usize,
+    /// Failed batches by error category, across every game.
+    pub error_categories: BTreeMap<&'static str, usize>,
+}
+
+#[derive(Serialize)]
+pub struct BatchTiming {
+    /// Position of the batch in dispatch order.
+    pub index: usize,
+    pub fingerprints: usize,
+    /// Latency of the final attempt.
+    pub latency_ms: u64,
+    /// Latency across every attempt, including retry delays.
+    pub total_latency_ms: u64,
+}
+
+#[derive(Serialize)]
+pub struct BatchFailure {
+    /// Position of the batch in dispatch order.
+    pub index: usize,
+    pub fingerprints: usize,
+    /// `AuditError::kind` of the final attempt's error.
+    pub kind: &'static str,
+    pub error: String,
+    /// Request id header of the final attempt's response, to hand to the
+    /// API's support.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SlaStatus {
+    Pass,
+    Fail,
+    /// No batch succeeded, so there is no latency to judge.
+    Unknown,
+}
+
+/// Discrepant packages missing from the same APIs that share a fingerprint,
+/// usually a library bundled in each of them.
+#[derive(Serialize)]
+pub struct DiscrepancyGroup {
+    pub fingerprint: u32,
+    /// Folder the fingerprint belongs to in the first package of the group.
+    pub folder: String,
+    /// Keys of the APIs that matched none of the packages.
+    pub missing_from: Vec<&'static str>,
+    /// Summed over the packages, for ordering the text output.
+    pub download_count: f64,
+    pub packages: Vec<GroupedPackage>,
+}
+
+/// How a package some APIs matched failed to match on another.
+#[derive(Serialize)]
+pub struct MismatchExplanation {
+    pub id: i32,
+    pub name: String,
+    pub download_count: f64,
+    /// Key of the API that didn't match the package.
+    pub api: &'static str,
+    pub cause: MismatchCause,
+    /// Every module of the package's latest files, sorted by folder.
+    pub modules: Vec<ModuleMatch>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MismatchCause {
+    /// The API didn't even partially match the package, so it's missing
+    /// the file from its index altogether.
+    FileMissing,
+    /// The API partially matched the package, so some of its modules are
+    /// indexed and others differ, which points at the fingerprints rather
+    /// than the index.
+    ModuleDiffers,
+}
+
+impl MismatchCause {
+    pub fn name(self) -> &'static str {
+        match self {
+            MismatchCause::FileMissing => "whole file missing",
+            MismatchCause::ModuleDiffers => "module differs",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ModuleMatch {
+    pub folder: String,
+    /// Fingerprint sent for the module.
+    pub fingerprint: u32,
+    /// Whether the API's partially matched file has the same fingerprint.
+    pub matched: bool,
+    /// Fingerprint the API's partially matched file has for the same
+    /// folder, when it differs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub indexed_fingerprint: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct GroupedPackage {
+    pub id: i32,
+    pub name: String,
+}
+
+#[derive(Serialize)]
+pub struct PartialMismatch {
+    pub id: i32,
+    pub name: String,
+    pub download_count: f64,
+    /// Keys of the APIs that only partially matched the package.
+    pub partial: Vec<&'static str>,
+    /// Keys of the APIs that exactly matched it.
+    pub exact: Vec<&'static str>,
+}
+
+#[derive(Serialize)]
+pub struct AttributionConflict {
+    pub fingerprint: u32,
+    /// Folder name of the fingerprint in the first API's match.
+    pub folder: String,
+    /// Packages the fingerprint was matched to, keyed by API, sorted by id.
+    pub packages: BTreeMap<&'static str, Vec<GroupedPackage>>,
+}
+
+#[derive(Serialize)]
+pub struct MetadataMismatch {
+    /// The lowest fingerprint matched to these files.
+    pub fingerprint: u32,
+    /// Folder name of the fingerprint in the first API's match.
+    pub folder: String,
+    /// Fingerprints matched to the same files.
+    pub fingerprints: usize,
+    /// Fields that differ between the APIs, such as `file_id`.
+    pub fields: Vec<&'static str>,
+    /// The matched file, keyed by API.
+    pub files: BTreeMap<&'static str, FileMetadata>,
+    /// Downloads of the first API's package, used to rank the mismatch.
+    #[serde(skip)]
+    pub download_count: f64,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct FileMetadata {
+    pub package_id: i32,
+    pub file_id: i64,
+    pub display_name: String,
+    pub download_url: String,
+    pub release_type: u32,
+    pub file_date: String,
+}
+
+impl FileMetadata {
+    fn new(info: &curse::AddonFingerprintInfo) -> FileMetadata {
+        FileMetadata {
+            package_id: info.id,
+            file_id: info.file.id,
+            display_name: info.file.display_name.clone(),
+            download_url: info.file.download_url.clone(),
+            release_type: info.file.release_type,
+            file_date: info.file.file_date.clone(),
+        }
+    }
+
+    /// Names of the fields that differ from `other`.
+    fn differing_fields(&self, other: &FileMetadata) -> Vec<&'static str> {
+        let fields = [
+            ("package_id", self.package_id != other.package_id),
+            ("file_id", self.file_id != other.file_id),
+            ("display_name", self.display_name != other.display_name),
+            ("download_url", self.download_url != other.download_url),
+            ("release_type", self.release_type != other.release_type),
+            ("file_date", self.file_date != other.file_date),
+        ];
+
+        fields
+            .iter()
+            .filter(|(_, differs)| *differs)
+            .map(|(field, _)| *field)
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+pub struct ResponseBytes {
+    /// Body bytes as received, before decompression.
+    pub received: u64,
+    pub decoded: u64,
+    pub mean_received_per_batch: f64,
+    /// Responses per `Content-Encoding`, with `identity` for uncompressed
+    /// ones.
+    pub encodings: BTreeMap<String, usize>,
+}
+
+#[derive(Serialize)]
+pub struct PairAgreement {
+    /// Keys of the two APIs.
+    pub apis: [&'static str; 2],
+    /// Audited packages both APIs matched.
+    pub both: usize,
+    /// Audited packages exactly one of the APIs matched.
+    pub one: usize,
+    pub neither: usize,
+    /// Audited packages left out as their batch failed on either API.
+    pub indeterminate: usize,
+}
+
+/// Wall-clock time of each phase of the audit in milliseconds.
+#[derive(Default, Serialize)]
+pub struct PhaseTimings {
+    /// Searching, or fetching or reading the packages.
+    pub search_ms: u64,
+    /// From the first batch sent to each API until its last response, keyed
+    /// by API. The APIs are fingerprinted at the same time.
+    pub fingerprint_ms: BTreeMap<&'static str, u64>,
+    /// Comparing the responses and building the report.
+    pub report_ms: u64,
+}
+
+#[derive(Serialize)]
+pub struct CategoryReport {
+    pub name: String,
+    /// Audited packages in the category. A package in several categories
+    /// counts towards each.
+    pub packages: usize,
+    /// Share of the category's packages each API matched, keyed by API.
+    pub match_pct: BTreeMap<&'static str, f64>,
+    /// Gap between the best and worst match rate, in percentage points.
+    pub spread_pct: f64,
+}
+
+#[derive(Serialize)]
+pub struct FlavorReport {
+    pub flavor: String,
+    /// Audited packages with a latest file of the flavor.
+    pub packages: usize,
+    /// Share of those packages each API exactly matched to a file of the
+    /// flavor, keyed by API.
+    pub match_pct: BTreeMap<&'static str, f64>,
+}
+
+#[derive(Serialize)]
+pub struct DownloadBucketReport {
+    pub min_downloads: u64,
+    /// Download count the bucket ends below, absent for the last one.
+    pub max_downloads: Option<u64>,
+    pub packages: usize,
+    /// Share of the bucket's packages each API matched, keyed by API.
+    pub match_pct: BTreeMap<&'static str, f64>,
+}
+
+#[derive(Serialize)]
+pub struct VersionDisagreement {
+    pub id: i32,
+    pub name: String,
+    pub download_count: f64,
+    /// Display name of the matched file, keyed by API.
+    pub versions: BTreeMap<&'static str, String>,
+    /// Download URL of the matched file, keyed by API, with `--include-urls`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub download_urls: Option<BTreeMap<&'static str, String>>,
+}
+
+#[derive(Serialize)]
+pub struct KnownIssue {
+    pub id: i32,
+    pub name: String,
+    /// Why `--ignore-file` lists the package.
+    pub reason: String,
+}
+
+#[derive(Serialize)]
+pub struct Freshness {
+    /// Version disagreements where the API matched the newest file, keyed
+    /// by API.
+    pub fresher: BTreeMap<&'static str, usize>,
+    /// Version disagreements where several APIs matched equally new files.
+    pub ties: usize,
+    /// Version disagreements compared by file id, as a matched file had no
+    /// usable date.
+    pub compared_by_id: usize,
+}
+
+/// The files ajour-core deserializes carry no size, so sizes aren't
+/// compared.
+#[derive(Serialize)]
+pub struct FileDistribution {
+    /// Packages every API exactly matched, whose files are compared.
+    pub packages: usize,
+    /// In `--apis` order.
+    pub apis: Vec<ApiFileDistribution>,
+    /// How an API's files systematically differ from the other APIs', such
+    /// as serving older stable files.
+    pub skews: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct ApiFileDistribution {
+    pub api: &'static str,
+    /// Files per release type, keyed `stable`, `beta`, `alpha` or the
+    /// number of a type Curse doesn't document.
+    pub release_types: BTreeMap<String, usize>,
+    pub stable_pct: f64,
+    /// Average days the API's file was older than the newest file any API
+    /// matched for the package, absent if no file had a usable date.
+    pub mean_days_behind: Option<f64>,
+    /// Packages whose file dates differ between APIs where this API's file
+    /// was the only oldest.
+    pub oldest: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PackageStatus {
+    pub id: i32,
+    pub name: String,
+    /// Absent from reports written before it was added.
+    #[serde(default)]
+    pub download_count: f64,
+    /// Keys of the APIs that exactly matched this package.
+    pub matched_by: Vec<String>,
+    /// Keys of the APIs that only partially matched this package. With
+    /// `--count-partial` they're in `matched_by` too.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub partial_on: Vec<String>,
+    /// Keys of the APIs whose batch with this package failed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failed_on: Vec<String>,
+    /// Download URL of the matched file, keyed by API, with `--include-urls`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_urls: Option<BTreeMap<String, String>>,
+    /// File names of the package's latest files, only kept for packages
+    /// some APIs matched and others didn't.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub latest_files: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct UnmatchedPackage {
+    pub id: i32,
+    pub name: String,
+}
+
+impl AuditReport {
+    pub(crate) fn new(
+        opts: &Opts,
+        packages: &[curse::Package],
+        results: &[ApiResults],
+    ) -> AuditReport {
+        let package_ids = results
+            .iter()
+            .map(|result| result.package_ids(opts.count_partial))
+            .collect::<Vec<_>>();
+
+        let exact_ids = results
+            .iter()
+            .map(|result| result.package_ids(false))
+            .collect::<Vec<_>>();
+        // Packages each API partially matched without exactly matching any of
+        // their files
+        let partial_ids = results
+            .iter()
+            .zip(&exact_ids)
+            .map(|(result, exact)| {
+                result
+                    .partial_matches()
+                    .iter()
+                    .map(|i| i.id)
+                    .filter(|id| !exact.contains(id))
+                    .collect::<BTreeSet<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let unique_package_ids = package_ids.iter().flatten().collect::<BTreeSet<_>>();
+        // A package missing from an API whose batch with it failed isn't a
+        // discrepancy, there's just no telling whether it would have matched
+        let discrepancies = unique_package_ids
+            .iter()
+            .filter(|id| {
+                results
+                    .iter()
+                    .zip(&package_ids)
+                    .any(|(result, ids)| !ids.contains(id) && !result.failed_packages.contains(id))
+            })
+            .count();
+        let indeterminate_packages = results
+            .iter()
+            .flat_map(|result| &result.failed_packages)
+            .collect::<BTreeSet<_>>()
+            .len();
+
+        let discrepancy_pct = if unique_package_ids.is_empty() {
+            0.0
+        } else {
+            discrepancies as f64 / unique_package_ids.len() as f64 * 100.0
+        };
+
+        let apis = results
+            .iter()
+            .zip(&package_ids)
+            .zip(&partial_ids)
+            .map(|((result, ids), partial)| {
+                let exact_matches = result.exact_matches();
+
+                let matched_fingerprints = if opts.include_fingerprints_in_report {
+                    let mut fingerprints = exact_matches
+                        .iter()
+                        .flat_map(|m| m.file.modules.iter().map(|m| m.fingerprint))
+                        .collect::<Vec<_>>();
+                    fingerprints.sort_unstable();
+                    fingerprints.dedup();
+
+                    Some(fingerprints)
+                } else {
+                    None
+                };
+
+                let mut latencies = result
+                    .responses
+                    .iter()
+                    .filter(|r| r.result.is_ok())
+                    .map(|r| r.latency.as_millis() as u64)
+                    .collect::<Vec<_>>();
+                latencies.sort_unstable();
+
+                let latency_p95_ms = percentile(&latencies, 95.0);
+
+                let timings = result
+                    .responses
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, r)| r.result.is_ok())
+                    .map(|(index, r)| BatchTiming {
+                        index,
+                        fingerprints: r.fingerprints,
+                        latency_ms: r.latency.as_millis() as u64,
+                        total_latency_ms: r.total_latency.as_millis() as u64,
+                    });
+
+                let failures = result
+                    .responses
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, r)| {
+                        r.result.as_ref().err().map(|e| BatchFailure {
+                            index,
+                            fingerprints: r.fingerprints,
+                            kind: e.kind(),
+                            error: e.to_string(),
+                            request_id: e.request_id().map(str::to_owned),
+                        })
+                    })
+                    .collect();
+
+                // Ties go to the earliest batch for both, hence the reversed
+                // index when looking for the slowest
+                let slowest_batch = timings
+                    .clone()
+                    .max_by_key(|t| (t.latency_ms, Reverse(t.index)));
+                let fastest_batch = timings.min_by_key(|t| (t.latency_ms, t.index));
+
+                let algorithms = result
+                    .responses
+                    .iter()
+                    .filter_map(|r| r.fingerprint_algorithm.as_deref())
+                    .collect::<BTreeSet<_>>();
+
+                let fingerprint_algorithm = if algorithms.is_empty() {
+                    UNKNOWN_ALGORITHM.to_owned()
+                } else {
+                    algorithms.into_iter().collect::<Vec<_>>().join(", ")
+                };
+
+                let schema_drift = if opts.detect_schema_drift {
+                    let mut drift = SchemaDrift::default();
+
+                    for response in &result.responses {
+                        if let Some(response) = &response.schema_drift {
+                            drift.merge(response);
+                        }
+                    }
+
+                    Some(drift)
+                } else {
+                    None
+                };
+
+                let http_versions = result
+                    .responses
+                    .iter()
+                    .filter_map(|r| r.http_version.clone())
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .collect();
+
+                let (request_bytes, sent_bytes) = result
+                    .responses
+                    .iter()
+                    .filter_map(|r| r.request_bytes)
+                    .fold((0, 0), |(request, sent), (r, s)| (request + r, sent + s));
+
+                let compression_ratio =
+                    if opts.compress_request && opts.replay.is_none() && request_bytes > 0 {
+                        Some(sent_bytes as f64 / request_bytes as f64)
+                    } else {
+                        None
+                    };
+
+                let sized = result
+                    .responses
+                    .iter()
+                    .filter_map(|r| r.response_bytes.map(|bytes| (bytes, &r.content_encoding)))
+                    .collect::<Vec<_>>();
+
+                let response_bytes = if sized.is_empty() {
+                    None
+                } else {
+                    let received = sized.iter().map(|((r, _), _)| r).sum::<u64>();
+                    let mut encodings = BTreeMap::new();
+
+                    for (_, encoding) in &sized {
+                        let encoding = encoding.as_deref().unwrap_or("identity");
+                        *encodings.entry(encoding.to_owned()).or_default() += 1;
+                    }
+
+                    Some(ResponseBytes {
+                        received,
+                        decoded: sized.iter().map(|((_, d), _)| *d as u64).sum(),
+                        mean_received_per_batch: received as f64 / sized.len() as f64,
+                        encodings,
+                    })
+                };
+
+                let attempts = result.responses.iter().map(|r| r.attempts).sum::<usize>();
+                let succeeded = result.responses.len() - result.failed_batches();
+
+                let error_rate = if attempts == 0 {
+                    0.0
+                } else {
+                    (attempts - succeeded) as f64 / attempts as f64 * 100.0
+                };
+
+                let seconds = result.fingerprint_duration().as_secs_f64();
+                let per_second = |count: usize| {
+                    if seconds > 0.0 {
+                        count as f64 / seconds
+                    } else {
+                        0.0
+                    }
+                };
+
+                let sla = opts.latency_sla.map(|sla| match latency_p95_ms {
+                    Some(p95) if p95 <= sla => SlaStatus::Pass,
+                    Some(_) => SlaStatus::Fail,
+                    None => SlaStatus::Unknown,
+                });
+
+                ApiReport {
+                    api: result.api_choice.key(),
+                    name: result.api_choice.name(),
+                    matched_packages: ids.len(),
+                    fingerprint_matches: exact_matches.len(),
+                    partial_matches: result.partial_matches().len(),
+                    partially_matched_packages: partial.len(),
+                    batches: result.responses.len(),
+                    attempts,
+                    failed_batches: result.failed_batches(),
+                    oversized_responses: result.oversized_responses(),
+                    errors: result.error_kinds(),
+                    error_categories: result.error_categories(),
+                    error_statuses: result.error_statuses(),
+                    failures,
+                    success_rate: result.success_rate(),
+                    latency_p50_ms: percentile(&latencies, 50.0),
+                    latency_p95_ms,
+                    latency_p99_ms: percentile(&latencies, 99.0),
+                    error_rate,
+                    requests_per_second: per_second(attempts),
+                    fingerprints_per_second: per_second(
+                        result
+                            .responses
+                            .iter()
+                            .filter(|r| r.result.is_ok())
+                            .map(|r| r.fingerprints)
+                            .sum(),
+                    ),
+                    retry_overhead_ms: result
+                        .responses
+                        .iter()
+                        .map(|r| (r.total_latency - r.latency).as_millis() as u64)
+                        .sum(),
+                    rate_limited: result.responses.iter().map(|r| r.rate_limited).sum(),
+                    rate_limit_wait_ms: result
+                        .responses
+                        .iter()
+                        .map(|r| r.rate_limit_wait.as_millis() as u64)
+                        .sum(),
+                    slowest_batch,
+                    fastest_batch,
+                    concurrency: None,
+                    fingerprint_algorithm,
+                    http_versions,
+                    compression_ratio,
+                    response_bytes,
+                    sla,
+                    matched_fingerprints,
+                    download_urls: None,
+                    changelogs: None,
+                    repeats: None,
+                    schema_drift,
+                }
+            })
+            .collect();
+
+        let packages_by_id = packages
+            .iter()
+            .map(|p| (p.id, p))
+            .collect::<HashMap<_, _>>();
+
+        let matched_files = results
+            .iter()
+            .map(ApiResults::matched_files)
+            .collect::<Vec<_>>();
+
+        let download_urls = |id: &i32| {
+            if !opts.include_urls {
+                return None;
+            }
+
+            let urls = results
+                .iter()
+                .zip(&matched_files)
+                .filter_map(|(result, files)| {
+                    files
+                        .get(id)
+                        .map(|file| (result.api_choice.key(), file.download_url.clone()))
+                })
+                .collect::<BTreeMap<_, _>>();
+
+            Some(urls)
+        };
+
+        let version_disagreements = unique_package_ids
+            .iter()
+            .filter_map(|id| {
+                let versions = results
+                    .iter()
+                    .zip(&matched_files)
+                    .filter_map(|(result, files)| {
+                        files
+                            .get(id)
+                            .map(|file| (result.api_choice.key(), file.display_name.clone()))
+                    })
+                    .collect::<BTreeMap<_, _>>();
+
+                let distinct = versions.values().collect::<BTreeSet<_>>();
+
+                if distinct.len() > 1 {
+                    let package = packages_by_id.get(id);
+
+                    Some(VersionDisagreement {
+                        id: **id,
+                        name: package.map_or("unknown", |p| p.name.as_str()).to_owned(),
+                        download_count: package.map_or(0.0, |p| p.download_count),
+                        versions,
+                        download_urls: download_urls(id),
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let freshness = freshness(&version_disagreements, results, &matched_files);
+        let file_distribution = file_distribution(results, &matched_files);
+
+        let attribution_conflicts = attribution_conflicts(&packages_by_id, results);
+        let metadata_mismatches = metadata_mismatches(&packages_by_id, results);
+
+        let mut partial_mismatches = packages
+            .iter()
+            .filter_map(|package| {
+                let keys = |ids: &[BTreeSet<i32>]| {
+                    results
+                        .iter()
+                        .zip(ids)
+                        .filter(|(_, ids)| ids.contains(&package.id))
+                        .map(|(result, _)| result.api_choice.key())
+                        .collect::<Vec<_>>()
+                };
+
+                let partial = keys(&partial_ids);
+                let exact = keys(&exact_ids);
+
+                if partial.is_empty() || exact.is_empty() {
+                    return None;
+                }
+
+                Some(PartialMismatch {
+                    id: package.id,
+                    name: package.name.clone(),
+                    download_count: package.download_count,
+                    partial,
+                    exact,
+                })
+            })
+            .collect::<Vec<_>>();
+        partial_mismatches.sort_by_key(|m| m.id);
+
+        let pairwise = if opts.compare_apis_pairwise {
+            Some(pairwise(packages, results, &package_ids))
+        } else {
+            None
+        };
+
+        let discrepancy_groups = if opts.dedupe_report {
+            Some(discrepancy_groups(packages, results, &package_ids))
+        } else {
+            None
+        };
+
+        let index = FingerprintIndex::new(packages);
+        let fingerprint_attribution = if opts.attribute_fingerprints {
+            Some(index.attribute(results))
+        } else {
+            None
+        };
+
+        let mismatch_explanations = if opts.explain_mismatches {
+            Some(mismatch_explanations(packages, results, &package_ids))
+        } else {
+            None
+        };
+
+        let mut category_packages = BTreeMap::<&str, Vec<i32>>::new();
+
+        for package in packages {
+            for category in &package.categories {
+                category_packages
+                    .entry(category.name.as_str())
+                    .or_default()
+                    .push(package.id);
+            }
+        }
+
+        let mut categories = category_packages
+            .into_iter()
+            .map(|(name, ids)| {
+                let match_pct = results
+                    .iter()
+                    .zip(&package_ids)
+                    .map(|(result, matched)| {
+                        let count = ids.iter().filter(|id| matched.contains(id)).count();
+
+                        (
+                            result.api_choice.key(),
+                            count as f64 / ids.len() as f64 * 100.0,
+                        )
+                    })
+                    .collect::<BTreeMap<_, _>>();
+
+                let highest = match_pct.values().cloned().fold(0.0, f64::max);
+                let lowest = match_pct.values().cloned().fold(100.0, f64::min);
+
+                CategoryReport {
+                    name: name.to_owned(),
+                    packages: ids.len(),
+                    match_pct,
+                    spread_pct: highest - lowest,
+                }
+            })
+            .collect::<Vec<_>>();
+        // Stable, so ties stay sorted by name
+        categories.sort_by(|a, b| {
+            b.spread_pct
+                .partial_cmp(&a.spread_pct)
+                .unwrap_or(Ordering::Equal)
+        });
+
+        let flavors = flavors(packages, results);
+        let downloads = download_buckets(packages, results, &package_ids);
+
+        let fully_unmatched = if opts.include_unmatched {
+            let exact_ids = exact_ids.iter().flatten().collect::<BTreeSet<_>>();
+
+            let mut unmatched = packages
+                .iter()
+                .filter(|p| !exact_ids.contains(&p.id))
+                .filter(|p| package_fingerprints(p).next().is_some())
+                .map(|p| UnmatchedPackage {
+                    id: p.id,
+                    name: p.name.clone(),
+                })
+                .collect::<Vec<_>>();
+            unmatched.sort_by_key(|p| p.id);
+
+            Some(unmatched)
+        } else {
+            None
+        };
+
+        let mut packages = packages
+            .iter()
+            .map(|package| {
+                let matched_by = results
+                    .iter()
+                    .zip(&package_ids)
+                    .filter(|(_, ids)| ids.contains(&package.id))
+                    .map(|(result, _)| result.api_choice.key().to_owned())
+                    .collect::<Vec<_>>();
+                // Only kept where the APIs disagree, to keep the report small
+                let latest_files = if !matched_by.is_empty() && matched_by.len() < results.len() {
+                    package
+                        .latest_files
+                        .iter()
+                        .map(|f| f.file_name.clone())
+                        .collect()
+                } else {
+                    vec![]
+                };
+
+                PackageStatus {
+                    id: package.id,
+                    name: package.name.clone(),
+                    download_count: package.download_count,
+                    matched_by,
+                    partial_on: results
+                        .iter()
+                        .zip(&partial_ids)
+                        .filter(|(_, ids)| ids.contains(&package.id))
+                        .map(|(result, _)| result.api_choice.key().to_owned())
+                        .collect(),
+                    failed_on: results
+                        .iter()
+                        .filter(|result| result.failed_packages.contains(&package.id))
+                        .map(|result| result.api_choice.key().to_owned())
+                        .collect(),
+                    download_urls: download_urls(&package.id).map(|urls| {
+                        urls.into_iter()
+                            .map(|(api, url)| (api.to_owned(), url))
+                            .collect()
+                    }),
+                    latest_files,
+                }
+            })
+            .collect::<Vec<_>>();
+        packages.sort_by_key(|p| p.id);
+
+        AuditReport {
+            run_id: String::new(),
+            started_at: String::new(),
+            interrupted: shutdown::requested(),
+            packages_fetched: packages.len(),
+            packages_audited: packages.len(),
+            sample_seed: None,
+            count_partial: opts.count_partial,
+            unique_packages: unique_package_ids.len(),
+            discrepancies,
+            indeterminate_packages,
+            discrepancy_pct,
+            apis,
+            version_disagreements,
+            freshness,
+            file_distribution,
+            discrepancy_groups,
+            mismatch_explanations,
+            partial_mismatches,
+            attribution_conflicts,
+            shared_fingerprints: index.shared(),
+            fingerprint_attribution,
+            metadata_mismatches,
+            file_hashes: None,
+            fingerprint_self_test: None,
+            pairwise,
+            categories,
+            flavors,
+            downloads,
+            retries_used: 0,
+            retry_budget: None,
+            packages,
+            known_issues: vec![],
+            fully_unmatched,
+            phases: PhaseTimings {
+                fingerprint_ms: results
+                    .iter()
+                    .map(|r| {
+                        let ms = r.fingerprint_duration().as_millis() as u64;
+                        (r.api_choice.key(), ms)
+                    })
+                    .collect(),
+                ..PhaseTimings::default()
+            },
+            spilled_to_disk: false,
+            warnings: vec![],
+        }
+    }
+
+    /// Writes the summary, rendered from `template` if set, followed by the
+    /// discrepancies and version disagreements, each limited to the `top`
+    /// most downloaded if set.
+    pub fn write_text(
+        &self,
+        out: &mut impl Write,
+        top: Option<usize>,
+        template: Option<&str>,
+    ) -> io::Result<()> {
+        let template = match template {
+            Some(template) => template.to_owned(),
+            None => self.default_template(),
+        };
+
+        if self.interrupted {
+            writeln!(
+                out,
+                "PARTIAL REPORT: the run was interrupted, batches that hadn't completed count as failed"
+            )?;
+        }
+
+        write!(out, "{}", self.render_template(&template))?;
+
+        for api in &self.apis {
+            writeln!(
+                out,
+                "{} of {} {} batches succeeded ({:.1}%)",
+                api.batches - api.failed_batches,
+                api.batches,
+                api.name,
+                api.success_rate
+            )?;
+        }
+
+        for api in &self.apis {
+            if let (Some(p50), Some(p95), Some(p99)) =
+                (api.latency_p50_ms, api.latency_p95_ms, api.latency_p99_ms)
+            {
+                writeln!(
+                    out,
+                    "{} latency p50 {}ms, p95 {}ms, p99 {}ms",
+                    api.name, p50, p95, p99
+                )?;
+            }
+
+            if api.attempts > 0 {
+                writeln!(
+                    out,
+                    "{} throughput {:.1} requests/s, {:.0} fingerprints/s, {:.1}% of requests failed",
+                    api.name, api.requests_per_second, api.fingerprints_per_second, api.error_rate
+                )?;
+            }
+
+            if api.partial_matches > 0 {
+                writeln!(
+                    out,
+                    "{} partially matched {} files, {} packages only partially",
+                    api.name, api.partial_matches, api.partially_matched_packages
+                )?;
+            }
+
+            if api.fingerprint_algorithm != UNKNOWN_ALGORITHM {
+                writeln!(
+                    out,
+                    "{} fingerprint algorithm {}",
+                    api.name, api.fingerprint_algorithm
+                )?;
+            }
+
+            if let Some(ratio) = api.compression_ratio {
+                writeln!(
+                    out,
+                    "{} request bodies compressed to {:.1}% of their size",
+                    api.name,
+                    ratio * 100.0
+                )?;
+            }
+
+            if let Some(bytes) = &api.response_bytes {
+                let encodings = bytes
+                    .encodings
+                    .iter()
+                    .map(|(encoding, count)| format!("{} {}", count, encoding))
+                    .collect::<Vec<_>>();
+
+                writeln!(
+                    out,
+                    "{} responses took {} bytes over the wire for {} decoded, {:.0} per batch ({})",
+                    api.name,
+                    bytes.received,
+                    bytes.decoded,
+                    bytes.mean_received_per_batch,
+                    encodings.join(", ")
+                )?;
+            }
+
+            if !api.http_versions.is_empty() {
+                writeln!(
+                    out,
+                    "{} responses came over {}",
+                    api.name,
+                    api.http_versions.join(", ")
+                )?;
+            }
+
+            if let Some(concurrency) = api.concurrency {
+                writeln!(
+                    out,
+                    "{} adaptive concurrency settled at {}",
+                    api.name, concurrency
+                )?;
+            }
+
+            if !api.errors.is_empty() {
+                let errors = api
+                    .errors
+                    .iter()
+                    .map(|(kind, count)| format!("{} {}", count, kind))
+                    .collect::<Vec<_>>();
+
+                writeln!(out, "{} failed batches: {}", api.name, errors.join(", "))?;
+
+                let mut categories = api
+                    .error_categories
+                    .iter()
+                    .map(|(category, count)| format!("{} {}", count, category))
+                    .collect::<Vec<_>>();
+                categories.extend(
+                    api.error_statuses
+                        .iter()
+                        .map(|(status, count)| format!("{} with status {}", count, status)),
+                );
+
+                writeln!(
+                    out,
+                    "{} failed batches by category: {}",
+                    api.name,
+                    categories.join(", ")
+                )?;
+            }
+
+            let timed_out = api
+                .failures
+                .iter()
+                .filter(|f| f.kind == "timeout")
+                .map(|f| f.index.to_string())
+                .collect::<Vec<_>>();
+
+            if !timed_out.is_empty() {
+                writeln!(
+                    out,
+                    "{} batches that timed out: {}",
+                    api.name,
+                    timed_out.join(", ")
+                )?;
+            }
+
+            let request_ids = api
+                .failures
+                .iter()
+                .filter_map(|f| f.request_id.as_deref())
+                .collect::<Vec<_>>();
+
+            if !request_ids.is_empty() {
+                writeln!(
+                    out,
+                    "{} request ids of failed batches: {}",
+                    api.name,
+                    request_ids.join(", ")
+                )?;
+            }
+
+            if api.oversized_responses > 0 {
+                writeln!(
+                    out,
+                    "{} rejected {} responses over --max-response-bytes",
+                    api.name, api.oversized_responses
+                )?;
+            }
+
+            if api.rate_limited > 0 {
+                writeln!(
+                    out,
+                    "{} rate limited {} requests, which waited {}ms in total",
+                    api.name, api.rate_limited, api.rate_limit_wait_ms
+                )?;
+            }
+
+            if let Some(check) = &api.download_urls {
+                let other = check.other_statuses.values().sum::<usize>();
+
+                writeln!(
+                    out,
+                    "{} download URLs: {} checked, {} ok, {} redirected, {} dead, {} other statuses, {} failed",
+                    api.name,
+                    check.checked,
+                    check.ok,
+                    check.redirected,
+                    check.dead,
+                    other,
+                    check.failed
+                )?;
+            }
+
+            if let Some(check) = &api.changelogs {
+                let elsewhere = check
+                    .broken
+                    .iter()
+                    .filter(|broken| broken.ok_elsewhere)
+                    .map(|broken| broken.package_id.to_string())
+                    .collect::<Vec<_>>();
+
+                writeln!(
+                    out,
+                    "{} changelogs: {} checked, {} ok, {} empty, {} not found, {} garbled, {} failed",
+                    api.name,
+                    check.checked,
+                    check.ok,
+                    check.empty,
+                    check.not_found,
+                    check.garbled,
+                    check.failed
+                )?;
+
+                if !elsewhere.is_empty() {
+                    writeln!(
+                        out,
+                        "{} changelogs broken but readable on another API: {}",
+                        api.name,
+                        elsewhere.join(", ")
+                    )?;
+                }
+            }
+
+            if let Some(check) = &api.repeats {
+                writeln!(
+                    out,
+                    "{} repeats: {} sends of {} batches, {} failed, {} with changing matches",
+                    api.name,
+                    check.repeats,
+                    check.batches,
+                    check.failed_repeats,
+                    check.flaky.len()
+                )?;
+
+                for flaky in &check.flaky {
+                    let packages = flaky
+                        .packages
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>();
+
+                    let changed = if packages.is_empty() {
+                        "same packages, different files".to_owned()
+                    } else {
+                        format!("packages {}", packages.join(", "))
+                    };
+
+                    writeln!(
+                        out,
+                        "  batch {} ({} fingerprints): {} different results, {}",
+                        flaky.index, flaky.fingerprints, flaky.distinct_results, changed
+                    )?;
+                }
+            }
+
+            if let Some(drift) = api.schema_drift.as_ref().filter(|d| !d.is_empty()) {
+                let uncaptured = drift.uncaptured.iter().map(|(path, count)| {
+                    format!("\n  {} not captured, in {} responses", path, count)
+                });
+                let null = drift
+                    .null
+                    .iter()
+                    .map(|(path, count)| format!("\n  {} null, in {} responses", path, count));
+
+                writeln!(
+                    out,
+                    "{} schema drift across {} responses:{}",
+                    api.name,
+                    drift.responses,
+                    uncaptured.chain(null).collect::<String>()
+                )?;
+            }
+
+            if api.retry_overhead_ms > 0 {
+                writeln!(
+                    out,
+                    "{} spent {}ms on failed attempts and retry delays",
+                    api.name, api.retry_overhead_ms
+                )?;
+            }
+
+            match api.sla {
+                Some(SlaStatus::Pass) => writeln!(out, "{} met the latency SLA", api.name)?,
+                Some(SlaStatus::Fail) => writeln!(out, "{} missed the latency SLA", api.name)?,
+                Some(SlaStatus::Unknown) => {
+                    writeln!(out, "{} latency SLA unknown, no batch succeeded", api.name)?
+                }
+                None => {}
+            }
+        }
+
+        match self.retry_budget {
+            Some(budget) => writeln!(
+                out,
+                "{} of {} retries in the budget used",
+                self.retries_used, budget
+            )?,
+            None => writeln!(out, "{} retries used", self.retries_used)?,
+        }
+
+        writeln!(out, "\nPhase timings:")?;
+        writeln!(out, "  search {}ms", self.phases.search_ms)?;
+
+        for api in &self.apis {
+            if let Some(ms) = self.phases.fingerprint_ms.get(api.api) {
+                writeln!(out, "  {} fingerprints {}ms", api.name, ms)?;
+            }
+        }
+
+        writeln!(out, "  report {}ms", self.phases.report_ms)?;
+
+        if let Some(pairs) = &self.pairwise {
+            self.write_pairwise(out, pairs)?;
+        }
+
+        self.write_discrepancy_sections(out, top)?;
+
+        let indeterminate = self
+            .packages
+            .iter()
+            .filter(|p| !p.failed_on.is_empty())
+            .map(|p| {
+                (
+                    p.download_count,
+                    format!(
+                        "{} ({}): failed on {}",
+                        p.name,
+                        p.id,
+                        p.failed_on.join(", ")
+                    ),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        if !indeterminate.is_empty() {
+            writeln!(out, "\nIndeterminate due to request failure:")?;
+            write_top(out, indeterminate, top)?;
+        }
+
+        if self.file_distribution.packages > 0 {
+            writeln!(
+                out,
+                "\nFiles matched for the {} packages every API matched:",
+                self.file_distribution.packages
+            )?;
+
+            for (api, distribution) in self.apis.iter().zip(&self.file_distribution.apis) {
+                let release_types = distribution
+                    .release_types
+                    .iter()
+                    .map(|(release_type, count)| format!("{} {}", count, release_type))
+                    .collect::<Vec<_>>();
+
+                let behind = distribution
+                    .mean_days_behind
+                    .map(|days| format!(", {:.1} days behind the newest on average", days))
+                    .unwrap_or_default();
+
+                writeln!(
+                    out,
+                    "  {}: {}{}, oldest for {}",
+                    api.name,
+                    release_types.join(", "),
+                    behind,
+                    distribution.oldest
+                )?;
+            }
+
+            for skew in &self.file_distribution.skews {
+                writeln!(out, "  Skew: {}", skew)?;
+            }
+        }
+
+        if !self.flavors.is_empty() {
+            writeln!(out, "\nMatch rate by flavor:")?;
+
+            for f in &self.flavors {
+                let rates = self
+                    .apis
+                    .iter()
+                    .map(|api| {
+                        let pct = f.match_pct.get(api.api).copied().unwrap_or(0.0);
+                        format!("{:.1}% by {}", pct, api.name)
+                    })
+                    .collect::<Vec<_>>();
+
+                writeln!(out, "  {} ({}): {}", f.flavor, f.packages, rates.join(", "))?;
+            }
+        }
+
+        if !self.downloads.is_empty() {
+            writeln!(out, "\nMatch rate by total downloads:")?;
+
+            for bucket in &self.downloads {
+                let rates = self
+                    .apis
+                    .iter()
+                    .map(|api| {
+                        let pct = bucket.match_pct.get(api.api).copied().unwrap_or(0.0);
+                        format!("{:.1}% by {}", pct, api.name)
+                    })
+                    .collect::<Vec<_>>();
+
+                let range = match bucket.max_downloads {
+                    Some(max) => format!("{} to {}", bucket.min_downloads, max),
+                    None => format!("{} and more", bucket.min_downloads),
+                };
+
+                writeln!(
+                    out,
+                    "  {} ({}): {}",
+                    range,
+                    bucket.packages,
+                    rates.join(", ")
+                )?;
+            }
+        }
+
+        if !self.categories.is_empty() {
+            writeln!(out, "\nMatch rate by category:")?;
+            write_top(
+                out,
+                self.categories
+                    .iter()
+                    .map(|c| {
+                        let rates = self
+                            .apis
+                            .iter()
+                            .map(|api| {
+                                let pct = c.match_pct.get(api.api).copied().unwrap_or(0.0);
+                                format!("{:.1}% by {}", pct, api.name)
+                            })
+                            .collect::<Vec<_>>();
+
+                        (
+                            c.spread_pct,
+                            format!("{} ({}): {}", c.name, c.packages, rates.join(", ")),
+                        )
+                    })
+                    .collect(),
+                top,
+            )?;
+        }
+
+        if !self.known_issues.is_empty() {
+            writeln!(out, "\nKnown issues ({}):", self.known_issues.len())?;
+
+            for issue in &self.known_issues {
+                let status = match self.packages.binary_search_by_key(&issue.id, |p| p.id) {
+                    Ok(index) if !self.packages[index].matched_by.is_empty() => {
+                        format!("matched by {}", self.packages[index].matched_by.join(", "))
+                    }
+                    _ => "not matched".to_owned(),
+                };
+
+                writeln!(
+                    out,
+                    "  {} ({}): {} - {}",
+                    issue.name, issue.id, issue.reason, status
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the `--compare-apis-pairwise` agreement as a table with a row
+    /// and column per API.
+    fn write_pairwise(&self, out: &mut impl Write, pairs: &[PairAgreement]) -> io::Result<()> {
+        let cell = |a: &str, b: &str| {
+            pairs
+                .iter()
+                .find(|p| p.apis == [a, b] || p.apis == [b, a])
+                .map(|p| format!("{}/{}/{}", p.both, p.one, p.neither))
+                .unwrap_or_else(|| "-".to_owned())
+        };
+
+        let width = self
+            .apis
+            .iter()
+            .map(|api| api.name.len())
+            .chain(
+                pairs
+                    .iter()
+                    .map(|p| format!("{}/{}/{}", p.both, p.one, p.neither).len()),
+            )
+            .max()
+            .unwrap_or(0);
+
+        writeln!(out, "\nPairwise agreement (both/one/neither matched):")?;
+        write!(out, "  {:<width$}", "", width = width)?;
+
+        for api in &self.apis {
+            write!(out, "  {:<width$}", api.name, width = width)?;
+        }
+
+        writeln!(out)?;
+
+        for row in &self.apis {
+            write!(out, "  {:<width$}", row.name, width = width)?;
+
+            for column in &self.apis {
+                write!(
+                    out,
+                    "  {:<width$}",
+                    cell(row.api, column.api),
+                    width = width
+                )?;
+            }
+
+            writeln!(out)?;
+        }
+
+        let indeterminate = pairs.iter().map(|p| p.indeterminate).max().unwrap_or(0);
+
+        if indeterminate > 0 {
+            writeln!(
+                out,
+                "  up to {} packages per pair left out after failed batches",
+                indeterminate
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes how every API matched each package, most downloaded first, for
+    /// the `search` subcommand.
+    pub fn write_package_list(&self, out: &mut impl Write, top: Option<usize>) -> io::Result<()> {
+        writeln!(out, "\nPackages found:")?;
+
+        let lines = self
+            .packages
+            .iter()
+            .map(|package| {
+                let statuses = self
+                    .apis
+                    .iter()
+                    .map(|api| {
+                        let key = api.api.to_owned();
+                        let status = if package.failed_on.contains(&key) {
+                            "failed"
+                        } else if package.partial_on.contains(&key) {
+                            "partial"
+                        } else if package.matched_by.contains(&key) {
+                            "matched"
+                        } else {
+                            "missing"
+                        };
+
+                        format!("{} {}", api.name, status)
+                    })
+                    .collect::<Vec<_>>();
+
+                let line = format!(
+                    "{} ({}) - {}",
+                    package.name,
+                    package.id,
+                    statuses.join(", ")
+                );
+
+                (package.download_count, line)
+            })
+            .collect();
+
+        write_top(out, lines, top)
+    }
+
+    /// Writes only the discrepancies and version disagreements for
+    /// `--only-discrepancies`, or a single line if there are none.
+    pub fn write_discrepancies(&self, out: &mut impl Write, top: Option<usize>) -> io::Result<()> {
+        if self.discrepancies == 0
+            && self.version_disagreements.is_empty()
+            && self.attribution_conflicts.is_empty()
+            && self.partial_mismatches.is_empty()
+            && self.metadata_mismatches.is_empty()
+            && self
+                .file_hashes
+                .as_ref()
+                .map_or(true, |v| v.mismatches.is_empty())
+        {
+            return writeln!(out, "No discrepancies between the APIs");
+        }
+
+        self.write_discrepancy_sections(out, top)
+    }
+
+    fn write_discrepancy_sections(
+        &self,
+        out: &mut impl Write,
+        top: Option<usize>,
+    ) -> io::Result<()> {
+        // Listed first and in full, as each one is a bug in an API
+        if !self.attribution_conflicts.is_empty() {
+            writeln!(
+                out,
+                "\nCross-API attribution conflicts ({}):",
+                self.attribution_conflicts.len()
+            )?;
+
+            for conflict in &self.attribution_conflicts {
+                let attributions = self
+                    .apis
+                    .iter()
+                    .filter_map(|api| {
+                        let packages = conflict.packages.get(api.api)?;
+                        let packages = packages
+                            .iter()
+                            .map(|p| format!("{} ({})", p.name, p.id))
+                            .collect::<Vec<_>>();
+
+                        Some(format!("{} matched {}", api.name, packages.join(", ")))
+                    })
+                    .collect::<Vec<_>>();
+
+                writeln!(
+                    out,
+                    "  {} ({}): {}",
+                    conflict.folder,
+                    conflict.fingerprint,
+                    attributions.join("; ")
+                )?;
+            }
+        }
+
+        if let Some(attribution) = &self.fingerprint_attribution {
+            let foreign = attribution
+                .iter()
+                .filter(|a| a.foreign())
+                .map(|a| {
+                    let origins = a
+                        .origins
+                        .iter()
+                        .map(|o| format!("{} of {}", o.folder, o.package_id))
+                        .collect::<Vec<_>>();
+                    let matched = self
+                        .apis
+                        .iter()
+                        .filter_map(|api| {
+                            let id = a.matched_to.get(api.api)?;
+
+                            Some(format!("{} matched {}", api.name, id))
+                        })
+                        .collect::<Vec<_>>();
+                    let line = format!(
+                        "{} from {}: {}",
+                        a.fingerprint,
+                        origins.join(", "),
+                        matched.join("; ")
+                    );
+
+                    (a.origins.len() as f64, line)
+                })
+                .collect::<Vec<_>>();
+
+            if !foreign.is_empty() {
+                writeln!(
+                    out,
+                    "\nFingerprints matched to a package they weren't submitted for ({}, {} shared by several packages):",
+                    foreign.len(),
+                    self.shared_fingerprints
+                )?;
+                write_top(out, foreign, top)?;
+            }
+        }
+
+        if let Some(groups) = &self.discrepancy_groups {
+            if !groups.is_empty() {
+                writeln!(out, "\nDiscrepancies by shared fingerprint:")?;
+                write_top(
+                    out,
+                    groups
+                        .iter()
+                        .map(|g| {
+                            let packages = g
+                                .packages
+                                .iter()
+                                .map(|p| format!("{} ({})", p.name, p.id))
+                                .collect::<Vec<_>>();
+
+                            (
+                                g.download_count,
+                                format!(
+                                    "{} ({}) missing from {}, {} packages: {}",
+                                    g.folder,
+                                    g.fingerprint,
+                                    g.missing_from.join(", "),
+                                    g.packages.len(),
+                                    packages.join(", ")
+                                ),
+                            )
+                        })
+                        .collect(),
+                    top,
+                )?;
+            }
+        }
+
+        if let Some(explanations) = &self.mismatch_explanations {
+            if !explanations.is_empty() {
+                writeln!(out, "\nWhy discrepancies didn't match:")?;
+                write_top(
+                    out,
+                    explanations
+                        .iter()
+                        .map(|e| {
+                            let api = self
+                                .apis
+                                .iter()
+                                .find(|api| api.api == e.api)
+                                .map_or(e.api, |api| api.name);
+
+                            let mut line =
+                                format!("{} ({}) on {}: {}", e.name, e.id, api, e.cause.name());
+
+                            if e.cause == MismatchCause::ModuleDiffers {
+                                let differing = e
+                                    .modules
+                                    .iter()
+                                    .filter(|m| !m.matched)
+                                    .map(|m| match m.indexed_fingerprint {
+                                        Some(indexed) => format!(
+                                            "{} {} indexed as {}",
+                                            m.folder, m.fingerprint, indexed
+                                        ),
+                                        None => {
+                                            format!("{} {} not indexed", m.folder, m.fingerprint)
+                                        }
+                                    })
+                                    .collect::<Vec<_>>();
+
+                                line.push_str(&format!(
+                                    ", {} of {} modules matched",
+                                    e.modules.len() - differing.len(),
+                                    e.modules.len()
+                                ));
+
+                                if !differing.is_empty() {
+                                    line.push_str(&format!(" - {}", differing.join(", ")));
+                                }
+                            }
+
+                            (e.download_count, line)
+                        })
+                        .collect(),
+                    top,
+                )?;
+            }
+        }
+
+        // The groups already cover every discrepancy
+        if self.discrepancy_groups.is_none() {
+            for api in &self.apis {
+                let missing = self
+                    .packages
+                    .iter()
+                    .filter(|p| {
+                        !p.matched_by.is_empty() && !p.matched_by.iter().any(|m| m == api.api)
+                    })
+                    .filter(|p| !p.failed_on.iter().any(|f| f == api.api))
+                    .map(|p| {
+                        let line = if p.latest_files.is_empty() {
+                            format!("{} ({})", p.name, p.id)
+                        } else {
+                            format!("{} ({}) - {}", p.name, p.id, p.latest_files.join(", "))
+                        };
+
+                        (p.download_count, line)
+                    })
+                    .collect::<Vec<_>>();
+
+                if !missing.is_empty() {
+                    writeln!(out, "\nMatched by other APIs but not {}:", api.name)?;
+                    write_top(out, missing, top)?;
+                }
+            }
+        }
+
+        if !self.version_disagreements.is_empty() {
+            writeln!(out, "\nVersion disagreements:")?;
+            write_top(
+                out,
+                self.version_disagreements
+                    .iter()
+                    .map(|d| {
+                        let versions = d
+                            .versions
+                            .iter()
+                            .map(|(api, version)| format!("{}={}", api, version))
+                            .collect::<Vec<_>>();
+
+                        (
+                            d.download_count,
+                            format!("{} ({}): {}", d.name, d.id, versions.join(", ")),
+                        )
+                    })
+                    .collect(),
+                top,
+            )?;
+
+            let fresher = self
+                .apis
+                .iter()
+                .map(|api| {
+                    let count = self.freshness.fresher.get(api.api).copied().unwrap_or(0);
+                    format!("{} for {}", api.name, count)
+                })
+                .collect::<Vec<_>>();
+
+            write!(
+                out,
+                "Newer file: {}, {} tied",
+                fresher.join(", "),
+                self.freshness.ties
+            )?;
+
+            if self.freshness.compared_by_id > 0 {
+                write!(
+                    out,
+                    " ({} compared by file id for lack of a date)",
+                    self.freshness.compared_by_id
+                )?;
+            }
+
+            writeln!(out)?;
+        }
+
+        if !self.partial_mismatches.is_empty() {
+            writeln!(out, "\nPartial matches where other APIs matched exactly:")?;
+            write_top(
+                out,
+                self.partial_mismatches
+                    .iter()
+                    .map(|m| {
+                        (
+                            m.download_count,
+                            format!(
+                                "{} ({}): partial on {}, exact on {}",
+                                m.name,
+                                m.id,
+                                m.partial.join(", "),
+                                m.exact.join(", ")
+                            ),
+                        )
+                    })
+                    .collect(),
+                top,
+            )?;
+        }
+
+        if !self.metadata_mismatches.is_empty() {
+            writeln!(
+                out,
+                "\nSame fingerprint, different file metadata ({}):",
+                self.metadata_mismatches.len()
+            )?;
+            write_top(
+                out,
+                self.metadata_mismatches
+                    .iter()
+                    .map(|m| {
+                        let files = self
+                            .apis
+                            .iter()
+                            .filter_map(|api| {
+                                let file = m.files.get(api.api)?;
+
+                                Some(format!(
+                                    "{} file {} ({}, release type {}, {})",
+                                    api.name,
+                                    file.file_id,
+                                    file.display_name,
+                                    file.release_type,
+                                    file.file_date
+                                ))
+                            })
+                            .collect::<Vec<_>>();
+
+                        (
+                            m.download_count,
+                            format!(
+                                "{} ({}, {} fingerprints): {} differ - {}",
+                                m.folder,
+                                m.fingerprint,
+                                m.fingerprints,
+                                m.fields.join(", "),
+                                files.join("; ")
+                            ),
+                        )
+                    })
+                    .collect(),
+                top,
+            )?;
+        }
+
+        if let Some(verification) = &self.file_hashes {
+            writeln!(
+                out,
+                "\nFile hashes: {} of {} sampled fingerprints identical, {} failed to download",
+                verification.identical, verification.sampled, verification.failed
+            )?;
+
+            if let Some(dir) = &verification.kept_in {
+                writeln!(out, "  Downloads kept in {}", dir.display())?;
+            }
+        }
+
+        if let Some(verification) = self
+            .file_hashes
+            .as_ref()
+            .filter(|v| !v.mismatches.is_empty())
+        {
+            writeln!(
+                out,
+                "\nSame fingerprint, different file contents ({}):",
+                verification.mismatches.len()
+            )?;
+            write_top(
+                out,
+                verification
+                    .mismatches
+                    .iter()
+                    .map(|m| {
+                        let files = self
+                            .apis
+                            .iter()
+                            .filter_map(|api| {
+                                let file = m.files.get(api.api)?;
+
+                                Some(format!(
+                                    "{} file {} ({} bytes, sha256 {})",
+                                    api.name, file.file_id, file.bytes, file.sha256
+                                ))
+                            })
+                            .collect::<Vec<_>>();
+
+                        (
+                            0.0,
+                            format!("{} ({}): {}", m.folder, m.fingerprint, files.join("; ")),
+                        )
+                    })
+                    .collect(),
+                top,
+            )?;
+        }
+
+        if let Some(test) = &self.fingerprint_self_test {
+            writeln!(
+                out,
+                "\nRecomputed fingerprints: {} of {} folders agree with the APIs, {} of {} files failed to download",
+                test.agreed, test.folders, test.failed, test.sampled
+            )?;
+            write_top(
+                out,
+                test.mismatches
+                    .iter()
+                    .map(|m| {
+                        let computed = match (m.computed, &m.error) {
+                            (Some(computed), _) => format!("recomputed {}", computed),
+                            (None, Some(error)) => error.clone(),
+                            (None, None) => "not recomputed".to_owned(),
+                        };
+
+                        (
+                            0.0,
+                            format!(
+                                "{} ({}) file {}: advertised {} by {}, {}",
+                                m.folder,
+                                m.package_id,
+                                m.file_id,
+                                m.advertised,
+                                m.apis.join(", "),
+                                computed
+                            ),
+                        )
+                    })
+                    .collect(),
+                top,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// The `--template` that reproduces the fixed summary.
+    fn default_template(&self) -> String {
+        // Nothing to compare with a single API, so only its health
+        if let [api] = self.apis.as_slice() {
+            return format!(
+                "{{{key}_matches}} of {{total}} packages matched by {} ({{{key}_match_pct}}%) with {{{key}_fingerprint_matches}} fingerprint matches\n",
+                api.name,
+                key = api.api
+            );
+        }
+
+        let mut template = "{unique_packages} unique packages between both APIs\n".to_owned();
+
+        for api in &self.apis {
+            template.push_str(&format!(
+                "{{{key}_matches}} packages from {} with {{{key}_fingerprint_matches}} fingerprint matches\n",
+                api.name,
+                key = api.api
+            ));
+        }
+
+        template
+    }
+
+    /// Replaces every placeholder of `template`, which `validate_template`
+    /// has already checked, and turns `\n` into line breaks.
+    fn render_template(&self, template: &str) -> String {
+        let mut values = vec![
+            ("total".to_owned(), self.packages_audited.to_string()),
+            (
+                "packages_fetched".to_owned(),
+                self.packages_fetched.to_string(),
+            ),
+            (
+                "unique_packages".to_owned(),
+                self.unique_packages.to_string(),
+            ),
+            ("discrepancies".to_owned(), self.discrepancies.to_string()),
+            (
+                "discrepancy_pct".to_owned(),
+                format!("{:.1}", self.discrepancy_pct),
+            ),
+        ];
+
+        for api in &self.apis {
+            values.extend(vec![
+                (
+                    format!("{}_matches", api.api),
+                    api.matched_packages.to_string(),
+                ),
+                (
+                    format!("{}_fingerprint_matches", api.api),
+                    api.fingerprint_matches.to_string(),
+                ),
+                (
+                    format!("{}_match_pct", api.api),
+                    format!("{:.1}", self.match_rate(api)),
+                ),
+                (
+                    format!("{}_failed_batches", api.api),
+                    api.failed_batches.to_string(),
+                ),
+                (
+                    format!("{}_success_rate", api.api),
+                    format!("{:.1}", api.success_rate),
+                ),
+            ]);
+        }
+
+        let mut rendered = template.replace("\\n", "\n");
+
+        for (name, value) in values {
+            rendered = rendered.replace(&format!("{{{}}}", name), &value);
+        }
+
+        if !rendered.ends_with('\n') {
+            rendered.push('\n');
+        }
+
+        rendered
+    }
+
+    /// Share of the audited packages `api` matched, leaving out known
+    /// issues and treating an empty audit as fully matched.
+    pub fn match_rate(&self, api: &ApiReport) -> f64 {
+        let packages = self
+            .packages
+            .iter()
+            .filter(|p| !self.is_known_issue(p.id))
+            .collect::<Vec<_>>();
+
+        if packages.is_empty() {
+            return 100.0;
+        }
+
+        let matched = packages
+            .iter()
+            .filter(|p| p.matched_by.iter().any(|m| m == api.api))
+            .count();
+
+        matched as f64 / packages.len() as f64 * 100.0
+    }
+
+    /// Whether `--ignore-file` lists the package with this id.
+    pub fn is_known_issue(&self, id: i32) -> bool {
+        self.known_issues
+            .binary_search_by_key(&id, |issue| issue.id)
+            .is_ok()
+    }
+
+    /// One-line `key=value` summary written by `--summary-file`.
+    pub fn summary_line(&self) -> String {
+        let failed_batches = self.apis.iter().map(|a| a.failed_batches).sum::<usize>();
+        let status = if failed_batches == 0 { "ok" } else { "error" };
+
+        let mut line = format!("status={}", status);
+
+        for api in &self.apis {
+            line.push_str(&format!(" {}={}", api.api, api.matched_packages));
+        }
+
+        line.push_str(&format!(
+            " discrepancies={} discrepancy_pct={:.1}\n",
+            self.discrepancies, self.discrepancy_pct
+        ));
+
+        line
+    }
+}
+
+/// Every placeholder a `--template` may use when auditing `api_keys`.
+pub fn template_placeholders(api_keys: &[&str]) -> Vec<String> {
+    let mut names = [
+        "total",
+        "packages_fetched",
+        "unique_packages",
+        "discrepancies",
+        "discrepancy_pct",
+    ]
+    .iter()
+    .map(|name| name.to_string())
+    .collect::<Vec<_>>();
+
+    for key in api_keys {
+        for suffix in &[
+            "matches",
+            "fingerprint_matches",
+            "match_pct",
+            "failed_batches",
+            "success_rate",
+        ] {
+            names.push(format!("{}_{}", key, suffix));
+        }
+    }
+
+    names
+}
+
+/// Checks that every `{placeholder}` of `template` is one of `placeholders`,
+/// returning the unknown ones.
+pub fn validate_template(template: &str, placeholders: &[String]) -> Result<(), Vec<String>> {
+    let unknown = template
+        .split('{')
+        .skip(1)
+        .filter_map(|rest| rest.find('}').map(|end| &rest[..end]))
+        .filter(|name| !placeholders.iter().any(|p| p == name))
+        .map(|name| name.to_owned())
+        .collect::<Vec<_>>();
+
+    if unknown.is_empty() {
+        Ok(())
+    } else {
+        Err(unknown)
+    }
+}
+
+impl Totals {
+    /// Sums `reports`, which all cover the same APIs in the same order.
+    pub fn new(reports: &[&AuditReport]) -> Totals {
+        let unique_packages = reports.iter().map(|r| r.unique_packages).sum::<usize>();
+        let discrepancies = reports.iter().map(|r| r.discrepancies).sum::<usize>();
+
+        let discrepancy_pct = if unique_packages == 0 {
+            0.0
+        } else {
+            discrepancies as f64 / unique_packages as f64 * 100.0
+        };
+
+        let apis = reports
+            .first()
+            .map(|r| r.apis.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .enumerate()
+            .map(|(i, api)| {
+                let sum = |f: fn(&ApiReport) -> usize| {
+                    reports.iter().filter_map(|r| r.apis.get(i)).map(f).sum()
+                };
+
+                ApiTotals {
+                    api: api.api,
+                    name: api.name,
+                    matched_packages: sum(|a| a.matched_packages),
+                    fingerprint_matches: sum(|a| a.fingerprint_matches),
+                    batches: sum(|a| a.batches),
+                    failed_batches: sum(|a| a.failed_batches),
+                    error_categories: reports
+                        .iter()
+                        .filter_map(|r| r.apis.get(i))
+                        .flat_map(|a| &a.error_categories)
+                        .fold(BTreeMap::new(), |mut categories, (&category, count)| {
+                            *categories.entry(category).or_default() += count;
+                            categories
+                        }),
+                }
+            })
+            .collect();
+
+        Totals {
+            games: reports.len(),
+            packages_audited: reports.iter().map(|r| r.packages_audited).sum(),
+            unique_packages,
+            discrepancies,
+            discrepancy_pct,
+            apis,
+        }
+    }
+
+    pub fn write_text(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(
+            out,
+            "\nAcross {} games: {} packages audited, {} unique matched, {} discrepancies ({:.1}%)",
+            self.games,
+            self.packages_audited,
+            self.unique_packages,
+            self.discrepancies,
+            self.discrepancy_pct
+        )?;
+
+        for api in &self.apis {
+            writeln!(
+                out,
+                "{} packages from {} with {} fingerprint matches, {} of {} batches failed",
+                api.matched_packages,
+                api.name,
+                api.fingerprint_matches,
+                api.failed_batches,
+                api.batches
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes `lines` by descending download count, keeping only the first `top`
+/// and summarizing the rest.
+fn write_top(
+    out: &mut impl Write,
+    mut lines: Vec<(f64, String)>,
+    top: Option<usize>,
+) -> io::Result<()> {
+    lines.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+
+    let shown = top.map_or(lines.len(), |top| top.min(lines.len()));
+
+    for (_, line) in &lines[..shown] {
+        writeln!(out, "  {}", line)?;
+    }
+
+    if shown < lines.len() {
+        writeln!(out, "  ... and {} more", lines.len() - shown)?;
+    }
+
+    Ok(())
+}
+
+/// Splits the packages into buckets of total downloads, a power of ten wide
+/// from 1000 on, and how many of each bucket every API matched. Sampling the
+/// search with `--search-sampling` fills the lower buckets.
+fn download_buckets(
+    packages: &[curse::Package],
+    results: &[ApiResults],
+    package_ids: &[BTreeSet<i32>],
+) -> Vec<DownloadBucketReport> {
+    let mut bucket_packages = BTreeMap::<usize, Vec<i32>>::new();
+
+    for package in packages {
+        let bucket = DOWNLOAD_BUCKETS
+            .iter()
+            .filter(|&&min| package.download_count >= min as f64)
+            .count();
+
+        bucket_packages.entry(bucket).or_default().push(package.id);
+    }
+
+    bucket_packages
+        .into_iter()
+        .map(|(bucket, ids)| {
+            let match_pct = results
+                .iter()
+                .zip(package_ids)
+                .map(|(result, matched)| {
+                    let count = ids.iter().filter(|id| matched.contains(id)).count();
+
+                    (
+                        result.api_choice.key(),
+                        count as f64 / ids.len() as f64 * 100.0,
+                    )
+                })
+                .collect();
+
+            DownloadBucketReport {
+                min_downloads: bucket
+                    .checked_sub(1)
+                    .map_or(0, |index| DOWNLOAD_BUCKETS[index]),
+                max_downloads: DOWNLOAD_BUCKETS.get(bucket).copied(),
+                packages: ids.len(),
+                match_pct,
+            }
+        })
+        .collect()
+}
+
+/// Tallies which API matched the newest file of each version disagreement.
+/// Files are compared by date, or by id if any of them lacks a usable date,
+/// since Curse assigns file ids in upload order.
+fn flavors(packages: &[curse::Package], results: &[ApiResults]) -> Vec<FlavorReport> {
+    let mut flavor_packages = BTreeMap::<&str, BTreeSet<i32>>::new();
+
+    for package in packages {
+        for file in &package.latest_files {
+            flavor_packages
+                .entry(file.game_version_flavor.as_str())
+                .or_default()
+                .insert(package.id);
+        }
+    }
+
+    let matched = results
+        .iter()
+        .map(|result| {
+            result
+                .exact_matches()
+                .into_iter()
+                .map(|m| (m.file.game_version_flavor.as_str(), m.id))
+                .collect::<BTreeSet<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    flavor_packages
+        .into_iter()
+        .map(|(flavor, ids)| {
+            let match_pct = results
+                .iter()
+                .zip(&matched)
+                .map(|(result, matched)| {
+                    let count = ids
+                        .iter()
+                        .filter(|&&id| matched.contains(&(flavor, id)))
+                        .count();
+
+                    (
+                        result.api_choice.key(),
+                        count as f64 / ids.len() as f64 * 100.0,
+                    )
+                })
+                .collect();
+
+            FlavorReport {
+                flavor: flavor.to_owned(),
+                packages: ids.len(),
+                match_pct,
+            }
+        })
+        .collect()
+}
+
+fn freshness(
+    disagreements: &[VersionDisagreement],
+    results: &[ApiResults],
+    matched_files: &[BTreeMap<i32, &curse::File>],
+) -> Freshness {
+    let mut freshness = Freshness {
+        fresher: results.iter().map(|r| (r.api_choice.key(), 0)).collect(),
+        ties: 0,
+        compared_by_id: 0,
+    };
+
+    for disagreement in disagreements {
+        let files = results
+            .iter()
+            .zip(matched_files)
+            .filter_map(|(result, files)| {
+                files
+                    .get(&disagreement.id)
+                    .map(|file| (result.api_choice.key(), *file))
+            })
+            .collect::<Vec<_>>();
+
+        let dates = files
+            .iter()
+            .map(|(api, file)| file.file_date.parse::<Timestamp>().ok().map(|d| (*api, d)))
+            .collect::<Option<Vec<_>>>();
+
+        let newest = match dates {
+            Some(dates) => newest(dates),
+            None => {
+                freshness.compared_by_id += 1;
+                newest(files.iter().map(|(api, file)| (*api, file.id)).collect())
+            }
+        };
+
+        match newest {
+            Some(api) => *freshness.fresher.entry(api).or_default() += 1,
+            None => freshness.ties += 1,
+        }
+    }
+
+    freshness
+}
+
+/// The only API with the greatest value, or `None` if several share it.
+fn newest<T: Ord + Copy>(files: Vec<(&'static str, T)>) -> Option<&'static str> {
+    let max = files.iter().map(|(_, value)| *value).max()?;
+    let mut newest = files.iter().filter(|(_, value)| *value == max);
+
+    match (newest.next(), newest.next()) {
+        (Some((api, _)), None) => Some(api),
+        _ => None,
+    }
+}
+
+/// Compares the files of the packages every API exactly matched, so each
+/// API's distribution covers the same packages.
+fn file_distribution(
+    results: &[ApiResults],
+    matched_files: &[BTreeMap<i32, &curse::File>],
+) -> FileDistribution {
+    let common = match matched_files.split_first() {
+        Some((first, rest)) => first
+            .keys()
+            .filter(|id| rest.iter().all(|files| files.contains_key(id)))
+            .copied()
+            .collect::<Vec<_>>(),
+        None => vec![],
+    };
+
+    let mut apis = results
+        .iter()
+        .map(|result| ApiFileDistribution {
+            api: result.api_choice.key(),
+            release_types: BTreeMap::new(),
+            stable_pct: 0.0,
+            mean_days_behind: None,
+            oldest: 0,
+        })
+        .collect::<Vec<_>>();
+    let mut days_behind = vec![vec![]; apis.len()];
+    let mut dates_differ = 0;
+
+    for id in &common {
+        let files = matched_files
+            .iter()
+            .map(|files| files[id])
+            .collect::<Vec<_>>();
+
+        for (distribution, file) in apis.iter_mut().zip(&files) {
+            let release_type = match file.release_type {
+                1 => "stable".to_owned(),
+                2 => "beta".to_owned(),
+                3 => "alpha".to_owned(),
+                other => other.to_string(),
+            };
+
+            *distribution.release_types.entry(release_type).or_default() += 1;
+        }
+
+        let dates = files
+            .iter()
+            .map(|file| file.file_date.parse::<Timestamp>().ok())
+            .collect::<Option<Vec<_>>>();
+        let dates = match dates {
+            Some(dates) => dates,
+            None => continue,
+        };
+
+        let newest = dates.iter().max().copied();
+        let oldest = dates.iter().min().copied();
+
+        for (days, date) in days_behind.iter_mut().zip(&dates) {
+            let behind = newest.and_then(|newest| newest.duration_since(*date));
+            days.push(behind.map_or(0.0, |d| d.as_secs_f64() / 86_400.0));
+        }
+
+        if newest != oldest {
+            dates_differ += 1;
+
+            let mut oldest_apis = dates
+                .iter()
+                .enumerate()
+                .filter(|(_, d)| Some(**d) == oldest);
+
+            if let (Some((index, _)), None) = (oldest_apis.next(), oldest_apis.next()) {
+                apis[index].oldest += 1;
+            }
+        }
+    }
+
+    for (distribution, days) in apis.iter_mut().zip(&days_behind) {
+        let stable = distribution.release_types.get("stable").copied();
+        distribution.stable_pct = stable.unwrap_or(0) as f64 / common.len().max(1) as f64 * 100.0;
+
+        if !days.is_empty() {
+            distribution.mean_days_behind = Some(days.iter().sum::<f64>() / days.len() as f64);
+        }
+    }
+
+    let mut skews = vec![];
+
+    if apis.len() > 1 {
+        for (result, distribution) in results.iter().zip(&apis) {
+            let others = apis.iter().filter(|other| other.api != distribution.api);
+            let others_stable_pct =
+                others.map(|other| other.stable_pct).sum::<f64>() / (apis.len() - 1) as f64;
+
+            if common.len() >= SKEW_MIN_PACKAGES
+                && (distribution.stable_pct - others_stable_pct).abs() >= SKEW_STABLE_PCT
+            {
+                skews.push(format!(
+                    "{} matched stable files for {:.1}% of packages, the other APIs for {:.1}%",
+                    result.api_choice.name(),
+                    distribution.stable_pct,
+                    others_stable_pct
+                ));
+            }
+
+            if dates_differ >= SKEW_MIN_PACKAGES
+                && distribution.oldest as f64 >= dates_differ as f64 * SKEW_OLDEST_SHARE
+            {
+                skews.push(format!(
+                    "{} matched the oldest file for {} of the {} packages whose file dates differ",
+                    result.api_choice.name(),
+                    distribution.oldest,
+                    dates_differ
+                ));
+            }
+        }
+    }
+
+    FileDistribution {
+        packages: common.len(),
+        apis,
+        skews,
+    }
+}
+
+/// Fingerprints that at least two APIs exactly matched, but to packages none
+/// of which they have in common. A fingerprint shared by several packages,
+/// like a bundled library, only conflicts if the APIs agree on none of them.
+fn attribution_conflicts(
+    packages_by_id: &HashMap<i32, &curse::Package>,
+    results: &[ApiResults],
+) -> Vec<AttributionConflict> {
+    let mut attributions = BTreeMap::<u32, Vec<(&'static str, BTreeSet<i32>)>>::new();
+    let mut folders = HashMap::new();
+
+    for result in results {
+        let mut packages = BTreeMap::<u32, BTreeSet<i32>>::new();
+
+        for info in result.exact_matches() {
+            for module in &info.file.modules {
+                packages
+                    .entry(module.fingerprint)
+                    .or_default()
+                    .insert(info.id);
+                folders
+                    .entry(module.fingerprint)
+                    .or_insert_with(|| module.foldername.clone());
+            }
+        }
+
+        for (fingerprint, ids) in packages {
+            attributions
+                .entry(fingerprint)
+                .or_default()
+                .push((result.api_choice.key(), ids));
+        }
+    }
+
+    attributions
+        .into_iter()
+        .filter(|(_, by_api)| {
+            by_api
+                .iter()
+                .enumerate()
+                .any(|(index, (_, a))| by_api[index + 1..].iter().any(|(_, b)| a.is_disjoint(b)))
+        })
+        .map(|(fingerprint, by_api)| AttributionConflict {
+            fingerprint,
+            folder: folders.remove(&fingerprint).unwrap_or_default(),
+            packages: by_api
+                .into_iter()
+                .map(|(api, ids)| {
+                    let packages = ids
+                        .into_iter()
+                        .map(|id| GroupedPackage {
+                            id,
+                            name: packages_by_id
+                                .get(&id)
+                                .map_or("unknown", |p| p.name.as_str())
+                                .to_owned(),
+                        })
+                        .collect();
+
+                    (api, packages)
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Fingerprints that several APIs exactly matched to files with differing
+/// metadata. Each fingerprint of a file would otherwise repeat the same
+/// mismatch, so fingerprints matched to the same files are reported once.
+fn metadata_mismatches(
+    packages_by_id: &HashMap<i32, &curse::Package>,
+    results: &[ApiResults],
+) -> Vec<MetadataMismatch> {
+    let mut files = BTreeMap::<u32, Vec<(&'static str, FileMetadata)>>::new();
+    let mut folders = HashMap::new();
+
+    for result in results {
+        let mut seen = BTreeSet::new();
+
+        for info in result.exact_matches() {
+            for module in &info.file.modules {
+                // A fingerprint matched to several files is an attribution
+                // question, so only its first file is compared
+                if !seen.insert(module.fingerprint) {
+                    continue;
+                }
+
+                files
+                    .entry(module.fingerprint)
+                    .or_default()
+                    .push((result.api_choice.key(), FileMetadata::new(info)));
+                folders
+                    .entry(module.fingerprint)
+                    .or_insert_with(|| module.foldername.clone());
+            }
+        }
+    }
+
+    let mut mismatches: Vec<MetadataMismatch> = vec![];
+    let mut indices = HashMap::new();
+
+    for (fingerprint, by_api) in files {
+        let first = &by_api[0].1;
+        let fields = by_api[1..]
+            .iter()
+            .flat_map(|(_, file)| first.differing_fields(file))
+            .collect::<BTreeSet<_>>();
+
+        if fields.is_empty() {
+            continue;
+        }
+
+        if let Some(&index) = indices.get(&by_api) {
+            mismatches[index].fingerprints += 1;
+            continue;
+        }
+
+        let download_count = packages_by_id
+            .get(&first.package_id)
+            .map_or(0.0, |p| p.download_count);
+
+        mismatches.push(MetadataMismatch {
+            fingerprint,
+            folder: folders.remove(&fingerprint).unwrap_or_default(),
+            fingerprints: 1,
+            fields: fields.into_iter().collect(),
+            files: by_api.iter().cloned().collect(),
+            download_count,
+        });
+        indices.insert(by_api, mismatches.len() - 1);
+    }
+
+    mismatches
+}
+
+/// Counts, for every pair of APIs, the audited packages both, one or neither
+/// of them matched, leaving out packages whose batch failed on either.
+fn pairwise(
+    packages: &[curse::Package],
+    results: &[ApiResults],
+    package_ids: &[BTreeSet<i32>],
+) -> Vec<PairAgreement> {
+    let mut pairs = vec![];
+
+    for (index, (a, a_ids)) in results.iter().zip(package_ids).enumerate() {
+        for (b, b_ids) in results.iter().zip(package_ids).skip(index + 1) {
+            let mut pair = PairAgreement {
+                apis: [a.api_choice.key(), b.api_choice.key()],
+                both: 0,
+                one: 0,
+                neither: 0,
+                indeterminate: 0,
+            };
+
+            for id in packages.iter().map(|p| &p.id) {
+                if a.failed_packages.contains(id) || b.failed_packages.contains(id) {
+                    pair.indeterminate += 1;
+                    continue;
+                }
+
+                match (a_ids.contains(id), b_ids.contains(id)) {
+                    (true, true) => pair.both += 1,
+                    (false, false) => pair.neither += 1,
+                    _ => pair.one += 1,
+                }
+            }
+
+            pairs.push(pair);
+        }
+    }
+
+    pairs
+}
+
+/// Groups each discrepant package under the fingerprint it shares with the
+/// most other discrepant packages missing from the same APIs, so a library
+/// bundled in many addons is reported once.
+/// Compares the modules of every discrepancy with the file each API missing
+/// it partially matched, if any.
+fn mismatch_explanations(
+    packages: &[curse::Package],
+    results: &[ApiResults],
+    package_ids: &[BTreeSet<i32>],
+) -> Vec<MismatchExplanation> {
+    let mut explanations = vec![];
+
+    for package in packages {
+        if !package_ids.iter().any(|ids| ids.contains(&package.id)) {
+            continue;
+        }
+
+        let modules = package
+            .latest_files
+            .iter()
+            .flat_map(|f| &f.modules)
+            .map(|m| (m.foldername.as_str(), m.fingerprint))
+            .collect::<BTreeSet<_>>();
+
+        for (result, ids) in results.iter().zip(package_ids) {
+            if ids.contains(&package.id) || result.failed_packages.contains(&package.id) {
+                continue;
+            }
+
+            let partial = result.partial_matches();
+            let indexed = partial
+                .iter()
+                .filter(|info| info.id == package.id)
+                .flat_map(|info| &info.file.modules)
+                .map(|m| (m.foldername.as_str(), m.fingerprint))
+                .collect::<Vec<_>>();
+
+            let cause = if indexed.is_empty() {
+                MismatchCause::FileMissing
+            } else {
+                MismatchCause::ModuleDiffers
+            };
+
+            let modules = modules
+                .iter()
+                .map(|&(folder, fingerprint)| {
+                    let matched = indexed.iter().any(|&(_, f)| f == fingerprint);
+                    let indexed_fingerprint = indexed
+                        .iter()
+                        .find(|&&(f, _)| f == folder)
+                        .map(|&(_, fingerprint)| fingerprint)
+                        .filter(|_| !matched);
+
+                    ModuleMatch {
+                        folder: folder.to_owned(),
+                        fingerprint,
+                        matched,
+                        indexed_fingerprint,
+                    }
+                })
+                .collect();
+
+            explanations.push(MismatchExplanation {
+                id: package.id,
+                name: package.name.clone(),
+                download_count: package.download_count,
+                api: result.api_choice.key(),
+                cause,
+                modules,
+            });
+        }
+    }
+
+    explanations.sort_by_key(|e| (e.id, e.api));
+    explanations
+}
+
+fn discrepancy_groups(
+    packages: &[curse::Package],
+    results: &[ApiResults],
+    package_ids: &[BTreeSet<i32>],
+) -> Vec<DiscrepancyGroup> {
+    let discrepant = packages
+        .iter()
+        .filter(|p| package_ids.iter().any(|ids| ids.contains(&p.id)))
+        .map(|p| {
+            let missing_from = results
+                .iter()
+                .zip(package_ids)
+                .filter(|(result, ids)| {
+                    !ids.contains(&p.id) && !result.failed_packages.contains(&p.id)
+                })
+                .map(|(result, _)| result.api_choice.key())
+                .collect::<Vec<_>>();
+            let fingerprints = package_fingerprints(p).collect::<BTreeSet<_>>();
+
+            (p, missing_from, fingerprints)
+        })
+        .filter(|(_, missing_from, _)| !missing_from.is_empty())
+        .collect::<Vec<_>>();
+
+    let mut sharing = HashMap::<(u32, &[&str]), usize>::new();
+
+    for (_, missing_from, fingerprints) in &discrepant {
+        for &fingerprint in fingerprints {
+            *sharing
+                .entry((fingerprint, missing_from.as_slice()))
+                .or_default() += 1;
+        }
+    }
+
+    let mut groups = BTreeMap::<(u32, &[&str]), DiscrepancyGroup>::new();
+
+    for (package, missing_from, fingerprints) in &discrepant {
+        // Ties go to the lowest fingerprint, keeping the grouping stable
+        let fingerprint = fingerprints
+            .iter()
+            .max_by_key(|&&f| (sharing[&(f, missing_from.as_slice())], Reverse(f)));
+
+        let fingerprint = match fingerprint {
+            Some(&fingerprint) => fingerprint,
+            None => continue,
+        };
+
+        let group = groups
+            .entry((fingerprint, missing_from.as_slice()))
+            .or_insert_with(|| DiscrepancyGroup {
+                fingerprint,
+                folder: package
+                    .latest_files
+                    .iter()
+                    .flat_map(|f| &f.modules)
+                    .find(|m| m.fingerprint == fingerprint)
+                    .map_or_else(String::new, |m| m.foldername.clone()),
+                missing_from: missing_from.clone(),
+                download_count: 0.0,
+                packages: vec![],
+            });
+
+        group.download_count += package.download_count;
+        group.packages.push(GroupedPackage {
+            id: package.id,
+            name: package.name.clone(),
+        });
+    }
+
+    groups.into_values().collect()
+}
+
+/// Nearest-rank percentile of an ascending slice.
+pub fn percentile(sorted: &[u64], pct: f64) -> Option<u64> {
+    if sorted.is_empty() {
+        return None;
+    }
+
+    let rank = (pct / 100.0 * sorted.len() as f64).ceil() as usize;
+
+    Some(sorted[rank.saturating_sub(1).min(sorted.len() - 1)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ApiChoice, BatchResponse};
+
+    use serde_json::json;
+    use structopt::StructOpt;
+
+    use std::time::{Duration, Instant};
+
+    fn matched(id: i32, file_id: i64, fingerprint: u32) -> curse::AddonFingerprintInfo {
+        serde_json::from_value(json!({
+            "id": id,
+            "file": {
+                "id": file_id,
+                "displayName": format!("Addon-{}", file_id),
+                "fileName": format!("Addon-{}.zip", file_id),
+                "fileDate": "2020-12-01T18:44:12.303Z",
+                "fileLength": 1000,
+                "releaseType": 1,
+                "fileStatus": 4,
+                "downloadUrl": format!("https://edge.forgecdn.net/files/{}.zip", file_id),
+                "isAlternate": false,
+                "alternateFileId": 0,
+                "dependencies": [],
+                "isAvailable": true,
+                "modules": [{ "foldername": "Addon", "fingerprint": fingerprint, "type": 3 }],
+                "packageFingerprint": fingerprint,
+                "gameVersion": ["9.0.2"],
+                "gameVersionFlavor": "wow_retail"
+            },
+            "latestFiles": []
+        }))
+        .unwrap()
+    }
+
+    fn response(
+        now: Instant,
+        latency_ms: u64,
+        exact_matches: Vec<curse::AddonFingerprintInfo>,
+    ) -> BatchResponse {
+        let latency = Duration::from_millis(latency_ms);
+
+        BatchResponse {
+            enqueued: now,
+            started: now,
+            fingerprints: exact_matches.len(),
+            latency,
+            total_latency: latency,
+            attempts: 1,
+            attempt_latencies: vec![latency],
+            rate_limited: 0,
+            rate_limit_wait: Duration::default(),
+            fingerprint_algorithm: None,
+            http_version: None,
+            request_bytes: None,
+            response_bytes: None,
+            content_encoding: None,
+            schema_drift: None,
+            spill_id: None,
+            result: Ok(curse::FingerprintInfo {
+                exact_matches,
+                partial_matches: vec![],
+            }),
+        }
+    }
+
+    fn render(opts: &Opts, results: &[ApiResults]) -> (Vec<u8>, Vec<u8>) {
+        let report = AuditReport::new(opts, &[], results);
+
+        let json = serde_json::to_vec(&report).unwrap();
+        let mut text = vec![];
+        report.write_text(&mut text, None, None).unwrap();
+
+        (json, text)
+    }
+
+    #[test]
+    fn report_is_identical_whatever_order_the_matches_came_in() {
+        let opts = Opts::from_iter(&["ajour-api-test", "--include-fingerprints-in-report"]);
+        let now = Instant::now();
+
+        let results = |curse: Vec<Vec<(i32, i64, u32)>>, wowup: Vec<Vec<(i32, i64, u32)>>| {
+            let api_results = |api_choice, batches: Vec<Vec<(i32, i64, u32)>>| ApiResults {
+                api_choice,
+                responses: batches
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, batch)| {
+                        let matches = batch
+                            .into_iter()
+                            .map(|(id, file_id, fingerprint)| matched(id, file_id, fingerprint))
+                            .collect();
+
+                        response(now, 100 * (index as u64 + 1), matches)
+                    })
+                    .collect(),
+                failed_packages: BTreeSet::new(),
+            };
+
+            vec![
+                api_results(ApiChoice::Curse, curse),
+                api_results(ApiChoice::WowUp, wowup),
+            ]
+        };
+
+        let first = results(
+            vec![vec![(1, 10, 100), (2, 20, 200)], vec![(3, 30, 300)]],
+            vec![vec![(1, 10, 100)], vec![(3, 31, 301)]],
+        );
+        let second = results(
+            vec![vec![(3, 30, 300), (1, 10, 100)], vec![(2, 20, 200)]],
+            vec![vec![(3, 31, 301)], vec![(1, 10, 100)]],
+        );
+
+        let (first_json, first_text) = render(&opts, &first);
+        let (second_json, second_text) = render(&opts, &second);
+
+        assert_eq!(
+            String::from_utf8(first_json).unwrap(),
+            String::from_utf8(second_json).unwrap()
+        );
+        assert_eq!(
+            String::from_utf8(first_text).unwrap(),
+            String::from_utf8(second_text).unwrap()
+        );
+    }
+}
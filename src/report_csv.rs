@@ -0,0 +1,65 @@
+//! Writes a CSV row per audited package for `--format csv`, with its match
+//! status on each API.
+
+use crate::report::AuditReport;
+
+use std::borrow::Cow;
+use std::io::{self, Write};
+
+/// Writes the header, with a status column named after each API's key.
+pub fn write_header(out: &mut impl Write, report: &AuditReport) -> io::Result<()> {
+    let apis = report.apis.iter().map(|api| api.api).collect::<Vec<_>>();
+
+    writeln!(
+        out,
+        "game_id,id,name,download_count,{},discrepancy",
+        apis.join(",")
+    )
+}
+
+/// Writes the packages of one game, sorted by id. Each API's column is
+/// `matched`, `failed` if its batch with the package failed, `partial` if it
+/// only partially matched it, or `missing`.
+pub fn write(out: &mut impl Write, game_id: u32, report: &AuditReport) -> io::Result<()> {
+    for package in &report.packages {
+        let statuses = report
+            .apis
+            .iter()
+            .map(|api| {
+                if package.matched_by.iter().any(|m| m == api.api) {
+                    "matched"
+                } else if package.failed_on.iter().any(|f| f == api.api) {
+                    "failed"
+                } else if package.partial_on.iter().any(|p| p == api.api) {
+                    "partial"
+                } else {
+                    "missing"
+                }
+            })
+            .collect::<Vec<_>>();
+        let discrepancy = !package.matched_by.is_empty()
+            && (statuses.contains(&"missing") || statuses.contains(&"partial"));
+
+        writeln!(
+            out,
+            "{},{},{},{},{},{}",
+            game_id,
+            package.id,
+            field(&package.name),
+            package.download_count,
+            statuses.join(","),
+            discrepancy
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Quotes a field that would otherwise break the row.
+fn field(value: &str) -> Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
@@ -0,0 +1,54 @@
+use crate::sample;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Delay before retrying a failed batch for the `attempt`th time, counting
+/// from 1.
+///
+/// Doubles from `base` with every attempt up to `max`, then picks a random
+/// point in the upper half of it so batches that failed together don't
+/// retry together.
+pub fn backoff(base: Duration, max: Duration, attempt: usize) -> Duration {
+    let factor = 1u32 << (attempt.saturating_sub(1).min(16) as u32);
+    let delay = base.saturating_mul(factor).min(max);
+    let half = delay.as_millis() as u64 / 2;
+
+    delay - Duration::from_millis(sample::random_below(half + 1))
+}
+
+/// Caps the number of retries across every batch of the run, so a widespread
+/// outage doesn't multiply into a flood of requests.
+pub struct RetryBudget {
+    limit: Option<usize>,
+    used: AtomicUsize,
+}
+
+impl RetryBudget {
+    pub fn new(limit: Option<usize>) -> RetryBudget {
+        RetryBudget {
+            limit,
+            used: AtomicUsize::new(0),
+        }
+    }
+
+    /// Consumes one retry, returning `false` if the budget is exhausted.
+    pub fn try_acquire(&self) -> bool {
+        let limit = self.limit;
+
+        self.used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| match limit {
+                Some(limit) if used >= limit => None,
+                _ => Some(used + 1),
+            })
+            .is_ok()
+    }
+
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::SeqCst)
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+}
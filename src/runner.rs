@@ -0,0 +1,92 @@
+//! Runs audits from other programs, such as Ajour's CI, which would rather
+//! get the report than shell out and parse its output.
+
+use crate::cli::{Format, Opts};
+use crate::date::Timestamp;
+use crate::rate_limit::RateLimits;
+use crate::report::AuditReport;
+use crate::retry::RetryBudget;
+use crate::spill::Spill;
+use crate::{audit_and_report, build_client, check_options, sample, ApiChoice};
+
+use anyhow::anyhow;
+use structopt::StructOpt;
+
+use std::ffi::OsString;
+
+/// Audits the games of a set of options, returning a report per game.
+///
+/// Failures that would exit the command line tool are returned as errors
+/// instead, and the reports aren't written to stdout or `--output-file`.
+/// Logging goes through `tracing`, so it reaches whichever subscriber the
+/// embedding program installed.
+pub struct AuditRunner {
+    opts: Opts,
+}
+
+impl AuditRunner {
+    pub fn new(mut opts: Opts) -> AuditRunner {
+        opts.embedded = true;
+        // Reports are returned rather than written, so nothing may reach
+        // stdout
+        opts.format = Format::Json;
+
+        AuditRunner { opts }
+    }
+
+    /// A runner for the options the command line tool would parse from
+    /// `args`, whose first item is the program name.
+    pub fn from_args<I>(args: I) -> Result<AuditRunner, structopt::clap::Error>
+    where
+        I: IntoIterator,
+        I::Item: Into<OsString> + Clone,
+    {
+        Ok(AuditRunner::new(Opts::from_iter_safe(args)?))
+    }
+
+    pub fn opts(&self) -> &Opts {
+        &self.opts
+    }
+
+    /// The APIs each audit sends fingerprints to, from `--apis`.
+    pub fn backends(&self) -> Result<Vec<ApiChoice>, anyhow::Error> {
+        ApiChoice::parse_list(&self.opts.apis).map_err(|e| anyhow!("{}", e))
+    }
+
+    /// Audits every `--game-id` in order, returning their reports in the
+    /// same order.
+    pub async fn run(&self) -> Result<Vec<AuditReport>, anyhow::Error> {
+        let apis = self.backends()?;
+        check_options(&self.opts, &apis)?;
+
+        let client = build_client(&self.opts)?;
+        let retry_budget = RetryBudget::new(self.opts.retry_budget);
+        let rate_limits = RateLimits::new();
+        let spill = Spill::new(self.opts.max_memory_mb);
+
+        let run_id = sample::run_id();
+        let started_at = Timestamp::now();
+        let mut reports = vec![];
+
+        for &game_id in &self.opts.game_ids {
+            let (game, _) = audit_and_report(
+                &client,
+                &self.opts,
+                &apis,
+                &retry_budget,
+                &rate_limits,
+                &spill,
+                game_id,
+                None,
+            )
+            .await?;
+
+            let mut report = game.report;
+            report.run_id = run_id.clone();
+            report.started_at = started_at.to_string();
+            reports.push(report);
+        }
+
+        Ok(reports)
+    }
+}
@@ -0,0 +1,122 @@
+//! Seeded random sampling of the searched packages and search pages.
+//!
+//! Uses its own SplitMix64 generator rather than `rand`, so a seed reproduces
+//! the same sample regardless of dependency versions.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform-enough value in `0..bound` for sampling purposes.
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
+/// A seed for runs that ask for a sample without `--seed`.
+pub fn random_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+/// A random value in `0..bound`, for spreading out retries rather than
+/// anything that needs to be reproduced.
+pub fn random_below(bound: u64) -> u64 {
+    SplitMix64(random_seed()).next() % bound
+}
+
+/// A random version 4 UUID, identifying a run in its report and logs.
+pub fn run_id() -> String {
+    let mut rng = SplitMix64(random_seed() ^ u64::from(std::process::id()).rotate_left(32));
+    let high = rng.next();
+    let low = rng.next();
+
+    // Version 4 and the RFC 4122 variant
+    let high = (high & !0xf000) | 0x4000;
+    let low = (low & !(0b11 << 62)) | (0b10 << 62);
+
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        high >> 32,
+        (high >> 16) & 0xffff,
+        high & 0xffff,
+        low >> 48,
+        low & 0xffff_ffff_ffff
+    )
+}
+
+/// Picks `size` items with a partial Fisher-Yates shuffle, keeping them in
+/// their original order.
+pub fn sample<T>(items: Vec<T>, size: usize, seed: u64) -> Vec<T> {
+    if size >= items.len() {
+        return items;
+    }
+
+    let mut rng = SplitMix64(seed);
+    let mut indices = (0..items.len()).collect::<Vec<_>>();
+
+    for i in 0..size {
+        let j = i + rng.below(indices.len() - i);
+        indices.swap(i, j);
+    }
+
+    let mut chosen = indices[..size].to_vec();
+    chosen.sort_unstable();
+
+    let mut chosen = chosen.into_iter().peekable();
+
+    items
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, item)| {
+            if chosen.peek() == Some(&i) {
+                chosen.next();
+                Some(item)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Search indices of `pages` random pages of `page_size` packages among the
+/// first `catalog_size`, split as evenly as possible across `strata` equal
+/// ranges of the catalog, in ascending order. A range with fewer pages than
+/// its share has all of them taken.
+pub fn page_starts(
+    pages: usize,
+    page_size: usize,
+    catalog_size: usize,
+    strata: usize,
+    seed: u64,
+) -> Vec<usize> {
+    let catalog_pages = catalog_size / page_size;
+    let mut starts = vec![];
+
+    for stratum in 0..strata {
+        let first = catalog_pages * stratum / strata;
+        let end = catalog_pages * (stratum + 1) / strata;
+        let wanted = pages / strata + usize::from(stratum < pages % strata);
+
+        let chosen = sample(
+            (first..end).collect(),
+            wanted,
+            seed.wrapping_add(stratum as u64),
+        );
+        starts.extend(chosen.into_iter().map(|page| page * page_size));
+    }
+
+    starts
+}
@@ -0,0 +1,114 @@
+//! Validates API responses against the JSON Schemas in `schemas/`,
+//! which pinpoints where a response drifted from the expected shape far
+//! better than serde's errors do.
+//!
+//! Only the keywords those schemas use are supported: `type`, `required`,
+//! `properties`, `items` and `$ref`. Every `#/definitions/<name>` ref resolves
+//! against the shared `schemas/definitions.json`.
+
+use serde_json::{Map, Value};
+
+const DEFINITIONS: &str = include_str!("../schemas/definitions.json");
+pub const CURSE_FINGERPRINT: &str = include_str!("../schemas/curse_fingerprint.json");
+pub const WOWUP_FINGERPRINT: &str = include_str!("../schemas/wowup_fingerprint.json");
+pub const TUKUI_ADDONS: &str = include_str!("../schemas/tukui_addons.json");
+pub const WOWINTERFACE_FILE_LIST: &str = include_str!("../schemas/wowinterface_file_list.json");
+pub const TOWNLONG_YAK_ADDONS: &str = include_str!("../schemas/townlong_yak_addons.json");
+
+/// Validates a response against one of the schemas above, returning every
+/// mismatch as `<path>: <problem>`.
+pub fn validate_response(schema: &str, response: &Value) -> Vec<String> {
+    // The schemas are embedded and always parse
+    let schema = serde_json::from_str::<Value>(schema).unwrap_or_default();
+    let definitions = serde_json::from_str::<Map<String, Value>>(DEFINITIONS).unwrap_or_default();
+
+    let mut errors = vec![];
+    validate(&schema, &definitions, response, "$", &mut errors);
+
+    errors
+}
+
+fn validate(
+    schema: &Value,
+    definitions: &Map<String, Value>,
+    value: &Value,
+    path: &str,
+    errors: &mut Vec<String>,
+) {
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        match reference
+            .strip_prefix("#/definitions/")
+            .and_then(|name| definitions.get(name))
+        {
+            Some(schema) => validate(schema, definitions, value, path, errors),
+            None => errors.push(format!("{}: unresolved schema ref `{}`", path, reference)),
+        }
+        return;
+    }
+
+    if let Some(expected) = schema.get("type") {
+        let allowed = match expected {
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            _ => expected.as_str().into_iter().collect::<Vec<_>>(),
+        };
+
+        if !allowed.iter().any(|t| is_type(value, t)) {
+            errors.push(format!(
+                "{}: expected {}, got {}",
+                path,
+                allowed.join(" or "),
+                type_name(value)
+            ));
+            return;
+        }
+    }
+
+    if let Value::Object(object) = value {
+        for field in schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+        {
+            if !object.contains_key(field) {
+                errors.push(format!("{}: missing required field `{}`", path, field));
+            }
+        }
+
+        if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+            for (field, schema) in properties {
+                if let Some(value) = object.get(field) {
+                    let path = format!("{}.{}", path, field);
+                    validate(schema, definitions, value, &path, errors);
+                }
+            }
+        }
+    }
+
+    if let (Value::Array(items), Some(schema)) = (value, schema.get("items")) {
+        for (i, item) in items.iter().enumerate() {
+            let path = format!("{}[{}]", path, i);
+            validate(schema, definitions, item, &path, errors);
+        }
+    }
+}
+
+fn is_type(value: &Value, name: &str) -> bool {
+    match name {
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        name => type_name(value) == name,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
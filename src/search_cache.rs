@@ -0,0 +1,98 @@
+//! Keeps search response bodies on disk, so iterating on an audit of the
+//! same packages doesn't search again every run.
+//!
+//! Each page is stored under its request URI, which holds every query
+//! parameter, and is reused until it's older than `--search-cache-ttl`.
+
+use crate::cli::Opts;
+use crate::env_var;
+
+use tracing::warn;
+
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+pub struct SearchCache {
+    dir: PathBuf,
+    ttl: Duration,
+    /// Set by `--refresh`, which skips reading the cache but still updates
+    /// it.
+    refresh: bool,
+}
+
+impl SearchCache {
+    /// The cache the options ask for, if any. Recording, replaying and
+    /// `--state-dir` need every search to go through them, so none of them
+    /// uses the cache.
+    pub fn new(opts: &Opts) -> Option<SearchCache> {
+        if opts.search_cache_ttl == 0
+            || opts.record.is_some()
+            || opts.replay.is_some()
+            || opts.state_dir.is_some()
+        {
+            return None;
+        }
+
+        let dir = opts.search_cache_dir.clone().or_else(default_dir)?;
+
+        Some(SearchCache {
+            dir,
+            ttl: Duration::from_secs(opts.search_cache_ttl),
+            refresh: opts.refresh,
+        })
+    }
+
+    /// The cached body of `uri`, unless there's none, it has expired or
+    /// `--refresh` was given.
+    pub fn load(&self, uri: &str) -> Option<Vec<u8>> {
+        if self.refresh {
+            return None;
+        }
+
+        let path = self.path(uri);
+        let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+
+        if age > self.ttl {
+            return None;
+        }
+
+        std::fs::read(&path).ok()
+    }
+
+    /// Stores the body of `uri`. A cache that can't be written only costs a
+    /// search next time, so failures are just logged.
+    pub fn save(&self, uri: &str, body: &[u8]) {
+        let result =
+            std::fs::create_dir_all(&self.dir).and_then(|_| std::fs::write(self.path(uri), body));
+
+        if let Err(e) = result {
+            warn!(
+                "failed to cache the search in {} - {}",
+                self.dir.display(),
+                e
+            );
+        }
+    }
+
+    fn path(&self, uri: &str) -> PathBuf {
+        let name = uri
+            .trim_start_matches("https://")
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect::<String>();
+
+        self.dir.join(format!("{}.json", name))
+    }
+}
+
+/// `ajour-api-test` under `XDG_CACHE_HOME`, or `~/.cache` without it.
+fn default_dir() -> Option<PathBuf> {
+    let cache_home = env_var(&["XDG_CACHE_HOME"])
+        .map(PathBuf::from)
+        .or_else(|| env_var(&["HOME"]).map(|home| PathBuf::from(home).join(".cache")))?;
+
+    Some(cache_home.join("ajour-api-test"))
+}
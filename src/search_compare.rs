@@ -0,0 +1,312 @@
+//! Runs the same query against the Curse search API and the WowUp hub's
+//! search for the `search-compare` subcommand, and diffs what they return,
+//! since Ajour's catalog search behaves differently on each backend.
+//!
+//! Results are paired by name, ignoring case, as the hub doesn't list the
+//! Curse id of its addons.
+
+use crate::cli::{Format, Opts};
+use crate::error::AuditError;
+use crate::{percent_encode, search_packages, send_request, write_json};
+
+use async_std::future::timeout;
+use isahc::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::time::Duration;
+
+const WOWUP_SEARCH_URL: &str = "https://hub.wowup.io/addons/search";
+
+#[derive(Deserialize)]
+struct WowUpSearchResponse {
+    addons: Vec<WowUpAddon>,
+}
+
+#[derive(Deserialize)]
+struct WowUpAddon {
+    name: String,
+    #[serde(default)]
+    author: String,
+    #[serde(default)]
+    total_download_count: f64,
+}
+
+#[derive(Serialize)]
+struct SearchHit {
+    name: String,
+    author: String,
+    download_count: f64,
+}
+
+#[derive(Serialize)]
+struct SharedHit {
+    name: String,
+    /// Position in each API's results, counting from 1.
+    curse_rank: usize,
+    wowup_rank: usize,
+    curse_author: String,
+    wowup_author: String,
+    curse_download_count: f64,
+    wowup_download_count: f64,
+}
+
+#[derive(Serialize)]
+struct SearchComparison<'a> {
+    term: &'a str,
+    curse: Vec<SearchHit>,
+    wowup: Vec<SearchHit>,
+    /// Names only one of the APIs returned, in its order.
+    only_curse: Vec<String>,
+    only_wowup: Vec<String>,
+    /// Results both APIs returned, in Curse's order.
+    shared: Vec<SharedHit>,
+    /// Shared results over the results of either API.
+    overlap_pct: f64,
+    /// Spearman correlation of the shared results' ranks, absent with fewer
+    /// than two of them.
+    rank_correlation: Option<f64>,
+}
+
+/// Searches both APIs for `term` and writes how their results differ.
+pub async fn compare(
+    client: &HttpClient,
+    opts: &Opts,
+    term: &str,
+    game_type: &str,
+    limit: usize,
+    out: &mut impl Write,
+) -> Result<(), anyhow::Error> {
+    let request_timeout = Duration::from_secs(opts.request_timeout);
+
+    let mut curse_opts = opts.clone();
+    curse_opts.search_filter = Some(term.to_owned());
+
+    let curse = search_packages(client, &curse_opts, opts.game_ids[0], opts.sort, 0, limit);
+    let curse = timeout(request_timeout, curse)
+        .await
+        .map_err(|_| AuditError::Timeout(request_timeout))??
+        .into_iter()
+        .map(|package| SearchHit {
+            name: package.name,
+            author: package
+                .authors
+                .first()
+                .map_or_else(String::new, |author| author.name.clone()),
+            download_count: package.download_count,
+        })
+        .collect::<Vec<_>>();
+
+    let wowup = timeout(
+        request_timeout,
+        search_wowup(client, opts, term, game_type, limit),
+    )
+    .await
+    .map_err(|_| AuditError::Timeout(request_timeout))??
+    .into_iter()
+    .map(|addon| SearchHit {
+        name: addon.name,
+        author: addon.author,
+        download_count: addon.total_download_count,
+    })
+    .collect::<Vec<_>>();
+
+    let comparison = diff(term, curse, wowup);
+
+    match opts.format {
+        Format::Json => write_json(out, &comparison, opts.compact)?,
+        _ => write_text(out, &comparison)?,
+    }
+
+    Ok(())
+}
+
+async fn search_wowup(
+    client: &HttpClient,
+    opts: &Opts,
+    term: &str,
+    game_type: &str,
+    limit: usize,
+) -> Result<Vec<WowUpAddon>, AuditError> {
+    let url = format!(
+        "{}/{}?query={}&limit={}",
+        WOWUP_SEARCH_URL,
+        percent_encode(game_type),
+        percent_encode(term),
+        limit
+    );
+
+    let mut request = Request::builder().method("GET").uri(&url);
+
+    if let Some(api_key) = &opts.wowup_api_key {
+        request = request.header("authorization", format!("Bearer {}", api_key));
+    }
+
+    let (body, _, _) = send_request(client, opts, request.body(vec![])?).await?;
+    let response: WowUpSearchResponse =
+        serde_json::from_slice(&body).map_err(|e| AuditError::Deserialize(e.into()))?;
+
+    Ok(response.addons)
+}
+
+fn diff(term: &str, curse: Vec<SearchHit>, wowup: Vec<SearchHit>) -> SearchComparison<'_> {
+    let key = |hit: &SearchHit| hit.name.trim().to_lowercase();
+
+    let wowup_ranks = wowup
+        .iter()
+        .enumerate()
+        .map(|(rank, hit)| (key(hit), rank))
+        .rev()
+        .collect::<HashMap<_, _>>();
+    let curse_ranks = curse
+        .iter()
+        .enumerate()
+        .map(|(rank, hit)| (key(hit), rank))
+        .rev()
+        .collect::<HashMap<_, _>>();
+
+    let mut shared = vec![];
+    let mut only_curse = vec![];
+
+    for (rank, hit) in curse.iter().enumerate() {
+        // A name listed twice is paired once, at its first position
+        if curse_ranks[&key(hit)] != rank {
+            continue;
+        }
+
+        match wowup_ranks.get(&key(hit)) {
+            Some(&wowup_rank) => {
+                let other = &wowup[wowup_rank];
+
+                shared.push(SharedHit {
+                    name: hit.name.clone(),
+                    curse_rank: rank + 1,
+                    wowup_rank: wowup_rank + 1,
+                    curse_author: hit.author.clone(),
+                    wowup_author: other.author.clone(),
+                    curse_download_count: hit.download_count,
+                    wowup_download_count: other.download_count,
+                });
+            }
+            None => only_curse.push(hit.name.clone()),
+        }
+    }
+
+    let only_wowup = wowup
+        .iter()
+        .filter(|hit| !curse_ranks.contains_key(&key(hit)))
+        .map(|hit| hit.name.clone())
+        .collect::<Vec<_>>();
+
+    let total = shared.len() + only_curse.len() + only_wowup.len();
+    let overlap_pct = if total == 0 {
+        100.0
+    } else {
+        shared.len() as f64 / total as f64 * 100.0
+    };
+
+    SearchComparison {
+        term,
+        rank_correlation: rank_correlation(&shared),
+        curse,
+        wowup,
+        only_curse,
+        only_wowup,
+        shared,
+        overlap_pct,
+    }
+}
+
+/// Spearman's rank correlation of the shared results, ranked among
+/// themselves so results only one API returned don't shift it.
+fn rank_correlation(shared: &[SharedHit]) -> Option<f64> {
+    let n = shared.len();
+
+    if n < 2 {
+        return None;
+    }
+
+    let mut by_wowup = (0..n).collect::<Vec<_>>();
+    by_wowup.sort_by_key(|&i| shared[i].wowup_rank);
+
+    let mut wowup_rank = vec![0; n];
+
+    for (rank, &i) in by_wowup.iter().enumerate() {
+        wowup_rank[i] = rank;
+    }
+
+    // Shared results are in Curse's order, so their index is their rank
+    let squared = wowup_rank
+        .iter()
+        .enumerate()
+        .map(|(curse_rank, &wowup_rank)| (curse_rank as f64 - wowup_rank as f64).powi(2))
+        .sum::<f64>();
+    let n = n as f64;
+
+    Some(1.0 - 6.0 * squared / (n * (n * n - 1.0)))
+}
+
+fn write_text(out: &mut impl Write, comparison: &SearchComparison<'_>) -> io::Result<()> {
+    writeln!(
+        out,
+        "Search \"{}\": {} results on Curse, {} on WowUp, {} on both ({:.1}% overlap)",
+        comparison.term,
+        comparison.curse.len(),
+        comparison.wowup.len(),
+        comparison.shared.len(),
+        comparison.overlap_pct
+    )?;
+
+    if let Some(correlation) = comparison.rank_correlation {
+        writeln!(
+            out,
+            "Rank correlation of shared results: {:.2}",
+            correlation
+        )?;
+    }
+
+    for (api, names) in &[
+        ("Curse", &comparison.only_curse),
+        ("WowUp", &comparison.only_wowup),
+    ] {
+        if !names.is_empty() {
+            writeln!(out, "\nOnly on {}:", api)?;
+
+            for name in names.iter() {
+                writeln!(out, "  {}", name)?;
+            }
+        }
+    }
+
+    let differing = comparison
+        .shared
+        .iter()
+        .filter(|hit| {
+            hit.curse_rank != hit.wowup_rank
+                || !hit.curse_author.eq_ignore_ascii_case(&hit.wowup_author)
+        })
+        .collect::<Vec<_>>();
+
+    if !differing.is_empty() {
+        writeln!(out, "\nOn both, ranked or attributed differently:")?;
+
+        for hit in differing {
+            let mut line = format!(
+                "  {} - rank {} on Curse, {} on WowUp",
+                hit.name, hit.curse_rank, hit.wowup_rank
+            );
+
+            if !hit.curse_author.eq_ignore_ascii_case(&hit.wowup_author) {
+                line.push_str(&format!(
+                    ", author {} on Curse, {} on WowUp",
+                    hit.curse_author, hit.wowup_author
+                ));
+            }
+
+            writeln!(out, "{}", line)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,66 @@
+//! Stops a run on Ctrl-C or SIGTERM without losing what it completed.
+//!
+//! The first signal cancels the batches in flight and those still queued,
+//! which then fail as interrupted, so the run goes on to write a report of
+//! the batches that completed, marked as partial. With `--state-dir` every
+//! completed request is already kept, so `--resume` picks up from there. A
+//! second signal is left to the caller of `install`, which the command line
+//! tool exits on at once.
+
+use crate::error::AuditError;
+
+use async_std::task;
+use futures::future::{self, Either};
+use futures::pin_mut;
+use tracing::warn;
+
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// How often a cancellable request checks whether it was interrupted.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Handles Ctrl-C and SIGTERM for the rest of the process, calling
+/// `on_second` on every signal after the first.
+pub fn install(on_second: impl Fn() + Send + 'static) -> Result<(), ctrlc::Error> {
+    ctrlc::set_handler(move || {
+        if REQUESTED.swap(true, Ordering::SeqCst) {
+            on_second();
+            return;
+        }
+
+        warn!("interrupted, writing a partial report of the completed batches, interrupt again to quit at once");
+    })
+}
+
+/// Whether the run was interrupted.
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Runs `request` unless the run is interrupted first, in which case it's
+/// dropped, cancelling it, and fails as `AuditError::Interrupted`.
+pub async fn cancellable<T>(
+    request: impl Future<Output = Result<T, AuditError>>,
+) -> Result<T, AuditError> {
+    if requested() {
+        return Err(AuditError::Interrupted);
+    }
+
+    let interrupted = async {
+        while !requested() {
+            task::sleep(POLL_INTERVAL).await;
+        }
+    };
+
+    pin_mut!(request);
+    pin_mut!(interrupted);
+
+    match future::select(request, interrupted).await {
+        Either::Left((result, _)) => result,
+        Either::Right(_) => Err(AuditError::Interrupted),
+    }
+}
@@ -0,0 +1,145 @@
+//! Bounds the memory held by fingerprint matches for `--max-memory-mb`.
+//!
+//! Once the matches kept in memory pass the limit, the matches of every
+//! further batch are written to a temporary ndjson file instead. Before the
+//! report is built they're read back without the `latest_files` of each
+//! match, which the report doesn't use and which make up most of their size.
+
+use crate::{ApiResults, BatchResult};
+
+use ajour_core::repository::curse;
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// One line of the spill file.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SpilledBatch<T> {
+    exact_matches: T,
+    partial_matches: T,
+}
+
+pub struct Spill {
+    limit: Option<usize>,
+    /// Estimated bytes of matches kept in memory.
+    held: AtomicUsize,
+    file: Mutex<Option<BufWriter<File>>>,
+    /// Batches written to the file, which is also the id of the next one.
+    spilled: AtomicUsize,
+    path: PathBuf,
+}
+
+impl Spill {
+    pub fn new(limit_mb: Option<usize>) -> Spill {
+        let path = std::env::temp_dir().join(format!(
+            "ajour-api-test-spill-{}.ndjson",
+            std::process::id()
+        ));
+
+        Spill {
+            limit: limit_mb.map(|mb| mb * 1024 * 1024),
+            held: AtomicUsize::new(0),
+            file: Mutex::new(None),
+            spilled: AtomicUsize::new(0),
+            path,
+        }
+    }
+
+    /// Keeps the matches of a successful batch in memory while under the
+    /// limit. Past it they're moved to the spill file, returning the id to
+    /// restore them by.
+    pub fn keep(&self, result: &mut BatchResult) -> io::Result<Option<usize>> {
+        let (limit, info) = match (self.limit, result) {
+            (Some(limit), Ok(info)) => (limit, info),
+            _ => return Ok(None),
+        };
+
+        let line = serde_json::to_vec(&SpilledBatch {
+            exact_matches: &info.exact_matches,
+            partial_matches: &info.partial_matches,
+        })?;
+
+        let held = self.held.fetch_add(line.len(), Ordering::SeqCst) + line.len();
+
+        if held <= limit {
+            return Ok(None);
+        }
+
+        let mut file = self.file.lock().unwrap();
+
+        if file.is_none() {
+            *file = Some(BufWriter::new(File::create(&self.path)?));
+        }
+
+        if let Some(out) = &mut *file {
+            out.write_all(&line)?;
+            out.write_all(b"\n")?;
+        }
+
+        info.exact_matches = vec![];
+        info.partial_matches = vec![];
+
+        Ok(Some(self.spilled.fetch_add(1, Ordering::SeqCst)))
+    }
+
+    /// Whether any batch was spilled since the last `restore`.
+    pub fn spilled(&self) -> bool {
+        self.spilled.load(Ordering::SeqCst) > 0
+    }
+
+    /// Moves every spilled batch back into its response, then removes the
+    /// spill file and resets the memory accounting for the next game.
+    pub fn restore(&self, results: &mut [ApiResults]) -> io::Result<()> {
+        let out = self.file.lock().unwrap().take();
+
+        let mut out = match out {
+            Some(out) => out,
+            None => return Ok(()),
+        };
+        out.flush()?;
+        drop(out);
+
+        let mut pending = results
+            .iter_mut()
+            .flat_map(|r| &mut r.responses)
+            .filter_map(|r| r.spill_id.take().map(|id| (id, r)))
+            .collect::<HashMap<_, _>>();
+
+        let reader = BufReader::new(File::open(&self.path)?);
+
+        for (id, line) in reader.lines().enumerate() {
+            let mut batch =
+                serde_json::from_str::<SpilledBatch<Vec<curse::AddonFingerprintInfo>>>(&line?)?;
+
+            for info in batch
+                .exact_matches
+                .iter_mut()
+                .chain(&mut batch.partial_matches)
+            {
+                info.latest_files = vec![];
+            }
+
+            if let Some(Ok(info)) = pending.remove(&id).map(|r| &mut r.result) {
+                info.exact_matches = batch.exact_matches;
+                info.partial_matches = batch.partial_matches;
+            }
+        }
+
+        std::fs::remove_file(&self.path)?;
+
+        self.held.store(0, Ordering::SeqCst);
+        self.spilled.store(0, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+}
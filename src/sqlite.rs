@@ -0,0 +1,290 @@
+//! Appends a run to a SQLite database for `--export-sqlite` and `--store`,
+//! so results can be queried across runs instead of juggling json reports,
+//! and reads the match rates of the runs kept back for `history`.
+
+use crate::report::AuditReport;
+use crate::ApiResults;
+
+use rusqlite::{params, Connection, OpenFlags};
+use serde::Serialize;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Every table is keyed by run and game, so runs append rather than
+/// replace each other.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    run_id TEXT NOT NULL,
+    game_id INTEGER NOT NULL,
+    started_at TEXT NOT NULL,
+    packages_audited INTEGER NOT NULL,
+    unique_packages INTEGER NOT NULL,
+    discrepancies INTEGER NOT NULL,
+    discrepancy_pct REAL NOT NULL,
+    PRIMARY KEY (run_id, game_id)
+);
+
+CREATE TABLE IF NOT EXISTS apis (
+    run_id TEXT NOT NULL,
+    game_id INTEGER NOT NULL,
+    api TEXT NOT NULL,
+    matched_packages INTEGER NOT NULL,
+    fingerprint_matches INTEGER NOT NULL,
+    batches INTEGER NOT NULL,
+    failed_batches INTEGER NOT NULL,
+    success_rate REAL NOT NULL,
+    latency_p50_ms INTEGER,
+    latency_p95_ms INTEGER,
+    latency_p99_ms INTEGER,
+    PRIMARY KEY (run_id, game_id, api)
+);
+
+CREATE TABLE IF NOT EXISTS packages (
+    run_id TEXT NOT NULL,
+    game_id INTEGER NOT NULL,
+    package_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    download_count REAL NOT NULL,
+    discrepancy INTEGER NOT NULL,
+    PRIMARY KEY (run_id, game_id, package_id)
+);
+CREATE INDEX IF NOT EXISTS packages_package_id ON packages (package_id);
+
+CREATE TABLE IF NOT EXISTS matches (
+    run_id TEXT NOT NULL,
+    game_id INTEGER NOT NULL,
+    package_id INTEGER NOT NULL,
+    api TEXT NOT NULL,
+    PRIMARY KEY (run_id, game_id, package_id, api)
+);
+CREATE INDEX IF NOT EXISTS matches_package_id ON matches (package_id, api);
+
+CREATE TABLE IF NOT EXISTS batches (
+    run_id TEXT NOT NULL,
+    game_id INTEGER NOT NULL,
+    api TEXT NOT NULL,
+    batch INTEGER NOT NULL,
+    fingerprints INTEGER NOT NULL,
+    attempts INTEGER NOT NULL,
+    succeeded INTEGER NOT NULL,
+    latency_ms INTEGER NOT NULL,
+    total_latency_ms INTEGER NOT NULL,
+    PRIMARY KEY (run_id, game_id, api, batch)
+);
+";
+
+/// Writes the report and batch latencies of one game, creating the schema
+/// if the database is new.
+pub fn export(
+    path: &Path,
+    game_id: u32,
+    report: &AuditReport,
+    results: &[ApiResults],
+) -> rusqlite::Result<()> {
+    let mut connection = Connection::open(path)?;
+    connection.execute_batch(SCHEMA)?;
+
+    let transaction = connection.transaction()?;
+    let run_id = report.run_id.as_str();
+
+    transaction.execute(
+        "INSERT INTO runs VALUES (?, ?, ?, ?, ?, ?, ?)",
+        params![
+            run_id,
+            game_id,
+            report.started_at,
+            report.packages_audited as i64,
+            report.unique_packages as i64,
+            report.discrepancies as i64,
+            report.discrepancy_pct
+        ],
+    )?;
+
+    for api in &report.apis {
+        transaction.execute(
+            "INSERT INTO apis VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                run_id,
+                game_id,
+                api.api,
+                api.matched_packages as i64,
+                api.fingerprint_matches as i64,
+                api.batches as i64,
+                api.failed_batches as i64,
+                api.success_rate,
+                api.latency_p50_ms.map(|ms| ms as i64),
+                api.latency_p95_ms.map(|ms| ms as i64),
+                api.latency_p99_ms.map(|ms| ms as i64)
+            ],
+        )?;
+    }
+
+    {
+        let mut insert_package =
+            transaction.prepare("INSERT INTO packages VALUES (?, ?, ?, ?, ?, ?)")?;
+        let mut insert_match = transaction.prepare("INSERT INTO matches VALUES (?, ?, ?, ?)")?;
+
+        for package in &report.packages {
+            let discrepancy = !package.matched_by.is_empty()
+                && report.apis.iter().any(|api| {
+                    !package.matched_by.iter().any(|m| m == api.api)
+                        && !package.failed_on.iter().any(|f| f == api.api)
+                });
+
+            insert_package.execute(params![
+                run_id,
+                game_id,
+                package.id,
+                package.name,
+                package.download_count,
+                discrepancy
+            ])?;
+
+            for api in &package.matched_by {
+                insert_match.execute(params![run_id, game_id, package.id, api])?;
+            }
+        }
+
+        let mut insert_batch =
+            transaction.prepare("INSERT INTO batches VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)")?;
+
+        for result in results {
+            for (index, response) in result.responses.iter().enumerate() {
+                insert_batch.execute(params![
+                    run_id,
+                    game_id,
+                    result.api_choice.key(),
+                    index as i64,
+                    response.fingerprints as i64,
+                    response.attempts as i64,
+                    response.result.is_ok(),
+                    response.latency.as_millis() as i64,
+                    response.total_latency.as_millis() as i64
+                ])?;
+            }
+        }
+    }
+
+    transaction.commit()
+}
+
+/// A kept run of one game, with the match rate of each API it audited.
+#[derive(Serialize)]
+pub struct HistoryRun {
+    pub run_id: String,
+    pub game_id: u32,
+    pub started_at: String,
+    pub packages_audited: i64,
+    /// Percentage of the audited packages each API matched, keyed by API.
+    pub match_pct: BTreeMap<String, f64>,
+}
+
+/// The last `last` runs kept of each game, oldest first. The database is
+/// opened read-only, so a mistyped path fails instead of creating an empty
+/// database.
+pub fn history(path: &Path, game_ids: &[u32], last: usize) -> rusqlite::Result<Vec<HistoryRun>> {
+    let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut select = connection.prepare(
+        "SELECT r.run_id, r.started_at, r.packages_audited, a.api, a.matched_packages
+         FROM runs r LEFT JOIN apis a ON a.run_id = r.run_id AND a.game_id = r.game_id
+         WHERE r.game_id = ?
+         ORDER BY r.started_at, r.run_id",
+    )?;
+
+    let mut history = vec![];
+
+    for &game_id in game_ids {
+        let mut runs = Vec::<HistoryRun>::new();
+        let mut rows = select.query(params![game_id])?;
+
+        while let Some(row) = rows.next()? {
+            let run_id: String = row.get(0)?;
+
+            if runs.last().map(|run| &run.run_id) != Some(&run_id) {
+                runs.push(HistoryRun {
+                    run_id,
+                    game_id,
+                    started_at: row.get(1)?,
+                    packages_audited: row.get(2)?,
+                    match_pct: BTreeMap::new(),
+                });
+            }
+
+            let run = runs.last_mut().expect("pushed above");
+
+            if let Some(api) = row.get::<_, Option<String>>(3)? {
+                let matched: i64 = row.get(4)?;
+                let pct = if run.packages_audited == 0 {
+                    100.0
+                } else {
+                    matched as f64 / run.packages_audited as f64 * 100.0
+                };
+
+                run.match_pct.insert(api, pct);
+            }
+        }
+
+        runs.drain(..runs.len().saturating_sub(last));
+        history.extend(runs);
+    }
+
+    Ok(history)
+}
+
+/// Writes a table of the runs of each game, with the change in each API's
+/// match rate since the game's previous run that audited it.
+pub fn write_history(out: &mut impl Write, history: &[HistoryRun]) -> io::Result<()> {
+    if history.is_empty() {
+        return writeln!(out, "No runs kept for these games yet");
+    }
+
+    let games = history
+        .iter()
+        .map(|run| run.game_id)
+        .collect::<BTreeSet<_>>();
+
+    for (index, game_id) in games.into_iter().enumerate() {
+        let runs = history.iter().filter(|run| run.game_id == game_id);
+        let apis = runs
+            .clone()
+            .flat_map(|run| run.match_pct.keys())
+            .collect::<BTreeSet<_>>();
+
+        if index > 0 {
+            writeln!(out)?;
+        }
+
+        writeln!(out, "Game {}:", game_id)?;
+        write!(out, "  {:<25} {:>8}", "Started", "Packages")?;
+
+        for api in &apis {
+            write!(out, " {:>16}", api)?;
+        }
+
+        writeln!(out)?;
+
+        let mut previous = BTreeMap::<&str, f64>::new();
+
+        for run in runs {
+            write!(out, "  {:<25} {:>8}", run.started_at, run.packages_audited)?;
+
+            for api in &apis {
+                let cell = match run.match_pct.get(api.as_str()) {
+                    Some(&pct) => match previous.insert(api.as_str(), pct) {
+                        Some(before) => format!("{:.1}% ({:+.1})", pct, pct - before),
+                        None => format!("{:.1}%", pct),
+                    },
+                    None => "-".to_owned(),
+                };
+
+                write!(out, " {:>16}", cell)?;
+            }
+
+            writeln!(out)?;
+        }
+    }
+
+    Ok(())
+}
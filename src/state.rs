@@ -0,0 +1,84 @@
+//! Keeps a run's completed requests in `--state-dir`, so a run that dies
+//! halfway continues with `--resume` instead of starting over.
+//!
+//! Completed requests are stored as recordings, like `--record` does, and a
+//! resumed run serves every request it finds a recording of. Since requests
+//! are matched by URI and body, the queue of batches left is whatever the
+//! same options produce minus what's already there, per API.
+
+use crate::cli::{self, Opts};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use std::path::Path;
+
+const STATE_FILE: &str = "state.json";
+
+#[derive(Serialize, Deserialize)]
+struct RunState {
+    /// Seed of the run, so a resumed run samples the same packages.
+    seed: u64,
+    /// Arguments the run was started with, to warn about a resumed run
+    /// that differs.
+    args: Vec<String>,
+}
+
+/// Starts keeping the run's state in `--state-dir`, or with `--resume` loads
+/// the state kept there, setting `--seed` to the kept run's.
+pub fn prepare(opts: &mut Opts, seed: u64) -> Result<(), String> {
+    let dir = match &opts.state_dir {
+        Some(dir) => dir.clone(),
+        None => return Ok(()),
+    };
+    let path = dir.join(STATE_FILE);
+    // Kept without credentials, which are passed again on resuming anyway
+    let args = cli::redact_args(&std::env::args().skip(1).collect::<Vec<_>>());
+
+    if opts.resume {
+        let contents = std::fs::read(&path)
+            .map_err(|e| format!("no run to resume in {} - {}", dir.display(), e))?;
+        let state: RunState = serde_json::from_slice(&contents)
+            .map_err(|e| format!("invalid run state {} - {}", path.display(), e))?;
+
+        if without_resume(&state.args) != without_resume(&args) {
+            warn!(
+                "resuming a run started with different arguments ({}), only the requests both send are reused",
+                state.args.join(" ")
+            );
+        }
+
+        if opts.seed.is_some() && opts.seed != Some(state.seed) {
+            warn!("--seed differs from the resumed run's, so its sample differs too");
+        } else {
+            opts.seed = Some(state.seed);
+        }
+
+        info!("Resuming the run kept in {}", dir.display());
+        return Ok(());
+    }
+
+    if !is_empty(&dir) {
+        return Err(format!(
+            "{} already holds a run, continue it with --resume or remove it",
+            dir.display()
+        ));
+    }
+
+    let seed = opts.seed.unwrap_or(seed);
+    opts.seed = Some(seed);
+
+    let state = RunState { seed, args };
+    std::fs::create_dir_all(&dir)
+        .and_then(|_| std::fs::write(&path, serde_json::to_vec_pretty(&state)?))
+        .map_err(|e| format!("failed to write {} - {}", path.display(), e))
+}
+
+/// Whether `dir` is missing or has nothing in it.
+fn is_empty(dir: &Path) -> bool {
+    std::fs::read_dir(dir).map_or(true, |mut entries| entries.next().is_none())
+}
+
+fn without_resume(args: &[String]) -> Vec<&String> {
+    args.iter().filter(|arg| *arg != "--resume").collect()
+}
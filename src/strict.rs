@@ -0,0 +1,112 @@
+//! Mirrors of the fingerprint response types that reject any field they
+//! don't know, for `--strict-json`.
+//!
+//! The response types themselves ignore unknown fields, so an API adding or
+//! renaming one goes unnoticed. These only check field names: every field is
+//! optional and values that aren't matched on are skipped, leaving types and
+//! required fields to the regular parsing and `--validate-response-schema`.
+//! Fields are listed for Curse v2, Curse v1 after its rewrite into the v2
+//! shape, and WowUp, whose response is a subset of Curse's.
+//!
+//! The fields are only ever deserialized, never read.
+#![allow(dead_code)]
+
+use crate::error::AuditError;
+
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use serde_json::Value;
+
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+struct FingerprintResponse {
+    is_cache_built: IgnoredAny,
+    exact_matches: Vec<AddonFingerprintInfo>,
+    exact_fingerprints: IgnoredAny,
+    partial_matches: Vec<AddonFingerprintInfo>,
+    partial_match_fingerprints: IgnoredAny,
+    installed_fingerprints: IgnoredAny,
+    unmatched_fingerprints: IgnoredAny,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+struct AddonFingerprintInfo {
+    id: IgnoredAny,
+    file: File,
+    latest_files: Vec<File>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+struct File {
+    id: IgnoredAny,
+    display_name: IgnoredAny,
+    file_name: IgnoredAny,
+    file_date: IgnoredAny,
+    file_length: IgnoredAny,
+    release_type: IgnoredAny,
+    file_status: IgnoredAny,
+    download_url: IgnoredAny,
+    is_alternate: IgnoredAny,
+    alternate_file_id: IgnoredAny,
+    dependencies: Vec<Dependency>,
+    is_available: IgnoredAny,
+    modules: Vec<Module>,
+    package_fingerprint: IgnoredAny,
+    package_fingerprint_id: IgnoredAny,
+    game_version: IgnoredAny,
+    sortable_game_version: IgnoredAny,
+    game_version_flavor: IgnoredAny,
+    game_version_date_released: IgnoredAny,
+    game_version_id: IgnoredAny,
+    game_version_mapping_id: IgnoredAny,
+    game_id: IgnoredAny,
+    install_metadata: IgnoredAny,
+    changelog: IgnoredAny,
+    has_install_script: IgnoredAny,
+    is_compatible_with_client: IgnoredAny,
+    category_section_package_type: IgnoredAny,
+    restrict_project_file_access: IgnoredAny,
+    project_status: IgnoredAny,
+    project_id: IgnoredAny,
+    render_cache_id: IgnoredAny,
+    file_legacy_mapping_id: IgnoredAny,
+    parent_project_file_id: IgnoredAny,
+    parent_file_legacy_mapping_id: IgnoredAny,
+    file_type_id: IgnoredAny,
+    expose_as_alternative: IgnoredAny,
+    is_server_pack: IgnoredAny,
+    server_pack_file_id: IgnoredAny,
+    // Only sent by Curse v1
+    mod_id: IgnoredAny,
+    download_count: IgnoredAny,
+    file_fingerprint: IgnoredAny,
+    hashes: IgnoredAny,
+    sortable_game_versions: IgnoredAny,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Module {
+    foldername: IgnoredAny,
+    fingerprint: IgnoredAny,
+    r#type: IgnoredAny,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+struct Dependency {
+    id: IgnoredAny,
+    addon_id: IgnoredAny,
+    r#type: IgnoredAny,
+    file_id: IgnoredAny,
+}
+
+/// Fails on the first field of a fingerprint response that isn't known,
+/// naming it.
+pub fn check_fingerprint_response(response: &Value) -> Result<(), AuditError> {
+    FingerprintResponse::deserialize(response)
+        .map(|_| ())
+        .map_err(AuditError::Strict)
+}
@@ -0,0 +1,44 @@
+//! Writes the latency of every fingerprint request as json for
+//! `--timings-file`, for benchmarking the APIs against each other.
+
+use crate::ApiResults;
+
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct RequestTiming {
+    pub game_id: u32,
+    pub api: &'static str,
+    /// Position of the batch in dispatch order.
+    pub batch: usize,
+    /// Attempt of the batch, counting from 1.
+    pub attempt: usize,
+    pub latency_ms: u64,
+    /// Whether this is the batch's final attempt and it succeeded.
+    pub succeeded: bool,
+}
+
+/// Every request sent for one game's batches, by API and then in dispatch
+/// order.
+pub fn collect(game_id: u32, results: &[ApiResults]) -> Vec<RequestTiming> {
+    let mut timings = vec![];
+
+    for result in results {
+        for (index, response) in result.responses.iter().enumerate() {
+            let attempts = response.attempt_latencies.len();
+
+            for (attempt, latency) in response.attempt_latencies.iter().enumerate() {
+                timings.push(RequestTiming {
+                    game_id,
+                    api: result.api_choice.key(),
+                    batch: index,
+                    attempt: attempt + 1,
+                    latency_ms: latency.as_millis() as u64,
+                    succeeded: attempt + 1 == attempts && response.result.is_ok(),
+                });
+            }
+        }
+    }
+
+    timings
+}
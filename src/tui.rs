@@ -0,0 +1,492 @@
+//! Browses a finished audit for `--tui`, as a filterable table of packages
+//! with their match status on each API under a pane of each API's status,
+//! drilling into a package to see its fingerprints and which API matched
+//! each one, or the raw entries every API's responses had for it.
+
+use crate::report::{AuditReport, PackageStatus};
+use crate::ApiResults;
+
+use ajour_core::repository::curse;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Paragraph, Row, Table, TableState};
+use ratatui::{Frame, Terminal};
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Stderr};
+
+/// Rows moved by page up and page down.
+const PAGE: usize = 20;
+
+type Backend = CrosstermBackend<Stderr>;
+
+/// Takes over the terminal until the browser is quit. It draws on stderr,
+/// so a report written to stdout can still be piped.
+pub fn run<'a>(
+    report: &'a AuditReport,
+    packages: &'a [curse::Package],
+    results: &'a [ApiResults],
+) -> io::Result<()> {
+    enable_raw_mode()?;
+    execute!(io::stderr(), EnterAlternateScreen)?;
+
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stderr()))?;
+    let result = Browser::new(report, packages, results).run(&mut terminal);
+
+    // Restore the terminal even if drawing failed
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+
+    result
+}
+
+/// Match status of a package on one API.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Matched,
+    Failed,
+    Missing,
+}
+
+impl Status {
+    fn of(package: &PackageStatus, api: &str) -> Status {
+        if package.matched_by.iter().any(|m| m == api) {
+            Status::Matched
+        } else if package.failed_on.iter().any(|f| f == api) {
+            Status::Failed
+        } else {
+            Status::Missing
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Status::Matched => "matched",
+            Status::Failed => "failed",
+            Status::Missing => "-",
+        }
+    }
+}
+
+struct Browser<'a> {
+    report: &'a AuditReport,
+    packages: HashMap<i32, &'a curse::Package>,
+    /// Exactly matched fingerprints of each package, keyed by API name.
+    matched: Vec<(&'static str, HashMap<i32, HashSet<u32>>)>,
+    /// Exact and partial match entries of each package as the API returned
+    /// them, keyed by API name.
+    payloads: Vec<(
+        &'static str,
+        HashMap<i32, Vec<&'a curse::AddonFingerprintInfo>>,
+    )>,
+    filter: String,
+    editing_filter: bool,
+    only_discrepancies: bool,
+    /// Indexes into the report's packages that pass the filters.
+    rows: Vec<usize>,
+    table: TableState,
+    /// Package being drilled into and how far its detail is scrolled.
+    detail: Option<(usize, u16)>,
+    /// Whether the detail shows the raw response entries rather than the
+    /// fingerprints.
+    raw: bool,
+}
+
+impl<'a> Browser<'a> {
+    fn new(
+        report: &'a AuditReport,
+        packages: &'a [curse::Package],
+        results: &'a [ApiResults],
+    ) -> Browser<'a> {
+        let matched = results
+            .iter()
+            .map(|result| {
+                let mut fingerprints = HashMap::<_, HashSet<_>>::new();
+
+                for m in result.exact_matches() {
+                    fingerprints
+                        .entry(m.id)
+                        .or_default()
+                        .extend(m.file.modules.iter().map(|m| m.fingerprint));
+                }
+
+                (result.api_choice.name(), fingerprints)
+            })
+            .collect();
+
+        let payloads = results
+            .iter()
+            .map(|result| {
+                let mut entries = HashMap::<_, Vec<_>>::new();
+
+                for info in result
+                    .exact_matches()
+                    .into_iter()
+                    .chain(result.partial_matches())
+                {
+                    entries.entry(info.id).or_default().push(info);
+                }
+
+                (result.api_choice.name(), entries)
+            })
+            .collect();
+
+        let mut browser = Browser {
+            report,
+            packages: packages.iter().map(|p| (p.id, p)).collect(),
+            matched,
+            payloads,
+            filter: String::new(),
+            editing_filter: false,
+            only_discrepancies: false,
+            rows: vec![],
+            table: TableState::default(),
+            detail: None,
+            raw: false,
+        };
+        browser.refilter();
+
+        browser
+    }
+
+    fn run(&mut self, terminal: &mut Terminal<Backend>) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            let key = match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => key,
+                _ => continue,
+            };
+
+            if self.editing_filter {
+                match key.code {
+                    KeyCode::Enter | KeyCode::Esc => self.editing_filter = false,
+                    KeyCode::Backspace => {
+                        self.filter.pop();
+                        self.refilter();
+                    }
+                    KeyCode::Char(c) => {
+                        self.filter.push(c);
+                        self.refilter();
+                    }
+                    _ => {}
+                }
+            } else if let Some((_, scroll)) = &mut self.detail {
+                match key.code {
+                    KeyCode::Char('q') => return Ok(()),
+                    KeyCode::Esc | KeyCode::Backspace | KeyCode::Left => self.detail = None,
+                    KeyCode::Char('r') => {
+                        self.raw = !self.raw;
+                        *scroll = 0;
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => *scroll = scroll.saturating_add(1),
+                    KeyCode::Up | KeyCode::Char('k') => *scroll = scroll.saturating_sub(1),
+                    KeyCode::PageDown => *scroll = scroll.saturating_add(PAGE as u16),
+                    KeyCode::PageUp => *scroll = scroll.saturating_sub(PAGE as u16),
+                    _ => {}
+                }
+            } else {
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Char('/') => self.editing_filter = true,
+                    KeyCode::Char('d') => {
+                        self.only_discrepancies = !self.only_discrepancies;
+                        self.refilter();
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+                    KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+                    KeyCode::PageDown => self.move_selection(PAGE as isize),
+                    KeyCode::PageUp => self.move_selection(-(PAGE as isize)),
+                    KeyCode::Enter | KeyCode::Right => {
+                        if let Some(selected) = self.table.selected() {
+                            self.detail = Some((self.rows[selected], 0));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Whether an API missed the package without its batch having failed.
+    fn is_discrepancy(&self, package: &PackageStatus) -> bool {
+        !package.matched_by.is_empty()
+            && self
+                .report
+                .apis
+                .iter()
+                .any(|api| Status::of(package, api.api) == Status::Missing)
+    }
+
+    fn refilter(&mut self) {
+        let needle = self.filter.to_lowercase();
+
+        self.rows = self
+            .report
+            .packages
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| {
+                p.name.to_lowercase().contains(&needle) || p.id.to_string().starts_with(&needle)
+            })
+            .filter(|(_, p)| !self.only_discrepancies || self.is_discrepancy(p))
+            .map(|(index, _)| index)
+            .collect();
+
+        self.table
+            .select(if self.rows.is_empty() { None } else { Some(0) });
+    }
+
+    fn move_selection(&mut self, by: isize) {
+        if self.rows.is_empty() {
+            return;
+        }
+
+        let selected = self.table.selected().unwrap_or(0) as isize + by;
+        let last = self.rows.len() as isize - 1;
+
+        self.table.select(Some(selected.max(0).min(last) as usize));
+    }
+
+    fn draw(&mut self, frame: &mut Frame<'_>) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(self.report.apis.len() as u16 + 2),
+                Constraint::Min(1),
+                Constraint::Length(1),
+            ])
+            .split(frame.size());
+
+        let status = Paragraph::new(self.status_lines())
+            .block(Block::default().borders(Borders::ALL).title("APIs"));
+        frame.render_widget(status, chunks[0]);
+
+        match self.detail {
+            Some((index, scroll)) => {
+                let package = &self.report.packages[index];
+                let (title, lines) = if self.raw {
+                    ("Raw responses", self.raw_lines(package))
+                } else {
+                    ("Package", self.detail_lines(package))
+                };
+
+                let detail = Paragraph::new(lines)
+                    .block(Block::default().borders(Borders::ALL).title(title))
+                    .scroll((scroll, 0));
+
+                frame.render_widget(detail, chunks[1]);
+            }
+            None => {
+                let table = self.table_widget();
+                frame.render_stateful_widget(table, chunks[1], &mut self.table);
+            }
+        }
+
+        let help = if self.editing_filter {
+            format!("Filter: {}_  (enter to apply)", self.filter)
+        } else if self.detail.is_some() {
+            format!(
+                "up/down scroll  r {}  esc back  q quit",
+                if self.raw {
+                    "fingerprints"
+                } else {
+                    "raw responses"
+                }
+            )
+        } else {
+            format!(
+                "{} of {} packages  / filter  d discrepancies only{}  enter details  q quit",
+                self.rows.len(),
+                self.report.packages.len(),
+                if self.only_discrepancies { " (on)" } else { "" }
+            )
+        };
+
+        frame.render_widget(Paragraph::new(help), chunks[2]);
+    }
+
+    /// One line per API with its matches, failed batches and latency.
+    fn status_lines(&self) -> Vec<Line<'static>> {
+        self.report
+            .apis
+            .iter()
+            .map(|api| {
+                let latency = api
+                    .latency_p50_ms
+                    .map(|ms| format!(", p50 {}ms", ms))
+                    .unwrap_or_default();
+
+                let line = Line::from(format!(
+                    "{}: {} packages matched, {} partially, {} of {} batches failed{}",
+                    api.name,
+                    api.matched_packages,
+                    api.partially_matched_packages,
+                    api.failed_batches,
+                    api.batches,
+                    latency
+                ));
+
+                if api.failed_batches > 0 {
+                    line.style(Style::default().fg(Color::Red))
+                } else {
+                    line
+                }
+            })
+            .collect()
+    }
+
+    fn table_widget(&self) -> Table<'static> {
+        let report = self.report;
+
+        let header = ["Id", "Name", "Downloads"]
+            .iter()
+            .map(|h| h.to_string())
+            .chain(report.apis.iter().map(|api| api.name.to_owned()))
+            .collect::<Vec<_>>();
+
+        let rows = self
+            .rows
+            .iter()
+            .map(|&index| {
+                let package = &report.packages[index];
+                let cells = vec![
+                    package.id.to_string(),
+                    package.name.clone(),
+                    format!("{:.0}", package.download_count),
+                ]
+                .into_iter()
+                .chain(
+                    report
+                        .apis
+                        .iter()
+                        .map(|api| Status::of(package, api.api).label().to_owned()),
+                );
+
+                let row = Row::new(cells.collect::<Vec<_>>());
+
+                if self.is_discrepancy(package) {
+                    row.style(Style::default().fg(Color::Yellow))
+                } else {
+                    row
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let widths = [
+            Constraint::Length(10),
+            Constraint::Min(20),
+            Constraint::Length(12),
+        ]
+        .iter()
+        .copied()
+        .chain(report.apis.iter().map(|_| Constraint::Length(10)))
+        .collect::<Vec<_>>();
+
+        Table::new(rows, widths)
+            .header(Row::new(header).style(Style::default().add_modifier(Modifier::BOLD)))
+            .block(Block::default().borders(Borders::ALL).title("Packages"))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+    }
+
+    /// Each API's status for the package, then every fingerprint of its
+    /// latest files along with the APIs that exactly matched it.
+    fn detail_lines(&self, package: &PackageStatus) -> Vec<Line<'static>> {
+        let mut lines = vec![
+            Line::from(format!("{} ({})", package.name, package.id)),
+            Line::from(""),
+        ];
+
+        for api in &self.report.apis {
+            let url = package
+                .download_urls
+                .as_ref()
+                .and_then(|urls| urls.get(api.api))
+                .map(|url| format!(" - {}", url))
+                .unwrap_or_default();
+
+            lines.push(Line::from(format!(
+                "{}: {}{}",
+                api.name,
+                Status::of(package, api.api).label(),
+                url
+            )));
+        }
+
+        let files = match self.packages.get(&package.id) {
+            Some(p) => &p.latest_files,
+            None => {
+                lines.push(Line::from(""));
+                lines.push(Line::from("Fingerprints aren't available for this package"));
+                return lines;
+            }
+        };
+
+        for file in files {
+            lines.push(Line::from(""));
+            lines.push(Line::from(format!(
+                "{} ({})",
+                file.display_name, file.game_version_flavor
+            )));
+
+            for module in &file.modules {
+                let matched_by = self
+                    .matched
+                    .iter()
+                    .filter(|(_, matched)| {
+                        matches!(matched.get(&package.id), Some(f) if f.contains(&module.fingerprint))
+                    })
+                    .map(|(name, _)| *name)
+                    .collect::<Vec<_>>();
+
+                lines.push(Line::from(format!(
+                    "  {:>10} {} - {}",
+                    module.fingerprint,
+                    module.foldername,
+                    if matched_by.is_empty() {
+                        "unmatched".to_owned()
+                    } else {
+                        matched_by.join(", ")
+                    }
+                )));
+            }
+        }
+
+        lines
+    }
+
+    /// The match entries each API's responses had for the package, as the
+    /// JSON they were returned in.
+    fn raw_lines(&self, package: &PackageStatus) -> Vec<Line<'static>> {
+        let mut lines = vec![Line::from(format!("{} ({})", package.name, package.id))];
+
+        for (name, payloads) in &self.payloads {
+            lines.push(Line::from(""));
+
+            let entries = match payloads.get(&package.id) {
+                Some(entries) => entries,
+                None => {
+                    lines.push(Line::from(format!("{}: no entry", name)));
+                    continue;
+                }
+            };
+
+            lines.push(Line::from(format!("{}:", name)));
+
+            match serde_json::to_string_pretty(entries) {
+                Ok(json) => lines.extend(json.lines().map(|l| Line::from(format!("  {}", l)))),
+                Err(e) => lines.push(Line::from(format!("  failed to serialize - {}", e))),
+            }
+        }
+
+        lines
+    }
+}
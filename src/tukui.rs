@@ -0,0 +1,203 @@
+//! Checks the endpoints Tukui serves ElvUI and Tukui through for the
+//! `tukui` subcommand, which Ajour queries apart from the rest of Tukui's
+//! addons.
+//!
+//! On retail both come from their own `ui` endpoint, and on the classic
+//! flavors from the flavor's single addon endpoint under a fixed id. Each
+//! is checked for a version and download URL Ajour can use, and compared
+//! with the entry of the flavor's addon list, if it has one.
+
+use crate::cli::{Format, Opts};
+use crate::error::AuditError;
+use crate::{send_request, write_json};
+
+use async_std::future::timeout;
+use isahc::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use std::io::{self, Write};
+use std::time::Duration;
+
+const TUKUI_API_URL: &str = "https://www.tukui.org/api.php";
+
+/// The UIs with special endpoints, with their id on the classic flavors.
+const UIS: [(&str, &str, u32); 2] = [("elvui", "ElvUI", 2), ("tukui", "Tukui", 1)];
+
+/// Flavors with the query of their addon list and of a single addon, absent
+/// for retail, which has the `ui` endpoint instead.
+const FLAVORS: [(&str, &str, Option<&str>); 4] = [
+    ("retail", "addons", None),
+    ("classic", "classic-addons", Some("classic-addon")),
+    (
+        "classic-tbc",
+        "classic-tbc-addons",
+        Some("classic-tbc-addon"),
+    ),
+    (
+        "classic-wotlk",
+        "classic-wotlk-addons",
+        Some("classic-wotlk-addon"),
+    ),
+];
+
+#[derive(Deserialize)]
+pub struct TukuiAddon {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    version: String,
+    #[serde(default)]
+    url: String,
+}
+
+#[derive(Serialize)]
+pub struct SpecialCheck {
+    pub ui: &'static str,
+    pub flavor: &'static str,
+    /// Query of the special endpoint, such as `ui=elvui`.
+    pub endpoint: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Version the flavor's addon list has, absent if it doesn't list the
+    /// UI.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listed_version: Option<String>,
+    /// Why Ajour couldn't use the special endpoint, or how it disagrees
+    /// with the list, empty if neither.
+    pub problems: Vec<String>,
+}
+
+/// Checks every UI's special endpoint on every flavor and writes the
+/// outcome, returning how many have problems.
+pub async fn check(
+    client: &HttpClient,
+    opts: &Opts,
+    out: &mut impl Write,
+) -> Result<usize, anyhow::Error> {
+    let mut checks = vec![];
+
+    for &(flavor, list_query, addon_query) in &FLAVORS {
+        let list = fetch::<Vec<TukuiAddon>>(client, opts, list_query).await;
+
+        for &(ui, name, classic_id) in &UIS {
+            let endpoint = match addon_query {
+                Some(query) => format!("{}={}", query, classic_id),
+                None => format!("ui={}", ui),
+            };
+
+            let mut check = SpecialCheck {
+                ui,
+                flavor,
+                endpoint,
+                version: None,
+                url: None,
+                listed_version: None,
+                problems: vec![],
+            };
+
+            match fetch::<TukuiAddon>(client, opts, &check.endpoint).await {
+                Ok(addon) => {
+                    if !addon.version.chars().any(|c| c.is_ascii_digit()) {
+                        check
+                            .problems
+                            .push(format!("version `{}` holds no number", addon.version));
+                    }
+
+                    if !addon.url.starts_with("https://") {
+                        check
+                            .problems
+                            .push(format!("download URL `{}` isn't https", addon.url));
+                    }
+
+                    check.version = Some(addon.version);
+                    check.url = Some(addon.url);
+                }
+                Err(e) => check.problems.push(format!("special endpoint - {}", e)),
+            }
+
+            match &list {
+                Ok(list) => {
+                    let listed = list.iter().find(|a| a.name.eq_ignore_ascii_case(name));
+
+                    if let (Some(listed), Some(version)) = (listed, &check.version) {
+                        if listed.version != *version {
+                            check
+                                .problems
+                                .push(format!("the addon list has version {}", listed.version));
+                        }
+
+                        if check.url.as_deref() != Some(listed.url.as_str()) {
+                            check
+                                .problems
+                                .push(format!("the addon list has URL {}", listed.url));
+                        }
+                    }
+
+                    check.listed_version = listed.map(|a| a.version.clone());
+                }
+                Err(e) => check.problems.push(format!("addon list - {}", e)),
+            }
+
+            checks.push(check);
+        }
+    }
+
+    match opts.format {
+        Format::Json => write_json(out, &checks, opts.compact)?,
+        _ => write_text(out, &checks)?,
+    }
+
+    Ok(checks.iter().filter(|c| !c.problems.is_empty()).count())
+}
+
+async fn fetch<T: serde::de::DeserializeOwned>(
+    client: &HttpClient,
+    opts: &Opts,
+    query: &str,
+) -> Result<T, AuditError> {
+    let request_timeout = Duration::from_secs(opts.request_timeout);
+    let url = format!("{}?{}", TUKUI_API_URL, query);
+    let request = Request::builder().method("GET").uri(&url).body(vec![])?;
+
+    let (body, _, _) = timeout(request_timeout, send_request(client, opts, request))
+        .await
+        .map_err(|_| AuditError::Timeout(request_timeout))??;
+
+    serde_json::from_slice(&body).map_err(|e| AuditError::Deserialize(e.into()))
+}
+
+fn write_text(out: &mut impl Write, checks: &[SpecialCheck]) -> io::Result<()> {
+    for check in checks {
+        let subject = format!("{} {} ({})", check.ui, check.flavor, check.endpoint);
+
+        if !check.problems.is_empty() {
+            writeln!(out, "{}: {}", subject, check.problems.join("; "))?;
+            continue;
+        }
+
+        let listed = if check.listed_version.is_some() {
+            ", same as the addon list"
+        } else {
+            ", not in the addon list"
+        };
+
+        writeln!(
+            out,
+            "{}: version {}{}",
+            subject,
+            check.version.as_deref().unwrap_or_default(),
+            listed
+        )?;
+    }
+
+    let failing = checks.iter().filter(|c| !c.problems.is_empty()).count();
+
+    writeln!(
+        out,
+        "{} of {} special endpoints have problems",
+        failing,
+        checks.len()
+    )
+}
@@ -0,0 +1,272 @@
+//! Audits again every `--interval` for `--watch`, turning the one-shot audit
+//! into a monitor that only reports what changed since its previous run.
+//!
+//! Each run is compared against a snapshot of the previous one, like
+//! `--baseline` compares against a saved one, with gained matches included.
+//! A run that fails is logged and the next one compares against the last
+//! run that succeeded. With `--metrics-listen` or `--pushgateway-url` the
+//! runs are also handed to Prometheus.
+
+use crate::baseline::Baseline;
+use crate::cli::{Format, Opts};
+use crate::date::Timestamp;
+use crate::metrics::WatchMetrics;
+use crate::metrics_export;
+use crate::notify::{self, Alert};
+use crate::rate_limit::RateLimits;
+use crate::report::AuditReport;
+use crate::retry::RetryBudget;
+use crate::shutdown;
+use crate::spill::Spill;
+use crate::{audit_and_report, open_output, sample, write_json, ApiChoice, EXIT_INTERRUPTED};
+
+use async_std::net::TcpListener;
+use async_std::task;
+use isahc::HttpClient;
+use serde::Serialize;
+use tracing::{error, info, warn};
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// What changed in one game since the previous run, written as a json line.
+#[derive(Serialize)]
+struct Delta<'a> {
+    run_id: &'a str,
+    started_at: &'a str,
+    game_id: u32,
+    /// Set on the first run, which has nothing to compare against and
+    /// carries the summary line instead.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<String>,
+    changes: Vec<String>,
+}
+
+/// What the previous successful run of a game left to compare against.
+struct Previous {
+    baseline: Baseline,
+    /// APIs and flags of the `--notify-*` thresholds it crossed.
+    alerted: HashSet<(&'static str, &'static str)>,
+}
+
+/// Runs the audit every `--interval` until the process is stopped.
+pub async fn run(
+    client: &HttpClient,
+    opts: &Opts,
+    apis: &[ApiChoice],
+) -> Result<(), anyhow::Error> {
+    let mut previous: HashMap<u32, Previous> = HashMap::new();
+    let mut metrics = WatchMetrics::default();
+    let exposition = Arc::new(Mutex::new(String::new()));
+
+    if let Some(addr) = opts.metrics_listen {
+        let listener = TcpListener::bind(addr).await?;
+        info!("Serving metrics on http://{}/metrics", addr);
+        task::spawn(metrics_export::serve(listener, exposition.clone()));
+    }
+
+    loop {
+        let start = Instant::now();
+        let run_id = sample::run_id();
+        let started_at = Timestamp::now();
+        info!("Run {} started at {}", run_id, started_at);
+
+        // Every run gets the full budget, rather than a long running watch
+        // using it up
+        let retry_budget = RetryBudget::new(opts.retry_budget);
+        let rate_limits = RateLimits::new();
+        let spill = Spill::new(opts.max_memory_mb);
+
+        let (output_path, mut out) = open_output(opts, &run_id, started_at)?;
+        let mut alerts = vec![];
+
+        for &game_id in &opts.game_ids {
+            let mut game = match audit_and_report(
+                client,
+                opts,
+                apis,
+                &retry_budget,
+                &rate_limits,
+                &spill,
+                game_id,
+                None,
+            )
+            .await
+            {
+                Ok((game, _)) => game,
+                Err(e) => {
+                    error!("run {} failed to audit game {} - {}", run_id, game_id, e);
+                    continue;
+                }
+            };
+            game.report.run_id = run_id.clone();
+            game.report.started_at = started_at.to_string();
+
+            #[cfg(feature = "sqlite")]
+            {
+                if let Some(crate::cli::Store::Sqlite(path)) = &opts.store {
+                    if let Err(e) =
+                        crate::sqlite::export(path, game_id, &game.report, &game.results)
+                    {
+                        warn!(
+                            "failed to append run {} to {} - {}",
+                            run_id,
+                            path.display(),
+                            e
+                        );
+                    }
+                }
+            }
+
+            let report = &game.report;
+            let last = previous.get(&game_id);
+
+            let delta = Delta {
+                run_id: &run_id,
+                started_at: &report.started_at,
+                game_id,
+                summary: match last {
+                    Some(_) => None,
+                    None => Some(report.summary_line().trim_end().to_owned()),
+                },
+                changes: last.map_or_else(Vec::new, |last| changes(&last.baseline, report)),
+            };
+
+            match opts.format {
+                Format::Json => write_json(&mut out, &delta, true)?,
+                _ => write_text(&mut out, &delta)?,
+            }
+
+            let crossed = notify::alerts(opts, &[(game_id, report)]);
+            let alerted = crossed
+                .iter()
+                .map(|alert| (alert.api, alert.threshold))
+                .collect::<HashSet<_>>();
+
+            // Thresholds stay crossed across runs, so only the newly crossed
+            // ones are sent again
+            alerts.extend(crossed.into_iter().filter(|alert| {
+                !last.map_or(false, |last| {
+                    last.alerted.contains(&(alert.api, alert.threshold))
+                })
+            }));
+
+            if opts.webhook_url.is_some() {
+                alerts.extend(delta.changes.iter().map(|change| Alert {
+                    game_id,
+                    api: "",
+                    threshold: "--watch",
+                    message: format!("game {} - {}", game_id, change),
+                }));
+            }
+
+            previous.insert(
+                game_id,
+                Previous {
+                    baseline: Baseline::new(report),
+                    alerted,
+                },
+            );
+
+            metrics.record(game_id, game.report, &game.results);
+        }
+
+        out.flush()?;
+
+        if let Some(path) = &output_path {
+            info!("Wrote changes to {}", path.display());
+        }
+
+        // Changes against a partial run would only raise false alerts
+        if shutdown::requested() {
+            info!("Stopped watching, run {} was interrupted", run_id);
+            process::exit(EXIT_INTERRUPTED);
+        }
+
+        notify::send(client, opts, &run_id, &alerts).await;
+
+        if opts.metrics_listen.is_some() || opts.pushgateway_url.is_some() {
+            let rendered = metrics.render();
+            *exposition.lock().unwrap() = rendered.clone();
+            metrics_export::push(client, opts, rendered).await;
+        }
+
+        let wait = opts.interval.0.saturating_sub(start.elapsed());
+
+        if wait.is_zero() {
+            warn!(
+                "run {} took longer than --interval, starting the next one now",
+                run_id
+            );
+        }
+
+        info!(
+            "Run {} finished, the next one starts in {}s",
+            run_id,
+            wait.as_secs()
+        );
+        task::sleep(wait).await;
+    }
+}
+
+/// Match counts and per-package match statuses that differ from the
+/// previous run.
+fn changes(previous: &Baseline, report: &AuditReport) -> Vec<String> {
+    let api_name = |key: &str| {
+        report
+            .apis
+            .iter()
+            .find(|api| api.api == key)
+            .map_or_else(|| key.to_owned(), |api| api.name.to_owned())
+    };
+
+    let counts = previous.count_changes(report).into_iter().map(|change| {
+        let subject = match &change.api {
+            Some(api) => format!("{} {}", api_name(api), change.what),
+            None => change.what.to_owned(),
+        };
+
+        format!(
+            "{} went from {} to {}",
+            subject, change.before, change.after
+        )
+    });
+
+    counts
+        .chain(
+            previous
+                .compare(report, true)
+                .iter()
+                .map(ToString::to_string),
+        )
+        .collect()
+}
+
+fn write_text(out: &mut impl Write, delta: &Delta<'_>) -> io::Result<()> {
+    if let Some(summary) = &delta.summary {
+        return writeln!(
+            out,
+            "Run {} game {}: {}",
+            delta.run_id, delta.game_id, summary
+        );
+    }
+
+    if delta.changes.is_empty() {
+        return writeln!(
+            out,
+            "Run {} game {}: no changes since the previous run",
+            delta.run_id, delta.game_id
+        );
+    }
+
+    writeln!(out, "Run {} game {}:", delta.run_id, delta.game_id)?;
+
+    for change in &delta.changes {
+        writeln!(out, "  {}", change)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,283 @@
+//! Compares the latest release of addons published on both Curse and
+//! WowInterface for the `wowinterface` subcommand, listing those whose
+//! WowInterface release lags the one on Curse.
+//!
+//! The searched Curse packages are paired with WowInterface's file list by
+//! `--mapping-file` first, then by name and author, both compared ignoring
+//! case, spaces and punctuation. A package whose name several WowInterface
+//! addons of the same author share is left unpaired rather than guessed.
+
+use crate::cli::{Format, Opts};
+use crate::date::Timestamp;
+use crate::error::AuditError;
+use crate::{
+    exit_on_search_failure, read_input_file, sample, search_all_packages, send_request, write_json,
+    SearchPlan,
+};
+
+use ajour_core::repository::curse;
+use async_std::future::timeout;
+use isahc::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+pub const WOWINTERFACE_FILE_LIST_URL: &str = "https://api.mmoui.com/v3/game/WOW/filelist.json";
+
+#[derive(Deserialize)]
+pub struct WowiAddon {
+    #[serde(rename = "UID")]
+    id: String,
+    #[serde(rename = "UIName", default)]
+    pub name: String,
+    #[serde(rename = "UIAuthorName", default)]
+    author: String,
+    #[serde(rename = "UIVersion", default)]
+    version: String,
+    /// Milliseconds since the epoch of the latest release.
+    #[serde(rename = "UIDate", default)]
+    date: i64,
+}
+
+/// A line of `--mapping-file`, a CurseForge id and a WowInterface id
+/// separated by a comma.
+struct Mapping {
+    curse_id: i32,
+    wowi_id: String,
+}
+
+impl FromStr for Mapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (curse_id, wowi_id) = s
+            .split_once(',')
+            .ok_or_else(|| format!("expected `curse_id,wowinterface_id`, got `{}`", s))?;
+        let curse_id = curse_id
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid CurseForge id `{}`", curse_id))?;
+
+        Ok(Mapping {
+            curse_id,
+            wowi_id: wowi_id.trim().to_owned(),
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct ParityReport {
+    /// Searched Curse packages with a latest file date.
+    pub packages: usize,
+    /// Packages paired with a WowInterface addon, by how they were paired.
+    pub paired_by_mapping: usize,
+    pub paired_by_name: usize,
+    pub max_lag_days: u64,
+    /// Paired addons whose WowInterface release is more than
+    /// `--max-lag-days` older than the Curse one, the furthest behind first.
+    pub lagging: Vec<Lagging>,
+}
+
+#[derive(Serialize)]
+pub struct Lagging {
+    pub curse_id: i32,
+    pub wowinterface_id: String,
+    pub name: String,
+    pub curse_version: String,
+    pub wowinterface_version: String,
+    pub curse_date: String,
+    pub wowinterface_date: String,
+    pub days_behind: u64,
+}
+
+/// Pairs the searched packages with WowInterface's addons and writes those
+/// lagging behind, returning how many do.
+pub async fn check(
+    client: &HttpClient,
+    opts: &Opts,
+    mapping_file: Option<&Path>,
+    max_lag_days: u64,
+    out: &mut impl Write,
+) -> Result<usize, anyhow::Error> {
+    let mappings = match mapping_file {
+        Some(path) => read_input_file::<Mapping>(opts, path)?,
+        None => vec![],
+    };
+
+    let seed = opts.seed.unwrap_or_else(sample::random_seed);
+    let plan = SearchPlan::new(opts, seed);
+    let packages = search_all_packages(client, opts, opts.game_ids[0], &plan).await;
+    let packages = exit_on_search_failure(opts, packages)?;
+
+    let wowi = fetch_file_list(client, opts).await?;
+    info!(
+        "Comparing {} Curse packages with {} WowInterface addons",
+        packages.len(),
+        wowi.len()
+    );
+
+    let by_id = wowi
+        .iter()
+        .map(|addon| (addon.id.as_str(), addon))
+        .collect::<HashMap<_, _>>();
+    let mut by_name = HashMap::<(String, String), Vec<&WowiAddon>>::new();
+
+    for addon in &wowi {
+        by_name
+            .entry((key(&addon.name), key(&addon.author)))
+            .or_default()
+            .push(addon);
+    }
+
+    let mapped = mappings
+        .iter()
+        .map(|m| (m.curse_id, m.wowi_id.as_str()))
+        .collect::<HashMap<_, _>>();
+
+    let mut report = ParityReport {
+        packages: 0,
+        paired_by_mapping: 0,
+        paired_by_name: 0,
+        max_lag_days,
+        lagging: vec![],
+    };
+
+    for package in &packages {
+        let (file, curse_date) = match latest_file(package) {
+            Some(latest) => latest,
+            None => continue,
+        };
+        report.packages += 1;
+
+        let addon = if let Some(id) = mapped.get(&package.id) {
+            match by_id.get(id) {
+                Some(addon) => {
+                    report.paired_by_mapping += 1;
+                    *addon
+                }
+                None => continue,
+            }
+        } else {
+            let candidates = package
+                .authors
+                .iter()
+                .filter_map(|author| by_name.get(&(key(&package.name), key(&author.name))))
+                .flatten()
+                .collect::<Vec<_>>();
+
+            match candidates.as_slice() {
+                [addon] => {
+                    report.paired_by_name += 1;
+                    *addon
+                }
+                _ => continue,
+            }
+        };
+
+        let wowi_date = Timestamp::from_unix(addon.date / 1000);
+        let days_behind = curse_date
+            .duration_since(wowi_date)
+            .map_or(0, |behind| behind.as_secs() / 86_400);
+
+        if days_behind > max_lag_days {
+            report.lagging.push(Lagging {
+                curse_id: package.id,
+                wowinterface_id: addon.id.clone(),
+                name: package.name.clone(),
+                curse_version: file.display_name.clone(),
+                wowinterface_version: addon.version.clone(),
+                curse_date: curse_date.to_string(),
+                wowinterface_date: wowi_date.to_string(),
+                days_behind,
+            });
+        }
+    }
+
+    report
+        .lagging
+        .sort_by(|a, b| (b.days_behind, a.curse_id).cmp(&(a.days_behind, b.curse_id)));
+
+    match opts.format {
+        Format::Json => write_json(out, &report, opts.compact)?,
+        _ => write_text(out, &report)?,
+    }
+
+    Ok(report.lagging.len())
+}
+
+async fn fetch_file_list(client: &HttpClient, opts: &Opts) -> Result<Vec<WowiAddon>, AuditError> {
+    let request_timeout = Duration::from_secs(opts.request_timeout);
+    let request = Request::builder()
+        .method("GET")
+        .uri(WOWINTERFACE_FILE_LIST_URL)
+        .body(vec![])?;
+
+    let (body, _, _) = timeout(request_timeout, send_request(client, opts, request))
+        .await
+        .map_err(|_| AuditError::Timeout(request_timeout))??;
+
+    serde_json::from_slice(&body).map_err(|e| AuditError::Deserialize(e.into()))
+}
+
+/// The package's most recently published latest file and its date,
+/// preferring stable releases over betas and alphas.
+fn latest_file(package: &curse::Package) -> Option<(&curse::File, Timestamp)> {
+    let dated = package
+        .latest_files
+        .iter()
+        .filter_map(|file| {
+            file.file_date
+                .parse::<Timestamp>()
+                .ok()
+                .map(|date| (file, date))
+        })
+        .collect::<Vec<_>>();
+
+    dated
+        .iter()
+        .filter(|(file, _)| file.release_type == 1)
+        .max_by_key(|(_, date)| *date)
+        .or_else(|| dated.iter().max_by_key(|(_, date)| *date))
+        .copied()
+}
+
+/// `value` lowercased with only its letters and digits, so `Deadly Boss
+/// Mods` and `DeadlyBossMods` compare equal.
+pub fn key(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn write_text(out: &mut impl Write, report: &ParityReport) -> io::Result<()> {
+    for lagging in &report.lagging {
+        writeln!(
+            out,
+            "{} ({}): WowInterface {} of {} is {} days behind Curse {} of {}",
+            lagging.name,
+            lagging.curse_id,
+            lagging.wowinterface_version,
+            lagging.wowinterface_date,
+            lagging.days_behind,
+            lagging.curse_version,
+            lagging.curse_date
+        )?;
+    }
+
+    writeln!(
+        out,
+        "{} of {} packages paired with WowInterface ({} by mapping, {} by name and author) are more than {} days behind",
+        report.lagging.len(),
+        report.paired_by_mapping + report.paired_by_name,
+        report.paired_by_mapping,
+        report.paired_by_name,
+        report.max_lag_days
+    )
+}
@@ -0,0 +1,105 @@
+//! Audits a package against the recordings in `tests/fixtures/replay`, made
+//! from the bundled samples, so the whole run from fetching the package to
+//! the report is covered without network access.
+
+use ajour_api_test::{AuditReport, AuditRunner};
+
+use std::path::PathBuf;
+use std::process::{self, Command};
+
+fn fixtures() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/replay")
+}
+
+async fn replay(args: &[&str]) -> Vec<AuditReport> {
+    let replay = fixtures();
+    let mut all = vec!["ajour-api-test", "--replay", replay.to_str().unwrap()];
+    all.extend(args);
+
+    AuditRunner::from_args(all).unwrap().run().await.unwrap()
+}
+
+#[async_std::test]
+async fn replayed_package_matches_on_every_api() {
+    let reports = replay(&["--package-id", "61284"]).await;
+    assert_eq!(reports.len(), 1);
+
+    let report = &reports[0];
+    assert!(!report.interrupted);
+    assert_eq!(report.packages_fetched, 1);
+    assert_eq!(report.packages_audited, 1);
+    assert_eq!(report.discrepancies, 0);
+
+    let apis = report.apis.iter().map(|api| api.api).collect::<Vec<_>>();
+    assert_eq!(apis, ["curse", "wowup"]);
+
+    for api in &report.apis {
+        assert_eq!(api.matched_packages, 1, "{}", api.api);
+        assert_eq!(api.fingerprint_matches, 1, "{}", api.api);
+        assert_eq!(api.batches, 1, "{}", api.api);
+        assert_eq!(api.failed_batches, 0, "{}", api.api);
+    }
+}
+
+#[async_std::test]
+async fn unrecorded_package_is_an_error() {
+    let replay = fixtures();
+    let runner = AuditRunner::from_args(&[
+        "ajour-api-test",
+        "--replay",
+        replay.to_str().unwrap(),
+        "--package-id",
+        "1",
+    ])
+    .unwrap();
+
+    // Embedded runs return what the command line tool would exit on
+    assert!(runner.run().await.is_err());
+}
+
+#[async_std::test]
+async fn invalid_options_are_an_error() {
+    let replay = fixtures();
+    let runner = AuditRunner::from_args(&[
+        "ajour-api-test",
+        "--replay",
+        replay.to_str().unwrap(),
+        "--package-id",
+        "61284",
+        "--batch-size",
+        "0",
+    ])
+    .unwrap();
+
+    let e = runner.run().await.unwrap_err();
+    assert!(e.to_string().contains("--batch-size"), "{}", e);
+}
+
+#[test]
+fn streamed_stdout_is_only_json_lines() {
+    let report = std::env::temp_dir().join(format!("ajour-api-test-stream-{}.txt", process::id()));
+    let output = Command::new(env!("CARGO_BIN_EXE_ajour-api-test"))
+        .arg("--replay")
+        .arg(fixtures())
+        .arg("--input-file")
+        .arg(fixtures().with_file_name("package_ids.txt"))
+        .args(&["--stream", "ndjson", "--output-file"])
+        .arg(&report)
+        .output()
+        .unwrap();
+    let _ = std::fs::remove_file(&report);
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 1, "{}", stdout);
+
+    for line in stdout.lines() {
+        let parsed = serde_json::from_str::<serde_json::Value>(line);
+        assert!(parsed.is_ok(), "not json: {}", line);
+    }
+}